@@ -1,18 +1,32 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc, time::SystemTime};
 
 use axum::{
     extract::{FromRef, Path, State},
-    http::StatusCode,
-    routing, Router,
+    http::{header, HeaderMap, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing, Extension, Router,
 };
+use log::warn;
 use rand::{seq::IteratorRandom, thread_rng};
 use serenity::all::{Cache, ChannelId, GuildId, Http, UserId};
-use songbird::{CoreEvent, Event, Songbird};
-use tokio::sync::Mutex;
+use songbird::Songbird;
+use tokio::sync::{oneshot, Mutex};
 use ulid::Ulid;
 
 use crate::{
-    find_voice_channel, history::History, recorder::Recorder, soundboard::Soundboard, VoiceHandler,
+    api_keys::{ApiKey, ApiKeys, Scope},
+    audit::{DownloadAudit, DownloadRecord},
+    guild_cache::find_voice_channel,
+    handler::{current_voice_channel, Handler},
+    history::History,
+    metrics::Metrics,
+    mix,
+    recorder::{Recorder, RecorderAction},
+    settings::Settings,
+    share::Shares,
+    soundboard::Soundboard,
+    wav::{self, SampleFormat},
 };
 
 #[derive(FromRef, Clone)]
@@ -23,52 +37,112 @@ pub struct ApiState {
     pub recorder: Arc<Mutex<Recorder>>,
     pub soundboard: Arc<Soundboard>,
     pub history: Arc<History>,
+    pub metrics: Arc<Metrics>,
+    pub shares: Option<Arc<Shares>>,
+    pub settings: Arc<Settings>,
+    pub download_audit: Arc<DownloadAudit>,
+    pub api_keys: Arc<ApiKeys>,
+    /// Guild requests without an API key are refused, rather than trusted as
+    /// coming from the host.
+    pub require_api_key: bool,
+    /// Joins channels, its context being set once the bot is ready.
+    pub(crate) handler: Handler,
 }
 
+/// API key check of a group of guild routes.
+#[derive(Clone)]
+struct KeyCheck {
+    keys: Arc<ApiKeys>,
+    required: bool,
+    scope: Scope,
+}
+
+/// Check the bearer token of a request against the guild in its path and the
+/// scope of its route, passing the matching key on to the handler. Reading
+/// recordings always needs a key, so that downloads are audited.
+async fn check_key<B>(
+    State(check): State<KeyCheck>,
+    Path(params): Path<HashMap<String, String>>,
+    headers: HeaderMap,
+    mut request: Request<B>,
+    next: Next<B>,
+) -> Response {
+    let Some(guild) = params.get("guild").and_then(|guild| guild.parse().ok()) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let token = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    match token {
+        Some(token) => match check.keys.authorize(token, guild, check.scope).await {
+            Some(key) => {
+                request.extensions_mut().insert(key);
+            }
+            None => return StatusCode::UNAUTHORIZED.into_response(),
+        },
+        None if check.required || check.scope == Scope::ReadRecordings => {
+            return StatusCode::UNAUTHORIZED.into_response()
+        }
+        None => (),
+    }
+    next.run(request).await
+}
+
+/// Join a channel like `/join` does, its text chat getting the answers of
+/// voice commands.
 async fn join_channel(
     State(songbird): State<Arc<Songbird>>,
-    State(recorder): State<Arc<Mutex<Recorder>>>,
+    State(settings): State<Arc<Settings>>,
+    State(handler): State<Handler>,
     Path((guild, channel)): Path<(GuildId, ChannelId)>,
 ) -> StatusCode {
-    let call = songbird.get_or_insert(guild);
-    let mut call_lock = call.lock().await;
-
-    let recorder = VoiceHandler {
-        guild_recorder: recorder.lock().await.get_guild_recorder(guild).await,
+    let Some(ctx) = handler.context.get() else {
+        return StatusCode::SERVICE_UNAVAILABLE;
     };
-    call_lock.remove_all_global_events();
-    call_lock.add_global_event(
-        Event::Core(CoreEvent::SpeakingStateUpdate),
-        recorder.clone(),
-    );
-    call_lock.add_global_event(Event::Core(CoreEvent::VoiceTick), recorder);
-
-    let handle = call_lock
-        .join(channel)
-        .await
-        .expect("Voice connexion failure");
-    drop(call_lock);
-    handle.await.expect("Voice connexion failure");
-
-    StatusCode::OK
+    let settings = settings.get(guild).await;
+    if settings.disabled || settings.blocked_channels.contains(&channel) {
+        return StatusCode::FORBIDDEN;
+    }
+    match current_voice_channel(&songbird, guild).await {
+        Some(current) if current == channel => return StatusCode::OK,
+        Some(_) if !settings.join_switches_channel => return StatusCode::CONFLICT,
+        Some(_) => handler.end_session(ctx, guild).await,
+        None => (),
+    }
+    match handler.connect(ctx, guild, channel, None, channel).await {
+        Ok(()) => StatusCode::OK,
+        Err(err) => {
+            warn!("cannot join channel {channel} of guild {guild}: {err}");
+            StatusCode::BAD_GATEWAY
+        }
+    }
 }
 
 async fn join_user_channel(
-    State(http): State<Arc<Http>>,
     State(cache): State<Arc<Cache>>,
     State(songbird): State<Arc<Songbird>>,
-    State(recorder): State<Arc<Mutex<Recorder>>>,
+    State(settings): State<Arc<Settings>>,
+    State(handler): State<Handler>,
     Path((guild, user)): Path<(GuildId, UserId)>,
 ) -> StatusCode {
-    let Some(channel) = find_voice_channel(http, cache, guild, user).await else {
+    let Some(channel) = find_voice_channel(&cache, guild, user) else {
         return StatusCode::NOT_FOUND;
     };
-    join_channel(State(songbird), State(recorder), Path((guild, channel))).await
+    join_channel(
+        State(songbird),
+        State(settings),
+        State(handler),
+        Path((guild, channel)),
+    )
+    .await
 }
 
 async fn play_sound(
     State(songbird): State<Arc<Songbird>>,
     State(soundboard): State<Arc<Soundboard>>,
+    State(recorder): State<Arc<Mutex<Recorder>>>,
+    State(settings): State<Arc<Settings>>,
     Path((guild, sounds)): Path<(GuildId, String)>,
 ) -> StatusCode {
     let Some(selected) = sounds
@@ -78,73 +152,225 @@ async fn play_sound(
     else {
         return StatusCode::BAD_REQUEST;
     };
-    if super::play_sound(songbird, &soundboard, guild, selected).await {
-        StatusCode::OK
-    } else {
-        StatusCode::NOT_FOUND
-    }
+    play_sound_id(
+        State(songbird),
+        State(soundboard),
+        State(recorder),
+        State(settings),
+        Path((guild, selected)),
+    )
+    .await
 }
 
 async fn play_random_sound(
     State(songbird): State<Arc<Songbird>>,
     State(soundboard): State<Arc<Soundboard>>,
+    State(recorder): State<Arc<Mutex<Recorder>>>,
+    State(settings): State<Arc<Settings>>,
     Path(guild): Path<GuildId>,
 ) -> StatusCode {
     let Some(sound) = soundboard.random_id(guild).await else {
         return StatusCode::NOT_FOUND;
     };
-    play_sound_id(State(songbird), State(soundboard), Path((guild, sound))).await
+    play_sound_id(
+        State(songbird),
+        State(soundboard),
+        State(recorder),
+        State(settings),
+        Path((guild, sound)),
+    )
+    .await
 }
 
 async fn play_latest_sound(
     State(songbird): State<Arc<Songbird>>,
     State(soundboard): State<Arc<Soundboard>>,
+    State(recorder): State<Arc<Mutex<Recorder>>>,
+    State(settings): State<Arc<Settings>>,
     Path(guild): Path<GuildId>,
 ) -> StatusCode {
     let Some(sound) = soundboard.latest_id(guild).await else {
         return StatusCode::NOT_FOUND;
     };
-    play_sound_id(State(songbird), State(soundboard), Path((guild, sound))).await
+    play_sound_id(
+        State(songbird),
+        State(soundboard),
+        State(recorder),
+        State(settings),
+        Path((guild, sound)),
+    )
+    .await
 }
 
 async fn play_last_played_sound(
     State(songbird): State<Arc<Songbird>>,
     State(soundboard): State<Arc<Soundboard>>,
+    State(recorder): State<Arc<Mutex<Recorder>>>,
     State(history): State<Arc<History>>,
+    State(settings): State<Arc<Settings>>,
     Path(guild): Path<GuildId>,
 ) -> StatusCode {
     let Some(sound) = history.get_latest_played(guild, 0).await else {
         return StatusCode::NOT_FOUND;
     };
-    play_sound_id(State(songbird), State(soundboard), Path((guild, sound))).await
+    play_sound_id(
+        State(songbird),
+        State(soundboard),
+        State(recorder),
+        State(settings),
+        Path((guild, sound)),
+    )
+    .await
 }
 
 async fn play_last_played_offset_sound(
     State(songbird): State<Arc<Songbird>>,
     State(soundboard): State<Arc<Soundboard>>,
+    State(recorder): State<Arc<Mutex<Recorder>>>,
     State(history): State<Arc<History>>,
+    State(settings): State<Arc<Settings>>,
     Path((guild, offset)): Path<(GuildId, usize)>,
 ) -> StatusCode {
     let Some(sound) = history.get_latest_played(guild, offset).await else {
         return StatusCode::NOT_FOUND;
     };
-    play_sound_id(State(songbird), State(soundboard), Path((guild, sound))).await
+    play_sound_id(
+        State(songbird),
+        State(soundboard),
+        State(recorder),
+        State(settings),
+        Path((guild, sound)),
+    )
+    .await
 }
 
 async fn play_sound_id(
     State(songbird): State<Arc<Songbird>>,
     State(soundboard): State<Arc<Soundboard>>,
+    State(recorder): State<Arc<Mutex<Recorder>>>,
+    State(settings): State<Arc<Settings>>,
     Path((guild, sound)): Path<(GuildId, Ulid)>,
 ) -> StatusCode {
-    if super::play_sound(songbird, &soundboard, guild, sound).await {
+    if settings.get(guild).await.disabled {
+        return StatusCode::FORBIDDEN;
+    }
+    let guild_recorder = recorder.lock().await.get_guild_recorder(guild).await;
+    if crate::handler::play_sound(songbird, &soundboard, &guild_recorder, guild, sound)
+        .await
+        .is_some()
+    {
         StatusCode::OK
     } else {
         StatusCode::NOT_FOUND
     }
 }
 
-pub fn router(state: ApiState) -> Router {
-    Router::new()
+async fn user_recording(
+    State(recorder): State<Arc<Mutex<Recorder>>>,
+    State(settings): State<Arc<Settings>>,
+    State(download_audit): State<Arc<DownloadAudit>>,
+    Extension(key): Extension<ApiKey>,
+    Path((guild, user)): Path<(GuildId, UserId)>,
+) -> Response {
+    if settings.get(guild).await.disabled {
+        return StatusCode::FORBIDDEN.into_response();
+    }
+    let (tx, rx) = oneshot::channel();
+    if recorder
+        .lock()
+        .await
+        .get_guild_recorder(guild)
+        .await
+        .send(RecorderAction::GetVoiceData(user, tx))
+        .await
+        .is_err()
+    {
+        return StatusCode::SERVICE_UNAVAILABLE.into_response();
+    }
+    let Ok(Some(data)) = rx.await else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    download_audit
+        .record(DownloadRecord {
+            at: SystemTime::now(),
+            guild,
+            requester: key.created_by,
+            targets: vec![user],
+            kind: "api".to_owned(),
+            duration: mix::samples_duration(data.captured_len()),
+        })
+        .await;
+
+    (
+        [
+            (header::CONTENT_TYPE, "audio/wav".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{user}.wav\""),
+            ),
+        ],
+        // Served as stored, without resampling.
+        wav::package_as(&data.data, 1, data.rate(), SampleFormat::S16),
+    )
+        .into_response()
+}
+
+async fn metrics(State(metrics): State<Arc<Metrics>>) -> String {
+    metrics.render()
+}
+
+async fn shared_clip(
+    State(shares): State<Option<Arc<Shares>>>,
+    Path(token): Path<String>,
+) -> Response {
+    let Some(shares) = shares else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    match shares.get(&token).await {
+        Some(clip) => (
+            [
+                (
+                    header::CONTENT_TYPE,
+                    content_type(&clip.filename).to_string(),
+                ),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("inline; filename=\"{}\"", clip.filename),
+                ),
+            ],
+            clip.data,
+        )
+            .into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+/// Content type of a shared file, guessed from its extension.
+fn content_type(filename: &str) -> &'static str {
+    match filename.rsplit_once('.').map(|(_, extension)| extension) {
+        Some("wav") => "audio/wav",
+        Some("flac") => "audio/flac",
+        Some("mp3") => "audio/mpeg",
+        Some("ogg") => "audio/ogg",
+        Some("mp4") => "video/mp4",
+        Some("zip") => "application/zip",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Endpoints of the HTTP API, including `/metrics` when `serve_metrics`.
+pub fn router(state: ApiState, serve_metrics: bool) -> Router {
+    let check = |scope| {
+        middleware::from_fn_with_state(
+            KeyCheck {
+                keys: Arc::clone(&state.api_keys),
+                required: state.require_api_key,
+                scope,
+            },
+            check_key,
+        )
+    };
+    let join = Router::new()
         .route(
             "/guilds/:guild/channels/:channel/join",
             routing::post(join_channel),
@@ -153,6 +379,8 @@ pub fn router(state: ApiState) -> Router {
             "/guilds/:guild/users/:user/follow",
             routing::post(join_user_channel),
         )
+        .route_layer(check(Scope::Join));
+    let play_sounds = Router::new()
         .route(
             "/guilds/:guild/sounds/:sound/play",
             routing::post(play_sound),
@@ -173,5 +401,29 @@ pub fn router(state: ApiState) -> Router {
             "/guilds/:guild/sounds/last-played/:offset/play",
             routing::post(play_last_played_offset_sound),
         )
-        .with_state(state)
+        .route_layer(check(Scope::PlaySounds));
+    let read_recordings = Router::new()
+        .route(
+            "/guilds/:guild/users/:user/recording",
+            routing::get(user_recording),
+        )
+        .route_layer(check(Scope::ReadRecordings));
+
+    let mut router = Router::new()
+        .route("/share/:token", routing::get(shared_clip))
+        .merge(join)
+        .merge(play_sounds)
+        .merge(read_recordings);
+    // Metrics aren't scoped to a guild, so no API key can be checked for them.
+    if serve_metrics {
+        router = router.route("/metrics", routing::get(metrics));
+    }
+    router.with_state(state)
+}
+
+/// Router serving metrics alone, on a separate address than the HTTP API.
+pub fn metrics_router(metrics: Arc<Metrics>) -> Router {
+    Router::new()
+        .route("/metrics", routing::get(self::metrics))
+        .with_state(metrics)
 }
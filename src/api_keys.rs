@@ -0,0 +1,290 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use log::{info, warn};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{GuildId, UserId};
+use thiserror::Error as ThisError;
+use tokio::{fs, sync::Mutex};
+use ulid::Ulid;
+
+use crate::{
+    crypto::{digest_token, digests_match, Encryption, LoadError},
+    disk,
+    error_code::Coded,
+};
+
+const TOKEN_LENGTH: usize = 40;
+/// Keys a guild may have at once, also the number of revoke buttons that fit
+/// in a message.
+pub const MAX_KEYS_PER_GUILD: usize = 25;
+/// Last uses are only written to disk this often, not on every request.
+const LAST_USED_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// What an API key may do in its guild.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scope {
+    /// Play sounds of the soundboard.
+    PlaySounds,
+    /// Join voice channels.
+    Join,
+    /// Download the buffered audio of users.
+    ReadRecordings,
+}
+
+impl Scope {
+    pub const ALL: [Self; 3] = [Self::PlaySounds, Self::Join, Self::ReadRecordings];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PlaySounds => "play-sounds",
+            Self::Join => "join",
+            Self::ReadRecordings => "read-recordings",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|scope| scope.name() == name)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ApiKey {
+    pub id: Ulid,
+    pub guild: GuildId,
+    pub scope: Scope,
+    pub created_by: UserId,
+    pub created_at: SystemTime,
+    pub last_used: Option<SystemTime>,
+}
+
+#[derive(ThisError, Debug)]
+pub enum ApiKeyError {
+    #[error("API key not found.")]
+    NotFound,
+    #[error("This server has too many API keys.")]
+    TooMany,
+    #[error("Failed to save API keys.")]
+    SaveFailed,
+}
+
+impl Coded for ApiKeyError {
+    fn code(&self) -> u16 {
+        match self {
+            Self::NotFound => 701,
+            Self::TooMany => 702,
+            Self::SaveFailed => 703,
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::TooMany => Some("Revoke unused keys with `/apikey list` first."),
+            _ => None,
+        }
+    }
+}
+
+/// API keys of every guild by digest of their token, persisted as JSON.
+pub struct ApiKeys {
+    path: PathBuf,
+    encryption: Arc<Encryption>,
+    keys: Mutex<HashMap<String, ApiKey>>,
+}
+
+impl ApiKeys {
+    pub async fn new(path: PathBuf, encryption: Arc<Encryption>) -> Result<Self, LoadError> {
+        let keys: HashMap<String, ApiKey> = encryption.read_json(&path).await?;
+        info!("loaded {} API keys", keys.len());
+
+        // Keys used to be stored by their token.
+        let legacy = keys.keys().any(|token| token.len() == TOKEN_LENGTH);
+        let keys = keys
+            .into_iter()
+            .map(|(token, key)| match token.len() {
+                TOKEN_LENGTH => (digest_token(&token), key),
+                _ => (token, key),
+            })
+            .collect();
+        let api_keys = Self {
+            path,
+            encryption,
+            keys: Mutex::new(keys),
+        };
+        if legacy {
+            let keys = api_keys.keys.lock().await;
+            let saved = match disk::shred(&api_keys.path).await {
+                Ok(()) => api_keys.save(&keys).await,
+                Err(_) => Err(ApiKeyError::SaveFailed),
+            };
+            match saved {
+                Ok(()) => info!("API key tokens replaced by their digest"),
+                Err(err) => warn!("cannot replace API key tokens by their digest: {err}"),
+            }
+        }
+        Ok(api_keys)
+    }
+
+    /// Create a key and return its token, which isn't shown again.
+    pub async fn create(
+        &self,
+        guild: GuildId,
+        scope: Scope,
+        created_by: UserId,
+    ) -> Result<(String, ApiKey), ApiKeyError> {
+        let mut keys = self.keys.lock().await;
+        if keys.values().filter(|key| key.guild == guild).count() >= MAX_KEYS_PER_GUILD {
+            return Err(ApiKeyError::TooMany);
+        }
+        let token = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(TOKEN_LENGTH)
+            .map(char::from)
+            .collect::<String>();
+        let key = ApiKey {
+            id: Ulid::new(),
+            guild,
+            scope,
+            created_by,
+            created_at: SystemTime::now(),
+            last_used: None,
+        };
+        keys.insert(digest_token(&token), key.clone());
+        self.save(&keys).await?;
+        info!("API key {} created in guild {guild}", key.id);
+        Ok((token, key))
+    }
+
+    /// Keys of a guild, oldest first.
+    pub async fn list(&self, guild: GuildId) -> Vec<ApiKey> {
+        let mut keys = self
+            .keys
+            .lock()
+            .await
+            .values()
+            .filter(|key| key.guild == guild)
+            .cloned()
+            .collect::<Vec<_>>();
+        keys.sort_by_key(|key| key.id);
+        keys
+    }
+
+    pub async fn revoke(&self, guild: GuildId, id: Ulid) -> Result<(), ApiKeyError> {
+        let mut keys = self.keys.lock().await;
+        let len = keys.len();
+        keys.retain(|_, key| key.guild != guild || key.id != id);
+        if keys.len() == len {
+            return Err(ApiKeyError::NotFound);
+        }
+        self.save(&keys).await?;
+        info!("API key {id} revoked in guild {guild}");
+        Ok(())
+    }
+
+    /// Key of a token if it allows `scope` in `guild`, recording its use.
+    pub async fn authorize(&self, token: &str, guild: GuildId, scope: Scope) -> Option<ApiKey> {
+        let digest = digest_token(token);
+        let mut keys = self.keys.lock().await;
+        // Every digest is compared, rather than looked up, to take the same
+        // time whichever matches.
+        let key = keys
+            .iter_mut()
+            .fold(None, |found, (candidate, key)| {
+                if digests_match(candidate, &digest) {
+                    Some(key)
+                } else {
+                    found
+                }
+            })
+            .filter(|key| key.guild == guild && key.scope == scope)?;
+        let now = SystemTime::now();
+        let save = key.last_used.is_none_or(|last_used| {
+            now.duration_since(last_used)
+                .is_ok_and(|elapsed| elapsed >= LAST_USED_SAVE_INTERVAL)
+        });
+        key.last_used = Some(now);
+        let key = key.clone();
+        if save {
+            // Failing to track the use shouldn't refuse the request.
+            let _ = self.save(&keys).await;
+        }
+        Some(key)
+    }
+
+    /// Delete the keys of a guild, or every key if `None`, overwriting the
+    /// previous file. Returns the number of deleted keys.
+    pub async fn purge(&self, guild: Option<GuildId>) -> Result<usize, ApiKeyError> {
+        let mut keys = self.keys.lock().await;
+        let len = keys.len();
+        keys.retain(|_, key| guild.is_some_and(|guild| guild != key.guild));
+        disk::shred(&self.path)
+            .await
+            .map_err(|_| ApiKeyError::SaveFailed)?;
+        self.save(&keys).await?;
+        Ok(len - keys.len())
+    }
+
+    async fn save(&self, keys: &HashMap<String, ApiKey>) -> Result<(), ApiKeyError> {
+        let data = serde_json::to_vec(keys).map_err(|_| ApiKeyError::SaveFailed)?;
+        fs::write(&self.path, self.encryption.encrypt(data))
+            .await
+            .map_err(|_| ApiKeyError::SaveFailed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use serenity::model::id::{GuildId, UserId};
+
+    use super::{ApiKeys, Scope};
+    use crate::crypto::Encryption;
+
+    #[tokio::test]
+    async fn keys_are_scoped_to_their_guild() {
+        let path = std::env::temp_dir().join(format!("disrecord-api-keys-{}", std::process::id()));
+        let keys = ApiKeys::new(path.clone(), Arc::new(Encryption::new(None)))
+            .await
+            .unwrap();
+        let guild = GuildId::new(1);
+        let (token, key) = keys
+            .create(guild, Scope::PlaySounds, UserId::new(2))
+            .await
+            .unwrap();
+
+        assert!(keys
+            .authorize(&token, guild, Scope::PlaySounds)
+            .await
+            .is_some());
+        assert!(keys
+            .authorize(&token, guild, Scope::ReadRecordings)
+            .await
+            .is_none());
+        assert!(keys
+            .authorize(&token, GuildId::new(3), Scope::PlaySounds)
+            .await
+            .is_none());
+        assert!(keys.list(guild).await[0].last_used.is_some());
+        // Only the digest of the token is written.
+        let file = tokio::fs::read_to_string(&path).await.unwrap();
+        assert!(!file.contains(&token));
+
+        let reloaded = ApiKeys::new(path.clone(), Arc::new(Encryption::new(None)))
+            .await
+            .unwrap();
+        assert!(reloaded.revoke(GuildId::new(3), key.id).await.is_err());
+        reloaded.revoke(guild, key.id).await.unwrap();
+        assert!(reloaded
+            .authorize(&token, guild, Scope::PlaySounds)
+            .await
+            .is_none());
+        let _ = tokio::fs::remove_file(path).await;
+    }
+}
@@ -0,0 +1,136 @@
+use std::{
+    io,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{GuildId, UserId};
+use tokio::{fs, fs::OpenOptions, io::AsyncWriteExt, sync::Mutex};
+
+use crate::{
+    crypto::{Encryption, LoadError},
+    disk,
+};
+
+/// Export of recorded audio.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DownloadRecord {
+    pub at: SystemTime,
+    pub guild: GuildId,
+    pub requester: UserId,
+    /// Users whose audio was exported.
+    pub targets: Vec<UserId>,
+    /// Command used, such as `download` or `mixdown`.
+    pub kind: String,
+    /// Length of the exported audio.
+    pub duration: Duration,
+}
+
+/// Append-only log of the exports of recorded audio, persisted as JSON lines.
+/// With encryption enabled, the whole log is encrypted and rewritten instead of
+/// appended to.
+pub struct DownloadAudit {
+    path: PathBuf,
+    encryption: Arc<Encryption>,
+    records: Mutex<Vec<DownloadRecord>>,
+}
+
+impl DownloadAudit {
+    pub async fn new(path: PathBuf, encryption: Arc<Encryption>) -> Result<Self, LoadError> {
+        let records: Vec<DownloadRecord> = encryption
+            .read(&path)
+            .await?
+            .map(|file| {
+                String::from_utf8_lossy(&file)
+                    .lines()
+                    .filter_map(|line| serde_json::from_str(line).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        info!("loaded {} download audit records", records.len());
+
+        Ok(Self {
+            path,
+            encryption,
+            records: Mutex::new(records),
+        })
+    }
+
+    pub async fn record(&self, record: DownloadRecord) {
+        let mut records = self.records.lock().await;
+        records.push(record);
+        let written = if self.encryption.enabled() {
+            fs::write(&self.path, self.encryption.encrypt(to_lines(&records))).await
+        } else {
+            let line = to_lines(&records[records.len() - 1..]);
+            match OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)
+                .await
+            {
+                Ok(mut file) => file.write_all(&line).await,
+                Err(err) => Err(err),
+            }
+        };
+        if let Err(err) = written {
+            warn!("cannot write download audit record: {err}");
+        }
+    }
+
+    /// Latest records of a guild, optionally only those exporting the audio
+    /// of a user, newest first.
+    pub async fn latest(
+        &self,
+        guild: GuildId,
+        target: Option<UserId>,
+        max: usize,
+    ) -> Vec<DownloadRecord> {
+        self.records
+            .lock()
+            .await
+            .iter()
+            .rev()
+            .filter(|record| {
+                record.guild == guild && target.is_none_or(|user| record.targets.contains(&user))
+            })
+            .take(max)
+            .cloned()
+            .collect()
+    }
+
+    /// Number of exports of a user's audio in a guild within the period.
+    pub async fn count_since(&self, guild: GuildId, target: UserId, period: Duration) -> usize {
+        self.records
+            .lock()
+            .await
+            .iter()
+            .rev()
+            .take_while(|record| record.at.elapsed().is_ok_and(|age| age <= period))
+            .filter(|record| record.guild == guild && record.targets.contains(&target))
+            .count()
+    }
+
+    /// Delete the records of a guild, or every record if `None`, overwriting
+    /// the previous file. Returns the number of deleted records.
+    pub async fn purge(&self, guild: Option<GuildId>) -> io::Result<usize> {
+        let mut records = self.records.lock().await;
+        let len = records.len();
+        records.retain(|record| guild.is_some_and(|guild| guild != record.guild));
+        disk::shred(&self.path).await?;
+        fs::write(&self.path, self.encryption.encrypt(to_lines(&records))).await?;
+        Ok(len - records.len())
+    }
+}
+
+fn to_lines(records: &[DownloadRecord]) -> Vec<u8> {
+    let mut file = Vec::new();
+    for record in records {
+        file.extend(serde_json::to_vec(record).expect("Cannot serialize audit record"));
+        file.push(b'\n');
+    }
+    file
+}
@@ -3,12 +3,19 @@ use std::{
     hash::{Hash, Hasher},
 };
 
-use serenity::{all::ButtonStyle, builder::CreateButton, model::channel::ReactionType};
+use serenity::{
+    all::{ActionRow, ActionRowComponent, ButtonKind, ButtonStyle},
+    builder::{CreateActionRow, CreateButton},
+    model::channel::ReactionType,
+};
 
 use crate::soundboard::SoundMetadata;
 
 const DEFAULT: ButtonStyle = ButtonStyle::Primary;
 const DEFAULT_STR: &str = "blue";
+/// Style of the button of a looping sound while it plays, pressing it again
+/// stops the sound.
+const PLAYING_LOOP: ButtonStyle = ButtonStyle::Danger;
 
 pub fn parse_color(s: &str) -> ButtonStyle {
     match s {
@@ -92,4 +99,95 @@ impl SoundButton {
         }
         button
     }
+
+    /// Button of a looping sound while it plays.
+    pub fn create_playing(&self) -> CreateButton {
+        self.create()
+            .style(PLAYING_LOOP)
+            .emoji(ReactionType::from('⏹'))
+    }
+}
+
+/// Rows of a message with the button of `custom_id` replaced, other buttons
+/// being kept as is.
+pub fn replace(rows: &[ActionRow], custom_id: &str, button: CreateButton) -> Vec<CreateActionRow> {
+    rows.iter()
+        .map(|row| {
+            CreateActionRow::Buttons(
+                row.components
+                    .iter()
+                    .filter_map(|component| {
+                        // Soundboard messages only have sound buttons.
+                        let ActionRowComponent::Button(current) = component else {
+                            return None;
+                        };
+                        let ButtonKind::NonLink {
+                            custom_id: id,
+                            style,
+                        } = &current.data
+                        else {
+                            return None;
+                        };
+                        if id == custom_id {
+                            return Some(button.clone());
+                        }
+                        let mut copy = CreateButton::new(id)
+                            .style(*style)
+                            .disabled(current.disabled);
+                        if let Some(label) = &current.label {
+                            copy = copy.label(label);
+                        }
+                        if let Some(emoji) = &current.emoji {
+                            copy = copy.emoji(emoji.clone());
+                        }
+                        Some(copy)
+                    })
+                    .collect(),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+    use serenity::{
+        all::{ActionRow, ButtonStyle},
+        builder::CreateButton,
+    };
+
+    use super::replace;
+
+    #[test]
+    fn replaces_one_button() {
+        let rows: Vec<ActionRow> = serde_json::from_value(json!([{
+            "type": 1,
+            "components": [
+                { "type": 2, "style": 1, "label": "bruh", "custom_id": "sound" },
+                {
+                    "type": 2,
+                    "style": 3,
+                    "label": "Random",
+                    "custom_id": "random",
+                    "emoji": { "name": "🎲" },
+                    "disabled": true
+                },
+            ],
+        }]))
+        .unwrap();
+        let playing = CreateButton::new("sound")
+            .style(ButtonStyle::Danger)
+            .label("bruh");
+
+        let replaced = serde_json::to_value(replace(&rows, "sound", playing)).unwrap();
+        let buttons = &replaced[0]["components"];
+        assert_eq!(buttons.as_array().map(Vec::len), Some(2));
+        assert_eq!(buttons[0]["custom_id"], "sound");
+        assert_eq!(buttons[0]["style"], 4);
+        assert_eq!(buttons[1]["custom_id"], "random");
+        assert_eq!(buttons[1]["style"], 3);
+        assert_eq!(buttons[1]["label"], "Random");
+        assert_eq!(buttons[1]["emoji"]["name"], "🎲");
+        assert_eq!(buttons[1]["disabled"], Value::Bool(true));
+    }
 }
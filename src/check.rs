@@ -0,0 +1,234 @@
+use std::{
+    io::{Cursor, ErrorKind},
+    path::Path,
+    process::{ExitCode, Stdio},
+};
+
+use itertools::Itertools;
+use serenity::{all::ApplicationFlags, http::Http};
+use symphonia::core::{io::MediaSourceStream, probe::Hint};
+use tokio::{
+    fs::{self, OpenOptions},
+    process::Command,
+};
+
+use crate::{disk, options::Options};
+
+enum Status {
+    Ok,
+    Warning,
+    Failure,
+}
+
+struct Report {
+    failed: bool,
+}
+
+impl Report {
+    fn print(&mut self, status: Status, message: impl AsRef<str>) {
+        let label = match status {
+            Status::Ok => " OK ",
+            Status::Warning => "WARN",
+            Status::Failure => {
+                self.failed = true;
+                "FAIL"
+            }
+        };
+        println!("[{label}] {}", message.as_ref());
+    }
+}
+
+/// Validate the configuration and the environment without starting the bot,
+/// then print a readiness report.
+pub async fn run(options: &Options) -> ExitCode {
+    let mut report = Report { failed: false };
+
+    // Token and intents.
+    match &options.discord_token {
+        Some(token) => check_application(&mut report, token).await,
+        None => report.print(Status::Failure, "no Discord token given"),
+    }
+
+    // State files, rewritten in place or next to themselves.
+    #[cfg_attr(
+        not(any(feature = "http-api", feature = "transcription", feature = "storage")),
+        allow(unused_mut)
+    )]
+    let mut state_files = vec![
+        ("whitelist", options.record_whitelist_path.clone()),
+        (
+            "consents",
+            options.record_whitelist_path.with_extension("consents"),
+        ),
+        (
+            "soundboard metadata",
+            options.soundboard_metadata_path.clone(),
+        ),
+        ("guild settings", options.guild_settings_path.clone()),
+        ("schedules", options.schedules_path.clone()),
+        ("download audit", options.download_audit_path.clone()),
+    ];
+    #[cfg(feature = "http-api")]
+    state_files.push(("API keys", options.api_keys_path.clone()));
+    #[cfg(feature = "transcription")]
+    state_files.push(("user preferences", options.user_preferences_path.clone()));
+    #[cfg(feature = "storage")]
+    state_files.push(("storage index", options.storage.storage_index_path.clone()));
+    for (name, path) in &state_files {
+        match check_file_writable(path).await {
+            Ok(()) => report.print(
+                Status::Ok,
+                format!("{name} file {} is writable", path.display()),
+            ),
+            Err(err) => report.print(
+                Status::Failure,
+                format!("{name} file {} is not writable: {err}", path.display()),
+            ),
+        }
+    }
+
+    // Storage directories.
+    let dirs = state_files
+        .iter()
+        .map(|(_, path)| disk::parent_dir(path))
+        .chain([options.sounds_dir_path.clone()])
+        .unique();
+    for dir in dirs {
+        match check_writable(&dir).await {
+            Ok(()) => report.print(
+                Status::Ok,
+                format!("directory {} is writable", dir.display()),
+            ),
+            Err(err) => report.print(
+                Status::Failure,
+                format!("directory {} is not writable: {err}", dir.display()),
+            ),
+        }
+        match disk::available_space(&dir) {
+            Some(available) if available < options.min_free_space => report.print(
+                Status::Warning,
+                format!(
+                    "directory {} only has {available} bytes available",
+                    dir.display()
+                ),
+            ),
+            _ => (),
+        }
+    }
+
+    if let Some(cover) = &options.export_cover_path {
+        match fs::metadata(cover).await {
+            Ok(_) => report.print(
+                Status::Ok,
+                format!("export cover {} is readable", cover.display()),
+            ),
+            Err(err) => report.print(
+                Status::Warning,
+                format!(
+                    "export cover {} is not readable, MP4 exports will fail: {err}",
+                    cover.display()
+                ),
+            ),
+        }
+    }
+
+    // Optional binaries.
+    match Command::new(&options.ffmpeg_path)
+        .arg("-version")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+    {
+        Ok(status) if status.success() => report.print(
+            Status::Ok,
+            format!("{} is available", options.ffmpeg_path.display()),
+        ),
+        _ => report.print(
+            Status::Warning,
+            format!(
+                "{} is not available, only PCM s16le WAV sounds can be uploaded",
+                options.ffmpeg_path.display()
+            ),
+        ),
+    }
+
+    // Codecs.
+    let mut hint = Hint::new();
+    hint.with_extension("wav");
+    match symphonia::default::get_probe().format(
+        &hint,
+        MediaSourceStream::new(
+            Box::new(Cursor::new(include_bytes!("hello.wav").to_vec())),
+            Default::default(),
+        ),
+        &Default::default(),
+        &Default::default(),
+    ) {
+        Ok(_) => report.print(Status::Ok, "WAV decoding available"),
+        Err(err) => report.print(Status::Failure, format!("WAV decoding unavailable: {err}")),
+    }
+
+    if report.failed {
+        println!("disrecord is not ready");
+        ExitCode::FAILURE
+    } else {
+        println!("disrecord is ready");
+        ExitCode::SUCCESS
+    }
+}
+
+async fn check_writable(dir: &Path) -> std::io::Result<()> {
+    let path = dir.join(format!(".disrecord-check-{}", std::process::id()));
+    fs::write(&path, []).await?;
+    fs::remove_file(&path).await
+}
+
+/// Check that an existing file can be rewritten, missing files being created
+/// in a directory checked separately.
+async fn check_file_writable(path: &Path) -> std::io::Result<()> {
+    match OpenOptions::new().write(true).open(path).await {
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+/// Authenticate with the token and check the intents enabled for the bot.
+async fn check_application(report: &mut Report, token: &str) {
+    let http = Http::new(token);
+    match http.get_current_user().await {
+        Ok(user) => report.print(Status::Ok, format!("authenticated as {}", user.name)),
+        Err(err) => report.print(Status::Failure, format!("invalid Discord token: {err}")),
+    }
+    match http.get_current_application_info().await {
+        Ok(info) => {
+            let flags = info.flags.unwrap_or(ApplicationFlags::empty());
+            if flags.intersects(
+                ApplicationFlags::GATEWAY_PRESENCE | ApplicationFlags::GATEWAY_PRESENCE_LIMITED,
+            ) {
+                report.print(Status::Ok, "presence intent enabled");
+            } else {
+                report.print(
+                    Status::Failure,
+                    "presence intent is not enabled in the developer portal",
+                );
+            }
+            if flags.intersects(
+                ApplicationFlags::GATEWAY_GUILD_MEMBERS
+                    | ApplicationFlags::GATEWAY_GUILD_MEMBERS_LIMITED,
+            ) {
+                report.print(Status::Ok, "server members intent enabled");
+            } else {
+                report.print(
+                    Status::Failure,
+                    "server members intent is not enabled in the developer portal",
+                );
+            }
+        }
+        Err(err) => report.print(
+            Status::Failure,
+            format!("cannot fetch application info: {err}"),
+        ),
+    }
+}
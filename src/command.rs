@@ -2,7 +2,7 @@ use std::time::Duration;
 
 use regex::Regex;
 use serenity::{
-    all::{Context, GuildId, ResolvedOption, ResolvedValue},
+    all::{ChannelId, Context, GuildId, ResolvedOption, ResolvedValue, RoleId},
     model::{application::CommandInteraction, channel::Attachment, user::User},
 };
 
@@ -47,6 +47,18 @@ pub fn find_integer_option(
     }
 }
 
+pub fn find_number_option(
+    command: &CommandInteraction,
+    name: &str,
+    default: Option<f64>,
+) -> Option<f64> {
+    match find_option(command, name) {
+        Some(ResolvedValue::Number(n)) => Some(n),
+        Some(_) => None,
+        None => default,
+    }
+}
+
 pub fn find_boolean_option(
     command: &CommandInteraction,
     name: &str,
@@ -66,6 +78,20 @@ pub fn find_user_option<'a>(command: &'a CommandInteraction, name: &str) -> Opti
     }
 }
 
+pub fn find_channel_option(command: &CommandInteraction, name: &str) -> Option<ChannelId> {
+    match find_option(command, name) {
+        Some(ResolvedValue::Channel(c)) => Some(c.id),
+        _ => None,
+    }
+}
+
+pub fn find_role_option(command: &CommandInteraction, name: &str) -> Option<RoleId> {
+    match find_option(command, name) {
+        Some(ResolvedValue::Role(r)) => Some(r.id),
+        _ => None,
+    }
+}
+
 pub fn find_attachment_option<'a>(
     command: &'a CommandInteraction,
     name: &str,
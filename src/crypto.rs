@@ -0,0 +1,224 @@
+use std::{
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Key, Nonce,
+};
+use serde::de::DeserializeOwned;
+#[cfg(feature = "http-api")]
+use sha2::{Digest, Sha256};
+#[cfg(feature = "http-api")]
+use subtle::ConstantTimeEq;
+use thiserror::Error as ThisError;
+use tokio::fs;
+
+/// Prefix of encrypted files, files without it are read as plain text so
+/// existing data stays readable after enabling encryption.
+const MAGIC: &[u8] = b"DISRECORD-AES256GCM\0";
+const NONCE_SIZE: usize = 12;
+
+/// Key of the encryption at rest, redacted from debug output.
+#[derive(Clone)]
+pub struct EncryptionKey([u8; 32]);
+
+impl EncryptionKey {
+    /// Parse a key written as 64 hexadecimal characters.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        if input.len() != 64 || !input.is_ascii() {
+            return Err("expected 64 hexadecimal characters".to_owned());
+        }
+        let mut key = [0; 32];
+        for (byte, chunk) in key.iter_mut().zip(input.as_bytes().chunks(2)) {
+            let chunk = std::str::from_utf8(chunk).map_err(|err| err.to_string())?;
+            *byte = u8::from_str_radix(chunk, 16).map_err(|err| err.to_string())?;
+        }
+        Ok(Self(key))
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("EncryptionKey(..)")
+    }
+}
+
+/// AES-GCM encryption of files written to disk, a no-op without a key.
+#[derive(Default)]
+pub struct Encryption {
+    cipher: Option<Aes256Gcm>,
+}
+
+impl Encryption {
+    pub fn new(key: Option<EncryptionKey>) -> Self {
+        Self {
+            cipher: key.map(|key| Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key.0))),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    pub fn encrypt(&self, data: Vec<u8>) -> Vec<u8> {
+        let Some(cipher) = &self.cipher else {
+            return data;
+        };
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, data.as_slice())
+            .expect("Encryption failure");
+
+        let mut encrypted = Vec::with_capacity(MAGIC.len() + NONCE_SIZE + ciphertext.len());
+        encrypted.extend_from_slice(MAGIC);
+        encrypted.extend_from_slice(&nonce);
+        encrypted.extend(ciphertext);
+        encrypted
+    }
+
+    /// Plain text data is returned as is, encrypted data fails to decrypt
+    /// without the right key.
+    pub fn decrypt(&self, data: Vec<u8>) -> Option<Vec<u8>> {
+        let Some(encrypted) = data.strip_prefix(MAGIC) else {
+            return Some(data);
+        };
+        if encrypted.len() < NONCE_SIZE {
+            return None;
+        }
+        let (nonce, ciphertext) = encrypted.split_at(NONCE_SIZE);
+        self.cipher
+            .as_ref()?
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .ok()
+    }
+
+    /// Read and decrypt a file, `None` if it doesn't exist such as before it
+    /// is first written.
+    pub async fn read(&self, path: &Path) -> Result<Option<Vec<u8>>, LoadError> {
+        match fs::read(path).await {
+            Ok(file) => self
+                .decrypt(file)
+                .map(Some)
+                .ok_or_else(|| LoadError::Decrypt(path.to_owned())),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(LoadError::Read(path.to_owned(), err)),
+        }
+    }
+
+    /// Read, decrypt and parse a JSON file, the default value if it doesn't
+    /// exist.
+    pub async fn read_json<T: DeserializeOwned + Default>(
+        &self,
+        path: &Path,
+    ) -> Result<T, LoadError> {
+        match self.read(path).await? {
+            Some(file) => serde_json::from_slice(&file)
+                .map_err(|err| LoadError::Parse(path.to_owned(), err.to_string())),
+            None => Ok(T::default()),
+        }
+    }
+}
+
+/// State written to disk that cannot be loaded at startup, which would
+/// otherwise be overwritten.
+#[derive(ThisError, Debug)]
+pub enum LoadError {
+    #[error("Cannot read {}: {1}", .0.display())]
+    Read(PathBuf, io::Error),
+    #[error("Cannot decrypt {}, check the encryption key.", .0.display())]
+    Decrypt(PathBuf),
+    #[error("Cannot parse {}: {1}, fix or remove the file.", .0.display())]
+    Parse(PathBuf, String),
+    #[error("Cannot migrate {}: {1}", .0.display())]
+    Migrate(PathBuf, String),
+}
+
+/// SHA-256 digest of a random token, such as an API key, for it to be checked
+/// without being stored. Tokens are long and random, so unsalted digests
+/// cannot be reversed by guessing.
+#[cfg(feature = "http-api")]
+pub fn digest_token(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Compare digests in constant time, for timing not to leak how much of a
+/// digest matched.
+#[cfg(feature = "http-api")]
+pub fn digests_match(digest: &str, other: &str) -> bool {
+    digest.as_bytes().ct_eq(other.as_bytes()).into()
+}
+
+impl fmt::Debug for Encryption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Encryption")
+            .field("enabled", &self.enabled())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(feature = "http-api")]
+    use super::{digest_token, digests_match};
+    use std::collections::HashMap;
+
+    use super::{Encryption, EncryptionKey, LoadError};
+
+    #[test]
+    fn encryption_round_trip() {
+        let key = EncryptionKey::parse(&"2a".repeat(32)).unwrap();
+        let encryption = Encryption::new(Some(key));
+        let encrypted = encryption.encrypt(b"voice".to_vec());
+        assert_ne!(encrypted, b"voice");
+        assert_eq!(encryption.decrypt(encrypted.clone()).unwrap(), b"voice");
+
+        // Plain text files stay readable, encrypted ones need the key.
+        assert_eq!(encryption.decrypt(b"plain".to_vec()).unwrap(), b"plain");
+        assert_eq!(Encryption::default().decrypt(encrypted), None);
+        assert!(EncryptionKey::parse("2a").is_err());
+    }
+
+    #[tokio::test]
+    async fn unreadable_state_is_an_error() {
+        let encryption = Encryption::default();
+        let dir = std::env::temp_dir().join(format!("disrecord-read-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+
+        let missing = encryption
+            .read_json::<HashMap<String, u32>>(&dir.join("missing"))
+            .await;
+        assert!(missing.unwrap().is_empty());
+        let corrupt = dir.join("corrupt");
+        tokio::fs::write(&corrupt, b"{\"a\": 1").await.unwrap();
+        assert!(matches!(
+            encryption.read_json::<HashMap<String, u32>>(&corrupt).await,
+            Err(LoadError::Parse(..))
+        ));
+        // Other errors than a missing file aren't taken for an empty state.
+        assert!(matches!(
+            encryption.read(&dir).await,
+            Err(LoadError::Read(..))
+        ));
+        let _ = tokio::fs::remove_dir_all(dir).await;
+    }
+
+    #[cfg(feature = "http-api")]
+    #[test]
+    fn token_digests() {
+        let token = "a".repeat(40);
+        assert_eq!(
+            digest_token("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let other = format!("{}b", &token[..39]);
+        assert!(digests_match(&digest_token(&token), &digest_token(&token)));
+        assert!(!digests_match(&digest_token(&token), &digest_token(&other)));
+        assert!(!digests_match(&digest_token(&token), ""));
+    }
+}
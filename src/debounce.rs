@@ -0,0 +1,67 @@
+use std::{
+    collections::HashMap,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use serenity::model::id::{InteractionId, UserId};
+
+/// How long interaction IDs are remembered, Discord redelivers duplicates
+/// within seconds.
+const INTERACTION_MEMORY: Duration = Duration::from_secs(60);
+
+/// Filters duplicate component interactions, delivered twice on flaky
+/// connections, and repeated presses of the same button by the same user.
+pub struct Debounce {
+    window: Duration,
+    interactions: Mutex<HashMap<InteractionId, Instant>>,
+    presses: Mutex<HashMap<(UserId, String), Instant>>,
+}
+
+impl Debounce {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            interactions: Mutex::new(HashMap::new()),
+            presses: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Whether the interaction wasn't seen already.
+    pub fn first_delivery(&self, interaction: InteractionId) -> bool {
+        let now = Instant::now();
+        let mut interactions = self.interactions.lock().expect("Debounce lock poisoned");
+        interactions.retain(|_, seen| now.duration_since(*seen) < INTERACTION_MEMORY);
+        interactions.insert(interaction, now).is_none()
+    }
+
+    /// Whether the user didn't press the same button within the window.
+    pub fn accept_press(&self, user: UserId, custom_id: &str) -> bool {
+        let now = Instant::now();
+        let mut presses = self.presses.lock().expect("Debounce lock poisoned");
+        presses.retain(|_, pressed| now.duration_since(*pressed) < self.window);
+        presses.insert((user, custom_id.to_owned()), now).is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serenity::model::id::{InteractionId, UserId};
+
+    use super::Debounce;
+
+    #[test]
+    fn duplicates_are_rejected() {
+        let debounce = Debounce::new(Duration::from_secs(60));
+        assert!(debounce.first_delivery(InteractionId::new(1)));
+        assert!(!debounce.first_delivery(InteractionId::new(1)));
+        assert!(debounce.first_delivery(InteractionId::new(2)));
+
+        assert!(debounce.accept_press(UserId::new(1), "random"));
+        assert!(!debounce.accept_press(UserId::new(1), "random"));
+        assert!(debounce.accept_press(UserId::new(2), "random"));
+        assert!(debounce.accept_press(UserId::new(1), "latest"));
+    }
+}
@@ -0,0 +1,52 @@
+use std::time::{Duration, Instant};
+
+use tokio::{net::TcpStream, time::timeout};
+
+/// Round trips slower than this point at the network to the voice server.
+pub const HIGH_ROUND_TRIP: Duration = Duration::from_millis(150);
+/// Packet loss above this ratio is audible in recordings.
+pub const HIGH_LOSS: f64 = 0.02;
+/// Recorder backlog, in actions, above which the bot falls behind the voice
+/// it receives.
+pub const HIGH_QUEUE: usize = 100;
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Region of a voice server from its endpoint, such as `ams` for
+/// `c-ams08-1a2b.discord.media:443` or `us-east` for `us-east123.discord.gg`.
+pub fn voice_region(endpoint: &str) -> &str {
+    let host = endpoint.split(['.', ':']).next().unwrap_or(endpoint);
+    let host = host.strip_prefix("c-").unwrap_or(host);
+    host[..host
+        .find(|c: char| c.is_ascii_digit())
+        .unwrap_or(host.len())]
+        .trim_end_matches('-')
+}
+
+/// Round trip time to a voice server. Songbird doesn't expose the one of its
+/// UDP socket, the TCP handshake with the voice endpoint is timed instead.
+pub async fn round_trip(endpoint: &str) -> Option<Duration> {
+    let address = if endpoint.contains(':') {
+        endpoint.to_owned()
+    } else {
+        format!("{endpoint}:443")
+    };
+    let start = Instant::now();
+    timeout(CONNECT_TIMEOUT, TcpStream::connect(address))
+        .await
+        .ok()?
+        .ok()?;
+    Some(start.elapsed())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::voice_region;
+
+    #[test]
+    fn voice_regions() {
+        assert_eq!(voice_region("c-ams08-1a2b.discord.media:443"), "ams");
+        assert_eq!(voice_region("us-east123.discord.gg"), "us-east");
+        assert_eq!(voice_region("localhost"), "localhost");
+    }
+}
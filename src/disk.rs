@@ -0,0 +1,117 @@
+use std::{
+    io::{self, ErrorKind},
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use itertools::Itertools;
+use log::{error, info, warn};
+use serenity::{http::Http, model::id::ChannelId};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt, time::sleep};
+
+use crate::metrics::{self, Metrics};
+
+pub fn available_space(path: &Path) -> Option<u64> {
+    fs2::available_space(path).ok()
+}
+
+/// Check if `required` bytes can be written to the volume of `path` while
+/// keeping at least `min_free` bytes available. Optimistically returns true if
+/// the free space cannot be queried.
+pub fn has_space_for(path: &Path, min_free: u64, required: u64) -> bool {
+    available_space(path)
+        .map(|available| available >= min_free.saturating_add(required))
+        .unwrap_or(true)
+}
+
+/// Returns the directory containing a file, `.` if the file path is relative
+/// and has no parent.
+pub fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Overwrite the content of a file with zeros before it is rewritten or
+/// deleted, so that its previous data doesn't linger on the disk. Missing
+/// files are ignored.
+pub async fn shred(path: &Path) -> io::Result<()> {
+    let mut file = match OpenOptions::new().write(true).open(path).await {
+        Ok(file) => file,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err),
+    };
+    let zeros = [0; 64 * 1024];
+    let mut left = file.metadata().await?.len();
+    while left > 0 {
+        let len = left.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..len]).await?;
+        left -= len as u64;
+    }
+    file.sync_all().await
+}
+
+/// Watch the free space of storage paths, alerting `log_channel` once each
+/// time a path runs low or recovers.
+pub fn monitor_loop(
+    paths: Vec<PathBuf>,
+    min_free: u64,
+    metrics: Arc<Metrics>,
+    http: Arc<Http>,
+    log_channel: Option<ChannelId>,
+) {
+    let paths = paths.into_iter().unique().collect_vec();
+    tokio::spawn(async move {
+        let mut low = vec![false; paths.len()];
+        loop {
+            for (path, low) in paths.iter().zip(&mut low) {
+                let Some(available) = available_space(path) else {
+                    continue;
+                };
+                metrics.set_gauge(
+                    "disrecord_free_space_bytes",
+                    "Space available on the volume of a storage path.",
+                    &metrics::labels(&[("path", &path.to_string_lossy())]),
+                    available as f64,
+                );
+
+                let is_low = available < min_free;
+                let alert = if is_low && !*low {
+                    error!(
+                        "low disk space for {}: {} bytes available, uploads will be refused",
+                        path.display(),
+                        available
+                    );
+                    format!(
+                        "⚠️ Low disk space for `{}`: {} bytes available, uploads will be refused.",
+                        path.display(),
+                        available
+                    )
+                } else if !is_low && *low {
+                    info!(
+                        "disk space recovered for {}: {} bytes available",
+                        path.display(),
+                        available
+                    );
+                    format!(
+                        "✅ Disk space recovered for `{}`: {} bytes available.",
+                        path.display(),
+                        available
+                    )
+                } else {
+                    continue;
+                };
+                *low = is_low;
+
+                if let Some(channel) = log_channel {
+                    if let Err(err) = channel.say(&http, alert).await {
+                        warn!("cannot post disk space alert: {err}");
+                    }
+                }
+            }
+            sleep(Duration::from_secs(30)).await;
+        }
+    });
+}
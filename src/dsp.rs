@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{recorder::FREQUENCY, vad};
+
+/// Drop frames quieter than `threshold` (in dBFS), unless a louder frame was
+/// received less than `hold` ago.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct NoiseGate {
+    pub threshold: f32,
+    pub hold: Duration,
+}
+
+impl NoiseGate {
+    /// `remaining` is the number of samples the gate will stay open for,
+    /// tracked by the caller for each source.
+    pub fn accept(&self, remaining: &mut usize, frame: &[i16]) -> bool {
+        if vad::level(frame) >= self.threshold {
+            *remaining = self.hold.as_millis() as usize * FREQUENCY / 1000;
+            true
+        } else if *remaining > 0 {
+            *remaining = remaining.saturating_sub(frame.len());
+            true
+        } else {
+            false
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use std::error::Error;
+
+use log::warn;
+
+/// Error shown to users, with a stable code they can quote when asking for
+/// help.
+pub trait Coded: Error {
+    /// Number of the error, shown as `DR-<code>`. Codes are grouped by
+    /// module: 1xx soundboard, 2xx packs, 3xx storage, 4xx persisted state,
+    /// 5xx clips, 6xx interactions, 7xx API keys.
+    fn code(&self) -> u16;
+
+    /// What the user can do about the error, if anything.
+    fn hint(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// Message of an error to show users, logged along with what was being done.
+pub fn report(err: &impl Coded, context: &str) -> String {
+    warn!("DR-{} while handling {context}: {err}", err.code());
+    match err.hint() {
+        Some(hint) => format!("DR-{}: {err} {hint}", err.code()),
+        None => format!("DR-{}: {err}", err.code()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::report;
+    use crate::{schedule::ScheduleError, soundboard::SoundboardError};
+
+    #[test]
+    fn reports_code_and_hint() {
+        assert_eq!(
+            report(&ScheduleError::NotFound, "test"),
+            "DR-404: Schedule not found."
+        );
+        assert_eq!(
+            report(&SoundboardError::LowDiskSpace, "test"),
+            "DR-107: Not enough disk space left to save this sound. Ask the bot host to free some space."
+        );
+    }
+}
@@ -0,0 +1,312 @@
+use std::{path::PathBuf, sync::Arc};
+
+use log::warn;
+use reqwest::header;
+use serenity::async_trait;
+use ulid::Ulid;
+
+use crate::{
+    ffmpeg,
+    recorder::FREQUENCY,
+    resample,
+    wav::{self, SampleFormat},
+};
+
+/// Name of the exporter used when none is requested, or when the requested one
+/// fails.
+pub const DEFAULT_EXPORTER: &str = "wav";
+
+/// Information about an exported recording.
+pub struct Metadata {
+    /// File name without extension, usually the name of the recorded user.
+    pub name: String,
+    /// Embedded in the exported files, such as the legal notice of the guild.
+    pub comment: Option<String>,
+    /// Number of interleaved channels of the samples, 2 for stereo mixdowns.
+    pub channels: u16,
+    /// Rate the samples are exported at, one of the storage rates.
+    pub sample_rate: usize,
+    /// Sample format of lossless exports, lossy ones choosing their own.
+    pub sample_format: SampleFormat,
+    /// Image shown by video exports when none is configured, such as the
+    /// guild icon.
+    pub cover_url: Option<String>,
+}
+
+/// File produced by an exporter.
+pub struct Artifact {
+    pub filename: String,
+    pub data: Vec<u8>,
+}
+
+/// Audio format recordings can be exported to.
+#[async_trait]
+pub trait Exporter: Send + Sync {
+    /// Name shown as a choice of the commands' format options.
+    fn name(&self) -> &'static str;
+
+    fn extension(&self) -> &'static str;
+
+    /// Name of the main artifact.
+    fn filename(&self, metadata: &Metadata) -> String {
+        format!("{}.{}", metadata.name, self.extension())
+    }
+
+    /// Encode PCM s16le samples, already resampled to the rate of the
+    /// metadata, `None` if the encoder is unavailable.
+    async fn encode(&self, pcm: &[i16], metadata: &Metadata) -> Option<Vec<Artifact>>;
+}
+
+struct Wav;
+
+#[async_trait]
+impl Exporter for Wav {
+    fn name(&self) -> &'static str {
+        "wav"
+    }
+
+    fn extension(&self) -> &'static str {
+        "wav"
+    }
+
+    async fn encode(&self, pcm: &[i16], metadata: &Metadata) -> Option<Vec<Artifact>> {
+        let mut data = wav::package_as(
+            pcm,
+            metadata.channels,
+            metadata.sample_rate,
+            metadata.sample_format,
+        );
+        if let Some(comment) = &metadata.comment {
+            wav::append_comment(&mut data, comment);
+        }
+        Some(vec![Artifact {
+            filename: self.filename(metadata),
+            data,
+        }])
+    }
+}
+
+/// Format encoded by ffmpeg.
+struct Ffmpeg {
+    ffmpeg_path: PathBuf,
+    name: &'static str,
+    extension: &'static str,
+    output_args: &'static [&'static str],
+    /// Whether the codec keeps more than 16 bits per sample if asked to.
+    lossless: bool,
+}
+
+#[async_trait]
+impl Exporter for Ffmpeg {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn extension(&self) -> &'static str {
+        self.extension
+    }
+
+    async fn encode(&self, pcm: &[i16], metadata: &Metadata) -> Option<Vec<Artifact>> {
+        let comment = metadata
+            .comment
+            .as_ref()
+            .map(|comment| format!("comment={comment}"));
+        let mut output_args = self.output_args.to_vec();
+        if let Some(comment) = &comment {
+            output_args.extend(["-metadata", comment]);
+        }
+        // FLAC has no floating point samples, both are stored as 24 bits.
+        if self.lossless && metadata.sample_format != SampleFormat::S16 {
+            output_args.extend(["-sample_fmt", "s32", "-bits_per_raw_sample", "24"]);
+        }
+        let data = ffmpeg::encode(
+            &self.ffmpeg_path,
+            pcm,
+            metadata.channels,
+            metadata.sample_rate,
+            &output_args,
+        )
+        .await?;
+        Some(vec![Artifact {
+            filename: self.filename(metadata),
+            data,
+        }])
+    }
+}
+
+/// Audio muxed with a still cover image into an MP4 video, for platforms only
+/// accepting videos.
+struct Video {
+    ffmpeg_path: PathBuf,
+    /// Cover of every export, the one of the metadata if unset.
+    cover_path: Option<PathBuf>,
+}
+
+impl Video {
+    /// Download the cover of an export to a temporary file, named with the
+    /// extension of its type for ffmpeg to pick its decoder, Discord icons
+    /// being WebP or GIF images.
+    async fn download_cover(url: &str) -> Option<PathBuf> {
+        let response = reqwest::get(url)
+            .await
+            .and_then(|response| response.error_for_status());
+        let response = match response {
+            Ok(response) => response,
+            Err(err) => {
+                warn!("cannot download export cover {url}: {err}");
+                return None;
+            }
+        };
+        let extension = response
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|mime| mime.strip_prefix("image/"))
+            .and_then(|subtype| subtype.split(';').next())
+            .map(str::trim)
+            .filter(|subtype| {
+                !subtype.is_empty() && subtype.chars().all(|c| c.is_ascii_alphanumeric())
+            })
+            .unwrap_or("png")
+            .to_owned();
+        let image = response.bytes().await.ok()?;
+        let path =
+            std::env::temp_dir().join(format!("disrecord-cover-{}.{extension}", Ulid::new()));
+        tokio::fs::write(&path, image).await.ok()?;
+        Some(path)
+    }
+}
+
+#[async_trait]
+impl Exporter for Video {
+    fn name(&self) -> &'static str {
+        "mp4"
+    }
+
+    fn extension(&self) -> &'static str {
+        "mp4"
+    }
+
+    async fn encode(&self, pcm: &[i16], metadata: &Metadata) -> Option<Vec<Artifact>> {
+        let downloaded = match (&self.cover_path, &metadata.cover_url) {
+            (None, Some(url)) => Self::download_cover(url).await,
+            _ => None,
+        };
+        let comment = metadata
+            .comment
+            .as_ref()
+            .map(|comment| format!("comment={comment}"));
+        let output_args = match &comment {
+            Some(comment) => vec!["-metadata", comment],
+            None => Vec::new(),
+        };
+        let data = ffmpeg::encode_video(
+            &self.ffmpeg_path,
+            pcm,
+            metadata.channels,
+            metadata.sample_rate,
+            self.cover_path.as_deref().or(downloaded.as_deref()),
+            &output_args,
+        )
+        .await;
+        if let Some(downloaded) = downloaded {
+            let _ = tokio::fs::remove_file(downloaded).await;
+        }
+        Some(vec![Artifact {
+            filename: self.filename(metadata),
+            data: data?,
+        }])
+    }
+}
+
+/// Registered exporters, in the order they are offered.
+pub struct Exporters {
+    exporters: Vec<Arc<dyn Exporter>>,
+}
+
+impl Exporters {
+    /// Registry of the built-in WAV, FLAC, Ogg Opus, MP3 and MP4 exporters,
+    /// MP4 videos showing `cover_path` or the guild icon.
+    pub fn new(ffmpeg_path: PathBuf, cover_path: Option<PathBuf>) -> Self {
+        let mut exporters = Self {
+            exporters: vec![Arc::new(Wav)],
+        };
+        let formats: [(_, _, &'static [&'static str]); 3] = [
+            ("flac", "flac", &["-c:a", "flac", "-f", "flac"]),
+            (
+                "ogg",
+                "ogg",
+                &[
+                    "-c:a",
+                    "libopus",
+                    "-b:a",
+                    "64k",
+                    "-application",
+                    "voip",
+                    "-f",
+                    "ogg",
+                ],
+            ),
+            (
+                "mp3",
+                "mp3",
+                &["-c:a", "libmp3lame", "-q:a", "4", "-f", "mp3"],
+            ),
+        ];
+        for (name, extension, output_args) in formats {
+            exporters.register(Arc::new(Ffmpeg {
+                ffmpeg_path: ffmpeg_path.clone(),
+                name,
+                extension,
+                output_args,
+                lossless: name == "flac",
+            }));
+        }
+        exporters.register(Arc::new(Video {
+            ffmpeg_path,
+            cover_path,
+        }));
+        exporters
+    }
+
+    /// Add an exporter, replacing any previous one with the same name.
+    pub fn register(&mut self, exporter: Arc<dyn Exporter>) {
+        match self
+            .exporters
+            .iter_mut()
+            .find(|e| e.name() == exporter.name())
+        {
+            Some(previous) => *previous = exporter,
+            None => self.exporters.push(exporter),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<dyn Exporter>> {
+        self.exporters.iter().find(|e| e.name() == name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.exporters.iter().map(|e| e.name())
+    }
+
+    /// Resample captured samples to the rate of the metadata and export them
+    /// using the named exporter, falling back to WAV if it is unknown or
+    /// fails.
+    pub async fn export(&self, name: &str, pcm: &[i16], metadata: &Metadata) -> Vec<Artifact> {
+        let resampled;
+        let pcm = if metadata.sample_rate == FREQUENCY {
+            pcm
+        } else {
+            resampled = resample::downsample(pcm, metadata.channels, metadata.sample_rate);
+            &resampled
+        };
+        if let Some(exporter) = self.get(name) {
+            if let Some(artifacts) = exporter.encode(pcm, metadata).await {
+                return artifacts;
+            }
+        }
+        Wav.encode(pcm, metadata)
+            .await
+            .expect("WAV export cannot fail")
+    }
+}
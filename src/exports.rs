@@ -0,0 +1,77 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+};
+
+use serenity::model::id::GuildId;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Limits the number of exports running at once, globally and per guild.
+/// Waiting exports are served in order.
+pub struct Exports {
+    global: Arc<Semaphore>,
+    guild_limit: usize,
+    guilds: Mutex<HashMap<GuildId, Arc<Semaphore>>>,
+    queued: AtomicUsize,
+}
+
+/// Allows an export to run until dropped.
+pub struct ExportPermit {
+    _guild: OwnedSemaphorePermit,
+    _global: OwnedSemaphorePermit,
+}
+
+impl Exports {
+    pub fn new(global_limit: usize, guild_limit: usize) -> Self {
+        Self {
+            global: Arc::new(Semaphore::new(global_limit.max(1))),
+            guild_limit: guild_limit.max(1),
+            guilds: Mutex::new(HashMap::new()),
+            queued: AtomicUsize::new(0),
+        }
+    }
+
+    /// Number of exports waiting to start.
+    pub fn queued(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    pub fn try_acquire(&self, guild: GuildId) -> Option<ExportPermit> {
+        Some(ExportPermit {
+            _guild: self.guild(guild).try_acquire_owned().ok()?,
+            _global: Arc::clone(&self.global).try_acquire_owned().ok()?,
+        })
+    }
+
+    pub async fn acquire(&self, guild: GuildId) -> ExportPermit {
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        let guild_permit = self
+            .guild(guild)
+            .acquire_owned()
+            .await
+            .expect("Export semaphore closed");
+        let global_permit = Arc::clone(&self.global)
+            .acquire_owned()
+            .await
+            .expect("Export semaphore closed");
+        self.queued.fetch_sub(1, Ordering::Relaxed);
+
+        ExportPermit {
+            _guild: guild_permit,
+            _global: global_permit,
+        }
+    }
+
+    fn guild(&self, guild: GuildId) -> Arc<Semaphore> {
+        Arc::clone(
+            self.guilds
+                .lock()
+                .expect("Export limits lock poisoned")
+                .entry(guild)
+                .or_insert_with(|| Arc::new(Semaphore::new(self.guild_limit))),
+        )
+    }
+}
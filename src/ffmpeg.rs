@@ -0,0 +1,218 @@
+use std::{path::Path, process::Stdio};
+
+use itertools::Itertools;
+use tokio::{io::AsyncWriteExt, process::Command};
+use ulid::Ulid;
+
+use crate::recorder::FREQUENCY;
+
+/// Pipe `data` through ffmpeg and return its output. The input format should
+/// be specified in `input_args` as reading from stdin prevents ffmpeg from
+/// seeking to probe it.
+pub async fn pipe(
+    ffmpeg_path: &Path,
+    data: Vec<u8>,
+    input_args: &[&str],
+    output_args: &[&str],
+) -> Option<Vec<u8>> {
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.args(input_args)
+        .args(["-i", "-"]) // Read from stdin.
+        .args(output_args)
+        .arg("-") // Output to stdout.
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null());
+    let mut child = cmd.spawn().ok()?;
+
+    let mut stdin = child.stdin.take()?;
+    tokio::spawn(async move {
+        // Ffmpeg may stop reading early on invalid input, the error is caught
+        // using the exit status.
+        let _ = stdin.write_all(&data).await;
+    });
+
+    let out = child.wait_with_output().await.ok()?;
+    out.status.success().then_some(out.stdout)
+}
+
+/// Decode audio of any format ffmpeg recognizes to mono PCM s16le samples.
+/// It is written to a temporary file for ffmpeg to probe it, formats such as
+/// M4A needing to be seeked.
+pub async fn decode_pcm(ffmpeg_path: &Path, data: Vec<u8>) -> Option<Vec<i16>> {
+    let path = std::env::temp_dir().join(format!("disrecord-decode-{}", Ulid::new()));
+    tokio::fs::write(&path, data).await.ok()?;
+    let frequency = FREQUENCY.to_string();
+    let out = Command::new(ffmpeg_path)
+        .arg("-i")
+        .arg(&path)
+        .args(["-f", "s16le", "-ar", &frequency, "-ac", "1", "-"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .output()
+        .await;
+    let _ = tokio::fs::remove_file(&path).await;
+    let out = out.ok().filter(|out| out.status.success())?;
+    Some(
+        out.stdout
+            .chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect_vec(),
+    )
+}
+
+/// Apply an audio filter graph to mono PCM s16le samples.
+pub async fn filter_pcm(ffmpeg_path: &Path, pcm: &[i16], filter: &str) -> Option<Vec<i16>> {
+    let frequency = FREQUENCY.to_string();
+    let out = pipe(
+        ffmpeg_path,
+        pcm.iter().flat_map(|n| n.to_le_bytes()).collect(),
+        &["-f", "s16le", "-ar", &frequency, "-ac", "1"],
+        &["-af", filter, "-f", "s16le", "-ar", &frequency, "-ac", "1"],
+    )
+    .await?;
+    Some(
+        out.chunks_exact(2)
+            .map(|b| i16::from_le_bytes([b[0], b[1]]))
+            .collect_vec(),
+    )
+}
+
+/// Render mono PCM s16le samples to a PNG image, `filter` being a filter
+/// graph turning audio into a single picture such as `showwavespic`.
+pub async fn render_png(ffmpeg_path: &Path, pcm: &[i16], filter: &str) -> Option<Vec<u8>> {
+    let frequency = FREQUENCY.to_string();
+    pipe(
+        ffmpeg_path,
+        pcm.iter().flat_map(|n| n.to_le_bytes()).collect(),
+        &["-f", "s16le", "-ar", &frequency, "-ac", "1"],
+        &[
+            "-filter_complex",
+            filter,
+            "-frames:v",
+            "1",
+            "-c:v",
+            "png",
+            "-f",
+            "image2pipe",
+        ],
+    )
+    .await
+}
+
+/// Mux PCM s16le samples of `channels` interleaved channels sampled at `rate`
+/// with a still image, or a black background if there is none, into an MP4
+/// video. `output_args` are added to the encoding arguments, such as metadata.
+pub async fn encode_video(
+    ffmpeg_path: &Path,
+    pcm: &[i16],
+    channels: u16,
+    rate: usize,
+    image: Option<&Path>,
+    output_args: &[&str],
+) -> Option<Vec<u8>> {
+    let frequency = rate.to_string();
+    let channels = channels.to_string();
+    let image = image.map(|image| image.to_string_lossy().into_owned());
+    let mut input_args = match &image {
+        // The image demuxer loops a single frame, even of animated images.
+        Some(image) => vec!["-f", "image2", "-loop", "1", "-framerate", "1", "-i", image],
+        None => vec!["-f", "lavfi", "-i", "color=c=black:s=640x360:r=1"],
+    };
+    input_args.extend(["-f", "s16le", "-ar", &frequency, "-ac", &channels]);
+    let mut args = vec![
+        "-map",
+        "0:v",
+        "-map",
+        "1:a",
+        // H.264 needs even dimensions.
+        "-vf",
+        "scale=trunc(iw/2)*2:trunc(ih/2)*2",
+        "-c:v",
+        "libx264",
+        "-tune",
+        "stillimage",
+        "-pix_fmt",
+        "yuv420p",
+        "-c:a",
+        "aac",
+        "-b:a",
+        "128k",
+        "-shortest",
+        // Written to stdout, which can't be seeked back to the header.
+        "-movflags",
+        "frag_keyframe+empty_moov",
+    ];
+    args.extend(output_args);
+    args.extend(["-f", "mp4"]);
+    pipe(
+        ffmpeg_path,
+        pcm.iter().flat_map(|n| n.to_le_bytes()).collect(),
+        &input_args,
+        &args,
+    )
+    .await
+}
+
+/// Encode PCM s16le samples of `channels` interleaved channels sampled at
+/// `rate`, `output_args` selecting the codec and container.
+pub async fn encode(
+    ffmpeg_path: &Path,
+    pcm: &[i16],
+    channels: u16,
+    rate: usize,
+    output_args: &[&str],
+) -> Option<Vec<u8>> {
+    let frequency = rate.to_string();
+    let channels = channels.to_string();
+    pipe(
+        ffmpeg_path,
+        pcm.iter().flat_map(|n| n.to_le_bytes()).collect(),
+        &["-f", "s16le", "-ar", &frequency, "-ac", &channels],
+        output_args,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tokio::process::Command;
+
+    use super::encode_video;
+    use crate::recorder::FREQUENCY;
+
+    #[tokio::test]
+    async fn encodes_video_with_animated_cover() {
+        let ffmpeg = Path::new("ffmpeg");
+        if Command::new(ffmpeg).arg("-version").output().await.is_err() {
+            eprintln!("ffmpeg not found, skipping");
+            return;
+        }
+        let cover =
+            std::env::temp_dir().join(format!("disrecord-cover-{}.gif", std::process::id()));
+        let status = Command::new(ffmpeg)
+            .args([
+                "-y",
+                "-f",
+                "lavfi",
+                "-i",
+                "testsrc=s=64x48:r=2",
+                "-frames:v",
+                "3",
+            ])
+            .arg(&cover)
+            .output()
+            .await
+            .unwrap()
+            .status;
+        assert!(status.success());
+
+        let video =
+            encode_video(ffmpeg, &vec![0; FREQUENCY], 1, FREQUENCY, Some(&cover), &[]).await;
+        let _ = tokio::fs::remove_file(&cover).await;
+        assert!(video.is_some_and(|video| !video.is_empty()));
+    }
+}
@@ -0,0 +1,80 @@
+use std::{pin::pin, time::Duration};
+
+use log::{debug, warn};
+use serenity::{
+    all::PremiumTier,
+    cache::Cache,
+    client::Context,
+    model::id::{ChannelId, GuildId, UserId},
+};
+use tokio::{sync::Notify, time::timeout};
+
+/// Time given to the gateway to send a guild missing from the cache.
+const GUILD_CREATE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Whether the guild is cached. The cache is cold right after startup or a
+/// gateway resume, until the gateway sends the guild along with its voice
+/// states, `arrivals` being notified whenever it sends one.
+pub async fn ensure_guild(ctx: &Context, arrivals: &Notify, guild: GuildId) -> bool {
+    if ctx.cache.guild(guild).is_some() {
+        return true;
+    }
+    debug!("guild {guild} not cached, waiting for it");
+    let wait = async {
+        loop {
+            // Registered before looking the cache up, not to miss the guild
+            // arriving in between.
+            let mut arrival = pin!(arrivals.notified());
+            arrival.as_mut().enable();
+            if ctx.cache.guild(guild).is_some() {
+                return;
+            }
+            arrival.await;
+        }
+    };
+    timeout(GUILD_CREATE_TIMEOUT, wait).await.is_ok()
+}
+
+/// Voice channel a user is in, from the cached voice states.
+pub fn find_voice_channel(cache: &Cache, guild: GuildId, user: UserId) -> Option<ChannelId> {
+    cache.guild(guild)?.voice_states.get(&user)?.channel_id
+}
+
+/// Voice channel a user is in, waiting for the guild if it isn't cached yet.
+pub async fn voice_channel(
+    ctx: &Context,
+    arrivals: &Notify,
+    guild: GuildId,
+    user: UserId,
+) -> Option<ChannelId> {
+    ensure_guild(ctx, arrivals, guild).await;
+    find_voice_channel(&ctx.cache, guild, user)
+}
+
+/// Boost level of a guild, fetched over HTTP if it isn't cached.
+pub async fn premium_tier(ctx: &Context, guild: GuildId) -> PremiumTier {
+    if let Some(tier) = ctx.cache.guild(guild).map(|guild| guild.premium_tier) {
+        return tier;
+    }
+    match guild.to_partial_guild(&ctx.http).await {
+        Ok(guild) => guild.premium_tier,
+        Err(err) => {
+            warn!("cannot fetch guild {guild}: {err}");
+            PremiumTier::Tier0
+        }
+    }
+}
+
+/// Icon of a guild, fetched over HTTP if it isn't cached.
+pub async fn icon_url(ctx: &Context, guild: GuildId) -> Option<String> {
+    if let Some(guild) = ctx.cache.guild(guild) {
+        return guild.icon_url();
+    }
+    match guild.to_partial_guild(&ctx.http).await {
+        Ok(guild) => guild.icon_url(),
+        Err(err) => {
+            warn!("cannot fetch guild {guild}: {err}");
+            None
+        }
+    }
+}
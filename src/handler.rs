@@ -0,0 +1,1002 @@
+#[cfg(feature = "http-api")]
+use std::sync::OnceLock;
+use std::{
+    cell::Cell,
+    collections::HashMap,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use log::{debug, info, warn};
+use serenity::{
+    all::{
+        AutocompleteChoice, ChannelId, ChannelType, CommandInteraction, CommandOptionType,
+        ComponentInteraction, CreateAllowedMentions, CreateAutocompleteResponse,
+        CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, CreateThread,
+        Guild, GuildId, GuildMemberUpdateEvent, HttpError, Interaction, Member, Mention, MessageId,
+        PremiumTier, Reaction, ReactionType, Ready, UserId, VoiceState,
+    },
+    async_trait,
+    builder::CreateInteractionResponseFollowup,
+    client::{Context, EventHandler},
+    prelude::SerenityError,
+};
+use songbird::{tracks::TrackHandle, Event, TrackEvent};
+use tokio::sync::{oneshot, Mutex, Notify};
+use ulid::Ulid;
+
+#[cfg(feature = "transcription")]
+use crate::prefs;
+#[cfg(feature = "http-api")]
+use crate::share;
+#[cfg(feature = "storage")]
+use crate::storage;
+use crate::{
+    audit::DownloadAudit,
+    debounce::Debounce,
+    error_code,
+    exporter::Exporters,
+    exports::Exports,
+    guild_cache,
+    history::History,
+    indicator::Indicator,
+    interaction::InteractionError,
+    metrics::{self, Metrics},
+    recorder::{Recorder, RecorderAction, RecorderStats},
+    schedule::Schedules,
+    settings::Settings,
+    shutdown::InFlight,
+    soundboard::Soundboard,
+    stats::Stats,
+    timeline::Timeline,
+    trim::Trims,
+    upload::Uploads,
+};
+use session::Session;
+use soundboard::{PlaybackLatency, PlayingLoop};
+use whitelist::{consent_expiry_loop, migrate_legacy_whitelist};
+
+#[cfg(feature = "http-api")]
+mod api_keys;
+mod export;
+mod recording;
+mod register;
+mod schedule;
+mod session;
+mod settings;
+mod soundboard;
+mod voice;
+mod whitelist;
+
+pub(crate) use soundboard::play_sound;
+#[cfg(feature = "http-api")]
+pub(crate) use voice::current_voice_channel;
+
+/// Max body size is 25MiB including other fields. We cut at 24MiB because
+/// calculating the rest of the body is too unreliable.
+const MAX_FILE_SIZE: usize = 24 * (1 << 20);
+/// Limits of servers boosted to level 2 (50MB) and 3 (100MB), with the same
+/// margin.
+const TIER_2_MAX_FILE_SIZE: usize = 49 * (1 << 20);
+const TIER_3_MAX_FILE_SIZE: usize = 99 * (1 << 20);
+const AUTOCOMPLETE_MAX_CHOICES: usize = 25;
+pub(crate) const MAX_MESSAGE_LENGTH: usize = 2000;
+/// Commands still answered while the bot is disabled in a guild, those
+/// deleting recorded data included.
+const ALLOWED_WHILE_DISABLED: &[&str] =
+    &["enable", "disable", "version", "forget", "wipe", "mydata"];
+/// How often connected guilds check whether a part of their session is due.
+const SESSION_SPLIT_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// Upper bounds of the interaction latency histogram buckets, in seconds.
+const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.15, 0.25, 0.5, 1., 2., 3., 5., 10., 30.];
+/// Invalid Emoji error.
+const INVALID_EMOJI_CODE: isize = 50035;
+const INVALID_EMOJI_MESSAGE: &str = "BUTTON_COMPONENT_INVALID_EMOJI";
+
+#[derive(Clone)]
+pub(crate) struct Handler {
+    pub(crate) bot_id: Arc<AtomicU64>,
+    pub(crate) allow_delete: bool,
+    pub(crate) allow_grey: bool,
+    pub(crate) ffmpeg_path: PathBuf,
+    pub(crate) recorder: Arc<Mutex<Recorder>>,
+    pub(crate) soundboard: Arc<Soundboard>,
+    pub(crate) history: Arc<History>,
+    pub(crate) reviews: Arc<Mutex<HashMap<GuildId, TrackHandle>>>,
+    /// Looping sounds playing in each guild, until their button is pressed
+    /// again or `/stopsound`.
+    pub(crate) loops: Arc<Mutex<HashMap<GuildId, HashMap<Ulid, PlayingLoop>>>>,
+    pub(crate) trims: Arc<Trims>,
+    pub(crate) uploads: Arc<Uploads>,
+    /// Latest recording announcement of each guild, whose kill switch reaction
+    /// is listened to.
+    pub(crate) announcements: Arc<Mutex<HashMap<GuildId, MessageId>>>,
+    /// When the latest join or leave chime of each guild played.
+    pub(crate) chimes: Arc<Mutex<HashMap<GuildId, Instant>>>,
+    /// Channel the bot joined by itself in each guild, left once no
+    /// whitelisted user is in it.
+    pub(crate) auto_joined: Arc<Mutex<HashMap<GuildId, ChannelId>>>,
+    /// Latest voice connection of each guild, older connections stop
+    /// splitting their session.
+    pub(crate) sessions: Arc<Mutex<HashMap<GuildId, Session>>>,
+    /// Notified when the gateway sends a guild, for handlers waiting for one
+    /// missing from the cache.
+    pub(crate) guild_arrivals: Arc<Notify>,
+    pub(crate) indicator: Arc<Indicator>,
+    #[cfg(feature = "storage")]
+    pub(crate) storage: Option<Arc<storage::Storage>>,
+    #[cfg(feature = "http-api")]
+    pub(crate) shares: Option<Arc<share::Shares>>,
+    #[cfg(feature = "http-api")]
+    pub(crate) api_keys: Arc<crate::api_keys::ApiKeys>,
+    #[cfg(any(feature = "transcription", feature = "voice-commands"))]
+    pub(crate) speech_recognizer_path: Option<PathBuf>,
+    #[cfg(feature = "transcription")]
+    pub(crate) preferences: Arc<prefs::Preferences>,
+    pub(crate) settings: Arc<Settings>,
+    pub(crate) schedules: Arc<Schedules>,
+    pub(crate) download_audit: Arc<DownloadAudit>,
+    pub(crate) exports: Arc<Exports>,
+    pub(crate) exporters: Arc<Exporters>,
+    pub(crate) voice_buffer_max_duration: Duration,
+    pub(crate) timeline: Arc<Timeline>,
+    pub(crate) metrics: Arc<Metrics>,
+    pub(crate) slow_interaction_threshold: Duration,
+    pub(crate) log_channel: Option<ChannelId>,
+    pub(crate) low_power: bool,
+    pub(crate) stats: Arc<Stats>,
+    pub(crate) debounce: Arc<Debounce>,
+    pub(crate) in_flight: Arc<InFlight>,
+    /// Context of the first shard ready, for the HTTP API to join channels
+    /// the way commands do.
+    #[cfg(feature = "http-api")]
+    pub(crate) context: Arc<OnceLock<Context>>,
+}
+
+#[async_trait]
+impl EventHandler for Handler {
+    async fn ready(&self, ctx: Context, data_about_bot: Ready) {
+        let shard = data_about_bot.shard.map_or(0, |shard| shard.id.0);
+        info!("bot ready on shard {shard}");
+        // Every shard gets its own ready event, but the state and loops are
+        // shared.
+        let first_ready = self
+            .bot_id
+            .swap(data_about_bot.user.id.get(), Ordering::Relaxed)
+            == 0;
+        if shard == 0 {
+            self.register_global_commands(&ctx).await;
+        }
+        if first_ready {
+            #[cfg(feature = "http-api")]
+            let _ = self.context.set(ctx.clone());
+            migrate_legacy_whitelist(&ctx, &self.recorder).await;
+            Arc::clone(&self.schedules).run_loop(ctx.clone(), self.clone());
+            consent_expiry_loop(ctx, Arc::clone(&self.recorder), Arc::clone(&self.settings));
+        }
+    }
+
+    async fn guild_create(&self, _ctx: Context, _guild: Guild, _is_new: Option<bool>) {
+        self.guild_arrivals.notify_waiters();
+    }
+
+    async fn voice_state_update(&self, ctx: Context, old: Option<VoiceState>, new: VoiceState) {
+        let Some(guild) = new.guild_id else {
+            return;
+        };
+        // Voice states are only known from the cache.
+        guild_cache::ensure_guild(&ctx, &self.guild_arrivals, guild).await;
+        self.track_participants(&ctx, old.as_ref(), &new).await;
+        // Presence only changes with the bot moving, or members moving in or
+        // out of its channel, other guilds don't get a recorder for nothing.
+        let bot = UserId::new(self.bot_id.load(Ordering::Relaxed));
+        let bot_channel = ctx
+            .cache
+            .guild(guild)
+            .and_then(|guild| guild.voice_states.get(&bot).and_then(|s| s.channel_id));
+        let previous = old.as_ref().and_then(|state| state.channel_id);
+        if new.user_id == bot
+            || bot_channel
+                .is_some_and(|channel| previous == Some(channel) || new.channel_id == Some(channel))
+        {
+            self.update_presence(&ctx, guild).await;
+        }
+        self.auto_join(&ctx, guild, &new).await;
+        self.leave_if_blocked(&ctx, guild, &new).await;
+        if new.user_id == bot {
+            // Looping sounds end with the connection they played in.
+            if previous.is_some() && previous != new.channel_id {
+                self.stop_loops(&ctx, guild).await;
+                // However the bot left, it no longer follows the users of an
+                // auto-joined channel, unless it was auto-joined right after.
+                let mut auto_joined = self.auto_joined.lock().await;
+                if auto_joined.get(&guild).copied() != new.channel_id {
+                    auto_joined.remove(&guild);
+                }
+                drop(auto_joined);
+                // Left or moved by any means, not a session started since.
+                let session = self
+                    .remove_session(guild, |session| Some(session.channel) == previous)
+                    .await;
+                if let Some(session) = session {
+                    self.finish_session(&ctx, guild, session).await;
+                }
+            }
+            let settings = self.settings.get(guild).await;
+            // Nothing is recorded in soundboard-only guilds.
+            self.indicator
+                .update(
+                    &ctx,
+                    guild,
+                    new.channel_id.filter(|_| !settings.soundboard_only),
+                    settings.recording_nickname.as_deref(),
+                )
+                .await;
+        }
+
+        if let Some(channel) = old.and_then(|c| c.channel_id) {
+            self.disconnect_if_alone(&ctx, guild, channel).await;
+        }
+        if let Some(channel) = new.channel_id {
+            self.disconnect_if_alone(&ctx, guild, channel).await;
+        }
+    }
+
+    async fn guild_member_update(
+        &self,
+        ctx: Context,
+        old: Option<Member>,
+        _new: Option<Member>,
+        event: GuildMemberUpdateEvent,
+    ) {
+        // Roles granted or removed change who is recorded in the channel.
+        if old.is_some_and(|old| old.roles == event.roles) {
+            return;
+        }
+        let guild = event.guild_id;
+        let settings = self.settings.get(guild).await;
+        if settings.recorded_role.is_none() && settings.whitelisted_roles.is_empty() {
+            return;
+        }
+        let bot = UserId::new(self.bot_id.load(Ordering::Relaxed));
+        let with_bot = ctx.cache.guild(guild).is_some_and(|guild| {
+            let channel = |user| guild.voice_states.get(&user).and_then(|s| s.channel_id);
+            channel(bot).is_some() && channel(bot) == channel(event.user.id)
+        });
+        if with_bot {
+            self.update_presence(&ctx, guild).await;
+        }
+    }
+
+    async fn reaction_add(&self, ctx: Context, reaction: Reaction) {
+        let (Some(guild), Some(user)) = (reaction.guild_id, reaction.user_id) else {
+            return;
+        };
+        if user.get() == self.bot_id.load(Ordering::Relaxed)
+            || self.announcements.lock().await.get(&guild) != Some(&reaction.message_id)
+        {
+            return;
+        }
+        let settings = self.settings.get(guild).await;
+        if !matches!(&reaction.emoji, ReactionType::Unicode(emoji) if emoji == settings.kill_switch_emoji())
+        {
+            return;
+        }
+
+        if let Err(err) = self.opt_out(guild, user).await {
+            warn!("cannot opt user {user} out of recording: {err}");
+            return;
+        }
+        if let Ok(dm) = user.create_dm_channel(&ctx).await {
+            let _ = dm
+                .send_message(
+                    &ctx,
+                    CreateMessage::new().content(
+                        "You are no longer recorded until you leave the voice channel, your audio of this session has been deleted.",
+                    ),
+                )
+                .await;
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        let start = Instant::now();
+        let (kind, name, guild) = match &interaction {
+            Interaction::Command(command) => {
+                ("command", command.data.name.clone(), command.guild_id)
+            }
+            Interaction::Component(component) => (
+                "component",
+                component_name(&component.data.custom_id).to_owned(),
+                component.guild_id,
+            ),
+            Interaction::Autocomplete(autocomplete) => (
+                "autocomplete",
+                autocomplete.data.name.clone(),
+                autocomplete.guild_id,
+            ),
+            _ => return,
+        };
+
+        let Some(_in_flight) = self.in_flight.enter().await else {
+            let response = CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content("The bot is restarting, try again in a moment.")
+                    .ephemeral(true),
+            );
+            let _ = match &interaction {
+                Interaction::Command(command) => command.create_response(&ctx, response).await,
+                Interaction::Component(component) => {
+                    component.create_response(&ctx, response).await
+                }
+                _ => Ok(()),
+            };
+            return;
+        };
+
+        if let Some(guild) = guild {
+            let allowed = match &interaction {
+                Interaction::Command(_) => ALLOWED_WHILE_DISABLED.contains(&name.as_str()),
+                Interaction::Component(_) => false,
+                _ => true,
+            };
+            if !allowed && self.settings.get(guild).await.disabled {
+                let response = CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(
+                            "The bot is disabled on this server by its moderators, `/enable` turns it back on.",
+                        )
+                        .ephemeral(true),
+                );
+                let _ = match &interaction {
+                    Interaction::Command(command) => command.create_response(&ctx, response).await,
+                    Interaction::Component(component) => {
+                        component.create_response(&ctx, response).await
+                    }
+                    _ => Ok(()),
+                };
+                return;
+            }
+        }
+
+        let dispatch = async {
+            let result = match interaction.clone() {
+                Interaction::Command(command) => self.dispatch_command(ctx.clone(), command).await,
+                Interaction::Component(component) => {
+                    self.dispatch_component(ctx.clone(), component).await
+                }
+                Interaction::Autocomplete(autocomplete) => {
+                    self.dispatch_autocomplete(ctx.clone(), autocomplete).await
+                }
+                _ => Ok(()),
+            };
+            (result, DEFERRED_AT.with(Cell::get))
+        };
+        let (result, deferred_at) = DEFERRED_AT.scope(Cell::new(None), dispatch).await;
+        // Deferred handlers already answered Discord, the work left is
+        // expected to be slow.
+        let answered_at = deferred_at.unwrap_or_else(Instant::now);
+        if let Err(err) = result {
+            report_failure(&ctx, &interaction, &err, &format!("{kind} {name}")).await;
+        }
+
+        self.report_latency(&ctx, kind, &name, guild, answered_at - start)
+            .await;
+    }
+}
+
+tokio::task_local! {
+    /// When the interaction being handled was deferred, if it was.
+    static DEFERRED_AT: Cell<Option<Instant>>;
+}
+
+/// Record that the interaction being handled was deferred, so its latency is
+/// measured until then rather than until its handler returns.
+fn mark_deferred() {
+    let _ = DEFERRED_AT.try_with(|deferred| {
+        if deferred.get().is_none() {
+            deferred.set(Some(Instant::now()));
+        }
+    });
+}
+
+/// Tell the user that handling their interaction failed, whether or not it
+/// was already answered. Autocomplete failures are only logged.
+async fn report_failure(
+    ctx: &Context,
+    interaction: &Interaction,
+    err: &InteractionError,
+    context: &str,
+) {
+    let content = format!(
+        "Something went wrong, try again later ({}).",
+        error_code::report(err, context)
+    );
+    let result = match interaction {
+        Interaction::Command(command) => {
+            let response = CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(&content)
+                    .ephemeral(true),
+            );
+            match command.create_response(ctx, response).await {
+                Ok(()) => Ok(()),
+                Err(_) => command
+                    .create_followup(
+                        ctx,
+                        CreateInteractionResponseFollowup::new()
+                            .content(&content)
+                            .ephemeral(true),
+                    )
+                    .await
+                    .map(|_| ()),
+            }
+        }
+        Interaction::Component(component) => {
+            let response = CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(&content)
+                    .ephemeral(true),
+            );
+            match component.create_response(ctx, response).await {
+                Ok(()) => Ok(()),
+                Err(_) => component
+                    .create_followup(
+                        ctx,
+                        CreateInteractionResponseFollowup::new()
+                            .content(&content)
+                            .ephemeral(true),
+                    )
+                    .await
+                    .map(|_| ()),
+            }
+        }
+        _ => Ok(()),
+    };
+    if let Err(err) = result {
+        warn!("Failed to report failure of {context}: {err}");
+    }
+}
+
+impl Handler {
+    /// Record how long an interaction took to handle, alerting operators when
+    /// it comes close to Discord's response timeout.
+    async fn report_latency(
+        &self,
+        ctx: &Context,
+        kind: &str,
+        name: &str,
+        guild: Option<GuildId>,
+        elapsed: Duration,
+    ) {
+        let labels = metrics::labels(&[("kind", kind), ("name", name)]);
+        self.metrics.observe(
+            "disrecord_interaction_duration_seconds",
+            "Time until interactions are answered or deferred",
+            &labels,
+            LATENCY_BUCKETS,
+            elapsed.as_secs_f64(),
+        );
+        if elapsed < self.slow_interaction_threshold {
+            return;
+        }
+
+        self.metrics.increment(
+            "disrecord_slow_interactions_total",
+            "Interactions handled slower than the configured threshold",
+            &labels,
+            1.,
+        );
+        let guild = guild.map_or_else(|| "DMs".to_owned(), |guild| format!("guild {guild}"));
+        warn!(
+            "slow {kind} {name} in {guild}: {}",
+            humantime::format_duration(truncate_millis(elapsed))
+        );
+        if let Some(channel) = self.log_channel {
+            if let Err(err) = channel
+                .say(
+                    ctx,
+                    format!(
+                        "⚠️ Slow {kind} `{name}` in {guild}: {}.",
+                        humantime::format_duration(truncate_millis(elapsed))
+                    ),
+                )
+                .await
+            {
+                warn!("cannot post slow interaction alert: {err}");
+            }
+        }
+    }
+
+    async fn dispatch_command(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        match command.data.name.as_str() {
+            // Common.
+            "version" => self.version(ctx, command).await,
+            "disable" => self.set_disabled(ctx, command, true).await,
+            "enable" => self.set_disabled(ctx, command, false).await,
+            "stats" => self.stats(ctx, command).await,
+            "join" => self.join_voice(ctx, command).await,
+            "pause" => self.set_recording_paused(ctx, command, true).await,
+            "resume" => self.set_recording_paused(ctx, command, false).await,
+            "mark" => self.add_marker(ctx, command).await,
+            "synctone" => self.play_sync_tone(ctx, command).await,
+            "voicediag" => self.voice_diagnostics(ctx, command).await,
+            "quality-report" => self.quality_report(ctx, command).await,
+            "forget" => self.forget_recording(ctx, command).await,
+            "leave" => self.leave_session(ctx, command).await,
+            "wipe" => self.wipe_recording(ctx, command).await,
+            "import-recording" => self.import_recording(ctx, command).await,
+            "flush" => self.flush_recording(ctx, command).await,
+            "profile" => self.show_profile(ctx, command).await,
+            "overlap-report" => self.overlap_report(ctx, command).await,
+            "waveform" => self.render_waveform(ctx, command).await,
+            "clip" => self.clip_recording(ctx, command).await,
+            "mydata" => match parse_subcommand(&command) {
+                Some("export") => self.export_user_data(ctx, command).await,
+                Some("delete") => self.delete_user_data(ctx, command).await,
+                _ => Ok(()),
+            },
+            #[cfg(feature = "http-api")]
+            "share" => self.share_recording(ctx, command).await,
+            #[cfg(feature = "http-api")]
+            "apikey" => match parse_subcommand(&command) {
+                Some("create") => self.create_api_key(ctx, command).await,
+                Some("list") => self.list_api_keys(ctx, command).await,
+                _ => Ok(()),
+            },
+
+            // Recorder.
+            "recorder" => match parse_subcommand(&command) {
+                Some("list") => self.get_whitelist(ctx, command).await,
+                Some("join") => self.join_whitelist(ctx, command).await,
+                Some("leave") => self.leave_whitelist(ctx, command).await,
+                Some("download") => self.download_recording(ctx, command).await,
+                Some("download-chunks") => self.download_recording_chunks(ctx, command).await,
+                Some("review") => self.review_recording(ctx, command).await,
+                Some("mixdown") => self.download_mixdown(ctx, command).await,
+                Some("audacity") => self.download_session_archive(ctx, command, false).await,
+                Some("archive") => self.download_session_archive(ctx, command, true).await,
+                _ => Ok(()),
+            },
+
+            // Whitelist administration.
+            "whitelist" => match parse_subcommand(&command) {
+                Some("add") => self.add_to_whitelist(ctx, command).await,
+                Some("remove") => self.remove_from_whitelist(ctx, command).await,
+                Some("add-role") => self.set_whitelisted_role(ctx, command, true).await,
+                Some("remove-role") => self.set_whitelisted_role(ctx, command, false).await,
+                _ => Ok(()),
+            },
+
+            // Soundboard.
+            "soundboard" => match parse_subcommand(&command) {
+                Some("list") => self.list_sounds(ctx, command).await,
+                Some("upload") => self.upload_sound(ctx, command).await,
+                Some("download") => self.download_sound(ctx, command).await,
+                Some("delete") => self.delete_sound(ctx, command).await,
+                Some("rename") => self.rename_sound(ctx, command).await,
+                Some("move") => self.move_sound(ctx, command).await,
+                Some("change-color") => self.change_sound_color(ctx, command).await,
+                Some("change-emoji") => self.change_sound_emoji(ctx, command).await,
+                Some("change-loop") => self.change_sound_loop(ctx, command).await,
+                Some("id") => self.sound_id(ctx, command).await,
+                Some("backup") => self.backup_sounds(ctx, command).await,
+                Some("logs") => self.soundboard_logs(ctx, command).await,
+                _ => Ok(()),
+            },
+            "schedule-sound" => self.schedule_sound(ctx, command).await,
+            "schedule-recording" => self.schedule_recording(ctx, command).await,
+            "scheduled-sounds" => self.list_scheduled_sounds(ctx, command).await,
+            "bindslot" => self.bind_slot(ctx, command).await,
+            "stopsound" => self.stop_sounds(ctx, command).await,
+            "download-audit" => self.show_download_audit(ctx, command).await,
+
+            // Live transcription.
+            #[cfg(feature = "transcription")]
+            "transcribe" => match parse_subcommand(&command) {
+                Some("start") => self.start_live_transcript(ctx, command).await,
+                Some("stop") => self.stop_live_transcript(ctx, command).await,
+                _ => Ok(()),
+            },
+            #[cfg(feature = "transcription")]
+            "prefs" => self.set_preferences(ctx, command).await,
+
+            // Sound packs.
+            "packs" => match parse_subcommand(&command) {
+                Some("browse") => self.browse_packs(ctx, command).await,
+                Some("install") => self.install_pack(ctx, command).await,
+                _ => Ok(()),
+            },
+
+            // Settings.
+            "settings" => match parse_subcommand(&command) {
+                Some("show") => self.show_settings(ctx, command).await,
+                Some("vad-threshold") => self.set_vad_threshold(ctx, command).await,
+                Some("trim-silence") => self.set_trim_silence(ctx, command).await,
+                Some("noise-gate") => self.set_noise_gate(ctx, command).await,
+                Some("denoise") => self.set_denoise(ctx, command).await,
+                Some("stereo-width") => self.set_stereo_width(ctx, command).await,
+                Some("buffer-duration") => self.set_buffer_duration(ctx, command).await,
+                Some("storage-rate") => self.set_storage_rate(ctx, command).await,
+                Some("session-split") => self.set_session_split(ctx, command).await,
+                Some("retention") => self.set_retention(ctx, command).await,
+                Some("pause-retention") => self.set_pause_retention(ctx, command).await,
+                Some("announce") => self.set_announce_recording(ctx, command).await,
+                Some("legal-notice") => self.set_legal_notice(ctx, command).await,
+                Some("recording-nickname") => self.set_recording_nickname(ctx, command).await,
+                Some("chimes") => self.set_chimes(ctx, command).await,
+                Some("whitelist-expiry") => self.set_whitelist_expiry(ctx, command).await,
+                Some("auto-join") => self.set_auto_join(ctx, command).await,
+                Some("blocked-channel") => self.set_blocked_channel(ctx, command).await,
+                Some("soundboard-only") => self.set_soundboard_only(ctx, command).await,
+                Some("join-switch") => self.set_join_switch(ctx, command).await,
+                Some("recorded-role") => self.set_recorded_role(ctx, command).await,
+                #[cfg(feature = "voice-commands")]
+                Some("voice-commands") => self.set_voice_commands(ctx, command).await,
+                _ => Ok(()),
+            },
+            name => match name.strip_prefix('p').and_then(|slot| slot.parse().ok()) {
+                Some(slot) => self.play_slot(ctx, command, slot).await,
+                None => Ok(()),
+            },
+        }
+    }
+
+    async fn dispatch_component(
+        &self,
+        ctx: Context,
+        component: ComponentInteraction,
+    ) -> Result<(), InteractionError> {
+        let pressed = Instant::now();
+        if !self.debounce.first_delivery(component.id) {
+            debug!("ignoring duplicate interaction {}", component.id);
+            return Ok(());
+        }
+
+        // Sent in DMs, the guild is part of the ID.
+        if let Some(answer) = component.data.custom_id.strip_prefix("consent-") {
+            let answer = answer.to_owned();
+            return self.answer_consent(ctx, component, &answer).await;
+        }
+
+        let Some(guild) = component.guild_id else {
+            return Ok(());
+        };
+
+        if let Some(action) = component.data.custom_id.strip_prefix("review-") {
+            let action = action.to_owned();
+            return self.control_review(ctx, component, guild, &action).await;
+        }
+
+        if let Some(action) = component.data.custom_id.strip_prefix("trim-") {
+            let action = action.to_owned();
+            return self.trim_clip(ctx, component, &action).await;
+        }
+
+        if let Some(action) = component.data.custom_id.strip_prefix("upload-") {
+            let action = action.to_owned();
+            return self.confirm_upload(ctx, component, &action).await;
+        }
+
+        if let Some(channel) = component.data.custom_id.strip_prefix("join-move-") {
+            let Ok(channel) = channel.parse().map(ChannelId::new) else {
+                return Ok(());
+            };
+            return self.move_voice(ctx, component, guild, channel).await;
+        }
+
+        if let Some(action) = component.data.custom_id.strip_prefix("flush-") {
+            let action = action.to_owned();
+            return self.confirm_flush(ctx, component, guild, &action).await;
+        }
+
+        if let Some(id) = component.data.custom_id.strip_prefix("unschedule-") {
+            let id = id.to_owned();
+            return self.unschedule_sound(ctx, component, guild, &id).await;
+        }
+
+        #[cfg(feature = "http-api")]
+        if let Some(id) = component.data.custom_id.strip_prefix("apikey-revoke-") {
+            let id = id.to_owned();
+            return self.revoke_api_key(ctx, component, guild, &id).await;
+        }
+
+        if !self
+            .debounce
+            .accept_press(component.user.id, &component.data.custom_id)
+        {
+            component.defer(&ctx).await?;
+            mark_deferred();
+            return Ok(());
+        }
+
+        let sound = if component.data.custom_id.starts_with("random-") {
+            let Ok(hash) = component
+                .data
+                .custom_id
+                .trim_start_matches("random-")
+                .parse()
+            else {
+                return Ok(());
+            };
+            let Some(sound) = self.soundboard.random_id_in_group(guild, hash).await else {
+                return Ok(());
+            };
+            sound
+        } else if component.data.custom_id == "random" {
+            let Some(sound) = self.soundboard.random_id(guild).await else {
+                return Ok(());
+            };
+            sound
+        } else if component.data.custom_id == "latest" {
+            let Some(sound) = self.soundboard.latest_id(guild).await else {
+                return Ok(());
+            };
+            sound
+        } else {
+            let Ok(sound) = Ulid::from_string(&component.data.custom_id) else {
+                return Ok(());
+            };
+            sound
+        };
+
+        // Pressing the button of a looping sound again stops it.
+        let playing = self
+            .loops
+            .lock()
+            .await
+            .get_mut(&guild)
+            .and_then(|loops| loops.remove(&sound));
+        if let Some(playing) = playing {
+            // Unless it already ended, such as when leaving the channel.
+            if playing.track.stop().is_ok() {
+                component.defer(&ctx).await?;
+                mark_deferred();
+                self.show_loop(&ctx, &component.message, sound, false).await;
+                return Ok(());
+            }
+        }
+
+        let manager = songbird::get(&ctx)
+            .await
+            .ok_or(InteractionError::VoiceUnavailable)?;
+
+        let guild_recorder = self.recorder.lock().await.get_guild_recorder(guild).await;
+        let (defer, played) = tokio::join!(
+            component.defer(&ctx),
+            play_sound(manager, &self.soundboard, &guild_recorder, guild, sound)
+        );
+        defer?;
+        mark_deferred();
+        let Some(track) = played else {
+            return Ok(());
+        };
+        // Random and latest buttons play looping sounds once.
+        let looping = component.data.custom_id == sound.to_string()
+            && self
+                .soundboard
+                .metadata(sound)
+                .await
+                .is_some_and(|metadata| metadata.looping);
+        if looping {
+            match track.enable_loop() {
+                Ok(()) => {
+                    self.loops.lock().await.entry(guild).or_default().insert(
+                        sound,
+                        PlayingLoop {
+                            track: track.clone(),
+                            channel: component.channel_id,
+                            message: component.message.id,
+                        },
+                    );
+                    self.show_loop(&ctx, &component.message, sound, true).await;
+                }
+                Err(err) => warn!("cannot loop sound {sound}: {err}"),
+            }
+        }
+        if let Err(err) = track.add_event(
+            Event::Track(TrackEvent::Play),
+            PlaybackLatency {
+                pressed,
+                metrics: Arc::clone(&self.metrics),
+            },
+        ) {
+            warn!("cannot measure sound playback latency: {err}");
+        }
+
+        self.history.register(guild, component.user.id, sound).await;
+        Ok(())
+    }
+
+    async fn dispatch_autocomplete(
+        &self,
+        ctx: Context,
+        interaction: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = interaction.guild_id else {
+            return Ok(());
+        };
+
+        let Some(autocomplete) = interaction.data.autocomplete() else {
+            return Ok(());
+        };
+
+        let matches = match autocomplete.name {
+            "sound" | "join-sound" | "leave-sound" => {
+                self.soundboard
+                    .names_matching(guild, autocomplete.value, AUTOCOMPLETE_MAX_CHOICES)
+                    .await
+            }
+            "group" => {
+                self.soundboard
+                    .groups_matching(guild, autocomplete.value, AUTOCOMPLETE_MAX_CHOICES)
+                    .await
+            }
+            _ => return Ok(()),
+        };
+
+        interaction
+            .create_response(
+                ctx,
+                CreateInteractionResponse::Autocomplete(
+                    CreateAutocompleteResponse::new()
+                        .set_choices(matches.into_iter().map(AutocompleteChoice::from).collect()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn version(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(env!("CARGO_PKG_VERSION")),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn stats(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let mut content = format!(
+            "Uptime {}, low-power mode {}.\n",
+            humantime::format_duration(Duration::from_secs(self.stats.uptime().as_secs())),
+            if self.low_power { "on" } else { "off" }
+        );
+        content += &self.stats.report();
+
+        if let Some(guild) = command.guild_id {
+            let (tx, rx) = oneshot::channel();
+            self.recorder
+                .lock()
+                .await
+                .get_guild_recorder(guild)
+                .await
+                .send(RecorderAction::GetStats(tx))
+                .await?;
+            let RecorderStats {
+                mut users, ssrcs, ..
+            } = rx.await?;
+            let memory = users.iter().map(|user| user.memory).sum::<usize>();
+            let packets = users.iter().map(|user| user.packets).sum::<u64>();
+            let lost_packets = users.iter().map(|user| user.lost_packets).sum::<u64>();
+            users.retain(|user| user.buffered > Duration::ZERO);
+            users.sort_by_key(|user| (std::cmp::Reverse(user.buffered), user.id));
+            content += &format!(
+                "\n**This server**: {:.1} MiB of voice buffers, {ssrcs} active SSRCs, {packets} packets received and {lost_packets} lost ({:.1}%).\n",
+                memory as f64 / (1024. * 1024.),
+                lost_packets as f64 * 100. / packets.max(1) as f64
+            );
+            for user in users {
+                let line = format!(
+                    "- {}: {}\n",
+                    Mention::from(user.id),
+                    humantime::format_duration(Duration::from_secs(user.buffered.as_secs()))
+                );
+                if content.len() + line.len() > MAX_MESSAGE_LENGTH - 100 {
+                    content += "…\n";
+                    break;
+                }
+                content += &line;
+            }
+        }
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Whether Discord refused a message because of the emoji of a button.
+fn is_invalid_emoji(err: &SerenityError) -> bool {
+    match err {
+        SerenityError::Http(HttpError::UnsuccessfulRequest(req)) => {
+            req.status_code == reqwest::StatusCode::BAD_REQUEST
+                && req.error.code == INVALID_EMOJI_CODE
+                && req
+                    .error
+                    .errors
+                    .iter()
+                    .any(|sub_error| sub_error.code == INVALID_EMOJI_MESSAGE)
+        }
+        _ => false,
+    }
+}
+
+/// Kind of component for metrics, sound buttons use their raw ID.
+fn component_name(custom_id: &str) -> &str {
+    match custom_id.split('-').next() {
+        Some(prefix) if prefix.chars().all(|c| c.is_ascii_alphabetic()) => prefix,
+        _ => "sound",
+    }
+}
+
+/// Attachment size limit in a guild, raised by server boosts.
+async fn max_file_size(ctx: &Context, guild: GuildId) -> usize {
+    match guild_cache::premium_tier(ctx, guild).await {
+        PremiumTier::Tier3 => TIER_3_MAX_FILE_SIZE,
+        PremiumTier::Tier2 => TIER_2_MAX_FILE_SIZE,
+        _ => MAX_FILE_SIZE,
+    }
+}
+
+fn truncate_millis(duration: Duration) -> Duration {
+    Duration::from_millis(duration.as_millis() as u64)
+}
+
+/// Reuse the active thread of `channel` named `name`, or start a new one.
+async fn find_or_create_thread(
+    ctx: &Context,
+    guild: GuildId,
+    channel: ChannelId,
+    name: &str,
+) -> Option<ChannelId> {
+    let name = name.chars().take(100).collect::<String>();
+    let threads = guild.get_active_threads(ctx).await.ok()?;
+    if let Some(thread) = threads
+        .threads
+        .iter()
+        .find(|thread| thread.parent_id == Some(channel) && thread.name == name)
+    {
+        return Some(thread.id);
+    }
+
+    channel
+        .create_thread(ctx, CreateThread::new(name).kind(ChannelType::PublicThread))
+        .await
+        .ok()
+        .map(|thread| thread.id)
+}
+
+fn parse_subcommand(command: &CommandInteraction) -> Option<&str> {
+    let first_option = command.data.options.first()?;
+    if first_option.kind() != CommandOptionType::SubCommand {
+        return None;
+    };
+    Some(&first_option.name)
+}
@@ -0,0 +1,142 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use itertools::Itertools;
+use serenity::{
+    all::{
+        ButtonStyle, CommandInteraction, ComponentInteraction, CreateAllowedMentions, CreateButton,
+        CreateInteractionResponse, CreateInteractionResponseMessage, GuildId, Mention,
+    },
+    builder::CreateActionRow,
+    client::Context,
+};
+use ulid::Ulid;
+
+use super::Handler;
+use crate::api_keys;
+use crate::{command, error_code, interaction::InteractionError};
+
+impl Handler {
+    pub(super) async fn create_api_key(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(scope) =
+            command::find_string_option(&command, "scope", None).and_then(api_keys::Scope::parse)
+        else {
+            return Ok(());
+        };
+
+        let content = match self.api_keys.create(guild, scope, command.user.id).await {
+            Ok((token, key)) => format!(
+                "API key `{}` created with the `{}` scope, send it as `Authorization: Bearer <key>` to the HTTP API. It won't be shown again:\n```\n{token}\n```",
+                key.id,
+                scope.name()
+            ),
+            Err(err) => error_code::report(&err, "create_api_key"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn list_api_keys(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+
+        let keys = self.api_keys.list(guild).await;
+        let timestamp = |time: SystemTime| {
+            time.duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+        };
+        let content = if keys.is_empty() {
+            "No API keys.".to_owned()
+        } else {
+            keys.iter()
+                .map(|key| {
+                    format!(
+                        "1. `{}` with the `{}` scope, created by {} <t:{}:R>, {}",
+                        key.id,
+                        key.scope.name(),
+                        Mention::from(key.created_by),
+                        timestamp(key.created_at),
+                        match key.last_used {
+                            Some(last_used) => format!("last used <t:{}:R>", timestamp(last_used)),
+                            None => "never used".to_owned(),
+                        }
+                    )
+                })
+                .join("\n")
+        };
+        let buttons = keys
+            .iter()
+            .enumerate()
+            .map(|(i, key)| {
+                CreateButton::new(format!("apikey-revoke-{}", key.id))
+                    .label(format!("Revoke #{}", i + 1))
+                    .style(ButtonStyle::Danger)
+            })
+            .chunks(5)
+            .into_iter()
+            .map(|row| CreateActionRow::Buttons(row.collect()))
+            .collect_vec();
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .ephemeral(true)
+                        .allowed_mentions(CreateAllowedMentions::new())
+                        .components(buttons),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn revoke_api_key(
+        &self,
+        ctx: Context,
+        component: ComponentInteraction,
+        guild: GuildId,
+        id: &str,
+    ) -> Result<(), InteractionError> {
+        let Ok(id) = Ulid::from_string(id) else {
+            return Ok(());
+        };
+        let content = match self.api_keys.revoke(guild, id).await {
+            Ok(()) => format!("API key `{id}` revoked."),
+            Err(err) => error_code::report(&err, "revoke_api_key"),
+        };
+        component
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1660 @@
+use std::{
+    collections::HashSet,
+    io::{Cursor, Write},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use itertools::Itertools;
+#[cfg(any(feature = "storage", feature = "http-api"))]
+use serenity::all::ChannelId;
+use serenity::{
+    all::{
+        ButtonStyle, CommandInteraction, ComponentInteraction, CreateAllowedMentions, CreateButton,
+        CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, GuildId,
+        Mention, UserId,
+    },
+    builder::{
+        CreateActionRow, CreateAttachment, CreateInteractionResponseFollowup,
+        EditInteractionResponse,
+    },
+    client::Context,
+};
+use tokio::sync::oneshot;
+use zip::{write::FileOptions as ZipFileOptions, ZipWriter};
+
+use super::{
+    find_or_create_thread, mark_deferred, max_file_size, truncate_millis, Handler, MAX_FILE_SIZE,
+};
+#[cfg(any(feature = "storage", feature = "http-api"))]
+use crate::exporter;
+#[cfg(feature = "transcription")]
+use crate::transcription;
+use crate::{
+    audit::DownloadRecord,
+    button, command, error_code,
+    exporter::{Metadata as ExportMetadata, DEFAULT_EXPORTER},
+    exports::ExportPermit,
+    ffmpeg, guild_cache,
+    interaction::InteractionError,
+    mix,
+    recorder::{self, RecorderAction, StoredVoice},
+    resample,
+    trim::{self, Clip},
+    vad,
+    wav::{self, SampleFormat},
+};
+
+const MAX_ATTACHEMENTS_PER_MESSAGE: usize = 10;
+/// FFT based denoiser, reducing constant noise like fans or hum.
+const DENOISE_FILTER: &str = "afftdn=nf=-25";
+/// Filters rendering voice data to the images of `/waveform`.
+const WAVEFORM_FILTER: &str = "showwavespic=s=1200x300:colors=0x5865f2";
+const SPECTROGRAM_FILTER: &str = "showspectrumpic=s=1200x400:legend=1";
+/// Soundboard group clips are saved in.
+const CLIP_SOUND_GROUP: &str = "clips";
+/// Latest downloads listed by `/download-audit`.
+const DOWNLOAD_AUDIT_ENTRIES: usize = 15;
+
+impl Handler {
+    #[cfg(feature = "http-api")]
+    pub(super) async fn share_recording(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(shares) = &self.shares else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("Sharing links are not enabled on this bot."),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        };
+        let requested_user = command::find_user_option(&command, "user").unwrap_or(&command.user);
+        let Some(last) =
+            command::find_integer_option(&command, "last", Some(30)).map(|l| l as usize)
+        else {
+            return Ok(());
+        };
+
+        let (tx, rx) = oneshot::channel();
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::GetVoiceData(requested_user.id, tx))
+            .await?;
+        let Some(data) = rx.await? else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(format!("No voice data found for {}.", requested_user))
+                            .allowed_mentions(CreateAllowedMentions::new()),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        };
+
+        // Shared as stored, without resampling.
+        let rate = data.rate();
+        let data = data.data;
+        let clip = &data[data.len().saturating_sub(last * rate)..];
+        self.audit_download(
+            &command,
+            guild,
+            vec![requested_user.id],
+            clip.len() * resample::factor(rate),
+        )
+        .await;
+        let username = command::resolve_username(&ctx, requested_user, guild).await;
+        let content = match shares
+            .create(
+                guild,
+                vec![requested_user.id],
+                format!("{}.wav", username),
+                wav::package_as(clip, 1, rate, SampleFormat::S16),
+            )
+            .await
+        {
+            Ok(url) => format!(
+                "{url}\nThis link expires in {}.",
+                humantime::format_duration(shares.expiration())
+            ),
+            Err(err) => error_code::report(&err, "share_recording"),
+        };
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(content),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Post the latest voice of a user with buttons nudging its edges, until
+    /// it is saved.
+    pub(super) async fn clip_recording(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let requested_user = command::find_user_option(&command, "user").unwrap_or(&command.user);
+        let Some(last) =
+            command::find_integer_option(&command, "last", Some(10)).map(|l| l as usize)
+        else {
+            return Ok(());
+        };
+        let sound_name = command::find_string_option(&command, "sound", None).map(str::to_owned);
+
+        let (tx, rx) = oneshot::channel();
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::GetVoiceData(requested_user.id, tx))
+            .await?;
+        let Some(data) = rx.await? else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(format!("No voice data found for {}.", requested_user))
+                            .allowed_mentions(CreateAllowedMentions::new()),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        };
+        command.defer(&ctx).await?;
+        mark_deferred();
+
+        // Keep some audio before the clip for its start to be moved earlier.
+        let len = last * recorder::FREQUENCY;
+        let kept = len + mix::samples_len(trim::MARGIN);
+        let data = data.samples().await;
+        let data = data[data.len().saturating_sub(kept)..].to_vec();
+        let clip = Clip::new(
+            guild,
+            command.user.id,
+            requested_user.id,
+            sound_name,
+            data,
+            len,
+        );
+        self.audit_download(
+            &command,
+            guild,
+            vec![requested_user.id],
+            clip.samples().len(),
+        )
+        .await;
+
+        let _permit = self.wait_export_slot(&ctx, &command, guild).await?;
+        let (content, wav) = self
+            .package_clip(guild, clip.samples(), clip.describe())
+            .await;
+        let message = command
+            .edit_response(
+                &ctx,
+                EditInteractionResponse::new()
+                    .content(content)
+                    .allowed_mentions(CreateAllowedMentions::new())
+                    .new_attachment(CreateAttachment::bytes(wav, "clip.wav"))
+                    .components(trim_buttons(clip.sound_name.is_some())),
+            )
+            .await?;
+        self.trims.insert(message.id, clip).await;
+        Ok(())
+    }
+
+    pub(super) async fn trim_clip(
+        &self,
+        ctx: Context,
+        component: ComponentInteraction,
+        action: &str,
+    ) -> Result<(), InteractionError> {
+        let message = component.message.id;
+        let user = component.user.id;
+        let edit = match action {
+            "save" => match self.trims.take(message, user).await {
+                Ok(clip) => Ok(self.save_clip(clip).await),
+                Err(err) => Err(err),
+            },
+            "discard" => self.trims.take(message, user).await.map(|_| {
+                EditInteractionResponse::new()
+                    .content("Clip discarded.")
+                    .clear_attachments()
+                    .components(vec![])
+            }),
+            _ => {
+                let (Some(guild), Some((edge, seconds))) =
+                    (component.guild_id, trim::parse_nudge(action))
+                else {
+                    return Ok(());
+                };
+                match self.trims.nudge(message, user, edge, seconds).await {
+                    Ok((samples, description)) => {
+                        let (content, wav) = self.package_clip(guild, &samples, description).await;
+                        Ok(EditInteractionResponse::new()
+                            .content(content)
+                            .allowed_mentions(CreateAllowedMentions::new())
+                            .clear_attachments()
+                            .new_attachment(CreateAttachment::bytes(wav, "clip.wav")))
+                    }
+                    Err(err) => Err(err),
+                }
+            }
+        };
+
+        match edit {
+            Ok(edit) => {
+                // Uploading the new clip may take longer than Discord waits
+                // for an answer.
+                component.defer(&ctx).await?;
+                mark_deferred();
+                component.edit_response(&ctx, edit).await?;
+            }
+            Err(err) => {
+                component
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(error_code::report(&err, "trim_clip"))
+                                .ephemeral(true),
+                        ),
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Export a clip as WAV the way downloads are, with the legal notice of the
+    /// guild as comment and appended to the content of its message.
+    async fn package_clip(
+        &self,
+        guild: GuildId,
+        samples: &[i16],
+        content: String,
+    ) -> (String, Vec<u8>) {
+        let settings = self.settings.get(guild).await;
+        let notice = settings.render_legal_notice();
+        let metadata = ExportMetadata {
+            name: "clip".to_owned(),
+            comment: notice.clone(),
+            channels: 1,
+            sample_rate: settings.storage_rate(),
+            sample_format: SampleFormat::S16,
+            cover_url: None,
+        };
+        let wav = self
+            .exporters
+            .export("wav", samples, &metadata)
+            .await
+            .into_iter()
+            .next()
+            .expect("WAV export produces a file")
+            .data;
+        let content = match notice {
+            Some(notice) => format!("{content}\n{notice}"),
+            None => content,
+        };
+        (content, wav)
+    }
+
+    /// Remove the trimming buttons of a clip, adding it to the soundboard if
+    /// it was named.
+    async fn save_clip(&self, clip: Clip) -> EditInteractionResponse {
+        let edit = EditInteractionResponse::new().components(vec![]);
+        let Some(name) = clip.sound_name.clone() else {
+            return edit;
+        };
+        let color = button::determinist(&name.to_lowercase(), self.allow_grey);
+        let _permit = self.exports.acquire(clip.guild).await;
+        let (_, wav) = self
+            .package_clip(clip.guild, clip.samples(), String::new())
+            .await;
+        let content = match self
+            .soundboard
+            .add_data(
+                wav,
+                "clip.wav",
+                clip.guild,
+                name.clone(),
+                None,
+                color,
+                CLIP_SOUND_GROUP.to_owned(),
+                None,
+                Some(clip.owner),
+            )
+            .await
+        {
+            Ok(_) => format!(
+                "Clip of {} added to the soundboard as `{CLIP_SOUND_GROUP}/{name}`.",
+                Mention::from(clip.user)
+            ),
+            Err(err) => format!(
+                "Clip of {}, not added: {}",
+                Mention::from(clip.user),
+                error_code::report(&err, "save_clip")
+            ),
+        };
+        edit.content(content)
+            .allowed_mentions(CreateAllowedMentions::new())
+    }
+
+    /// Post an image of the buffered voice data of a user, to check whether
+    /// audio was captured before downloading it.
+    pub(super) async fn render_waveform(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let requested_user = command::find_user_option(&command, "user").unwrap_or(&command.user);
+        let Some(style) = command::find_string_option(&command, "style", Some("waveform")) else {
+            return Ok(());
+        };
+
+        let (tx, rx) = oneshot::channel();
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::GetVoiceData(requested_user.id, tx))
+            .await?;
+        let Some(data) = rx.await? else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(format!("No voice data found for {}.", requested_user))
+                            .allowed_mentions(CreateAllowedMentions::new()),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        };
+        command.defer(&ctx).await?;
+        mark_deferred();
+
+        let data = data.samples().await;
+        let filter = if style == "spectrogram" {
+            SPECTROGRAM_FILTER
+        } else {
+            WAVEFORM_FILTER
+        };
+        let timer = self.stats.timer("waveform");
+        let image = ffmpeg::render_png(&self.ffmpeg_path, &data, filter).await;
+        drop(timer);
+
+        let username = command::resolve_username(&ctx, requested_user, guild).await;
+        let followup = match image {
+            Some(image) => CreateInteractionResponseFollowup::new()
+                .content(format!(
+                    "{} of {} of voice data.",
+                    if style == "spectrogram" {
+                        "Spectrogram"
+                    } else {
+                        "Waveform"
+                    },
+                    humantime::format_duration(truncate_millis(mix::samples_duration(data.len())))
+                ))
+                .add_file(CreateAttachment::bytes(
+                    image,
+                    format!("{username}-{style}.png"),
+                )),
+            None => CreateInteractionResponseFollowup::new()
+                .content("Cannot render the image, ffmpeg is unavailable."),
+        };
+        command.create_followup(&ctx, followup).await?;
+        Ok(())
+    }
+
+    pub(super) async fn download_recording(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let requested_user = command::find_user_option(&command, "user").unwrap_or(&command.user);
+
+        let settings = self.settings.get(guild).await;
+        let Some(trim_silence) =
+            command::find_boolean_option(&command, "trim-silence", Some(settings.trim_silence))
+        else {
+            return Ok(());
+        };
+
+        let Some(denoise) =
+            command::find_boolean_option(&command, "denoise", Some(settings.denoise))
+        else {
+            return Ok(());
+        };
+        let denoise = denoise && !self.low_power;
+        let Some(in_thread) = command::find_boolean_option(&command, "thread", Some(false)) else {
+            return Ok(());
+        };
+        let in_dm =
+            command::find_string_option(&command, "destination", Some("channel")) == Some("dm");
+        let Some(format) = command::find_string_option(&command, "format", Some(DEFAULT_EXPORTER))
+        else {
+            return Ok(());
+        };
+        let Some(labels) = command::find_boolean_option(&command, "labels", Some(false)) else {
+            return Ok(());
+        };
+        let Some(sample_rate) =
+            command::find_integer_option(&command, "sample-rate", Some(recorder::FREQUENCY as i64))
+                .map(|rate| rate as usize)
+        else {
+            return Ok(());
+        };
+        let Some(sample_format) =
+            command::find_string_option(&command, "bits", Some("16")).and_then(SampleFormat::parse)
+        else {
+            return Ok(());
+        };
+        #[cfg(feature = "transcription")]
+        let Some(transcribe) = command::find_boolean_option(&command, "transcribe", Some(false)) else {
+            return Ok(());
+        };
+
+        let (tx, rx) = oneshot::channel();
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::GetVoiceData(requested_user.id, tx))
+            .await?;
+
+        let username = command::resolve_username(&ctx, &requested_user, guild).await;
+        let data = rx.await?;
+        match data {
+            Some(data) => {
+                self.audit_download(
+                    &command,
+                    guild,
+                    vec![requested_user.id],
+                    data.captured_len(),
+                )
+                .await;
+                if in_dm {
+                    command.defer_ephemeral(&ctx).await
+                } else {
+                    command.defer(&ctx).await
+                }?;
+                mark_deferred();
+                let _permit = self.wait_export_slot(&ctx, &command, guild).await?;
+                // Exported at most at the rate the voice was stored at.
+                let sample_rate = sample_rate.min(data.rate());
+                let data = data.samples().await;
+
+                let markers = self.markers(guild).await?;
+                let segments = if labels || !markers.is_empty() {
+                    let (tx, rx) = oneshot::channel();
+                    self.recorder
+                        .lock()
+                        .await
+                        .get_guild_recorder(guild)
+                        .await
+                        .send(RecorderAction::GetSegments(requested_user.id, tx))
+                        .await?;
+                    rx.await?
+                } else {
+                    Vec::new()
+                };
+
+                let export_timer = self.stats.timer("export");
+                // Trim segments one by one so labels stay aligned with the audio.
+                let (data, segments) = if trim_silence && !segments.is_empty() {
+                    let vad = settings.vad();
+                    let mut trimmed = Vec::with_capacity(data.len());
+                    let mut trimmed_segments = Vec::with_capacity(segments.len());
+                    let mut consumed = 0;
+                    for segment in segments {
+                        let Some(samples) = data.get(consumed..consumed + segment.len) else {
+                            break;
+                        };
+                        consumed += segment.len;
+                        let samples = vad.trim_silence(samples, vad::TRIMMED_SILENCE);
+                        if !samples.is_empty() {
+                            trimmed_segments.push(mix::Segment {
+                                len: samples.len(),
+                                ..segment
+                            });
+                            trimmed.extend(samples);
+                        }
+                    }
+                    (trimmed, trimmed_segments)
+                } else if trim_silence {
+                    (
+                        settings.vad().trim_silence(&data, vad::TRIMMED_SILENCE),
+                        segments,
+                    )
+                } else {
+                    (data, segments)
+                };
+                let data = if denoise {
+                    ffmpeg::filter_pcm(&self.ffmpeg_path, &data, DENOISE_FILTER)
+                        .await
+                        .unwrap_or(data)
+                } else {
+                    data
+                };
+                drop(export_timer);
+
+                let target = if in_dm {
+                    match command.user.create_dm_channel(&ctx).await {
+                        Ok(dm) => Some(dm.id),
+                        Err(_) => {
+                            command
+                                .create_followup(
+                                    &ctx,
+                                    CreateInteractionResponseFollowup::new()
+                                        .content("Cannot send you direct messages."),
+                                )
+                                .await?;
+                            return Ok(());
+                        }
+                    }
+                } else if in_thread {
+                    match find_or_create_thread(&ctx, guild, command.channel_id, &username).await {
+                        Some(thread) => Some(thread),
+                        None => {
+                            command
+                                .create_followup(
+                                    &ctx,
+                                    CreateInteractionResponseFollowup::new()
+                                        .content("Cannot create a thread here."),
+                                )
+                                .await?;
+                            return Ok(());
+                        }
+                    }
+                } else {
+                    None
+                };
+
+                let notice = settings.render_legal_notice();
+                let cover_url = guild_cache::icon_url(&ctx, guild).await;
+                // Boosts don't raise the limit in direct messages.
+                let max_file_size = if in_dm {
+                    MAX_FILE_SIZE
+                } else {
+                    max_file_size(&ctx, guild).await
+                };
+                // Samples are counted at the capture rate, before resampling.
+                let max_samples = (max_file_size - sample_format.header_size())
+                    / sample_format.bytes()
+                    * resample::factor(sample_rate);
+
+                // Send a link rather than splitting if possible.
+                #[cfg(any(feature = "storage", feature = "http-api"))]
+                let offloaded = data.len() > max_samples
+                    && self
+                        .offload_recording(
+                            &ctx,
+                            &command,
+                            guild,
+                            target,
+                            &[requested_user.id],
+                            format,
+                            &data,
+                            &ExportMetadata {
+                                name: username.clone(),
+                                comment: notice.clone(),
+                                channels: 1,
+                                sample_rate,
+                                sample_format,
+                                cover_url: cover_url.clone(),
+                            },
+                        )
+                        .await?;
+                #[cfg(not(any(feature = "storage", feature = "http-api")))]
+                let offloaded = false;
+                if !offloaded {
+                    let chunks = vad::split_at_silence(&data, max_samples);
+                    let parts = chunks.len();
+                    for (i, chunk) in chunks.into_iter().enumerate() {
+                        let name = if parts <= 1 {
+                            username.clone()
+                        } else {
+                            self.report_progress(
+                                &ctx,
+                                &command,
+                                format!("Encoding part {}/{parts}…", i + 1),
+                            )
+                            .await?;
+                            format!("{}-{}", username, i + 1)
+                        };
+                        let metadata = ExportMetadata {
+                            name,
+                            comment: notice.clone(),
+                            channels: 1,
+                            sample_rate,
+                            sample_format,
+                            cover_url: cover_url.clone(),
+                        };
+                        let attachments = self
+                            .exporters
+                            .export(format, chunk, &metadata)
+                            .await
+                            .into_iter()
+                            .map(|artifact| {
+                                CreateAttachment::bytes(artifact.data, artifact.filename)
+                            })
+                            .collect_vec();
+
+                        match target {
+                            Some(target) => {
+                                let mut message = CreateMessage::new().add_files(attachments);
+                                if let Some(notice) = &notice {
+                                    message = message
+                                        .content(notice)
+                                        .allowed_mentions(CreateAllowedMentions::new());
+                                }
+                                target.send_message(&ctx, message).await?;
+                            }
+                            None => {
+                                let mut followup =
+                                    CreateInteractionResponseFollowup::new().add_files(attachments);
+                                if let Some(notice) = &notice {
+                                    followup = followup
+                                        .content(notice)
+                                        .allowed_mentions(CreateAllowedMentions::new());
+                                }
+                                command.create_followup(&ctx, followup).await?;
+                            }
+                        }
+                    }
+                    if parts > 1 {
+                        self.report_progress(&ctx, &command, format!("Sent {parts} parts."))
+                            .await?;
+                    }
+                }
+
+                let mut extras = Vec::new();
+                if let Some(metadata) = self
+                    .capture_metadata(
+                        &ctx,
+                        guild,
+                        requested_user.id,
+                        &username,
+                        serde_json::json!({
+                            "trim_silence": trim_silence,
+                            "denoise": denoise,
+                            "sample_rate": sample_rate,
+                            "bits": sample_format.name(),
+                        }),
+                    )
+                    .await?
+                {
+                    extras.push(metadata);
+                }
+                if labels && !segments.is_empty() {
+                    extras.push(CreateAttachment::bytes(
+                        mix::audacity_labels(&segments, &username),
+                        format!("{username}.txt"),
+                    ));
+                }
+                if !markers.is_empty() {
+                    extras.push(CreateAttachment::bytes(
+                        mix::marker_labels(&segments, &markers),
+                        format!("{username}-markers.txt"),
+                    ));
+                }
+                #[cfg(feature = "transcription")]
+                if transcribe {
+                    extras.push(self.transcript(&data, requested_user.id, &username).await);
+                }
+                if !extras.is_empty() {
+                    match target {
+                        Some(target) => {
+                            target
+                                .send_message(&ctx, CreateMessage::new().add_files(extras))
+                                .await?;
+                        }
+                        None => {
+                            command
+                                .create_followup(
+                                    &ctx,
+                                    CreateInteractionResponseFollowup::new().add_files(extras),
+                                )
+                                .await?;
+                        }
+                    }
+                }
+
+                if let Some(target) = target {
+                    let content = if in_dm {
+                        "Recording sent in your DMs.".to_owned()
+                    } else {
+                        format!("Recording posted in {}.", Mention::from(target))
+                    };
+                    command
+                        .create_followup(
+                            &ctx,
+                            CreateInteractionResponseFollowup::new().content(content),
+                        )
+                        .await?;
+                }
+            }
+            None => {
+                command
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(format!("No voice data found for {}.", requested_user))
+                                .allowed_mentions(CreateAllowedMentions::new()),
+                        ),
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) async fn download_recording_chunks(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let requested_user = command::find_user_option(&command, "user").unwrap_or(&command.user);
+        let Some(count) = command::find_integer_option(
+            &command,
+            "count",
+            Some(MAX_ATTACHEMENTS_PER_MESSAGE as i64),
+        )
+        .map(|c| c as usize) else {
+            return Ok(());
+        };
+        let Some(min_duration) = command::find_duration_option(
+            &command,
+            "min-duration",
+            Some(Duration::from_millis(500)),
+        ) else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new().content("Invalid duration."),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        };
+
+        let Some(format) = command::find_string_option(&command, "format", Some("ogg")) else {
+            return Ok(());
+        };
+
+        let settings = self.settings.get(guild).await;
+        let vad = settings.vad();
+        let (tx, rx) = oneshot::channel();
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::GetVoiceData(requested_user.id, tx))
+            .await?;
+
+        let username = command::resolve_username(&ctx, &requested_user, guild).await;
+        let data = match rx.await? {
+            Some(data) => {
+                let data = data.samples().await;
+                let chunks = tokio::task::spawn_blocking(move || {
+                    vad.latest_voice(&data, count, min_duration)
+                })
+                .await?;
+                (!chunks.is_empty()).then_some(chunks)
+            }
+            None => None,
+        };
+        match data {
+            Some(data) => {
+                self.audit_download(
+                    &command,
+                    guild,
+                    vec![requested_user.id],
+                    data.iter().map(Vec::len).sum(),
+                )
+                .await;
+                command.defer(&ctx).await?;
+                mark_deferred();
+                let _permit = self.wait_export_slot(&ctx, &command, guild).await?;
+                let notice = settings.render_legal_notice();
+                let cover_url = guild_cache::icon_url(&ctx, guild).await;
+                for (group_index, chunks) in data.chunks(MAX_ATTACHEMENTS_PER_MESSAGE).enumerate() {
+                    if data.len() > MAX_ATTACHEMENTS_PER_MESSAGE {
+                        self.report_progress(
+                            &ctx,
+                            &command,
+                            format!(
+                                "Encoding chunks {}-{}/{}…",
+                                group_index * MAX_ATTACHEMENTS_PER_MESSAGE + 1,
+                                group_index * MAX_ATTACHEMENTS_PER_MESSAGE + chunks.len(),
+                                data.len()
+                            ),
+                        )
+                        .await?;
+                    }
+                    let mut attachments = Vec::with_capacity(chunks.len());
+                    for (i, chunk) in chunks.iter().enumerate() {
+                        let name = if data.len() > 1 {
+                            format!(
+                                "{}-{}",
+                                username,
+                                group_index * MAX_ATTACHEMENTS_PER_MESSAGE + i + 1
+                            )
+                        } else {
+                            username.clone()
+                        };
+                        let metadata = ExportMetadata {
+                            name,
+                            comment: notice.clone(),
+                            channels: 1,
+                            sample_rate: settings.storage_rate(),
+                            sample_format: SampleFormat::S16,
+                            cover_url: cover_url.clone(),
+                        };
+                        attachments.extend(
+                            self.exporters
+                                .export(format, chunk, &metadata)
+                                .await
+                                .into_iter()
+                                .map(|artifact| {
+                                    CreateAttachment::bytes(artifact.data, artifact.filename)
+                                }),
+                        );
+                    }
+
+                    let mut followup =
+                        CreateInteractionResponseFollowup::new().add_files(attachments);
+                    if let Some(notice) = &notice {
+                        followup = followup
+                            .content(notice)
+                            .allowed_mentions(CreateAllowedMentions::new());
+                    }
+                    command.create_followup(&ctx, followup).await?;
+                }
+                if data.len() > MAX_ATTACHEMENTS_PER_MESSAGE {
+                    self.report_progress(&ctx, &command, format!("Sent {} chunks.", data.len()))
+                        .await?;
+                }
+
+                if let Some(metadata) = self
+                    .capture_metadata(
+                        &ctx,
+                        guild,
+                        requested_user.id,
+                        &username,
+                        serde_json::json!({
+                            "chunks": data.len(),
+                            "min_duration_ms": min_duration.as_millis() as u64,
+                        }),
+                    )
+                    .await?
+                {
+                    command
+                        .create_followup(
+                            &ctx,
+                            CreateInteractionResponseFollowup::new().add_file(metadata),
+                        )
+                        .await?;
+                }
+            }
+            None => {
+                command
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(format!("No voice data found for {}.", requested_user))
+                                .allowed_mentions(CreateAllowedMentions::new()),
+                        ),
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether files too large to be attached can be sent as links instead.
+    #[cfg(any(feature = "storage", feature = "http-api"))]
+    fn can_offload(&self) -> bool {
+        #[cfg(feature = "storage")]
+        if self.storage.is_some() {
+            return true;
+        }
+        #[cfg(feature = "http-api")]
+        if self.shares.is_some() {
+            return true;
+        }
+        false
+    }
+
+    /// Link to a file too large to be attached, uploaded to the external
+    /// storage if any, otherwise served by the HTTP server until it expires.
+    #[cfg(any(feature = "storage", feature = "http-api"))]
+    #[cfg_attr(not(feature = "http-api"), allow(unused_variables))]
+    async fn offload(
+        &self,
+        guild: GuildId,
+        users: &[UserId],
+        artifact: exporter::Artifact,
+    ) -> Option<String> {
+        #[cfg(feature = "storage")]
+        if let Some(storage) = &self.storage {
+            return Some(
+                match storage
+                    .upload(guild, &artifact.filename, artifact.data)
+                    .await
+                {
+                    Ok(url) => url.to_string(),
+                    Err(err) => error_code::report(&err, "offload"),
+                },
+            );
+        }
+        #[cfg(feature = "http-api")]
+        if let Some(shares) = &self.shares {
+            return Some(
+                match shares
+                    .create(guild, users.to_vec(), artifact.filename, artifact.data)
+                    .await
+                {
+                    Ok(url) => format!(
+                        "{url} (expires in {})",
+                        humantime::format_duration(shares.expiration())
+                    ),
+                    Err(err) => error_code::report(&err, "offload"),
+                },
+            );
+        }
+        None
+    }
+
+    /// Send links to a recording too large to be attached to the target
+    /// channel or as a followup, returns whether it was offloaded.
+    #[cfg(any(feature = "storage", feature = "http-api"))]
+    #[allow(clippy::too_many_arguments)]
+    async fn offload_recording(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        guild: GuildId,
+        target: Option<ChannelId>,
+        users: &[UserId],
+        format: &str,
+        data: &[i16],
+        metadata: &ExportMetadata,
+    ) -> Result<bool, InteractionError> {
+        if !self.can_offload() {
+            return Ok(false);
+        }
+
+        let mut links = Vec::new();
+        for artifact in self.exporters.export(format, data, metadata).await {
+            links.extend(self.offload(guild, users, artifact).await);
+        }
+        links.extend(metadata.comment.clone());
+        let content = links.join("\n");
+        match target {
+            Some(target) => {
+                target
+                    .send_message(ctx, CreateMessage::new().content(content))
+                    .await?;
+            }
+            None => {
+                command
+                    .create_followup(
+                        ctx,
+                        CreateInteractionResponseFollowup::new().content(content),
+                    )
+                    .await?;
+            }
+        }
+        Ok(true)
+    }
+
+    /// Transcript of a recording, or a note explaining why it is missing.
+    #[cfg(feature = "transcription")]
+    async fn transcript(&self, pcm: &[i16], user: UserId, username: &str) -> CreateAttachment {
+        let transcript = match &self.speech_recognizer_path {
+            Some(recognizer_path) => {
+                let language = self.preferences.get(user).await.language;
+                transcription::recognize(recognizer_path, pcm, language.as_deref()).await
+            }
+            None => None,
+        };
+        match transcript {
+            Some(transcript) => {
+                let filename = format!(
+                    "{username}-transcript.{}",
+                    transcription::extension(&transcript)
+                );
+                CreateAttachment::bytes(transcript, filename)
+            }
+            None => CreateAttachment::bytes(
+                "Transcription is unavailable.",
+                format!("{username}-transcript.txt"),
+            ),
+        }
+    }
+
+    /// JSON metadata describing how the voice data of a user was captured, sent
+    /// alongside downloads.
+    async fn capture_metadata(
+        &self,
+        ctx: &Context,
+        guild: GuildId,
+        user: UserId,
+        username: &str,
+        processing: serde_json::Value,
+    ) -> Result<Option<CreateAttachment>, InteractionError> {
+        let (tx, rx) = oneshot::channel();
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::GetCaptureInfo(user, tx))
+            .await?;
+        let Some(info) = rx.await? else {
+            return Ok(None);
+        };
+
+        let manager = songbird::get(ctx)
+            .await
+            .ok_or(InteractionError::VoiceUnavailable)?;
+        let channel = match manager.get(guild) {
+            Some(call) => call.lock().await.current_channel().map(|channel| channel.0),
+            None => None,
+        };
+        let format_time = |time: SystemTime| humantime::format_rfc3339_millis(time).to_string();
+
+        let metadata = serde_json::json!({
+            "guild_id": guild.to_string(),
+            "channel_id": channel.map(|channel| channel.to_string()),
+            "user_id": user.to_string(),
+            "username": username,
+            "ssrc": info.ssrc,
+            "capture_start": info.start.map(format_time),
+            "capture_end": info.end.map(format_time),
+            "packets": info.packets,
+            "lost_packets": info.lost_packets,
+            "processing": processing,
+            "legal_notice": self.settings.get(guild).await.render_legal_notice(),
+        });
+        Ok(Some(CreateAttachment::bytes(
+            serde_json::to_vec_pretty(&metadata).expect("Metadata serialization failure"),
+            format!("{username}.json"),
+        )))
+    }
+
+    /// Wait for the export limits to allow a new export, telling the user their
+    /// position in the queue if needed. The interaction must be deferred.
+    async fn wait_export_slot(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        guild: GuildId,
+    ) -> Result<ExportPermit, InteractionError> {
+        if let Some(permit) = self.exports.try_acquire(guild) {
+            return Ok(permit);
+        }
+
+        command
+            .edit_response(
+                ctx,
+                EditInteractionResponse::new().content(format!(
+                    "Export queued, position {}.",
+                    self.exports.queued() + 1
+                )),
+            )
+            .await?;
+        Ok(self.exports.acquire(guild).await)
+    }
+
+    /// Show the progress of a multi-part export in the deferred response.
+    async fn report_progress(
+        &self,
+        ctx: &Context,
+        command: &CommandInteraction,
+        status: String,
+    ) -> Result<(), InteractionError> {
+        command
+            .edit_response(ctx, EditInteractionResponse::new().content(status))
+            .await?;
+        Ok(())
+    }
+
+    /// Log the export of the audio of users to the download audit trail.
+    async fn audit_download(
+        &self,
+        command: &CommandInteraction,
+        guild: GuildId,
+        targets: Vec<UserId>,
+        samples: usize,
+    ) {
+        self.download_audit
+            .record(DownloadRecord {
+                at: SystemTime::now(),
+                guild,
+                requester: command.user.id,
+                targets,
+                kind: command.data.name.clone(),
+                duration: mix::samples_duration(samples),
+            })
+            .await;
+    }
+
+    pub(super) async fn download_mixdown(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(normalize) = command::find_boolean_option(&command, "normalize", Some(true))
+        else {
+            return Ok(());
+        };
+        let Some(format) = command::find_string_option(&command, "format", Some(DEFAULT_EXPORTER))
+        else {
+            return Ok(());
+        };
+        let settings = self.settings.get(guild).await;
+        let stereo_width = settings.stereo_width();
+        let Some(stereo) =
+            command::find_boolean_option(&command, "stereo", Some(stereo_width > 0.))
+        else {
+            return Ok(());
+        };
+
+        let (tx, rx) = oneshot::channel();
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::GetTracks(tx))
+            .await?;
+        let stored = rx.await?;
+        if stored.is_empty() {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new().content("No voice data found."),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let users = stored.iter().map(|(user, _, _)| *user).collect_vec();
+        let samples = stored.iter().map(|(_, data, _)| data.captured_len()).max();
+        self.audit_download(&command, guild, users.clone(), samples.unwrap_or_default())
+            .await;
+        command.defer(&ctx).await?;
+        mark_deferred();
+        let _permit = self.wait_export_slot(&ctx, &command, guild).await?;
+        let tracks = StoredVoice::tracks(stored)
+            .await
+            .into_iter()
+            .map(|(_, track)| track)
+            .collect_vec();
+        let notice = settings.render_legal_notice();
+        let cover_url = guild_cache::icon_url(&ctx, guild).await;
+        let markers = self.markers(guild).await?;
+        let markers = (!markers.is_empty()).then(|| mix::mixdown_marker_labels(&tracks, &markers));
+        let channels = if stereo { 2 } else { 1 };
+        // Spread speakers even if the server mixes down to mono by default.
+        let width = if stereo_width > 0. {
+            stereo_width
+        } else {
+            mix::DEFAULT_STEREO_WIDTH
+        };
+        let timer = self.stats.timer("mixdown");
+        let data = tokio::task::spawn_blocking(move || {
+            let _timer = timer;
+            if stereo {
+                let pans = mix::pan_positions(tracks.len(), width);
+                mix::mixdown_stereo(&tracks, normalize, &pans)
+            } else {
+                mix::mixdown(&tracks, normalize)
+            }
+        })
+        .await?;
+        // Split points are even, keeping interleaved stereo frames whole.
+        let max_samples = (max_file_size(&ctx, guild).await - wav::HEADER_SIZE) / 2;
+        #[cfg(any(feature = "storage", feature = "http-api"))]
+        let offloaded = data.len() > max_samples
+            && self
+                .offload_recording(
+                    &ctx,
+                    &command,
+                    guild,
+                    None,
+                    &users,
+                    format,
+                    &data,
+                    &ExportMetadata {
+                        name: "mixdown".to_owned(),
+                        comment: notice.clone(),
+                        channels,
+                        sample_rate: settings.storage_rate(),
+                        sample_format: SampleFormat::S16,
+                        cover_url: cover_url.clone(),
+                    },
+                )
+                .await?;
+        #[cfg(not(any(feature = "storage", feature = "http-api")))]
+        let offloaded = false;
+        let chunks = if offloaded {
+            Vec::new()
+        } else {
+            vad::split_at_silence(&data, max_samples / 2 * 2)
+        };
+        let parts = chunks.len();
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let name = if parts <= 1 {
+                "mixdown".to_string()
+            } else {
+                self.report_progress(&ctx, &command, format!("Encoding part {}/{parts}…", i + 1))
+                    .await?;
+                format!("mixdown-{}", i + 1)
+            };
+            let metadata = ExportMetadata {
+                name,
+                comment: notice.clone(),
+                channels,
+                sample_rate: settings.storage_rate(),
+                sample_format: SampleFormat::S16,
+                cover_url: cover_url.clone(),
+            };
+            let attachments = self
+                .exporters
+                .export(format, chunk, &metadata)
+                .await
+                .into_iter()
+                .map(|artifact| CreateAttachment::bytes(artifact.data, artifact.filename));
+
+            let mut followup = CreateInteractionResponseFollowup::new().add_files(attachments);
+            if let Some(notice) = &notice {
+                followup = followup
+                    .content(notice)
+                    .allowed_mentions(CreateAllowedMentions::new());
+            }
+            command.create_followup(&ctx, followup).await?;
+        }
+        if parts > 1 {
+            self.report_progress(&ctx, &command, format!("Sent {parts} parts."))
+                .await?;
+        }
+
+        let mut extras = Vec::new();
+        if let Some(timeline) = self.timeline.to_csv(guild).await {
+            extras.push(CreateAttachment::bytes(
+                timeline.into_bytes(),
+                "timeline.csv",
+            ));
+        }
+        if let Some(markers) = markers {
+            extras.push(CreateAttachment::bytes(markers, "markers.txt"));
+        }
+        if !extras.is_empty() {
+            command
+                .create_followup(
+                    &ctx,
+                    CreateInteractionResponseFollowup::new().add_files(extras),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Send the track of every user aligned on the session timeline, zipped
+    /// with an Audacity list of files importing them at the right offsets,
+    /// and with the mixdown of the session if `with_mixdown` is set.
+    pub(super) async fn download_session_archive(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+        with_mixdown: bool,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+
+        let (tx, rx) = oneshot::channel();
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::GetTracks(tx))
+            .await?;
+        let stored = rx.await?;
+        if stored.is_empty() {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new().content("No voice data found."),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let users = stored.iter().map(|(user, _, _)| *user).collect_vec();
+        let samples = stored.iter().map(|(_, data, _)| data.captured_len()).max();
+        self.audit_download(&command, guild, users.clone(), samples.unwrap_or_default())
+            .await;
+        command.defer(&ctx).await?;
+        mark_deferred();
+        let _permit = self.wait_export_slot(&ctx, &command, guild).await?;
+        let tracks = StoredVoice::tracks(stored)
+            .await
+            .into_iter()
+            .map(|(_, track)| track)
+            .collect_vec();
+        let settings = self.settings.get(guild).await;
+        let notice = settings.render_legal_notice();
+        let stereo_width = settings.stereo_width();
+        let markers = self.markers(guild).await?;
+        let markers = (!markers.is_empty()).then(|| mix::mixdown_marker_labels(&tracks, &markers));
+        let timer = self.stats.timer("export");
+        let (aligned, mixdown) = tokio::task::spawn_blocking(move || {
+            let _timer = timer;
+            let mixdown = with_mixdown.then(|| {
+                if stereo_width > 0. {
+                    let pans = mix::pan_positions(tracks.len(), stereo_width);
+                    mix::mixdown_stereo(&tracks, true, &pans)
+                } else {
+                    mix::mixdown(&tracks, true)
+                }
+            });
+            (mix::align(&tracks), mixdown)
+        })
+        .await?;
+
+        let mut files = Vec::new();
+        // The mixdown isn't listed for Audacity, it would play twice along the
+        // tracks.
+        if let Some(mixdown) = mixdown {
+            let metadata = ExportMetadata {
+                name: "mixdown".to_owned(),
+                comment: notice.clone(),
+                channels: if stereo_width > 0. { 2 } else { 1 },
+                sample_rate: settings.storage_rate(),
+                sample_format: SampleFormat::S16,
+                cover_url: None,
+            };
+            for artifact in self.exporters.export("flac", &mixdown, &metadata).await {
+                files.push((artifact.filename, artifact.data));
+            }
+        }
+        let mut lof = Vec::new();
+        // A speaker named like the mixdown would overwrite it in the archive.
+        let mut names = HashSet::from(["mixdown".to_owned()]);
+        for (&user, (offset, data)) in users.iter().zip(aligned) {
+            let username = match user.to_user(&ctx).await {
+                Ok(user) => command::resolve_username(&ctx, &user, guild).await,
+                Err(_) => user.to_string(),
+            };
+            // Track names come from file names, which the list quotes.
+            let mut name =
+                username.replace(|c: char| c.is_control() || "/\\\":*?<>|".contains(c), "_");
+            if !names.insert(name.clone()) {
+                name = format!("{name}-{user}");
+            }
+            let metadata = ExportMetadata {
+                name,
+                comment: notice.clone(),
+                channels: 1,
+                sample_rate: settings.storage_rate(),
+                sample_format: SampleFormat::S16,
+                cover_url: None,
+            };
+            // Silences compress well in FLAC, WAV being the fallback.
+            for artifact in self.exporters.export("flac", &data, &metadata).await {
+                lof.push((artifact.filename.clone(), offset));
+                files.push((artifact.filename, artifact.data));
+            }
+        }
+        let mut manifests = vec![("session.lof".to_owned(), mix::audacity_lof(&lof))];
+        if let Some(markers) = markers {
+            manifests.push(("markers.txt".to_owned(), markers));
+        }
+        let manifests_len = manifests.iter().map(|(_, data)| data.len()).sum::<usize>();
+
+        let max_file_size = max_file_size(&ctx, guild).await;
+        let too_large = files
+            .iter()
+            .any(|(_, data)| manifests_len + data.len() > max_file_size);
+        // A single archive is linked rather than splitting it if possible.
+        #[cfg(any(feature = "storage", feature = "http-api"))]
+        let offload = self.can_offload()
+            && (too_large
+                || manifests_len + files.iter().map(|(_, data)| data.len()).sum::<usize>()
+                    > max_file_size);
+        #[cfg(not(any(feature = "storage", feature = "http-api")))]
+        let offload = false;
+        if too_large && !offload {
+            command
+                .create_followup(
+                    &ctx,
+                    CreateInteractionResponseFollowup::new().content(
+                        "A track is too large to be attached, use `/recorder download` and `/recorder mixdown` instead.",
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        // The manifests are part of every archive, extracting them all in the
+        // same directory gives the whole project.
+        let mut archives = Vec::new();
+        let mut files = files.into_iter().peekable();
+        while files.peek().is_some() {
+            let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+            for (filename, data) in &manifests {
+                archive
+                    .start_file(filename.as_str(), ZipFileOptions::default())
+                    .expect("Failed to create session archive");
+                archive
+                    .write_all(data.as_bytes())
+                    .expect("Failed to create session archive");
+            }
+            let mut written = manifests_len;
+            while let Some((_, data)) = files.peek() {
+                if !offload && written > manifests_len && written + data.len() > max_file_size {
+                    break;
+                }
+                let (filename, data) = files.next().expect("Peeked file");
+                written += data.len();
+                archive
+                    .start_file(filename, ZipFileOptions::default())
+                    .expect("Failed to create session archive");
+                archive
+                    .write_all(&data)
+                    .expect("Failed to create session archive");
+            }
+            archives.push(
+                archive
+                    .finish()
+                    .expect("Failed to create session archive")
+                    .into_inner(),
+            );
+        }
+
+        let parts = archives.len();
+        let mut content = if parts <= 1 {
+            "Open `session.lof` in Audacity once extracted.".to_owned()
+        } else {
+            "Extract every archive in the same folder, then open `session.lof` in Audacity."
+                .to_owned()
+        };
+        if with_mixdown {
+            content += " The `mixdown` file is ready to listen to as is.";
+        }
+        if let Some(notice) = &notice {
+            content = format!("{content}\n{notice}");
+        }
+
+        #[cfg(any(feature = "storage", feature = "http-api"))]
+        if offload {
+            let archive = archives.pop().expect("Single session archive");
+            let artifact = exporter::Artifact {
+                filename: "session.zip".to_owned(),
+                data: archive,
+            };
+            if let Some(link) = self.offload(guild, &users, artifact).await {
+                command
+                    .create_followup(
+                        &ctx,
+                        CreateInteractionResponseFollowup::new()
+                            .content(format!("{link}\n{content}")),
+                    )
+                    .await?;
+            }
+            return Ok(());
+        }
+        for (i, archive) in archives.into_iter().enumerate() {
+            let filename = if parts <= 1 {
+                "session.zip".to_owned()
+            } else {
+                format!("session-{}.zip", i + 1)
+            };
+            let mut followup = CreateInteractionResponseFollowup::new()
+                .add_file(CreateAttachment::bytes(archive, filename));
+            if i == 0 {
+                followup = followup.content(&content);
+            }
+            command.create_followup(&ctx, followup).await?;
+        }
+        Ok(())
+    }
+
+    pub(super) async fn show_download_audit(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let user = command::find_user_option(&command, "user");
+        let records = self
+            .download_audit
+            .latest(guild, user.map(|user| user.id), DOWNLOAD_AUDIT_ENTRIES)
+            .await;
+
+        let content = if records.is_empty() {
+            "No downloads recorded.".to_owned()
+        } else {
+            format!(
+                "Latest downloads:\n{}",
+                records
+                    .into_iter()
+                    .map(|record| {
+                        let at = record
+                            .at
+                            .duration_since(UNIX_EPOCH)
+                            .unwrap_or_default()
+                            .as_secs();
+                        format!(
+                            "- <t:{at}:f> {} downloaded {} of {} with `/{}`",
+                            Mention::from(record.requester),
+                            humantime::format_duration(Duration::from_secs(
+                                record.duration.as_secs()
+                            )),
+                            record.targets.into_iter().map(Mention::from).join(", "),
+                            record.kind
+                        )
+                    })
+                    .join("\n")
+            )
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .ephemeral(true)
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Buttons nudging the edges of a clip and saving it.
+fn trim_buttons(to_soundboard: bool) -> Vec<CreateActionRow> {
+    let nudges = |edge: &str, label: &str| {
+        CreateActionRow::Buttons(
+            ["-5", "-1", "+1", "+5"]
+                .into_iter()
+                .map(|seconds| {
+                    CreateButton::new(format!("trim-{edge}{seconds}"))
+                        .label(format!("{label} {seconds}s"))
+                        .style(ButtonStyle::Secondary)
+                })
+                .collect(),
+        )
+    };
+    vec![
+        nudges("start", "Start"),
+        nudges("end", "End"),
+        CreateActionRow::Buttons(vec![
+            CreateButton::new("trim-save")
+                .label(if to_soundboard {
+                    "Save to soundboard"
+                } else {
+                    "Save"
+                })
+                .style(ButtonStyle::Success),
+            CreateButton::new("trim-discard")
+                .label("Discard")
+                .style(ButtonStyle::Danger),
+        ]),
+    ]
+}
@@ -0,0 +1,835 @@
+use std::{
+    collections::HashMap,
+    io::{Cursor, Write},
+    sync::atomic::Ordering,
+    time::Duration,
+};
+
+use itertools::Itertools;
+use log::{info, warn};
+use serenity::{
+    all::{
+        ButtonStyle, CommandInteraction, ComponentInteraction, CreateAllowedMentions, CreateButton,
+        CreateInteractionResponse, CreateInteractionResponseMessage, GuildId, Mention,
+        ReactionType, UserId,
+    },
+    builder::{
+        CreateActionRow, CreateAttachment, CreateInteractionResponseFollowup,
+        EditInteractionResponse,
+    },
+    client::Context,
+};
+use songbird::{input::Input, tracks::PlayMode};
+use tokio::sync::oneshot;
+use zip::{write::FileOptions as ZipFileOptions, ZipWriter};
+
+use super::{mark_deferred, Handler, MAX_FILE_SIZE};
+use crate::{
+    command, error_code, ffmpeg,
+    interaction::InteractionError,
+    mix, overlap,
+    recorder::{self, RecorderAction},
+    wav,
+};
+
+const REVIEW_SEEK_STEP: Duration = Duration::from_secs(10);
+/// Speaker pairs listed by `/overlap-report`, the rest only being in its CSV.
+const MAX_OVERLAP_REPORT_LINES: usize = 10;
+/// Period of the download count shown in profiles.
+const DOWNLOAD_AUDIT_PERIOD: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
+impl Handler {
+    /// Stop recording a user until they leave the voice channel, and drop their
+    /// voice data of the session.
+    pub(super) async fn opt_out(
+        &self,
+        guild: GuildId,
+        user: UserId,
+    ) -> Result<(), InteractionError> {
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::OptOut(user))
+            .await?;
+        self.revoke_shares(Some(guild), Some(user)).await;
+        Ok(())
+    }
+
+    /// Revoke the sharing links to files holding the voice of a user, or of
+    /// anyone if `None`, in a guild or every guild if `None`.
+    #[cfg_attr(not(feature = "http-api"), allow(unused_variables))]
+    async fn revoke_shares(&self, guild: Option<GuildId>, user: Option<UserId>) {
+        #[cfg(feature = "http-api")]
+        if let Some(shares) = &self.shares {
+            shares.revoke(guild, user).await;
+        }
+    }
+
+    pub(super) async fn forget_recording(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        self.recorder.lock().await.clear_data(command.user.id).await;
+        self.revoke_shares(None, Some(command.user.id)).await;
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("Your recorded voice data has been deleted.")
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn wipe_recording(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(user) = command::find_user_option(&command, "user") else {
+            return Ok(());
+        };
+
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::ClearData(user.id))
+            .await?;
+        self.revoke_shares(Some(guild), Some(user.id)).await;
+        info!(
+            "voice data of user {} wiped by {}",
+            user.id, command.user.id
+        );
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(format!("Recorded voice data of {} deleted.", user))
+                        .allowed_mentions(CreateAllowedMentions::new())
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Replace the buffered voice of a user by a file they recorded themselves,
+    /// placed at the given offset from the start of the session.
+    pub(super) async fn import_recording(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let (Some(user), Some(attachment)) = (
+            command::find_user_option(&command, "user"),
+            command::find_attachment_option(&command, "file"),
+        ) else {
+            return Ok(());
+        };
+        let Some(offset) = command::find_duration_option(&command, "offset", Some(Duration::ZERO))
+        else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("Invalid duration.")
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        };
+        command.defer_ephemeral(&ctx).await?;
+        mark_deferred();
+
+        let session = self
+            .sessions
+            .lock()
+            .await
+            .get(&guild)
+            .map(|session| session.start);
+        let Some(session) = session else {
+            command
+                .edit_response(
+                    &ctx,
+                    EditInteractionResponse::new()
+                        .content("No session was recorded yet to align the file with."),
+                )
+                .await?;
+            return Ok(());
+        };
+        let guild_recorder = {
+            let mut recorder = self.recorder.lock().await;
+            if !recorder.get_whitelist(guild).contains(&user.id) {
+                command
+                    .edit_response(
+                        &ctx,
+                        EditInteractionResponse::new().content(format!(
+                            "{user} isn't in the whitelist, they must join it to be recorded."
+                        )),
+                    )
+                    .await?;
+                return Ok(());
+            }
+            recorder.get_guild_recorder(guild).await
+        };
+
+        let pcm = match attachment.download().await {
+            Ok(data) => ffmpeg::decode_pcm(&self.ffmpeg_path, data).await,
+            Err(err) => {
+                warn!("cannot download imported recording: {err}");
+                None
+            }
+        };
+        let Some(pcm) = pcm.filter(|pcm| !pcm.is_empty()) else {
+            command
+                .edit_response(
+                    &ctx,
+                    EditInteractionResponse::new()
+                        .content("Cannot decode the file, is it an audio file?"),
+                )
+                .await?;
+            return Ok(());
+        };
+        let duration = mix::samples_duration(pcm.len());
+
+        let (tx, rx) = oneshot::channel();
+        guild_recorder
+            .send(RecorderAction::ImportTrack(
+                user.id,
+                session + offset,
+                pcm,
+                tx,
+            ))
+            .await?;
+        let content = if rx.await? {
+            info!(
+                "recording of user {} imported by {}",
+                user.id, command.user.id
+            );
+            format!(
+                "Imported {} of audio for {user}, {} after the start of the session. It replaces their voice data and is included in downloads, archives and mixdowns.",
+                humantime::format_duration(Duration::from_secs(duration.as_secs())),
+                humantime::format_duration(offset),
+            )
+        } else {
+            "The offset is past the end of the session, nothing was imported.".to_owned()
+        };
+        command
+            .edit_response(&ctx, EditInteractionResponse::new().content(content))
+            .await?;
+        Ok(())
+    }
+
+    /// Send everything held about the user: whitelists, buffered voice data
+    /// and uploaded sounds, as archives of at most the attachment size limit.
+    pub(super) async fn export_user_data(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let user = command.user.id;
+        command.defer_ephemeral(&ctx).await?;
+        mark_deferred();
+
+        let (whitelisted, voice_data) = {
+            let recorder = self.recorder.lock().await;
+            (
+                recorder.whitelisted_guilds(user),
+                recorder.get_user_data(user).await,
+            )
+        };
+        let sounds = match self.soundboard.uploaded_by(user).await {
+            Ok(sounds) => sounds,
+            Err(err) => {
+                command
+                    .create_followup(
+                        &ctx,
+                        CreateInteractionResponseFollowup::new()
+                            .content(error_code::report(&err, "export_user_data"))
+                            .ephemeral(true),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        };
+
+        let mut files = Vec::new();
+        let mut voice_files = Vec::new();
+        for (guild, data) in voice_data {
+            let chunk_size = (MAX_FILE_SIZE - wav::HEADER_SIZE) / 2;
+            for (i, chunk) in data.chunks(chunk_size).enumerate() {
+                let filename = if data.len() <= chunk_size {
+                    format!("voice/{guild}.wav")
+                } else {
+                    format!("voice/{guild}-{}.wav", i + 1)
+                };
+                voice_files.push(serde_json::json!({
+                    "guild": guild.to_string(),
+                    "file": filename,
+                }));
+                files.push((filename, wav::package(chunk)));
+            }
+        }
+        let mut sound_metadata = Vec::new();
+        for (metadata, data) in sounds {
+            files.push((
+                format!("sounds/{}.wav", metadata["id"].as_str().unwrap_or_default()),
+                data,
+            ));
+            sound_metadata.push(metadata);
+        }
+        #[allow(unused_mut)]
+        let mut summary = serde_json::json!({
+            "user": user.to_string(),
+            "whitelisted_guilds": whitelisted.iter().map(ToString::to_string).collect_vec(),
+            "voice_data": voice_files,
+            "uploaded_sounds": sound_metadata,
+        });
+        #[cfg(feature = "transcription")]
+        {
+            summary["preferences"] = serde_json::json!(self.preferences.get(user).await);
+        }
+        let summary =
+            serde_json::to_vec_pretty(&summary).expect("Cannot serialize data export summary");
+
+        // The summary is part of every archive.
+        let mut files = files.into_iter().peekable();
+        loop {
+            let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+            archive
+                .start_file("data.json", ZipFileOptions::default())
+                .expect("Failed to create data export archive");
+            archive
+                .write_all(&summary)
+                .expect("Failed to create data export archive");
+            let mut written = summary.len();
+            let mut archived = 0;
+            while let Some((_, data)) = files.peek() {
+                // Files are never larger than the limit, but may not fit
+                // along the summary.
+                if archived > 0 && written + data.len() > MAX_FILE_SIZE {
+                    break;
+                }
+                archived += 1;
+                let (filename, data) = files.next().expect("Peeked file");
+                written += data.len();
+                archive
+                    .start_file(filename, ZipFileOptions::default())
+                    .expect("Failed to create data export archive");
+                archive
+                    .write_all(&data)
+                    .expect("Failed to create data export archive");
+            }
+            let archive = archive
+                .finish()
+                .expect("Failed to create data export archive")
+                .into_inner();
+
+            command
+                .create_followup(
+                    &ctx,
+                    CreateInteractionResponseFollowup::new()
+                        .add_file(CreateAttachment::bytes(archive, "my-data.zip"))
+                        .ephemeral(true),
+                )
+                .await?;
+            if files.peek().is_none() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Erase everything held about the user in every guild.
+    pub(super) async fn delete_user_data(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let user = command.user.id;
+        let whitelists = {
+            let mut recorder = self.recorder.lock().await;
+            recorder.clear_data(user).await;
+            recorder.forget_user(user).await
+        };
+        self.revoke_shares(None, Some(user)).await;
+        self.history.forget(user).await;
+        #[cfg(feature = "transcription")]
+        if let Err(err) = self.preferences.remove(user).await {
+            warn!("cannot delete preferences of user {user}: {err}");
+        }
+        let content = match self.soundboard.delete_uploaded_by(user).await {
+            Ok(sounds) => format!(
+                "Your voice data has been deleted, you were removed from {whitelists} whitelist(s) and {sounds} of your uploaded sound(s) were deleted."
+            ),
+            Err(err) => error_code::report(&err, "delete_user_data"),
+        };
+        info!("data of user {user} deleted on request");
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Ask for confirmation before discarding the buffered voice data of a user
+    /// or of everyone.
+    pub(super) async fn flush_recording(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let user = command::find_user_option(&command, "user");
+        let (content, target) = match user {
+            Some(user) => (
+                format!(
+                    "Discard the buffered audio of {} without downloading it?",
+                    user
+                ),
+                user.id.to_string(),
+            ),
+            None => (
+                "Discard the buffered audio of everyone without downloading it?".to_owned(),
+                "all".to_owned(),
+            ),
+        };
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .allowed_mentions(CreateAllowedMentions::new())
+                        .ephemeral(true)
+                        .components(vec![CreateActionRow::Buttons(vec![
+                            CreateButton::new(format!("flush-confirm-{target}"))
+                                .label("Discard")
+                                .style(ButtonStyle::Danger),
+                            CreateButton::new("flush-cancel")
+                                .label("Cancel")
+                                .style(ButtonStyle::Secondary),
+                        ])]),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn confirm_flush(
+        &self,
+        ctx: Context,
+        component: ComponentInteraction,
+        guild: GuildId,
+        action: &str,
+    ) -> Result<(), InteractionError> {
+        let content = match action.strip_prefix("confirm-") {
+            Some("all") => {
+                self.recorder
+                    .lock()
+                    .await
+                    .get_guild_recorder(guild)
+                    .await
+                    .send(RecorderAction::ClearAllData)
+                    .await?;
+                self.revoke_shares(Some(guild), None).await;
+                info!(
+                    "voice data of guild {guild} flushed by {}",
+                    component.user.id
+                );
+                "Buffered audio of everyone discarded.".to_owned()
+            }
+            Some(user) => {
+                let Ok(user) = user.parse().map(UserId::new) else {
+                    return Ok(());
+                };
+                self.recorder
+                    .lock()
+                    .await
+                    .get_guild_recorder(guild)
+                    .await
+                    .send(RecorderAction::ClearData(user))
+                    .await?;
+                self.revoke_shares(Some(guild), Some(user)).await;
+                info!(
+                    "voice data of user {user} in guild {guild} flushed by {}",
+                    component.user.id
+                );
+                format!("Buffered audio of {} discarded.", Mention::from(user))
+            }
+            None => "Nothing was discarded.".to_owned(),
+        };
+
+        component
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .components(vec![]),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn review_recording(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let requested_user = command::find_user_option(&command, "user").unwrap_or(&command.user);
+        let (Some(from), Some(to)) = (
+            command::find_duration_option(&command, "from", Some(Duration::ZERO)),
+            command::find_duration_option(&command, "to", Some(Duration::MAX)),
+        ) else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new().content("Invalid duration."),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        };
+
+        let manager = songbird::get(&ctx)
+            .await
+            .ok_or(InteractionError::VoiceUnavailable)?;
+        let call = match manager.get(guild) {
+            Some(call) if call.lock().await.current_channel().is_some() => Some(call),
+            _ => None,
+        };
+        let Some(call) = call else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("I'm not in a voice channel. Use `/join` first."),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        };
+
+        let (tx, rx) = oneshot::channel();
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::GetVoiceData(requested_user.id, tx))
+            .await?;
+        let data = match rx.await? {
+            Some(data) => Some(data.samples().await),
+            None => None,
+        };
+
+        let range = data.as_ref().and_then(|data| {
+            let start = (from.as_millis() as usize).saturating_mul(recorder::FREQUENCY / 1000);
+            let end = (to.as_millis() as usize)
+                .saturating_mul(recorder::FREQUENCY / 1000)
+                .min(data.len());
+            (start < end).then_some(start..end)
+        });
+        let (Some(data), Some(range)) = (data, range) else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(format!("No voice data found for {}.", requested_user))
+                            .allowed_mentions(CreateAllowedMentions::new()),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        };
+
+        let duration = Duration::from_millis((range.len() * 1000 / recorder::FREQUENCY) as u64);
+        let handle = call
+            .lock()
+            .await
+            .play_input(Input::from(wav::package(&data[range])));
+        if let Some(previous) = self.reviews.lock().await.insert(guild, handle) {
+            let _ = previous.stop();
+        }
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(format!(
+                            "Reviewing {} of {}'s recording.",
+                            humantime::format_duration(duration),
+                            requested_user
+                        ))
+                        .allowed_mentions(CreateAllowedMentions::new())
+                        .components(vec![CreateActionRow::Buttons(vec![
+                            CreateButton::new("review-backward")
+                                .emoji(ReactionType::from('⏪'))
+                                .style(ButtonStyle::Secondary),
+                            CreateButton::new("review-pause")
+                                .emoji(ReactionType::from('⏯'))
+                                .style(ButtonStyle::Primary),
+                            CreateButton::new("review-forward")
+                                .emoji(ReactionType::from('⏩'))
+                                .style(ButtonStyle::Secondary),
+                            CreateButton::new("review-stop")
+                                .emoji(ReactionType::from('⏹'))
+                                .style(ButtonStyle::Danger),
+                        ])]),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn control_review(
+        &self,
+        ctx: Context,
+        component: ComponentInteraction,
+        guild: GuildId,
+        action: &str,
+    ) -> Result<(), InteractionError> {
+        component.defer(&ctx).await?;
+        mark_deferred();
+
+        let mut reviews = self.reviews.lock().await;
+        let Some(handle) = reviews.get(&guild) else {
+            return Ok(());
+        };
+        let Ok(info) = handle.get_info().await else {
+            // Track already ended.
+            reviews.remove(&guild);
+            return Ok(());
+        };
+        let _ = match action {
+            "pause" if info.playing == PlayMode::Play => handle.pause(),
+            "pause" => handle.play(),
+            "backward" => handle
+                .seek_async(info.position.saturating_sub(REVIEW_SEEK_STEP))
+                .await
+                .map(|_| ()),
+            "forward" => handle
+                .seek_async(info.position + REVIEW_SEEK_STEP)
+                .await
+                .map(|_| ()),
+            "stop" => {
+                let res = handle.stop();
+                reviews.remove(&guild);
+                res
+            }
+            _ => Ok(()),
+        };
+        Ok(())
+    }
+
+    pub(super) async fn show_profile(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let user = command::find_user_option(&command, "user").unwrap_or(&command.user);
+
+        let recorder = self.recorder.lock().await.get_guild_recorder(guild).await;
+        let (tx, rx) = oneshot::channel();
+        recorder
+            .send(RecorderAction::GetSegments(user.id, tx))
+            .await?;
+        let buffered = rx.await?.iter().map(|segment| segment.len).sum();
+        let (tx, rx) = oneshot::channel();
+        recorder.send(RecorderAction::GetTalkTimes(tx)).await?;
+        let talk_times = rx.await?;
+        let opted_in = self
+            .recorder
+            .lock()
+            .await
+            .get_whitelist(guild)
+            .contains(&user.id);
+
+        let names: HashMap<_, _> = self
+            .soundboard
+            .list(guild)
+            .await
+            .into_iter()
+            .flat_map(|(_group, sounds)| sounds)
+            .map(|sound| (sound.id, sound.name))
+            .collect();
+        let sound_name = |id| {
+            names
+                .get(&id)
+                .map(|name| format!("`{name}`"))
+                .unwrap_or_else(|| "*deleted sound*".to_owned())
+        };
+        let (favorites, recent) = self.history.get_user_plays(guild, user.id, 3).await;
+        let downloads = self
+            .download_audit
+            .count_since(guild, user.id, DOWNLOAD_AUDIT_PERIOD)
+            .await;
+
+        let mut profile = format!(
+            "**Profile of {}**\nRecording: {}\nBuffered voice: {}\n",
+            Mention::from(user.id),
+            if opted_in { "opted in" } else { "not opted in" },
+            humantime::format_duration(Duration::from_secs(
+                mix::samples_duration(buffered).as_secs()
+            )),
+        );
+        match talk_times.iter().position(|(u, _)| *u == user.id) {
+            Some(rank) => profile.push_str(&format!(
+                "Talk time this session: {} (#{} of {})\n",
+                humantime::format_duration(Duration::from_secs(talk_times[rank].1.as_secs())),
+                rank + 1,
+                talk_times.len()
+            )),
+            None => profile.push_str("Talk time this session: *not in the session*\n"),
+        }
+        profile.push_str(&format!(
+            "{} audio was downloaded {downloads} time{} this week\n",
+            if user.id == command.user.id {
+                "Your"
+            } else {
+                "Their"
+            },
+            if downloads == 1 { "" } else { "s" }
+        ));
+        if favorites.is_empty() {
+            profile.push_str("Favorite sounds: *none yet*\n");
+        } else {
+            profile.push_str(&format!(
+                "Favorite sounds: {}\n",
+                favorites
+                    .into_iter()
+                    .map(|(sound, count)| format!("{} ({count})", sound_name(sound)))
+                    .join(", ")
+            ));
+        }
+        if !recent.is_empty() {
+            profile.push_str(&format!(
+                "Last plays: {}",
+                recent
+                    .into_iter()
+                    .map(|(sound, ago)| format!(
+                        "{} {} ago",
+                        sound_name(sound),
+                        humantime::format_duration(Duration::from_secs(ago.as_secs()))
+                    ))
+                    .join(", ")
+            ));
+        }
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(profile)
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Report how often and how long each speaker talked over the others since
+    /// the bot joined.
+    pub(super) async fn overlap_report(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(csv) = command::find_boolean_option(&command, "csv", Some(false)) else {
+            return Ok(());
+        };
+
+        let (tx, rx) = oneshot::channel();
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::GetAllSegments(tx))
+            .await?;
+        let mut speakers = rx.await?;
+        // Sounds played by the bot aren't anyone talking over.
+        let bot = UserId::new(self.bot_id.load(Ordering::Relaxed));
+        speakers.retain(|(user, _)| *user != bot);
+        if let Some(session) = self.sessions.lock().await.get(&guild) {
+            for (_, segments) in &mut speakers {
+                segments.retain(|segment| segment.start >= session.start);
+            }
+        }
+        let overlaps = overlap::analyze(&speakers);
+
+        let mut response = CreateInteractionResponseMessage::new();
+        if overlaps.is_empty() {
+            response = response.content("Nobody talked over anyone this session.");
+        } else if csv {
+            response = response.add_file(CreateAttachment::bytes(
+                overlap::to_csv(&overlaps).into_bytes(),
+                "overlaps.csv",
+            ));
+        } else {
+            let mut report = "**Talk-over this session**\n".to_owned();
+            for overlap in overlaps.iter().take(MAX_OVERLAP_REPORT_LINES) {
+                report.push_str(&format!(
+                    "{} talked over {} {} time{} ({})\n",
+                    Mention::from(overlap.interrupter),
+                    Mention::from(overlap.interrupted),
+                    overlap.count,
+                    if overlap.count == 1 { "" } else { "s" },
+                    humantime::format_duration(Duration::from_secs(overlap.duration.as_secs()))
+                ));
+            }
+            if overlaps.len() > MAX_OVERLAP_REPORT_LINES {
+                report.push_str("Use `csv:True` for the full report.");
+            }
+            response = response
+                .content(report)
+                .allowed_mentions(CreateAllowedMentions::new());
+        }
+        command
+            .create_response(&ctx, CreateInteractionResponse::Message(response))
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1605 @@
+use log::info;
+use serenity::{
+    all::{
+        ButtonStyle, ChannelType, Command, CommandOptionType, CommandType, CreateCommand,
+        CreateCommandOption, Permissions,
+    },
+    client::Context,
+};
+
+use super::Handler;
+#[cfg(feature = "http-api")]
+use crate::api_keys;
+use crate::{button, resample, vad, wav::SampleFormat};
+
+/// Number of quick-play commands, `/p1` to `/p9`.
+const SOUND_SLOTS: u8 = 9;
+
+impl Handler {
+    /// Format option offering every registered exporter.
+    fn format_option(&self, description: &str) -> CreateCommandOption {
+        self.exporters.names().fold(
+            CreateCommandOption::new(CommandOptionType::String, "format", description)
+                .required(false),
+            |option, name| option.add_string_choice(name, name),
+        )
+    }
+
+    pub(super) async fn register_global_commands(&self, ctx: &Context) {
+        info!("creating global commands");
+
+        let version = CreateCommand::new("version")
+            .description("Display version")
+            .kind(CommandType::ChatInput);
+        let stats = CreateCommand::new("stats")
+            .description(
+                "Display the processing time of each stage and the recorder usage of this server",
+            )
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD);
+        let join_voice_channel = CreateCommand::new("join")
+            .description("Join your voice channel")
+            .kind(CommandType::ChatInput)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Channel,
+                    "channel",
+                    "Voice channel to join instead of yours (needs the Move Members permission)",
+                )
+                .channel_types(vec![ChannelType::Voice, ChannelType::Stage])
+                .required(false),
+            );
+        let pause_recording = CreateCommand::new("pause")
+            .description("Temporarily stop recording the voice channel")
+            .kind(CommandType::ChatInput)
+            .dm_permission(false);
+        let resume_recording = CreateCommand::new("resume")
+            .description("Resume recording the voice channel")
+            .kind(CommandType::ChatInput)
+            .dm_permission(false);
+        let flush_recording = CreateCommand::new("flush")
+            .description("Discard buffered voice data without downloading it")
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::User,
+                    "user",
+                    "User to discard the voice data of (defaults to everyone)",
+                )
+                .required(false),
+            );
+        let leave_session = CreateCommand::new("leave")
+            .description("Stop being recorded and delete your audio of this session")
+            .kind(CommandType::ChatInput)
+            .dm_permission(false);
+        let forget_recording = CreateCommand::new("forget")
+            .description("Delete your recorded voice data")
+            .kind(CommandType::ChatInput);
+        let wipe_recording = CreateCommand::new("wipe")
+            .description("Delete the recorded voice data of a user")
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::User,
+                    "user",
+                    "User to delete the voice data of",
+                )
+                .required(true),
+            );
+        let import_recording = CreateCommand::new("import-recording")
+            .description("Replace the voice data of a user by a file they recorded themselves")
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::User,
+                    "user",
+                    "User who made the recording",
+                )
+                .required(true),
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Attachment, "file", "Audio file")
+                    .required(true),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "offset",
+                    "When the file starts after the start of the session (eg. 1m30s, defaults to 0)",
+                )
+                .required(false),
+            );
+        let mark = CreateCommand::new("mark")
+            .description("Bookmark the current moment, exported as a label with downloads")
+            .kind(CommandType::ChatInput)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "label",
+                    "What happened at this moment",
+                )
+                .max_length(100)
+                .required(true),
+            );
+        let synctone = CreateCommand::new("synctone")
+            .description(
+                "Play a tone marked in exports, to line up local recordings with the bot's",
+            )
+            .kind(CommandType::ChatInput)
+            .dm_permission(false);
+        let waveform = CreateCommand::new("waveform")
+            .description("Show an image of a user's buffered voice, to check it was captured")
+            .kind(CommandType::ChatInput)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::User,
+                    "user",
+                    "User to show the voice of (defaults to yourself)",
+                )
+                .required(false),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "style",
+                    "Kind of image (defaults to waveform)",
+                )
+                .add_string_choice("waveform", "waveform")
+                .add_string_choice("spectrogram", "spectrogram")
+                .required(false),
+            );
+        let clip = CreateCommand::new("clip")
+            .description("Clip the latest voice of a user, with buttons to trim it")
+            .kind(CommandType::ChatInput)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::User,
+                    "user",
+                    "User to clip the voice of (defaults to yourself)",
+                )
+                .required(false),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "last",
+                    "Number of seconds to clip (defaults to 10)",
+                )
+                .min_int_value(1)
+                .max_int_value(60)
+                .required(false),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "sound",
+                    "Add the clip to the soundboard under this name once saved",
+                )
+                .max_length(100)
+                .required(false),
+            );
+        let voicediag = CreateCommand::new("voicediag")
+            .description("Check the voice connection, to tell whether bad recordings come from Discord or the bot")
+            .kind(CommandType::ChatInput)
+            .dm_permission(false);
+        let quality_report = CreateCommand::new("quality-report")
+            .description("Check the buffered voice of every speaker for clipping, low level, noise and dropouts")
+            .kind(CommandType::ChatInput)
+            .dm_permission(false);
+        let profile = CreateCommand::new("profile")
+            .description("Show the recording and soundboard activity of a user")
+            .kind(CommandType::ChatInput)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::User,
+                    "user",
+                    "User to show the profile of (defaults to yourself)",
+                )
+                .required(false),
+            );
+        let overlap_report = CreateCommand::new("overlap-report")
+            .description("Show who talked over whom during the session")
+            .kind(CommandType::ChatInput)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "csv",
+                    "Attach the report as CSV (defaults to false)",
+                )
+                .required(false),
+            );
+        let my_data = CreateCommand::new("mydata")
+            .description("Export or delete everything the bot holds about you")
+            .kind(CommandType::ChatInput)
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "export",
+                "Get your voice data, whitelist entries and uploaded sounds",
+            ))
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "delete",
+                "Delete your voice data, whitelist entries and uploaded sounds",
+            ));
+        #[cfg(feature = "http-api")]
+        let share_recording = CreateCommand::new("share")
+            .description("Get a temporary link to the latest part of a user's recording")
+            .kind(CommandType::ChatInput)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::User,
+                    "user",
+                    "User to share the recording of (defaults to yourself)",
+                )
+                .required(false),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "last",
+                    "Number of seconds to share (defaults to 30)",
+                )
+                .min_int_value(1)
+                .required(false),
+            );
+        let download_recording = CreateCommandOption::new(
+            CommandOptionType::SubCommand,
+            "download",
+            "Download a user's recording",
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::User,
+                "user",
+                "User to download data for (defaults to yourself)",
+            )
+            .required(false),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "trim-silence",
+                "Shorten long silences (defaults to the server setting)",
+            )
+            .required(false),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "denoise",
+                "Reduce background noise (defaults to the server setting)",
+            )
+            .required(false),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "thread",
+                "Post the recording in a thread named after the user",
+            )
+            .required(false),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::String,
+                "destination",
+                "Where to send the recording (defaults to this channel)",
+            )
+            .add_string_choice("channel", "channel")
+            .add_string_choice("dm", "dm")
+            .required(false),
+        )
+        .add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "labels",
+                "Also attach an Audacity label track of the speaking segments",
+            )
+            .required(false),
+        )
+        .add_sub_option(self.format_option("Audio format of the recording (defaults to WAV)"))
+        .add_sub_option(
+            resample::STORAGE_RATES.iter().fold(
+                CreateCommandOption::new(
+                    CommandOptionType::Integer,
+                    "sample-rate",
+                    "Sample rate of the recording (defaults to 48 kHz)",
+                )
+                .required(false),
+                |option, rate| option.add_int_choice(format!("{} kHz", rate / 1000), *rate as i32),
+            ),
+        )
+        .add_sub_option(
+            SampleFormat::ALL.into_iter().fold(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "bits",
+                    "Bit depth of WAV and FLAC recordings, voice being 16-bit (defaults to 16)",
+                )
+                .required(false),
+                |option, format| option.add_string_choice(format.name(), format.name()),
+            ),
+        );
+        #[cfg(feature = "transcription")]
+        let download_recording = download_recording.add_sub_option(
+            CreateCommandOption::new(
+                CommandOptionType::Boolean,
+                "transcribe",
+                "Also attach a transcript of the recording",
+            )
+            .required(false),
+        );
+        let recorder = CreateCommand::new("recorder")
+            .description("Manage the recorder whitelist and download recordings")
+            .kind(CommandType::ChatInput)
+            // List.
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "list",
+                "Get recorder's whitelist, with how much audio is buffered for each member",
+            ))
+            // Join whitelist.
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "join",
+                "Join recorder's whitelist",
+            ))
+            // Leave whitelist.
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "leave",
+                "Leave recorder's whitelist",
+            ))
+            // Download recording.
+            .add_option(download_recording)
+            // Download recording chunks.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "download-chunks",
+                    "Download a user's recording chunks",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::User,
+                        "user",
+                        "User to download data for (defaults to yourself)",
+                    )
+                    .required(false),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "count",
+                        "Maximum number of chunks to fetch",
+                    )
+                    .required(false)
+                    .min_int_value(1),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "min-duration",
+                        "Minimum duration of chunks",
+                    )
+                    .required(false),
+                )
+                .add_sub_option(self.format_option("Audio format of chunks (defaults to OGG)")),
+            )
+            // Mixdown.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "mixdown",
+                    "Download the recordings of every user mixed together",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "normalize",
+                        "Balance speakers to a similar loudness (defaults to true)",
+                    )
+                    .required(false),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "stereo",
+                        "Pan each speaker to its own position (defaults to the server setting)",
+                    )
+                    .required(false),
+                )
+                .add_sub_option(
+                    self.format_option("Audio format of the mixdown (defaults to WAV)"),
+                ),
+            )
+            // Audacity project.
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "audacity",
+                "Download the track of every user as an Audacity project, aligned on the session",
+            ))
+            // Session archive.
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "archive",
+                "Download the Audacity project of the session along with its mixdown",
+            ))
+            // Review recording.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "review",
+                    "Play a user's recording in the voice channel",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::User,
+                        "user",
+                        "User to play the recording of (defaults to yourself)",
+                    )
+                    .required(false),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "from",
+                        "Position in the recording to start from (eg. 1m30s)",
+                    )
+                    .required(false),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "to",
+                        "Position in the recording to stop at",
+                    )
+                    .required(false),
+                ),
+            );
+        let whitelist = CreateCommand::new("whitelist")
+            .description("Manage the recorder whitelist")
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            // Add.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "add",
+                    "Add a user to the recorder whitelist",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::User, "user", "User to add")
+                        .required(true),
+                ),
+            )
+            // Remove.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "remove",
+                    "Remove a user from the recorder whitelist",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::User, "user", "User to remove")
+                        .required(true),
+                ),
+            )
+            // Add role.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "add-role",
+                    "Record every member holding a role",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::Role, "role", "Role to add")
+                        .required(true),
+                ),
+            )
+            // Remove role.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "remove-role",
+                    "Stop recording members because they hold a role",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::Role, "role", "Role to remove")
+                        .required(true),
+                ),
+            );
+        let mut soundboard = CreateCommand::new("soundboard")
+            .description("Add, delete or download sounds to/from the soundboard")
+            .kind(CommandType::ChatInput)
+            // List.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "list",
+                    "List all sounds available on this server",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "random",
+                        "Add a random sound button",
+                    )
+                    .required(false),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "latest",
+                        "Add a latest sound button",
+                    )
+                    .required(false),
+                ),
+            )
+            // Upload.
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::SubCommand, "upload", "Upload a sound")
+                    .add_sub_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::Attachment,
+                            "sound",
+                            "Sound file",
+                        )
+                        .required(true),
+                    )
+                    .add_sub_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::String,
+                            "name",
+                            "The name of the sound that will appear on the button",
+                        )
+                        .required(true),
+                    )
+                    .add_sub_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::String,
+                            "group",
+                            "The group to add this sound to",
+                        )
+                        .required(true)
+                        .set_autocomplete(true),
+                    )
+                    .add_sub_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::String,
+                            "emoji",
+                            "The emoji to prepend to the button",
+                        )
+                        .required(false),
+                    )
+                    .add_sub_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::String,
+                            "color",
+                            "Color of the button",
+                        )
+                        .required(false)
+                        .add_string_choice("blue", button::as_str(ButtonStyle::Primary))
+                        .add_string_choice("green", button::as_str(ButtonStyle::Success))
+                        .add_string_choice("red", button::as_str(ButtonStyle::Danger))
+                        .add_string_choice("grey", button::as_str(ButtonStyle::Secondary)),
+                    )
+                    .add_sub_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::Integer,
+                            "position",
+                            "The position of the sound in its group",
+                        )
+                        .required(false)
+                        .min_int_value(1),
+                    )
+                    .add_sub_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::Boolean,
+                            "loop",
+                            "Play in loop until the button is pressed again, for ambient sounds",
+                        )
+                        .required(false),
+                    ),
+            )
+            // Download.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "download",
+                    "Download a sound",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "sound",
+                        "Sound name, or group/name if used in several groups",
+                    )
+                    .required(true)
+                    .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "group",
+                        "Group name of the sound to delete",
+                    )
+                    .required(false)
+                    .set_autocomplete(true),
+                ),
+            )
+            // Rename.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "rename",
+                    "Change the name of a soundboard button",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "sound",
+                        "Sound name, or group/name if used in several groups",
+                    )
+                    .required(true)
+                    .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "new-name",
+                        "New name of the button",
+                    )
+                    .required(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "group",
+                        "Group name of the button",
+                    )
+                    .required(false)
+                    .set_autocomplete(true),
+                ),
+            )
+            // Move.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "move",
+                    "Change the group of a soundboard button",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "sound",
+                        "Sound name, or group/name if used in several groups",
+                    )
+                    .required(true)
+                    .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "new-group",
+                        "New group of the button",
+                    )
+                    .required(true)
+                    .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "group",
+                        "Group name of the button to modify",
+                    )
+                    .required(false)
+                    .set_autocomplete(true),
+                ),
+            )
+            // Change color.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "change-color",
+                    "Change the color of a soundboard button",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "sound",
+                        "Sound name, or group/name if used in several groups",
+                    )
+                    .required(true)
+                    .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "color",
+                        "New color of the button",
+                    )
+                    .required(true)
+                    .add_string_choice("blue", button::as_str(ButtonStyle::Primary))
+                    .add_string_choice("green", button::as_str(ButtonStyle::Success))
+                    .add_string_choice("red", button::as_str(ButtonStyle::Danger))
+                    .add_string_choice("grey", button::as_str(ButtonStyle::Secondary)),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "group",
+                        "Group name of the button to modify",
+                    )
+                    .required(false)
+                    .set_autocomplete(true),
+                ),
+            )
+            // Change Emoji.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "change-emoji",
+                    "Change the emoji of a soundboard button",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "sound",
+                        "Sound name, or group/name if used in several groups",
+                    )
+                    .required(true)
+                    .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "emoji",
+                        "New emoji of the button",
+                    )
+                    .required(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "group",
+                        "Group name of the button to modify",
+                    )
+                    .required(false)
+                    .set_autocomplete(true),
+                ),
+            )
+            // Change loop.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "change-loop",
+                    "Make a sound play in loop until its button is pressed again",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "sound",
+                        "Sound name, or group/name if used in several groups",
+                    )
+                    .required(true)
+                    .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "loop",
+                        "Whether the sound plays in loop",
+                    )
+                    .required(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "group",
+                        "Group name of the button to modify",
+                    )
+                    .required(false)
+                    .set_autocomplete(true),
+                ),
+            )
+            // ID.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "id",
+                    "Get the ID of a sound",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "sound",
+                        "Sound name, or group/name if used in several groups",
+                    )
+                    .required(true)
+                    .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "group",
+                        "Group name of the sound to fetch",
+                    )
+                    .required(false)
+                    .set_autocomplete(true),
+                ),
+            )
+            // Backup.
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "backup",
+                "Download all sounds and metadata as a zip archive",
+            ))
+            // Logs.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "logs",
+                    "Get latest usage of the soundboard in this channel",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "duration",
+                        "Logs aggregation duration",
+                    )
+                    .required(false),
+                ),
+            );
+        if self.allow_delete {
+            soundboard = soundboard.add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "delete",
+                    "Delete a sound from the soundboard",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "sound",
+                        "Sound name, or group/name if used in several groups",
+                    )
+                    .required(true)
+                    .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "group",
+                        "Group name of the sound to delete",
+                    )
+                    .required(false)
+                    .set_autocomplete(true),
+                ),
+            );
+        }
+
+        let packs = CreateCommand::new("packs")
+            .description("Install community sound packs")
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            // Browse.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "browse",
+                    "List the packs of an index",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "url",
+                        "URL of the pack index",
+                    )
+                    .required(true),
+                ),
+            )
+            // Install.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "install",
+                    "Install a pack of an index into the soundboard",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "url",
+                        "URL of the pack index",
+                    )
+                    .required(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(CommandOptionType::String, "pack", "Pack name")
+                        .required(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "group",
+                        "Group to install sounds into (defaults to the pack name)",
+                    )
+                    .required(false),
+                ),
+            );
+        let settings = CreateCommand::new("settings")
+            .description("Change the bot settings of this server")
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            // Show.
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "show",
+                "Display the current settings",
+            ))
+            // VAD threshold.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "vad-threshold",
+                    "Change the minimum level of voice used to split and trim recordings",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Number,
+                        "threshold",
+                        "Level in dBFS, reset to default if omitted",
+                    )
+                    .required(false)
+                    .min_number_value(vad::MIN_THRESHOLD as f64)
+                    .max_number_value(0.),
+                ),
+            )
+            // Trim silence.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "trim-silence",
+                    "Shorten long silences of recordings by default",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "enabled",
+                        "Trim silences by default",
+                    )
+                    .required(true),
+                ),
+            )
+            // Noise gate.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "noise-gate",
+                    "Ignore quiet voice packets (keyboard, fans...) while recording",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Number,
+                        "threshold",
+                        "Level in dBFS, disable the noise gate if omitted",
+                    )
+                    .required(false)
+                    .min_number_value(vad::MIN_THRESHOLD as f64)
+                    .max_number_value(0.),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "hold",
+                        "How long the gate stays open after voice (default: 200ms)",
+                    )
+                    .required(false),
+                ),
+            )
+            // Denoise.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "denoise",
+                    "Reduce background noise of recordings by default",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "enabled",
+                        "Reduce background noise by default",
+                    )
+                    .required(true),
+                ),
+            )
+            // Stereo width.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "stereo-width",
+                    "Pan speakers apart in mixdowns so listeners can tell voices apart",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Number,
+                        "width",
+                        "From 0 (mono) to 1 (hard left to hard right), reset to default if omitted",
+                    )
+                    .required(false)
+                    .min_number_value(0.)
+                    .max_number_value(1.),
+                ),
+            )
+            // Buffer duration.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "buffer-duration",
+                    "How much of the latest audio the recordings keep",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "duration",
+                        "Buffer duration (eg. 30m), reset to the default if omitted",
+                    )
+                    .required(false),
+                ),
+            )
+            // Recorded role.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "recorded-role",
+                    "Only record whitelisted users holding a role, such as the cast of a show",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Role,
+                        "role",
+                        "Role to record, anyone whitelisted is recorded if omitted",
+                    )
+                    .required(false),
+                ),
+            )
+            // Session split.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "session-split",
+                    "Post the recording in parts during long sessions",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "every",
+                        "Length of the parts (eg. 2h), never split if omitted",
+                    )
+                    .required(false),
+                ),
+            )
+            // Storage rate.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "storage-rate",
+                    "Sample rate voice is buffered at, lower rates fit more audio in memory",
+                )
+                .add_sub_option(
+                    resample::STORAGE_RATES.iter().fold(
+                        CreateCommandOption::new(
+                            CommandOptionType::Integer,
+                            "rate",
+                            "Sample rate, reset to 48 kHz if omitted",
+                        )
+                        .required(false),
+                        |option, rate| {
+                            option.add_int_choice(format!("{} kHz", rate / 1000), *rate as i32)
+                        },
+                    ),
+                ),
+            )
+            // Retention.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "retention",
+                    "How long recorded audio may be kept",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "duration",
+                        "Retention duration (eg. 24h), reset to the default if omitted",
+                    )
+                    .required(false),
+                ),
+            )
+            // Pause retention.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "pause-retention",
+                    "Stop expiring recorded audio while no whitelisted user is present",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "enabled",
+                        "Pause retention while nobody whitelisted is present",
+                    )
+                    .required(true),
+                ),
+            )
+            // Announce recording.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "announce",
+                    "Post a message naming who started recording",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "enabled",
+                        "Announce when recording starts",
+                    )
+                    .required(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Channel,
+                        "channel",
+                        "Channel to announce in (defaults to the voice channel's chat)",
+                    )
+                    .channel_types(vec![ChannelType::Text])
+                    .required(false),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "kill-switch-emoji",
+                        "Reaction stopping the recording of whoever adds it (defaults to 🛑)",
+                    )
+                    .required(false),
+                ),
+            )
+            // Legal notice.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "legal-notice",
+                    "Include a notice with every export, {date} being the export date",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "text",
+                        "Notice text (removes the notice if empty)",
+                    )
+                    .max_length(1000)
+                    .required(false),
+                ),
+            )
+            // Recording nickname.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "recording-nickname",
+                    "Prefix the bot's nickname while it records",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "prefix",
+                        "Nickname prefix, such as 🔴 (keeps the nickname if empty)",
+                    )
+                    .max_length(8)
+                    .required(false),
+                ),
+            )
+            // Whitelist expiry.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "whitelist-expiry",
+                    "Make whitelisted users consent again periodically",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "days",
+                        "Days before consent must be renewed (never expires if omitted)",
+                    )
+                    .min_int_value(1)
+                    .max_int_value(3650)
+                    .required(false),
+                ),
+            )
+            // Auto-join.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "auto-join",
+                    "Join a voice channel as soon as a whitelisted user enters it",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Channel,
+                        "channel",
+                        "Voice channel to join automatically",
+                    )
+                    .channel_types(vec![ChannelType::Voice, ChannelType::Stage])
+                    .required(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "enabled",
+                        "Join this channel automatically",
+                    )
+                    .required(true),
+                ),
+            )
+            // Blocked channels.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "blocked-channel",
+                    "Refuse to listen in a voice channel, such as the AFK one",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Channel,
+                        "channel",
+                        "Voice channel to block",
+                    )
+                    .channel_types(vec![ChannelType::Voice, ChannelType::Stage])
+                    .required(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "blocked",
+                        "Refuse to listen in this channel",
+                    )
+                    .required(true),
+                ),
+            )
+            // Soundboard only.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "soundboard-only",
+                    "Stop receiving voice entirely, only playing sounds",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "enabled",
+                        "Disable recording and keep the soundboard",
+                    )
+                    .required(true),
+                ),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "join-switch",
+                    "What /join does while the bot listens in another channel",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "enabled",
+                        "Move the bot, ending the current session, instead of refusing",
+                    )
+                    .required(true),
+                ),
+            )
+            // Chimes.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "chimes",
+                    "Play a sound when someone joins or leaves the bot's voice channel",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "join-sound",
+                        "Sound played on joins (disabled if omitted)",
+                    )
+                    .required(false)
+                    .set_autocomplete(true),
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "leave-sound",
+                        "Sound played on leaves (disabled if omitted)",
+                    )
+                    .required(false)
+                    .set_autocomplete(true),
+                ),
+            );
+        #[cfg(feature = "voice-commands")]
+        let settings = settings.add_option(
+            CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "voice-commands",
+                "Control the recorder by saying \"hey recorder\" in voice channels",
+            )
+            .add_sub_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Boolean,
+                    "enabled",
+                    "Listen for voice commands",
+                )
+                .required(true),
+            ),
+        );
+
+        #[cfg(feature = "transcription")]
+        let transcribe = CreateCommand::new("transcribe")
+            .description("Transcribe the voice channel into a text channel")
+            .kind(CommandType::ChatInput)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "start",
+                    "Start posting transcripts of whitelisted users",
+                )
+                .add_sub_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Channel,
+                        "channel",
+                        "Channel to post transcripts in (defaults to this one)",
+                    )
+                    .channel_types(vec![ChannelType::Text])
+                    .required(false),
+                ),
+            )
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "stop",
+                "Stop posting transcripts",
+            ));
+        #[cfg(feature = "transcription")]
+        let prefs = CreateCommand::new("prefs")
+            .description("Show or change your preferences")
+            .kind(CommandType::ChatInput)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "language",
+                    "Language you speak, for transcripts (e.g. en, pt-BR, or auto)",
+                )
+                .max_length(8)
+                .required(false),
+            );
+
+        let schedule_sound = CreateCommand::new("schedule-sound")
+            .description("Play a sound in a voice channel at scheduled times")
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "cron",
+                    "Cron expression in UTC (e.g. 30 9 * * mon-fri)",
+                )
+                .max_length(100)
+                .required(true),
+            )
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "sound", "Sound to play")
+                    .required(true)
+                    .set_autocomplete(true),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Channel,
+                    "channel",
+                    "Voice channel to play the sound in",
+                )
+                .channel_types(vec![ChannelType::Voice])
+                .required(true),
+            );
+        let stop_sound = CreateCommand::new("stopsound")
+            .description("Stop every sound playing, looping ones included")
+            .kind(CommandType::ChatInput)
+            .dm_permission(false);
+        let bind_slot = CreateCommand::new("bindslot")
+            .description("Bind a sound to one of the /p1 to /p9 quick-play commands")
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::Integer, "slot", "Quick-play slot")
+                    .min_int_value(1)
+                    .max_int_value(SOUND_SLOTS as u64)
+                    .required(true),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "sound",
+                    "Sound to play (clears the slot if omitted)",
+                )
+                .required(false)
+                .set_autocomplete(true),
+            );
+        let sound_slots = (1..=SOUND_SLOTS).map(|slot| {
+            CreateCommand::new(format!("p{slot}"))
+                .description(format!("Play the sound bound to quick-play slot {slot}"))
+                .kind(CommandType::ChatInput)
+                .dm_permission(false)
+        });
+        let disable = CreateCommand::new("disable")
+            .description("Stop all recording and soundboard activity on this server at once")
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false);
+        let enable = CreateCommand::new("enable")
+            .description("Allow recording and the soundboard again after `/disable`")
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false);
+        let schedule_recording = CreateCommand::new("schedule-recording")
+            .description("Record a voice channel at scheduled times and post the mixdown")
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "cron",
+                    "Cron expression in UTC of the session starts (e.g. 0 20 * * fri)",
+                )
+                .max_length(100)
+                .required(true),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Channel,
+                    "channel",
+                    "Voice channel to record",
+                )
+                .channel_types(vec![ChannelType::Voice, ChannelType::Stage])
+                .required(true),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "duration",
+                    "How long to record (eg. 3h)",
+                )
+                .required(true),
+            )
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::Channel,
+                    "post-channel",
+                    "Channel to post the recording in (defaults to this one)",
+                )
+                .channel_types(vec![ChannelType::Text])
+                .required(false),
+            );
+        let scheduled_sounds = CreateCommand::new("scheduled-sounds")
+            .description("List or remove the scheduled sounds and recordings")
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false);
+        #[cfg(feature = "http-api")]
+        let api_key = CreateCommand::new("apikey")
+            .description("Manage the keys of the HTTP API for this server")
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            // Create.
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::SubCommand,
+                    "create",
+                    "Create a key usable against the HTTP API for this server only",
+                )
+                .add_sub_option(
+                    api_keys::Scope::ALL.into_iter().fold(
+                        CreateCommandOption::new(
+                            CommandOptionType::String,
+                            "scope",
+                            "What the key allows",
+                        )
+                        .required(true),
+                        |option, scope| option.add_string_choice(scope.name(), scope.name()),
+                    ),
+                ),
+            )
+            // List.
+            .add_option(CreateCommandOption::new(
+                CommandOptionType::SubCommand,
+                "list",
+                "List the keys, when they were last used, and revoke them",
+            ));
+        let download_audit = CreateCommand::new("download-audit")
+            .description("Show the latest downloads of recorded audio")
+            .kind(CommandType::ChatInput)
+            .default_member_permissions(Permissions::MANAGE_GUILD)
+            .dm_permission(false)
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::User,
+                    "user",
+                    "Only show the downloads of the audio of this user",
+                )
+                .required(false),
+            );
+
+        let mut commands = vec![
+            version,
+            stats,
+            join_voice_channel,
+            pause_recording,
+            resume_recording,
+            leave_session,
+            forget_recording,
+            wipe_recording,
+            import_recording,
+            flush_recording,
+            mark,
+            synctone,
+            voicediag,
+            quality_report,
+            waveform,
+            clip,
+            profile,
+            overlap_report,
+            my_data,
+            #[cfg(feature = "http-api")]
+            share_recording,
+            #[cfg(feature = "http-api")]
+            api_key,
+            #[cfg(feature = "transcription")]
+            transcribe,
+            #[cfg(feature = "transcription")]
+            prefs,
+            recorder,
+            whitelist,
+            soundboard,
+            schedule_sound,
+            schedule_recording,
+            scheduled_sounds,
+            bind_slot,
+            stop_sound,
+            download_audit,
+            disable,
+            enable,
+            packs,
+            settings,
+        ];
+        commands.extend(sound_slots);
+        Command::set_global_commands(ctx, commands)
+            .await
+            .expect("Global commands creation failure");
+        info!("global commands created");
+    }
+}
@@ -0,0 +1,263 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use itertools::Itertools;
+use serenity::{
+    all::{
+        ButtonStyle, CommandInteraction, ComponentInteraction, CreateButton,
+        CreateInteractionResponse, CreateInteractionResponseMessage, GuildId, Mention,
+    },
+    builder::CreateActionRow,
+    client::Context,
+};
+use ulid::Ulid;
+
+use super::Handler;
+use crate::{
+    command, error_code,
+    interaction::InteractionError,
+    schedule::{Cron, Job},
+};
+
+impl Handler {
+    pub(super) async fn schedule_sound(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let (Some(cron), Some(name), Some(channel)) = (
+            command::find_string_option(&command, "cron", None),
+            command::find_string_option(&command, "sound", None),
+            command::find_channel_option(&command, "channel"),
+        ) else {
+            return Ok(());
+        };
+
+        let content = match Cron::parse(cron) {
+            None => "Invalid cron expression, expected 5 fields such as `30 9 * * mon-fri` (UTC)."
+                .to_owned(),
+            Some(cron) => match self.soundboard.get_id(guild, name, None).await {
+                Err(err) => error_code::report(&err, "schedule_sound"),
+                Ok(sound) => match self
+                    .schedules
+                    .add(guild, cron, Job::PlaySound { sound, channel })
+                    .await
+                {
+                    Err(err) => error_code::report(&err, "schedule_sound"),
+                    Ok(schedule) => format!(
+                        "Scheduled `{name}` in {} at `{}` (UTC), {}.",
+                        Mention::from(channel),
+                        schedule.cron,
+                        next_run(&schedule.cron)
+                    ),
+                },
+            },
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(content),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn schedule_recording(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let (Some(cron), Some(channel), Some(duration)) = (
+            command::find_string_option(&command, "cron", None),
+            command::find_channel_option(&command, "channel"),
+            command::find_string_option(&command, "duration", None),
+        ) else {
+            return Ok(());
+        };
+        let post_channel =
+            command::find_channel_option(&command, "post-channel").unwrap_or(command.channel_id);
+        let blocked = self
+            .settings
+            .get(guild)
+            .await
+            .blocked_channels
+            .contains(&channel);
+
+        let content = match (Cron::parse(cron), parse_duration::parse(duration)) {
+            _ if blocked => format!("I'm not allowed to listen in {}.", Mention::from(channel)),
+            (None, _) => {
+                "Invalid cron expression, expected 5 fields such as `0 20 * * fri` (UTC).".to_owned()
+            }
+            (_, Ok(duration))
+                if duration < Duration::from_secs(60)
+                    || duration > self.voice_buffer_max_duration =>
+            {
+                format!(
+                    "Invalid duration, it must be between 1m and {}.",
+                    humantime::format_duration(self.voice_buffer_max_duration)
+                )
+            }
+            (_, Err(_)) => "Invalid duration.".to_owned(),
+            (Some(cron), Ok(duration)) => match self
+                .schedules
+                .add(
+                    guild,
+                    cron,
+                    Job::Record {
+                        channel,
+                        duration,
+                        post_channel,
+                    },
+                )
+                .await
+            {
+                Err(err) => error_code::report(&err, "schedule_recording"),
+                Ok(schedule) => format!(
+                    "Scheduled recording {} for {} at `{}` (UTC), posted in {}, {}.\nRecordings keep at most the buffer duration, see `/settings buffer-duration`.",
+                    Mention::from(channel),
+                    humantime::format_duration(duration),
+                    schedule.cron,
+                    Mention::from(post_channel),
+                    next_run(&schedule.cron)
+                ),
+            },
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(content),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn list_scheduled_sounds(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+
+        let names: HashMap<_, _> = self
+            .soundboard
+            .list(guild)
+            .await
+            .into_iter()
+            .flat_map(|(_group, sounds)| sounds)
+            .map(|sound| (sound.id, sound.name))
+            .collect();
+        // 5 rows of 5 buttons.
+        let schedules = self
+            .schedules
+            .list(guild)
+            .await
+            .into_iter()
+            .take(25)
+            .collect_vec();
+        let content = if schedules.is_empty() {
+            "No scheduled sounds or recordings.".to_owned()
+        } else {
+            schedules
+                .iter()
+                .map(|schedule| match &schedule.job {
+                    Job::PlaySound { sound, channel } => format!(
+                        "1. `{}` in {} at `{}`, {}",
+                        names.get(sound).map_or("deleted sound", String::as_str),
+                        Mention::from(*channel),
+                        schedule.cron,
+                        next_run(&schedule.cron)
+                    ),
+                    Job::Record {
+                        channel,
+                        duration,
+                        post_channel,
+                    } => format!(
+                        "1. Recording {} for {} at `{}`, posted in {}, {}",
+                        Mention::from(*channel),
+                        humantime::format_duration(*duration),
+                        schedule.cron,
+                        Mention::from(*post_channel),
+                        next_run(&schedule.cron)
+                    ),
+                })
+                .join("\n")
+        };
+        let buttons = schedules
+            .iter()
+            .enumerate()
+            .map(|(i, schedule)| {
+                CreateButton::new(format!("unschedule-{}", schedule.id))
+                    .label(format!("Remove #{}", i + 1))
+                    .style(ButtonStyle::Danger)
+            })
+            .chunks(5)
+            .into_iter()
+            .map(|row| CreateActionRow::Buttons(row.collect()))
+            .collect_vec();
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .ephemeral(true)
+                        .components(buttons),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn unschedule_sound(
+        &self,
+        ctx: Context,
+        component: ComponentInteraction,
+        guild: GuildId,
+        id: &str,
+    ) -> Result<(), InteractionError> {
+        let Ok(id) = Ulid::from_string(id) else {
+            return Ok(());
+        };
+        let content = match self.schedules.remove(guild, id).await {
+            Ok(()) => "Schedule removed.".to_owned(),
+            Err(err) => error_code::report(&err, "unschedule_sound"),
+        };
+        component
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// When a schedule runs next, as a Discord relative timestamp.
+fn next_run(cron: &Cron) -> String {
+    match cron
+        .next_after(SystemTime::now())
+        .and_then(|next| next.duration_since(UNIX_EPOCH).ok())
+    {
+        Some(next) => format!("next <t:{}:R>", next.as_secs()),
+        None => "never runs".to_owned(),
+    }
+}
@@ -0,0 +1,956 @@
+use std::{
+    ops::Range,
+    sync::{atomic::Ordering, Arc},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use log::{error, info, warn};
+use serenity::{
+    all::{
+        ButtonStyle, ChannelId, CommandInteraction, ComponentInteraction, CreateAllowedMentions,
+        CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage,
+        GuildId, Mention, ReactionType, UserId,
+    },
+    builder::{CreateActionRow, CreateAttachment},
+    client::Context,
+};
+use tokio::{sync::oneshot, time::sleep};
+
+use super::soundboard::play_wav;
+use super::voice::{current_voice_channel, join_recorded, leave_voice};
+use super::{max_file_size, Handler, SESSION_SPLIT_CHECK_INTERVAL};
+#[cfg(feature = "transcription")]
+use crate::live_transcript;
+#[cfg(feature = "voice-commands")]
+use crate::voice_command;
+use crate::{
+    audit::DownloadRecord,
+    command,
+    exporter::{Metadata as ExportMetadata, DEFAULT_EXPORTER},
+    guild_cache,
+    interaction::InteractionError,
+    mix,
+    recorder::{RecorderAction, StoredVoice},
+    vad,
+    wav::{self, SampleFormat},
+};
+
+/// Voice connection of a guild, its recording posted in parts when the guild
+/// splits sessions.
+#[derive(Clone, Copy)]
+pub(crate) struct Session {
+    pub(super) start: Instant,
+    pub(super) channel: ChannelId,
+    /// Who asked for the recording, `None` when it started automatically.
+    started_by: Option<UserId>,
+    /// Where parts are posted without an announcement channel.
+    text_channel: ChannelId,
+    part: u32,
+    /// Start of the part not posted yet.
+    part_start: Instant,
+}
+
+impl Session {
+    /// Take the part recorded since the previous one, up to `end`, so that it
+    /// is posted once.
+    fn take_part(&mut self, end: Instant) -> (u32, Range<Instant>) {
+        let part = (self.part, self.part_start..end);
+        self.part += 1;
+        self.part_start = end;
+        part
+    }
+}
+
+impl Handler {
+    pub(super) async fn join_voice(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        // Moderators may send the bot to a channel they aren't in.
+        let channel = match command::find_channel_option(&command, "channel") {
+            Some(_)
+                if !command
+                    .member
+                    .as_ref()
+                    .and_then(|member| member.permissions)
+                    .is_some_and(|permissions| permissions.move_members()) =>
+            {
+                command
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(
+                                    "Only members allowed to move members can choose the channel.",
+                                )
+                                .ephemeral(true),
+                        ),
+                    )
+                    .await?;
+                return Ok(());
+            }
+            Some(channel) => channel,
+            None => {
+                match guild_cache::voice_channel(&ctx, &self.guild_arrivals, guild, command.user.id)
+                    .await
+                {
+                    Some(channel) => channel,
+                    None => {
+                        command
+                            .create_response(
+                                &ctx,
+                                CreateInteractionResponse::Message(
+                                    CreateInteractionResponseMessage::new()
+                                        .content("You aren't in a voice channel. Dahhh..."),
+                                ),
+                            )
+                            .await?;
+                        return Ok(());
+                    }
+                }
+            }
+        };
+        let settings = self.settings.get(guild).await;
+        if settings.blocked_channels.contains(&channel) {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(format!(
+                                "I'm not allowed to listen in {}.",
+                                Mention::from(channel)
+                            ))
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let manager = songbird::get(&ctx)
+            .await
+            .ok_or(InteractionError::VoiceUnavailable)?;
+        match current_voice_channel(&manager, guild).await {
+            Some(current) if current == channel => {
+                command
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(format!(
+                                    "I'm already listening in {}.",
+                                    Mention::from(channel)
+                                ))
+                                .ephemeral(true),
+                        ),
+                    )
+                    .await?;
+                return Ok(());
+            }
+            Some(current) if !settings.join_switches_channel => {
+                command
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(format!(
+                                    "I'm already listening in {}. Move me to {}? It ends the current session.",
+                                    Mention::from(current),
+                                    Mention::from(channel)
+                                ))
+                                .components(vec![CreateActionRow::Buttons(vec![
+                                    CreateButton::new(format!("join-move-{channel}"))
+                                        .label("Move")
+                                        .style(ButtonStyle::Primary),
+                                ])])
+                                .ephemeral(true),
+                        ),
+                    )
+                    .await?;
+                return Ok(());
+            }
+            Some(_) => self.end_session(&ctx, guild).await,
+            None => (),
+        }
+
+        self.connect(
+            &ctx,
+            guild,
+            channel,
+            Some(command.user.id),
+            command.channel_id,
+        )
+        .await?;
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("Listening and ready to play sounds."),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Move the bot to the channel offered when `/join` was refused.
+    pub(super) async fn move_voice(
+        &self,
+        ctx: Context,
+        component: ComponentInteraction,
+        guild: GuildId,
+        channel: ChannelId,
+    ) -> Result<(), InteractionError> {
+        let content = if self
+            .settings
+            .get(guild)
+            .await
+            .blocked_channels
+            .contains(&channel)
+        {
+            format!("I'm not allowed to listen in {}.", Mention::from(channel))
+        } else {
+            self.end_session(&ctx, guild).await;
+            self.connect(
+                &ctx,
+                guild,
+                channel,
+                Some(component.user.id),
+                component.channel_id,
+            )
+            .await?;
+            format!(
+                "Moved to {}, listening and ready to play sounds.",
+                Mention::from(channel)
+            )
+        };
+        component
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .components(vec![]),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// End the session of the channel the bot is in before it joins another,
+    /// so its split loop, announcement, auto-join, looping sounds and nickname
+    /// don't carry over. The part of a split session not posted yet is.
+    pub(crate) async fn end_session(&self, ctx: &Context, guild: GuildId) {
+        info!("ending the voice session of guild {guild} to switch channels");
+        let session = self.sessions.lock().await.remove(&guild);
+        if let Some(session) = session {
+            self.finish_session(ctx, guild, session).await;
+        }
+        self.announcements.lock().await.remove(&guild);
+        self.auto_joined.lock().await.remove(&guild);
+        self.stop_loops(ctx, guild).await;
+        self.indicator.update(ctx, guild, None, None).await;
+        leave_voice(ctx, guild).await;
+    }
+
+    /// Join a voice channel and start recording it, `started_by` being the
+    /// user who asked for it and `text_channel` where voice commands answer.
+    #[cfg_attr(not(feature = "voice-commands"), allow(unused_variables))]
+    pub(crate) async fn connect(
+        &self,
+        ctx: &Context,
+        guild: GuildId,
+        channel: ChannelId,
+        started_by: Option<UserId>,
+        text_channel: ChannelId,
+    ) -> Result<(), InteractionError> {
+        let manager = songbird::get(ctx)
+            .await
+            .ok_or(InteractionError::VoiceUnavailable)?;
+        let settings = self.settings.get(guild).await;
+        if !settings.soundboard_only {
+            self.set_presence(ctx, guild, Some(channel)).await;
+        }
+        join_recorded(
+            manager,
+            &self.recorder,
+            guild,
+            channel,
+            !settings.soundboard_only,
+        )
+        .await?;
+
+        // Avoid reading sounds from disk on their first press.
+        let soundboard = Arc::clone(&self.soundboard);
+        tokio::spawn(async move { soundboard.precache(guild).await });
+        if settings.soundboard_only {
+            return Ok(());
+        }
+
+        let start = Instant::now();
+        self.sessions.lock().await.insert(
+            guild,
+            Session {
+                start,
+                channel,
+                started_by,
+                text_channel,
+                part: 1,
+                part_start: start,
+            },
+        );
+        tokio::spawn(self.clone().split_session_loop(ctx.clone(), guild, start));
+
+        #[cfg(feature = "voice-commands")]
+        if let Some(recognizer_path) = &self.speech_recognizer_path {
+            voice_command::listen_loop(
+                ctx.clone(),
+                guild,
+                text_channel,
+                self.recorder.lock().await.get_guild_recorder(guild).await,
+                Arc::clone(&self.soundboard),
+                recognizer_path.clone(),
+            );
+        }
+
+        if settings.announce_recording {
+            let announcement_channel = settings.announcement_channel.unwrap_or(channel);
+            let kill_switch = settings.kill_switch_emoji();
+            match announcement_channel
+                .send_message(
+                    ctx,
+                    CreateMessage::new()
+                        .content(format!(
+                            "🔴 {} recording {}.\nReact with {kill_switch} or use `/leave` to stop being recorded and delete your audio of this session.",
+                            match started_by {
+                                Some(user) => format!("{} started", Mention::from(user)),
+                                None => "Automatically started".to_owned(),
+                            },
+                            Mention::from(channel)
+                        ))
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                )
+                .await
+            {
+                Ok(message) => {
+                    self.announcements.lock().await.insert(guild, message.id);
+                    if let Err(err) = message
+                        .react(ctx, ReactionType::Unicode(kill_switch.to_owned()))
+                        .await
+                    {
+                        warn!("cannot add kill switch reaction: {err}");
+                    }
+                }
+                Err(err) => warn!("cannot post recording announcement: {err}"),
+            }
+        }
+        Ok(())
+    }
+
+    /// Record a voice channel for a scheduled session, then post the mixdown
+    /// of what was said during it.
+    pub(crate) async fn record_session(
+        &self,
+        ctx: &Context,
+        guild: GuildId,
+        channel: ChannelId,
+        duration: Duration,
+        post_channel: ChannelId,
+    ) {
+        // Channels may have been blocked since the recording was scheduled.
+        if self
+            .settings
+            .get(guild)
+            .await
+            .blocked_channels
+            .contains(&channel)
+        {
+            info!("skipping scheduled recording in guild {guild}, channel {channel} is blocked");
+            return;
+        }
+        let manager = songbird::get(ctx)
+            .await
+            .expect("Failed to get songbird manager");
+        let current_channel = match manager.get(guild) {
+            Some(call) => call.lock().await.current_channel(),
+            None => None,
+        };
+        let joined = match current_channel {
+            Some(current) if current.0.get() == channel.get() => false,
+            Some(_) => {
+                // Don't leave a channel that may be recorded.
+                info!("skipping scheduled recording in guild {guild}, already in another channel");
+                return;
+            }
+            None => {
+                if let Err(err) = self.connect(ctx, guild, channel, None, post_channel).await {
+                    warn!("cannot join channel {channel} of guild {guild} for scheduled recording: {err}");
+                    return;
+                }
+                true
+            }
+        };
+        let start = Instant::now();
+        let started_at = SystemTime::now();
+        sleep(duration).await;
+        let end = Instant::now();
+        // Disabling the bot meanwhile left the channel, and nothing of the
+        // session is to be posted.
+        if self.settings.get(guild).await.disabled {
+            info!("dropping scheduled recording in guild {guild}, the bot was disabled during it");
+            return;
+        }
+        if joined {
+            leave_voice(ctx, guild).await;
+        }
+
+        let title = format!(
+            "Scheduled recording of {} started <t:{}:f> ({})",
+            Mention::from(channel),
+            started_at
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            humantime::format_duration(duration)
+        );
+        self.post_session(
+            ctx,
+            guild,
+            start..end,
+            post_channel,
+            &title,
+            "scheduled-recording",
+        )
+        .await;
+    }
+
+    /// Post the mixdown of the voice received during `period` in a text
+    /// channel, in as many messages as needed.
+    async fn post_session(
+        &self,
+        ctx: &Context,
+        guild: GuildId,
+        period: Range<Instant>,
+        post_channel: ChannelId,
+        title: &str,
+        kind: &str,
+    ) {
+        let (tx, rx) = oneshot::channel();
+        let sent = self
+            .recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::GetTracks(tx))
+            .await;
+        let stored = match sent {
+            Ok(()) => rx.await.map_err(InteractionError::from),
+            Err(err) => Err(err.into()),
+        };
+        let stored = match stored {
+            Ok(stored) => stored,
+            Err(err) => {
+                error!("cannot fetch the tracks of {title} in guild {guild}: {err}");
+                return;
+            }
+        };
+        let (users, tracks): (Vec<_>, Vec<_>) = StoredVoice::tracks(stored)
+            .await
+            .into_iter()
+            .map(|(user, track)| (user, track.between(period.start, period.end)))
+            .filter(|(_, track)| !track.data.is_empty())
+            .unzip();
+        if tracks.is_empty() {
+            if let Err(err) = post_channel
+                .say(ctx, format!("{title}: no voice was recorded."))
+                .await
+            {
+                warn!("cannot post session recording: {err}");
+            }
+            return;
+        }
+
+        let samples = tracks.iter().map(|track| track.data.len()).max();
+        self.download_audit
+            .record(DownloadRecord {
+                at: SystemTime::now(),
+                guild,
+                requester: UserId::new(self.bot_id.load(Ordering::Relaxed)),
+                targets: users,
+                kind: kind.to_owned(),
+                duration: mix::samples_duration(samples.unwrap_or_default()),
+            })
+            .await;
+        let _permit = self.exports.acquire(guild).await;
+        let settings = self.settings.get(guild).await;
+        let notice = settings.render_legal_notice();
+        let stereo_width = settings.stereo_width();
+        let channels = if stereo_width > 0. { 2 } else { 1 };
+        let timer = self.stats.timer("mixdown");
+        let data = tokio::task::spawn_blocking(move || {
+            let _timer = timer;
+            if stereo_width > 0. {
+                let pans = mix::pan_positions(tracks.len(), stereo_width);
+                mix::mixdown_stereo(&tracks, true, &pans)
+            } else {
+                mix::mixdown(&tracks, true)
+            }
+        })
+        .await;
+        let data = match data {
+            Ok(data) => data,
+            Err(err) => {
+                error!("cannot mix down {title} in guild {guild}: {err}");
+                return;
+            }
+        };
+        // Split points are even, keeping interleaved stereo frames whole.
+        let max_samples = (max_file_size(ctx, guild).await - wav::HEADER_SIZE) / 2;
+        let chunks = vad::split_at_silence(&data, max_samples / 2 * 2);
+        let parts = chunks.len();
+        for (i, chunk) in chunks.into_iter().enumerate() {
+            let metadata = ExportMetadata {
+                name: if parts <= 1 {
+                    "session".to_owned()
+                } else {
+                    format!("session-{}", i + 1)
+                },
+                comment: notice.clone(),
+                channels,
+                sample_rate: settings.storage_rate(),
+                sample_format: SampleFormat::S16,
+                cover_url: None,
+            };
+            let attachments = self
+                .exporters
+                .export(DEFAULT_EXPORTER, chunk, &metadata)
+                .await
+                .into_iter()
+                .map(|artifact| CreateAttachment::bytes(artifact.data, artifact.filename));
+            let mut message = CreateMessage::new()
+                .add_files(attachments)
+                .allowed_mentions(CreateAllowedMentions::new());
+            if i == 0 {
+                message = message.content(match &notice {
+                    Some(notice) => format!("{title}.\n{notice}"),
+                    None => format!("{title}."),
+                });
+            }
+            if let Err(err) = post_channel.send_message(ctx, message).await {
+                warn!("cannot post session recording: {err}");
+                return;
+            }
+        }
+    }
+
+    /// Post the latest part of the recording every `session_split` of the
+    /// guild settings, for as long as the connection started at `start`
+    /// lasts.
+    async fn split_session_loop(self, ctx: Context, guild: GuildId, start: Instant) {
+        loop {
+            sleep(SESSION_SPLIT_CHECK_INTERVAL).await;
+            let Some(session) = self.current_session(guild, start).await else {
+                break;
+            };
+            let manager = songbird::get(&ctx)
+                .await
+                .expect("Failed to get songbird manager");
+            // Ended by leaving or moving, should the voice state of the bot
+            // have been missed.
+            if current_voice_channel(&manager, guild).await != Some(session.channel) {
+                let session = self
+                    .remove_session(guild, |session| session.start == start)
+                    .await;
+                if let Some(session) = session {
+                    self.finish_session(&ctx, guild, session).await;
+                }
+                break;
+            }
+
+            let Some(split) = self.settings.get(guild).await.session_split else {
+                continue;
+            };
+            let now = Instant::now();
+            if now < session.part_start + split {
+                continue;
+            }
+            // Claimed before posting, for a teardown meanwhile not to post it
+            // again.
+            let taken = self
+                .sessions
+                .lock()
+                .await
+                .get_mut(&guild)
+                .filter(|session| session.start == start)
+                .map(|session| session.take_part(now));
+            let Some((part, period)) = taken else {
+                break;
+            };
+            self.post_session_part(&ctx, guild, &session, part, period)
+                .await;
+        }
+    }
+
+    /// Remove the session of a guild if it is the one `ended` matches.
+    pub(super) async fn remove_session(
+        &self,
+        guild: GuildId,
+        ended: impl FnOnce(&Session) -> bool,
+    ) -> Option<Session> {
+        let mut sessions = self.sessions.lock().await;
+        match sessions.get(&guild) {
+            Some(session) if ended(session) => sessions.remove(&guild),
+            _ => None,
+        }
+    }
+
+    /// Post the part of a split session not posted yet once it ended.
+    pub(super) async fn finish_session(&self, ctx: &Context, guild: GuildId, mut session: Session) {
+        if self.settings.get(guild).await.session_split.is_none() {
+            return;
+        }
+        let (part, period) = session.take_part(Instant::now());
+        let (handler, ctx) = (self.clone(), ctx.clone());
+        tokio::spawn(async move {
+            handler
+                .post_session_part(&ctx, guild, &session, part, period)
+                .await
+        });
+    }
+
+    /// Current session of a guild, if it is still the one started at `start`.
+    async fn current_session(&self, guild: GuildId, start: Instant) -> Option<Session> {
+        self.sessions
+            .lock()
+            .await
+            .get(&guild)
+            .filter(|session| session.start == start)
+            .copied()
+    }
+
+    async fn post_session_part(
+        &self,
+        ctx: &Context,
+        guild: GuildId,
+        session: &Session,
+        part: u32,
+        period: Range<Instant>,
+    ) {
+        let timestamp = |instant: Instant| {
+            (SystemTime::now() - instant.elapsed())
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs()
+        };
+        let title = format!(
+            "Part {part} of the recording of {}, from <t:{}:t> to <t:{}:t>",
+            Mention::from(session.channel),
+            timestamp(period.start),
+            timestamp(period.end)
+        );
+        info!("posting part {part} of the session in guild {guild}");
+        let settings = self.settings.get(guild).await;
+        self.post_session(
+            ctx,
+            guild,
+            period,
+            settings
+                .announcement_channel
+                .unwrap_or(session.text_channel),
+            &title,
+            "session-split",
+        )
+        .await;
+    }
+
+    #[cfg(feature = "transcription")]
+    pub(super) async fn start_live_transcript(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let channel =
+            command::find_channel_option(&command, "channel").unwrap_or(command.channel_id);
+
+        let content = match &self.speech_recognizer_path {
+            Some(recognizer_path) => {
+                live_transcript::live_loop(
+                    ctx.clone(),
+                    guild,
+                    channel,
+                    self.recorder.lock().await.get_guild_recorder(guild).await,
+                    recognizer_path.clone(),
+                    Arc::clone(&self.preferences),
+                );
+                format!(
+                    "Transcribing whitelisted users in {}.",
+                    Mention::from(channel)
+                )
+            }
+            None => "Transcription is not configured.".to_owned(),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(content),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "transcription")]
+    pub(super) async fn stop_live_transcript(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::TranscribeLive(None))
+            .await?;
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content("Live transcript stopped."),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_recording_paused(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+        paused: bool,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        // Only whoever started the recording or server managers may control it.
+        let started_by = self
+            .sessions
+            .lock()
+            .await
+            .get(&guild)
+            .and_then(|session| session.started_by);
+        if started_by != Some(command.user.id)
+            && !command
+                .member
+                .as_ref()
+                .and_then(|member| member.permissions)
+                .is_some_and(|permissions| permissions.manage_guild())
+        {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(
+                                "Only whoever started the recording or server managers can pause or resume it.",
+                            )
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let (tx, rx) = oneshot::channel::<bool>();
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::SetPaused(paused, tx))
+            .await?;
+        let changed = rx.await?;
+
+        let content = match (paused, changed) {
+            (true, true) => "Recording paused, use `/resume` to start again.",
+            (true, false) => "Recording is already paused.",
+            (false, true) => "Recording resumed.",
+            (false, false) => "Recording isn't paused.",
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(content),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn add_marker(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(label) = command::find_string_option(&command, "label", None) else {
+            return Ok(());
+        };
+        // Tabs separate the fields of label files.
+        let label = label.replace('\t', " ");
+
+        let username = command::resolve_username(&ctx, &command.user, guild).await;
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::AddMarker(mix::Marker {
+                at: Instant::now(),
+                label: format!("{label} ({username})"),
+            }))
+            .await?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(format!("🔖 Marker `{label}` added at <t:{now}:T>."))
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Play a tone into the voice channel and mark when it started, for
+    /// participants recording locally to line up their files with the bot's.
+    pub(super) async fn play_sync_tone(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+
+        let manager = songbird::get(&ctx)
+            .await
+            .ok_or(InteractionError::VoiceUnavailable)?;
+        let guild_recorder = self.recorder.lock().await.get_guild_recorder(guild).await;
+        // The tone is only useful to line up recordings with this one.
+        let (tx, rx) = oneshot::channel();
+        guild_recorder.send(RecorderAction::GetStats(tx)).await?;
+        if self.settings.get(guild).await.soundboard_only || rx.await?.paused {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("I'm not recording, there is nothing to sync with.")
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+        let wav = wav::package(&mix::sync_tone());
+        let Some((_, start)) = play_wav(manager, &guild_recorder, guild, wav).await else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("I'm not in a voice channel.")
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        };
+        let username = command::resolve_username(&ctx, &command.user, guild).await;
+        guild_recorder
+            .send(RecorderAction::AddMarker(mix::Marker {
+                at: start,
+                label: format!("sync tone ({username})"),
+            }))
+            .await?;
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(format!(
+                        "🔔 Sync tone played at <t:{now}:T>, marked as `sync tone` in exports. Line up the start of the beep in local recordings with it."
+                    )),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Markers of the session still covered by the voice buffer.
+    pub(super) async fn markers(
+        &self,
+        guild: GuildId,
+    ) -> Result<Vec<mix::Marker>, InteractionError> {
+        let (tx, rx) = oneshot::channel();
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::GetMarkers(tx))
+            .await?;
+        Ok(rx.await?)
+    }
+
+    pub(super) async fn leave_session(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        self.opt_out(guild, command.user.id).await?;
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("You are no longer recorded until you leave the voice channel, your audio of this session has been deleted.")
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1276 @@
+use std::time::Duration;
+
+use itertools::Itertools;
+use log::{info, warn};
+use serenity::{
+    all::{
+        ChannelId, CommandInteraction, CreateAllowedMentions, CreateInteractionResponse,
+        CreateInteractionResponseMessage, GuildId, Mention,
+    },
+    client::Context,
+};
+use ulid::Ulid;
+
+use super::voice::{join_recorded, leave_voice};
+use super::{Handler, SESSION_SPLIT_CHECK_INTERVAL};
+#[cfg(feature = "transcription")]
+use crate::prefs;
+#[cfg(feature = "voice-commands")]
+use crate::voice_command;
+use crate::{
+    command,
+    dsp::NoiseGate,
+    error_code,
+    interaction::InteractionError,
+    settings::{GuildSettings, SettingsError},
+};
+
+impl Handler {
+    /// Show or change the preferences of the user, shared by every server.
+    #[cfg(feature = "transcription")]
+    pub(super) async fn set_preferences(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let user = command.user.id;
+        let content = match command::find_string_option(&command, "language", None) {
+            Some(code) => {
+                let language = if code.eq_ignore_ascii_case("auto") {
+                    Some(None)
+                } else {
+                    prefs::parse_language(code).map(Some)
+                };
+                match language {
+                    Some(language) => match self
+                        .preferences
+                        .update(user, |prefs| prefs.language = language.clone())
+                        .await
+                    {
+                        Ok(()) => match language {
+                            Some(language) => {
+                                format!("Your speech will be transcribed as `{language}`.")
+                            }
+                            None => "Your language will be detected when transcribing.".to_owned(),
+                        },
+                        Err(err) => error_code::report(&err, "set_preferences"),
+                    },
+                    None => {
+                        "Invalid language code, expected a code such as `en` or `pt-BR`.".to_owned()
+                    }
+                }
+            }
+            None => {
+                let prefs = self.preferences.get(user).await;
+                format!(
+                    "Transcription language: {}",
+                    prefs.language.as_deref().unwrap_or("auto")
+                )
+            }
+        };
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Persist settings changes and propagate them to running components.
+    pub(super) async fn update_settings<R, F: FnOnce(&mut GuildSettings) -> R>(
+        &self,
+        guild: GuildId,
+        f: F,
+    ) -> Result<R, SettingsError> {
+        let res = self.settings.update(guild, f).await?;
+        self.recorder
+            .lock()
+            .await
+            .apply_settings(guild, self.settings.get(guild).await)
+            .await;
+        Ok(res)
+    }
+
+    pub(super) async fn show_settings(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let settings = self.settings.get(guild).await;
+
+        let mut content = format!(
+            "- Voice activity threshold: {} dBFS\n- Trim silence: {}\n- Noise gate: {}\n- Denoise: {}\n- Buffer duration: {}\n- Retention: {}{}\n- Announce recording: {}\n- Legal notice: {}",
+            settings.vad().threshold(),
+            settings.trim_silence,
+            match settings.noise_gate {
+                Some(gate) => format!(
+                    "{} dBFS, {} hold",
+                    gate.threshold,
+                    humantime::format_duration(gate.hold)
+                ),
+                None => "disabled".to_owned(),
+            },
+            settings.denoise,
+            match settings.buffer_duration {
+                Some(duration) => humantime::format_duration(duration).to_string(),
+                None => "default".to_owned(),
+            },
+            match settings.retention {
+                Some(retention) => humantime::format_duration(retention).to_string(),
+                None => "default".to_owned(),
+            },
+            if settings.pause_retention_when_absent {
+                " (paused while nobody whitelisted is present)"
+            } else {
+                ""
+            },
+            match (settings.announce_recording, settings.announcement_channel) {
+                (false, _) => "disabled".to_owned(),
+                (true, Some(channel)) => format!(
+                    "in {}, kill switch {}",
+                    Mention::from(channel),
+                    settings.kill_switch_emoji()
+                ),
+                (true, None) => format!(
+                    "in the voice channel, kill switch {}",
+                    settings.kill_switch_emoji()
+                ),
+            },
+            settings.legal_notice.as_deref().unwrap_or("none"),
+        );
+        content.push_str(&format!(
+            "\n- Soundboard only: {}",
+            settings.soundboard_only
+        ));
+        content.push_str(&format!(
+            "\n- Join while listening elsewhere: {}",
+            if settings.join_switches_channel {
+                "switch channels"
+            } else {
+                "refuse"
+            }
+        ));
+        content.push_str(&format!(
+            "\n- Recording nickname prefix: {}",
+            settings.recording_nickname.as_deref().unwrap_or("none")
+        ));
+        content.push_str(&format!(
+            "\n- Mixdown stereo width: {}",
+            match settings.stereo_width() {
+                width if width > 0. => format!("{:.0}%", width * 100.),
+                _ => "mono".to_owned(),
+            }
+        ));
+        content.push_str(&format!(
+            "\n- Whitelisted roles: {}",
+            if settings.whitelisted_roles.is_empty() {
+                "none".to_owned()
+            } else {
+                settings
+                    .whitelisted_roles
+                    .iter()
+                    .map(|role| Mention::from(*role).to_string())
+                    .join(", ")
+            }
+        ));
+        content.push_str(&format!(
+            "\n- Recorded role: {}",
+            match settings.recorded_role {
+                Some(role) => Mention::from(role).to_string(),
+                None => "anyone whitelisted".to_owned(),
+            }
+        ));
+        content.push_str(&format!(
+            "\n- Session split: {}",
+            match settings.session_split {
+                Some(split) => format!("every {}", humantime::format_duration(split)),
+                None => "never".to_owned(),
+            }
+        ));
+        content.push_str(&format!(
+            "\n- Storage rate: {} kHz",
+            settings.storage_rate() / 1000
+        ));
+        content.push_str(&format!(
+            "\n- Whitelist expiry: {}",
+            match settings.whitelist_expiry {
+                Some(expiry) => humantime::format_duration(expiry).to_string(),
+                None => "never".to_owned(),
+            }
+        ));
+        content.push_str(&format!(
+            "\n- Auto-join: {}",
+            if settings.auto_join_channels.is_empty() {
+                "none".to_owned()
+            } else {
+                settings
+                    .auto_join_channels
+                    .iter()
+                    .map(|channel| Mention::from(*channel).to_string())
+                    .join(", ")
+            }
+        ));
+        content.push_str(&format!(
+            "\n- Blocked channels: {}",
+            if settings.blocked_channels.is_empty() {
+                "none".to_owned()
+            } else {
+                settings
+                    .blocked_channels
+                    .iter()
+                    .map(|channel| Mention::from(*channel).to_string())
+                    .join(", ")
+            }
+        ));
+        let mut slots = settings.sound_slots.iter().collect_vec();
+        slots.sort();
+        let mut slot_names = Vec::with_capacity(slots.len());
+        for (slot, sound) in slots {
+            let name = match self.soundboard.name(*sound).await {
+                Some(name) => format!("`{name}`"),
+                None => "*deleted sound*".to_owned(),
+            };
+            slot_names.push(format!("/p{slot} {name}"));
+        }
+        content.push_str(&format!(
+            "\n- Quick-play slots: {}",
+            if slot_names.is_empty() {
+                "none".to_owned()
+            } else {
+                slot_names.join(", ")
+            }
+        ));
+        content.push_str(&format!(
+            "\n- Chimes: {}",
+            self.describe_chimes(settings.join_chime, settings.leave_chime)
+                .await
+        ));
+        #[cfg(feature = "voice-commands")]
+        content.push_str(&format!("\n- Voice commands: {}", settings.voice_commands));
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .allowed_mentions(CreateAllowedMentions::new())
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_vad_threshold(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let threshold = command::find_number_option(&command, "threshold", None);
+
+        let text = match self
+            .update_settings(guild, |settings| {
+                settings.vad_threshold = threshold.map(|t| t as f32);
+                settings.vad().threshold()
+            })
+            .await
+        {
+            Ok(threshold) => format!("Voice activity threshold set to {threshold} dBFS."),
+            Err(err) => error_code::report(&err, "set_vad_threshold"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_trim_silence(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(enabled) = command::find_boolean_option(&command, "enabled", None) else {
+            return Ok(());
+        };
+
+        let text = match self
+            .update_settings(guild, |settings| settings.trim_silence = enabled)
+            .await
+        {
+            Ok(()) if enabled => "Silences will be trimmed from recordings by default.".to_owned(),
+            Ok(()) => "Silences will be kept in recordings by default.".to_owned(),
+            Err(err) => error_code::report(&err, "set_trim_silence"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_noise_gate(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let threshold = command::find_number_option(&command, "threshold", None);
+        let Some(hold) =
+            command::find_duration_option(&command, "hold", Some(Duration::from_millis(200)))
+        else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new().content("Invalid duration."),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        };
+
+        let noise_gate = threshold.map(|threshold| NoiseGate {
+            threshold: threshold as f32,
+            hold,
+        });
+        let text = match self
+            .update_settings(guild, |settings| settings.noise_gate = noise_gate)
+            .await
+        {
+            Ok(()) if noise_gate.is_some() => "Noise gate enabled.".to_owned(),
+            Ok(()) => "Noise gate disabled.".to_owned(),
+            Err(err) => error_code::report(&err, "set_noise_gate"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_buffer_duration(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let buffer_duration = match command::find_string_option(&command, "duration", None) {
+            Some(duration) => match parse_duration::parse(duration) {
+                Ok(duration)
+                    if duration > Duration::from_secs(1)
+                        && duration <= self.voice_buffer_max_duration =>
+                {
+                    Some(duration)
+                }
+                _ => {
+                    command
+                        .create_response(
+                            &ctx,
+                            CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new().content(format!(
+                                    "Invalid duration, it must be between 1s and {}.",
+                                    humantime::format_duration(self.voice_buffer_max_duration)
+                                )),
+                            ),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+
+        let text = match self
+            .update_settings(guild, |settings| settings.buffer_duration = buffer_duration)
+            .await
+        {
+            Ok(()) => match buffer_duration {
+                Some(duration) => format!(
+                    "Recordings will keep the last {}.",
+                    humantime::format_duration(duration)
+                ),
+                None => "Recordings buffer duration reset to the default.".to_owned(),
+            },
+            Err(err) => error_code::report(&err, "set_buffer_duration"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_retention(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let retention = match command::find_string_option(&command, "duration", None) {
+            Some(duration) => match parse_duration::parse(duration) {
+                Ok(duration) if !duration.is_zero() => Some(duration),
+                _ => {
+                    command
+                        .create_response(
+                            &ctx,
+                            CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new()
+                                    .content("Invalid duration."),
+                            ),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+
+        let text = match self
+            .update_settings(guild, |settings| settings.retention = retention)
+            .await
+        {
+            Ok(()) => match retention {
+                Some(retention) => format!(
+                    "Recorded audio will be kept for {}.",
+                    humantime::format_duration(retention)
+                ),
+                None => "Recorded audio retention reset to the default.".to_owned(),
+            },
+            Err(err) => error_code::report(&err, "set_retention"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_pause_retention(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(enabled) = command::find_boolean_option(&command, "enabled", None) else {
+            return Ok(());
+        };
+
+        let text = match self
+            .update_settings(guild, |settings| {
+                settings.pause_retention_when_absent = enabled
+            })
+            .await
+        {
+            Ok(()) if enabled => {
+                "Recorded audio won't expire while no whitelisted user is present.".to_owned()
+            }
+            Ok(()) => "Recorded audio will always expire.".to_owned(),
+            Err(err) => error_code::report(&err, "set_pause_retention"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_announce_recording(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(enabled) = command::find_boolean_option(&command, "enabled", None) else {
+            return Ok(());
+        };
+        let channel = command::find_channel_option(&command, "channel");
+        let kill_switch = command::find_emoji_option(&command, "kill-switch-emoji");
+
+        let text = match self
+            .update_settings(guild, |settings| {
+                settings.announce_recording = enabled;
+                settings.announcement_channel = channel;
+                settings.kill_switch_emoji = kill_switch;
+            })
+            .await
+        {
+            Ok(()) if enabled => match channel {
+                Some(channel) => format!(
+                    "Recording starts will be announced in {}.",
+                    Mention::from(channel)
+                ),
+                None => "Recording starts will be announced in the voice channel.".to_owned(),
+            },
+            Ok(()) => "Recording starts won't be announced.".to_owned(),
+            Err(err) => error_code::report(&err, "set_announce_recording"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_legal_notice(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let notice = command::find_string_option(&command, "text", None).map(str::to_owned);
+
+        let text = match self
+            .update_settings(guild, |settings| settings.legal_notice = notice.clone())
+            .await
+        {
+            Ok(()) => match &notice {
+                Some(notice) => format!("Exports will include this notice:\n>>> {notice}"),
+                None => "Exports won't include a legal notice.".to_owned(),
+            },
+            Err(err) => error_code::report(&err, "set_legal_notice"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(text)
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_recording_nickname(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let prefix = command::find_string_option(&command, "prefix", None).map(str::to_owned);
+
+        let text = match self
+            .update_settings(guild, |settings| {
+                settings.recording_nickname = prefix.clone()
+            })
+            .await
+        {
+            Ok(()) => {
+                match &prefix {
+                    Some(prefix) => {
+                        format!("My nickname will start with {prefix} while I record, from my next join.")
+                    }
+                    None => "My nickname won't change while I record anymore.".to_owned(),
+                }
+            }
+            Err(err) => error_code::report(&err, "set_recording_nickname"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(text)
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_chimes(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+
+        let mut chimes = [None, None];
+        for (chime, option) in chimes.iter_mut().zip(["join-sound", "leave-sound"]) {
+            let Some(name) = command::find_string_option(&command, option, None) else {
+                continue;
+            };
+            match self.soundboard.get_id(guild, name, None).await {
+                Ok(sound) => *chime = Some(sound),
+                Err(err) => {
+                    command
+                        .create_response(
+                            &ctx,
+                            CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new()
+                                    .content(error_code::report(&err, "set_chimes")),
+                            ),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            }
+        }
+        let [join, leave] = chimes;
+
+        let text = match self
+            .update_settings(guild, |settings| {
+                settings.join_chime = join;
+                settings.leave_chime = leave;
+            })
+            .await
+        {
+            Ok(()) if join.is_none() && leave.is_none() => "Chimes disabled.".to_owned(),
+            Ok(()) => format!("Chimes set: {}.", self.describe_chimes(join, leave).await),
+            Err(err) => error_code::report(&err, "set_chimes"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn describe_chimes(&self, join: Option<Ulid>, leave: Option<Ulid>) -> String {
+        let mut names = Vec::with_capacity(2);
+        for (kind, chime) in [("join", join), ("leave", leave)] {
+            let name = match chime {
+                Some(sound) => match self.soundboard.name(sound).await {
+                    Some(name) => format!("`{name}`"),
+                    None => "*deleted sound*".to_owned(),
+                },
+                None => "none".to_owned(),
+            };
+            names.push(format!("{kind} {name}"));
+        }
+        names.join(", ")
+    }
+
+    pub(super) async fn set_whitelist_expiry(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let expiry = command::find_integer_option(&command, "days", None)
+            .map(|days| Duration::from_secs(days as u64 * 24 * 60 * 60));
+
+        let text = match self
+            .update_settings(guild, |settings| settings.whitelist_expiry = expiry)
+            .await
+        {
+            Ok(()) => match expiry {
+                Some(expiry) => format!(
+                    "Whitelisted users will need to consent again every {}.",
+                    humantime::format_duration(expiry)
+                ),
+                None => "Whitelist membership won't expire.".to_owned(),
+            },
+            Err(err) => error_code::report(&err, "set_whitelist_expiry"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_auto_join(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let (Some(channel), Some(enabled)) = (
+            command::find_channel_option(&command, "channel"),
+            command::find_boolean_option(&command, "enabled", None),
+        ) else {
+            return Ok(());
+        };
+
+        let text = match self
+            .update_settings(guild, |settings| {
+                settings.auto_join_channels.retain(|c| *c != channel);
+                if enabled {
+                    settings.auto_join_channels.push(channel);
+                }
+            })
+            .await
+        {
+            Ok(()) if enabled => format!(
+                "I'll join {} as soon as a whitelisted user enters it.",
+                Mention::from(channel)
+            ),
+            Ok(()) => format!("I won't join {} by myself anymore.", Mention::from(channel)),
+            Err(err) => error_code::report(&err, "set_auto_join"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_blocked_channel(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let (Some(channel), Some(blocked)) = (
+            command::find_channel_option(&command, "channel"),
+            command::find_boolean_option(&command, "blocked", None),
+        ) else {
+            return Ok(());
+        };
+
+        let text = match self
+            .update_settings(guild, |settings| {
+                settings.blocked_channels.retain(|c| *c != channel);
+                if blocked {
+                    settings.blocked_channels.push(channel);
+                }
+            })
+            .await
+        {
+            Ok(()) if blocked => {
+                let manager = songbird::get(&ctx)
+                    .await
+                    .ok_or(InteractionError::VoiceUnavailable)?;
+                let current = match manager.get(guild) {
+                    Some(call) => call
+                        .lock()
+                        .await
+                        .current_channel()
+                        .map(|channel| ChannelId::from(channel.0)),
+                    None => None,
+                };
+                if current == Some(channel) {
+                    self.auto_joined.lock().await.remove(&guild);
+                    leave_voice(&ctx, guild).await;
+                }
+                format!(
+                    "I won't listen in {} anymore, even when asked to.",
+                    Mention::from(channel)
+                )
+            }
+            Ok(()) => format!("I can listen in {} again.", Mention::from(channel)),
+            Err(err) => error_code::report(&err, "set_blocked_channel"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_join_switch(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(enabled) = command::find_boolean_option(&command, "enabled", None) else {
+            return Ok(());
+        };
+
+        let text = match self
+            .update_settings(guild, |settings| settings.join_switches_channel = enabled)
+            .await
+        {
+            Ok(()) if enabled => "`/join` will move me when I'm listening in another channel, ending the current session.".to_owned(),
+            Ok(()) => "`/join` will be refused when I'm listening in another channel, offering to move me.".to_owned(),
+            Err(err) => error_code::report(&err, "set_join_switch"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_soundboard_only(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(enabled) = command::find_boolean_option(&command, "enabled", None) else {
+            return Ok(());
+        };
+
+        let text = match self
+            .update_settings(guild, |settings| settings.soundboard_only = enabled)
+            .await
+        {
+            Ok(()) => {
+                // Rejoin the current channel for the change to apply now.
+                let manager = songbird::get(&ctx)
+                    .await
+                    .ok_or(InteractionError::VoiceUnavailable)?;
+                let current = match manager.get(guild) {
+                    Some(call) => call
+                        .lock()
+                        .await
+                        .current_channel()
+                        .map(|channel| ChannelId::from(channel.0)),
+                    None => None,
+                };
+                // Recording again goes through a full connection, for the
+                // session to be announced with its kill switch.
+                let res = match current {
+                    Some(channel) if enabled => {
+                        join_recorded(manager, &self.recorder, guild, channel, false)
+                            .await
+                            .map_err(InteractionError::from)
+                    }
+                    Some(channel) => {
+                        self.connect(
+                            &ctx,
+                            guild,
+                            channel,
+                            Some(command.user.id),
+                            command.channel_id,
+                        )
+                        .await
+                    }
+                    None => Ok(()),
+                };
+                if let Err(err) = res {
+                    warn!("cannot rejoin voice in guild {guild}: {err}");
+                }
+                if enabled {
+                    "I won't receive any voice anymore, only play sounds. Audio already recorded is kept until it expires or is deleted with `/wipe`.".to_owned()
+                } else {
+                    "I will receive and record voice again.".to_owned()
+                }
+            }
+            Err(err) => error_code::report(&err, "set_soundboard_only"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_denoise(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(enabled) = command::find_boolean_option(&command, "enabled", None) else {
+            return Ok(());
+        };
+
+        let text = match self
+            .update_settings(guild, |settings| settings.denoise = enabled)
+            .await
+        {
+            Ok(()) if enabled => "Background noise will be reduced by default.".to_owned(),
+            Ok(()) => "Background noise will be kept by default.".to_owned(),
+            Err(err) => error_code::report(&err, "set_denoise"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_stereo_width(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let width = command::find_number_option(&command, "width", None);
+
+        let text = match self
+            .update_settings(guild, |settings| {
+                settings.stereo_width = width;
+                settings.stereo_width()
+            })
+            .await
+        {
+            Ok(width) if width > 0. => {
+                format!(
+                    "Speakers will be spread across {:.0}% of the stereo field in mixdowns.",
+                    width * 100.
+                )
+            }
+            Ok(_) => "Mixdowns will be mono by default.".to_owned(),
+            Err(err) => error_code::report(&err, "set_stereo_width"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Stop or allow again every recording and soundboard activity in the
+    /// guild, leaving the voice channel when disabling.
+    pub(super) async fn set_disabled(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+        disabled: bool,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let text = match self
+            .update_settings(guild, |settings| settings.disabled = disabled)
+            .await
+        {
+            Ok(()) if disabled => {
+                info!("bot disabled in guild {guild} by {}", command.user.id);
+                self.auto_joined.lock().await.remove(&guild);
+                if let Some(review) = self.reviews.lock().await.remove(&guild) {
+                    let _ = review.stop();
+                }
+                leave_voice(&ctx, guild).await;
+                "Recording and the soundboard are disabled on this server until `/enable`."
+                    .to_owned()
+            }
+            Ok(()) => {
+                info!("bot enabled in guild {guild} by {}", command.user.id);
+                "Recording and the soundboard are enabled again.".to_owned()
+            }
+            Err(err) => error_code::report(&err, "set_disabled"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_recorded_role(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let role = command::find_role_option(&command, "role");
+
+        let text = match self
+            .update_settings(guild, |settings| settings.recorded_role = role)
+            .await
+        {
+            Ok(()) => {
+                self.update_presence(&ctx, guild).await;
+                match role {
+                    Some(role) => format!(
+                        "Only whitelisted users with the {} role will be recorded.",
+                        Mention::from(role)
+                    ),
+                    None => "Every whitelisted user will be recorded.".to_owned(),
+                }
+            }
+            Err(err) => error_code::report(&err, "set_recorded_role"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(text)
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Record members holding a role as if they were whitelisted, or stop
+    /// doing so.
+    pub(super) async fn set_whitelisted_role(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+        add: bool,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(role) = command::find_role_option(&command, "role") else {
+            return Ok(());
+        };
+
+        let text = match self
+            .update_settings(guild, |settings| {
+                settings.whitelisted_roles.retain(|r| *r != role);
+                if add {
+                    settings.whitelisted_roles.push(role);
+                }
+            })
+            .await
+        {
+            Ok(()) => {
+                self.update_presence(&ctx, guild).await;
+                if add {
+                    format!(
+                        "Members with the {} role will be recorded, unless they use `/leave`.",
+                        Mention::from(role)
+                    )
+                } else {
+                    format!(
+                        "Members with the {} role won't be recorded unless whitelisted themselves.",
+                        Mention::from(role)
+                    )
+                }
+            }
+            Err(err) => error_code::report(&err, "set_whitelisted_role"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(text)
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_session_split(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let split = match command::find_string_option(&command, "every", None) {
+            Some(split) => match parse_duration::parse(split) {
+                Ok(split)
+                    if split >= SESSION_SPLIT_CHECK_INTERVAL
+                        && split <= self.voice_buffer_max_duration =>
+                {
+                    Some(split)
+                }
+                _ => {
+                    command
+                        .create_response(
+                            &ctx,
+                            CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new().content(format!(
+                                    "Invalid duration, it must be between 1m and {}.",
+                                    humantime::format_duration(self.voice_buffer_max_duration)
+                                )),
+                            ),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+
+        let text = match self
+            .update_settings(guild, |settings| settings.session_split = split)
+            .await
+        {
+            Ok(()) => match split {
+                Some(split) => format!(
+                    "A part of the recording will be posted every {} while I'm in a voice channel. Parts keep at most the buffer duration, see `/settings buffer-duration`.",
+                    humantime::format_duration(split)
+                ),
+                None => "Sessions will not be split anymore.".to_owned(),
+            },
+            Err(err) => error_code::report(&err, "set_session_split"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn set_storage_rate(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let rate = command::find_integer_option(&command, "rate", None).map(|rate| rate as usize);
+
+        let text = match self
+            .update_settings(guild, |settings| {
+                settings.storage_rate = rate;
+                settings.storage_rate()
+            })
+            .await
+        {
+            Ok(rate) => format!(
+                "Voice will be buffered at {} kHz, exports are still 48 kHz.",
+                rate / 1000
+            ),
+            Err(err) => error_code::report(&err, "set_storage_rate"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    #[cfg(feature = "voice-commands")]
+    pub(super) async fn set_voice_commands(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(enabled) = command::find_boolean_option(&command, "enabled", None) else {
+            return Ok(());
+        };
+
+        let text = match self
+            .update_settings(guild, |settings| settings.voice_commands = enabled)
+            .await
+        {
+            Ok(()) if enabled => format!(
+                "Voice commands enabled, say \"{}\" followed by `bookmark`, `pause`, `resume`, `stop` or `play <sound>`.",
+                voice_command::WAKE_WORD
+            ),
+            Ok(()) => "Voice commands disabled.".to_owned(),
+            Err(err) => error_code::report(&err, "set_voice_commands"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,1285 @@
+use std::{
+    io::{Cursor, Write},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use itertools::Itertools;
+use log::{debug, info, warn};
+use serenity::{
+    all::{
+        ButtonStyle, ChannelId, CommandInteraction, ComponentInteraction, CreateAllowedMentions,
+        CreateButton, CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage,
+        GuildId, Mention, Message, MessageId, ReactionType, UserId,
+    },
+    async_trait,
+    builder::{
+        CreateActionRow, CreateAttachment, CreateInteractionResponseFollowup,
+        EditInteractionResponse, EditMessage,
+    },
+    client::Context,
+};
+use songbird::EventHandler as VoiceEventHandler;
+use songbird::{
+    driver::DecodeMode, input::Input, tracks::TrackHandle, Event, EventContext, Songbird,
+};
+use ulid::Ulid;
+use zip::{write::FileOptions as ZipFileOptions, ZipWriter};
+
+use super::{
+    is_invalid_emoji, mark_deferred, max_file_size, Handler, LATENCY_BUCKETS, MAX_MESSAGE_LENGTH,
+};
+use crate::{
+    button::{self, SoundButton},
+    command, error_code, ffmpeg,
+    interaction::InteractionError,
+    metrics::Metrics,
+    mix,
+    packs::{self, PackError},
+    recorder::{RecorderAction, RecorderSender},
+    soundboard::{Soundboard, SoundboardError},
+    upload::PendingUpload,
+    vad, wav,
+};
+
+const ROWS_PER_MESSAGE: usize = 5;
+const SOUNDS_PER_ROW: usize = 5;
+/// Filter rendering the thumbnail of uploaded sounds.
+const UPLOAD_PREVIEW_FILTER: &str = "showwavespic=s=600x120:colors=0x5865f2";
+/// Press-to-audio delay above which soundboard playback feels sluggish.
+const TARGET_SOUND_LATENCY: Duration = Duration::from_millis(150);
+/// Minimum delay between two join or leave chimes of a guild.
+const CHIME_COOLDOWN: Duration = Duration::from_secs(15);
+
+/// Looping sound playing in a guild, with the soundboard message showing it.
+pub(crate) struct PlayingLoop {
+    pub(super) track: TrackHandle,
+    pub(super) channel: ChannelId,
+    pub(super) message: MessageId,
+}
+
+/// Measures the delay between a soundboard button press and its sound starting
+/// to play.
+pub(super) struct PlaybackLatency {
+    pub(super) pressed: Instant,
+    pub(super) metrics: Arc<Metrics>,
+}
+
+#[async_trait]
+impl VoiceEventHandler for PlaybackLatency {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        let latency = self.pressed.elapsed();
+        self.metrics.observe(
+            "disrecord_sound_latency_seconds",
+            "Time from a soundboard button press to its sound playing",
+            "",
+            LATENCY_BUCKETS,
+            latency.as_secs_f64(),
+        );
+        if latency > TARGET_SOUND_LATENCY {
+            info!(
+                "sound started playing {}ms after press",
+                latency.as_millis()
+            );
+        } else {
+            debug!(
+                "sound started playing {}ms after press",
+                latency.as_millis()
+            );
+        }
+        Some(Event::Cancel)
+    }
+}
+
+impl Handler {
+    /// Restyle the button of a looping sound in a soundboard message to show
+    /// whether it plays.
+    pub(super) async fn show_loop(
+        &self,
+        ctx: &Context,
+        message: &Message,
+        sound: Ulid,
+        playing: bool,
+    ) {
+        let Some(metadata) = self.soundboard.metadata(sound).await else {
+            return;
+        };
+        let sound_button = SoundButton::Sound(metadata);
+        let components = button::replace(
+            &message.components,
+            &sound.to_string(),
+            if playing {
+                sound_button.create_playing()
+            } else {
+                sound_button.create()
+            },
+        );
+        if let Err(err) = message
+            .channel_id
+            .edit_message(ctx, message.id, EditMessage::new().components(components))
+            .await
+        {
+            warn!("cannot update the button of looping sound {sound}: {err}");
+        }
+    }
+
+    /// Stop every sound playing in the voice channel, looping ones included.
+    pub(super) async fn stop_sounds(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let manager = songbird::get(&ctx)
+            .await
+            .ok_or(InteractionError::VoiceUnavailable)?;
+        if let Some(call) = manager.get(guild) {
+            call.lock().await.stop();
+        }
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("Sounds stopped.")
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        self.stop_loops(&ctx, guild).await;
+        Ok(())
+    }
+
+    /// Stop the looping sounds of a guild and restyle their buttons.
+    pub(super) async fn stop_loops(&self, ctx: &Context, guild: GuildId) {
+        let loops = self.loops.lock().await.remove(&guild).unwrap_or_default();
+        for (sound, playing) in loops {
+            let _ = playing.track.stop();
+            match playing.channel.message(ctx, playing.message).await {
+                Ok(message) => self.show_loop(ctx, &message, sound, false).await,
+                Err(err) => warn!("cannot fetch the button of looping sound {sound}: {err}"),
+            }
+        }
+    }
+
+    pub(super) async fn list_sounds(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+
+        let Some(add_random) = command::find_boolean_option(&command, "random", Some(true)) else {
+            return Ok(());
+        };
+        let Some(add_latest) = command::find_boolean_option(&command, "latest", Some(true)) else {
+            return Ok(());
+        };
+
+        let sounds = self.soundboard.list(guild).await;
+        if sounds.is_empty() {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("There is no sounds uploaded to this server... yet."),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let mut sounds = sounds
+            .into_iter()
+            .map(|(g, sounds)| {
+                (
+                    g,
+                    sounds
+                        .into_iter()
+                        .map(|s| SoundButton::Sound(s))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // Add random and latest buttons.
+        let total_sounds = sounds
+            .iter()
+            .map(|(_g, sounds)| sounds.len())
+            .sum::<usize>();
+        let mut has_shortcuts_row = false;
+        if add_random && total_sounds >= 2 {
+            has_shortcuts_row = true;
+            sounds.push(("Shortcuts".to_owned(), vec![SoundButton::Random(None)]))
+        }
+        if add_latest && total_sounds >= 2 {
+            if has_shortcuts_row {
+                sounds.last_mut().unwrap().1.push(SoundButton::Latest);
+            } else {
+                has_shortcuts_row = true;
+                sounds.push(("Shortcuts".to_owned(), vec![SoundButton::Latest]));
+            }
+        }
+
+        command.defer(&ctx).await?;
+        mark_deferred();
+        command.delete_response(&ctx).await?;
+
+        let groups_len = sounds.len();
+        for (i, (group, mut sounds)) in sounds.into_iter().enumerate() {
+            // Add random button if enough sounds in group.
+            if add_random && sounds.len() >= 2 && (has_shortcuts_row && i != groups_len - 1) {
+                sounds.insert(0, SoundButton::Random(Some(group.clone())));
+            }
+
+            // Send the group name once then at most 5 sound rows per message.
+            for (message_index, sounds_message) in
+                sounds.chunks(ROWS_PER_MESSAGE * SOUNDS_PER_ROW).enumerate()
+            {
+                command
+                    .channel_id
+                    .send_message(&ctx, {
+                        let mut message = CreateMessage::new();
+                        if message_index == 0 {
+                            message = message.content(format!("# {group}"));
+                        }
+                        message.components(
+                            sounds_message
+                                .chunks(SOUNDS_PER_ROW)
+                                .map(|sounds_row| {
+                                    CreateActionRow::Buttons(
+                                        sounds_row
+                                            .into_iter()
+                                            .map(|button| button.create())
+                                            .collect(),
+                                    )
+                                })
+                                .collect(),
+                        )
+                    })
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) async fn upload_sound(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(attachment) = command::find_attachment_option(&command, "sound") else {
+            return Ok(());
+        };
+        let Some(name) = command::find_string_option(&command, "name", None) else {
+            return Ok(());
+        };
+        let Some(group) = command::find_string_option(&command, "group", None) else {
+            return Ok(());
+        };
+        let emoji = command::find_emoji_option(&command, "emoji");
+        let color = command::find_string_option(&command, "color", None)
+            .map(button::parse_color)
+            .unwrap_or_else(|| button::determinist(&name.to_lowercase(), self.allow_grey));
+        let index =
+            command::find_integer_option(&command, "position", None).map(|p| (p - 1) as usize);
+        let Some(looping) = command::find_boolean_option(&command, "loop", Some(false)) else {
+            return Ok(());
+        };
+
+        // Fetching and transcoding may take longer than Discord waits.
+        command.defer_ephemeral(&ctx).await?;
+        mark_deferred();
+        let fetched = if self
+            .soundboard
+            .get_id(guild, name, Some(group))
+            .await
+            .is_ok()
+        {
+            Err(SoundboardError::NameTaken)
+        } else {
+            self.soundboard.fetch(attachment).await
+        };
+        let data = match fetched {
+            Ok(data) => data,
+            Err(err) => {
+                command
+                    .edit_response(
+                        &ctx,
+                        EditInteractionResponse::new()
+                            .content(error_code::report(&err, "upload_sound")),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        };
+        let wav = data.clone();
+        let Some(pcm) = tokio::task::spawn_blocking(move || wav::decode_mono(wav)).await? else {
+            command
+                .edit_response(
+                    &ctx,
+                    EditInteractionResponse::new().content(error_code::report(
+                        &SoundboardError::InvalidSound,
+                        "upload_sound",
+                    )),
+                )
+                .await?;
+            return Ok(());
+        };
+
+        // The preview shows the button as it will be, an invalid emoji being
+        // refused before anything is saved.
+        let mut preview = CreateButton::new("upload-preview")
+            .label(name)
+            .style(color)
+            .disabled(true);
+        if let Some(emoji) = &emoji {
+            preview = preview.emoji(ReactionType::Unicode(emoji.clone()));
+        }
+        let mut edit = EditInteractionResponse::new()
+            .content(format!(
+                "`{name}`: {:.1}s, {:.0} KiB, {:.1} dBFS{}. Add it to the soundboard?",
+                mix::samples_duration(pcm.len()).as_secs_f64(),
+                data.len() as f64 / 1024.,
+                vad::level(&pcm),
+                if looping { ", played in loop" } else { "" },
+            ))
+            .components(vec![
+                CreateActionRow::Buttons(vec![preview]),
+                CreateActionRow::Buttons(vec![
+                    CreateButton::new("upload-confirm")
+                        .label("Add")
+                        .style(ButtonStyle::Success),
+                    CreateButton::new("upload-cancel")
+                        .label("Cancel")
+                        .style(ButtonStyle::Secondary),
+                ]),
+            ]);
+        if let Some(image) =
+            ffmpeg::render_png(&self.ffmpeg_path, &pcm, UPLOAD_PREVIEW_FILTER).await
+        {
+            edit = edit.new_attachment(CreateAttachment::bytes(image, "waveform.png"));
+        }
+        match command.edit_response(&ctx, edit).await {
+            Ok(message) => {
+                self.uploads
+                    .insert(
+                        message.id,
+                        PendingUpload {
+                            guild,
+                            uploader: command.user.id,
+                            name: name.to_owned(),
+                            group: group.to_owned(),
+                            emoji,
+                            color,
+                            index,
+                            looping,
+                            data,
+                        },
+                    )
+                    .await;
+            }
+            Err(err) if is_invalid_emoji(&err) => {
+                command
+                    .edit_response(
+                        &ctx,
+                        EditInteractionResponse::new().content("Invalid emoji."),
+                    )
+                    .await?;
+            }
+            Err(err) => return Err(err.into()),
+        }
+        Ok(())
+    }
+
+    /// Add a previewed upload to the soundboard, posting its button, or
+    /// discard it.
+    pub(super) async fn confirm_upload(
+        &self,
+        ctx: Context,
+        component: ComponentInteraction,
+        action: &str,
+    ) -> Result<(), InteractionError> {
+        if action != "confirm" && action != "cancel" {
+            return Ok(());
+        }
+        let upload = match self.uploads.take(component.message.id).await {
+            Ok(upload) => upload,
+            Err(err) => {
+                component
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::UpdateMessage(
+                            CreateInteractionResponseMessage::new()
+                                .content(error_code::report(&err, "confirm_upload"))
+                                .components(vec![]),
+                        ),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        };
+        if action == "cancel" {
+            component
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new()
+                            .content("Upload cancelled.")
+                            .components(vec![]),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        component.defer(&ctx).await?;
+        mark_deferred();
+        let PendingUpload {
+            guild,
+            uploader,
+            name,
+            group,
+            emoji,
+            color,
+            index,
+            looping,
+            data,
+        } = upload;
+        let added = self
+            .soundboard
+            .add_data(
+                data,
+                "upload.wav",
+                guild,
+                name.clone(),
+                emoji,
+                color,
+                group.clone(),
+                index,
+                Some(uploader),
+            )
+            .await;
+        let content = match added {
+            Ok(id) => {
+                if looping {
+                    self.soundboard
+                        .change_looping(guild, &name, Some(&group), true)
+                        .await?;
+                }
+                if let Some(metadata) = self.soundboard.metadata(id).await {
+                    component
+                        .create_followup(
+                            &ctx,
+                            CreateInteractionResponseFollowup::new().components(vec![
+                                CreateActionRow::Buttons(vec![
+                                    SoundButton::Sound(metadata).create()
+                                ]),
+                            ]),
+                        )
+                        .await?;
+                }
+                format!("`{name}` added to the soundboard.")
+            }
+            Err(err) => error_code::report(&err, "confirm_upload"),
+        };
+        component
+            .edit_response(
+                &ctx,
+                EditInteractionResponse::new()
+                    .content(content)
+                    .clear_attachments()
+                    .components(vec![]),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn download_sound(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(name) = command::find_string_option(&command, "sound", None) else {
+            return Ok(());
+        };
+        let group = command::find_string_option(&command, "group", None);
+
+        // Does not support splitting.
+        let max_file_size = max_file_size(&ctx, guild).await;
+        match self.soundboard.get_wav_by_name(guild, name, group).await {
+            Ok(data) if data.len() <= max_file_size => {
+                command.defer(&ctx).await?;
+                mark_deferred();
+                command
+                    .create_followup(
+                        &ctx,
+                        CreateInteractionResponseFollowup::new()
+                            .add_file(CreateAttachment::bytes(data, format!("{name}.wav"))),
+                    )
+                    .await?;
+            }
+            Ok(_) => {
+                command
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new().content("Sound too large."),
+                        ),
+                    )
+                    .await?;
+            }
+            Err(err) => {
+                command
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(error_code::report(&err, "download_sound")),
+                        ),
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) async fn delete_sound(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        if !self.allow_delete {
+            return Ok(());
+        }
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(name) = command::find_string_option(&command, "sound", None) else {
+            return Ok(());
+        };
+        let group = command::find_string_option(&command, "group", None);
+
+        let text = match self.soundboard.delete(guild, name, group).await {
+            Ok(()) => "Deleted. *(for ever)*".to_owned(),
+            Err(err) => error_code::report(&err, "delete_sound"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn rename_sound(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(name) = command::find_string_option(&command, "sound", None) else {
+            return Ok(());
+        };
+        let Some(new_name) = command::find_string_option(&command, "new-name", None) else {
+            return Ok(());
+        };
+        let group = command::find_string_option(&command, "group", None);
+
+        let text = match self
+            .soundboard
+            .rename(guild, name, group, new_name.to_owned())
+            .await
+        {
+            Ok(true) => "Sound's name changed.".to_owned(),
+            Ok(false) => "The sound already had this name.".to_owned(),
+            Err(err) => error_code::report(&err, "rename_sound"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn move_sound(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(name) = command::find_string_option(&command, "sound", None) else {
+            return Ok(());
+        };
+        let Some(new_name) = command::find_string_option(&command, "new-group", None) else {
+            return Ok(());
+        };
+        let group = command::find_string_option(&command, "group", None);
+
+        let text = match self
+            .soundboard
+            .move_group(guild, name, group, new_name.to_owned())
+            .await
+        {
+            Ok(true) => "Sound's group changed.".to_owned(),
+            Ok(false) => "This sound already was in this group.".to_owned(),
+            Err(err) => error_code::report(&err, "move_sound"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn change_sound_color(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(name) = command::find_string_option(&command, "sound", None) else {
+            return Ok(());
+        };
+        let group = command::find_string_option(&command, "group", None);
+        let Some(color) =
+            command::find_string_option(&command, "color", None).map(button::parse_color)
+        else {
+            return Ok(());
+        };
+
+        let text = match self
+            .soundboard
+            .change_color(guild, name, group, color)
+            .await
+        {
+            Ok(true) => "Sound's color changed.".to_owned(),
+            Ok(false) => "This sound already had this color.".to_owned(),
+            Err(err) => error_code::report(&err, "change_sound_color"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn change_sound_loop(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(name) = command::find_string_option(&command, "sound", None) else {
+            return Ok(());
+        };
+        let group = command::find_string_option(&command, "group", None);
+        let Some(looping) = command::find_boolean_option(&command, "loop", None) else {
+            return Ok(());
+        };
+
+        let text = match self
+            .soundboard
+            .change_looping(guild, name, group, looping)
+            .await
+        {
+            Ok(true) if looping => {
+                "Sound will play in loop until its button is pressed again.".to_owned()
+            }
+            Ok(true) => "Sound will play once.".to_owned(),
+            Ok(false) => "This sound already played this way.".to_owned(),
+            Err(err) => error_code::report(&err, "change_sound_loop"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn change_sound_emoji(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(name) = command::find_string_option(&command, "sound", None) else {
+            return Ok(());
+        };
+        let group = command::find_string_option(&command, "group", None);
+        let Some(emoji) = command::find_emoji_option(&command, "emoji") else {
+            return Ok(());
+        };
+
+        let text = match self
+            .soundboard
+            .change_emoji(guild, name, group, emoji)
+            .await
+        {
+            Ok(true) => "Sound's emoji changed.".to_owned(),
+            Ok(false) => "This sound already had this emoji.".to_owned(),
+            Err(err) => error_code::report(&err, "change_sound_emoji"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn sound_id(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(name) = command::find_string_option(&command, "sound", None) else {
+            return Ok(());
+        };
+        let group = command::find_string_option(&command, "group", None);
+
+        let text = match self.soundboard.get_id(guild, name, group).await {
+            Ok(id) => id.to_string(),
+            Err(err) => error_code::report(&err, "sound_id"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn backup_sounds(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+
+        match self.soundboard.backup(guild).await {
+            Ok((metadata, sounds)) => {
+                if sounds.is_empty() {
+                    command
+                        .create_response(
+                            &ctx,
+                            CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new()
+                                    .content("There is no sounds on this server."),
+                            ),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+                command.defer(&ctx).await?;
+                mark_deferred();
+                let max_file_size = max_file_size(&ctx, guild).await;
+
+                let mut sound_index = 0;
+                let mut too_large = 0;
+                while sound_index < sounds.len() {
+                    let mut written = 0;
+
+                    // Write metadata in every archive.
+                    let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+                    written += metadata.len();
+                    archive
+                        .start_file("sounds.json", ZipFileOptions::default())
+                        .expect("Failed to create backup archive");
+                    archive
+                        .write_all(metadata.as_bytes())
+                        .expect("Failed to create backup archive");
+
+                    while written < max_file_size && sound_index < sounds.len() {
+                        let (id, data) = &sounds[sound_index];
+                        if data.len() > max_file_size {
+                            too_large += 1;
+                        } else {
+                            written += data.len();
+                            archive
+                                .start_file(id, ZipFileOptions::default())
+                                .expect("Failed to create backup archive");
+                            archive
+                                .write_all(data)
+                                .expect("Failed to create backup archive");
+                        }
+                        sound_index += 1;
+                    }
+
+                    let archive = archive
+                        .finish()
+                        .expect("Failed to create backup archive")
+                        .into_inner();
+                    command
+                        .create_followup(&ctx,
+                                         {
+                                             let mut response = CreateInteractionResponseFollowup::new();
+                                             if too_large > 0 {
+                                                 response = response.content("{too_large} files were too large and weren't included in the backup.");
+                                             }
+                                             response.add_file(
+                                                 CreateAttachment::bytes(
+                                                     archive,
+                                                     "backup.zip"
+                                                 )
+                                             )
+                                         }
+                        )
+                        .await?;
+                }
+            }
+            Err(err) => {
+                command
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(error_code::report(&err, "backup_sounds")),
+                        ),
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) async fn soundboard_logs(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+
+        let Some(duration) =
+            command::find_duration_option(&command, "duration", Some(Duration::from_secs(30)))
+        else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new().content("Invalid duration."),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        };
+
+        match self.history.get_logs(guild, duration).await {
+            Some((resolved_duration, logs)) if !logs.is_empty() => {
+                command
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new()
+                                .content(format!(
+                                    "Soundboard usage for the last {}:\n{}",
+                                    humantime::format_duration(resolved_duration),
+                                    logs.into_iter()
+                                        .map(|(user, count)| format!(
+                                            // Markdown list auto increment number.
+                                            "1. {}: {}",
+                                            Mention::from(user),
+                                            count
+                                        ))
+                                        .join("\n")
+                                ))
+                                .allowed_mentions(CreateAllowedMentions::new()),
+                        ),
+                    )
+                    .await?;
+            }
+            _ => {
+                command
+                    .create_response(
+                        &ctx,
+                        CreateInteractionResponse::Message(
+                            CreateInteractionResponseMessage::new().content("No logs available."),
+                        ),
+                    )
+                    .await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) async fn browse_packs(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(url) = command::find_string_option(&command, "url", None) else {
+            return Ok(());
+        };
+
+        command.defer(&ctx).await?;
+        mark_deferred();
+        let content = match packs::fetch_index(url).await {
+            Ok(index) if index.packs.is_empty() => "*No packs.*".to_owned(),
+            Ok(index) => {
+                let mut content = String::new();
+                for pack in index.packs {
+                    let line = format!(
+                        "- **{}** ({} sounds){}\n",
+                        pack.name,
+                        pack.sounds.len(),
+                        pack.description
+                            .map(|description| format!(": {description}"))
+                            .unwrap_or_default()
+                    );
+                    if content.len() + line.len() > MAX_MESSAGE_LENGTH {
+                        break;
+                    }
+                    content.push_str(&line);
+                }
+                content
+            }
+            Err(err) => error_code::report(&err, "browse_packs"),
+        };
+
+        command
+            .create_followup(
+                &ctx,
+                CreateInteractionResponseFollowup::new().content(content),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn install_pack(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(url) = command::find_string_option(&command, "url", None) else {
+            return Ok(());
+        };
+        let Some(name) = command::find_string_option(&command, "pack", None) else {
+            return Ok(());
+        };
+
+        command.defer(&ctx).await?;
+        mark_deferred();
+        let pack = match packs::fetch_index(url).await.and_then(|index| {
+            index
+                .packs
+                .into_iter()
+                .find(|pack| pack.name.eq_ignore_ascii_case(name))
+                .ok_or(PackError::PackNotFound)
+        }) {
+            Ok(pack) => pack,
+            Err(err) => {
+                command
+                    .create_followup(
+                        &ctx,
+                        CreateInteractionResponseFollowup::new()
+                            .content(error_code::report(&err, "install_pack")),
+                    )
+                    .await?;
+                return Ok(());
+            }
+        };
+        let group = command::find_string_option(&command, "group", Some(&pack.name))
+            .unwrap_or(&pack.name)
+            .to_owned();
+
+        let mut installed = 0;
+        let mut errors = Vec::new();
+        for sound in &pack.sounds {
+            let res = match packs::fetch_sound(sound).await {
+                Ok(data) => self
+                    .soundboard
+                    .add_data(
+                        data,
+                        sound.filename(),
+                        guild,
+                        sound.name.clone(),
+                        sound.emoji.clone(),
+                        button::determinist(&sound.name.to_lowercase(), self.allow_grey),
+                        group.clone(),
+                        None,
+                        None,
+                    )
+                    .await
+                    .map(|_| ())
+                    .map_err(|err| error_code::report(&err, "install_pack")),
+                Err(err) => Err(error_code::report(&err, "install_pack")),
+            };
+            match res {
+                Ok(()) => installed += 1,
+                Err(err) => errors.push(format!("- {}: {}", sound.name, err)),
+            }
+        }
+        info!(
+            "installed {installed}/{} sounds of pack {} in guild {guild}",
+            pack.sounds.len(),
+            pack.name
+        );
+
+        let mut content = format!(
+            "Installed {installed}/{} sounds of **{}** in group **{}**.",
+            pack.sounds.len(),
+            pack.name,
+            group
+        );
+        for error in errors {
+            if content.len() + error.len() + 1 > MAX_MESSAGE_LENGTH {
+                break;
+            }
+            content.push('\n');
+            content.push_str(&error);
+        }
+        command
+            .create_followup(
+                &ctx,
+                CreateInteractionResponseFollowup::new().content(content),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn bind_slot(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(slot) = command::find_integer_option(&command, "slot", None) else {
+            return Ok(());
+        };
+        let slot = slot as u8;
+        let sound = match command::find_string_option(&command, "sound", None) {
+            Some(name) => match self.soundboard.get_id(guild, name, None).await {
+                Ok(sound) => Some((sound, name)),
+                Err(err) => {
+                    command
+                        .create_response(
+                            &ctx,
+                            CreateInteractionResponse::Message(
+                                CreateInteractionResponseMessage::new()
+                                    .content(error_code::report(&err, "bind_slot")),
+                            ),
+                        )
+                        .await?;
+                    return Ok(());
+                }
+            },
+            None => None,
+        };
+
+        let text = match self
+            .update_settings(guild, |settings| match sound {
+                Some((sound, _)) => {
+                    settings.sound_slots.insert(slot, sound);
+                }
+                None => {
+                    settings.sound_slots.remove(&slot);
+                }
+            })
+            .await
+        {
+            Ok(()) => match sound {
+                Some((_, name)) => format!("`/p{slot}` now plays `{name}`."),
+                None => format!("`/p{slot}` doesn't play anything anymore."),
+            },
+            Err(err) => error_code::report(&err, "bind_slot"),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new().content(text),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Play the sound bound to a quick-play slot.
+    pub(super) async fn play_slot(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+        slot: u8,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let sound = self
+            .settings
+            .get(guild)
+            .await
+            .sound_slots
+            .get(&slot)
+            .copied();
+        let content = match sound {
+            Some(sound) => {
+                let manager = songbird::get(&ctx)
+                    .await
+                    .ok_or(InteractionError::VoiceUnavailable)?;
+                let guild_recorder = self.recorder.lock().await.get_guild_recorder(guild).await;
+                match play_sound(manager, &self.soundboard, &guild_recorder, guild, sound).await {
+                    Some(_) => {
+                        self.history.register(guild, command.user.id, sound).await;
+                        match self.soundboard.name(sound).await {
+                            Some(name) => format!("Playing `{name}`."),
+                            None => "Playing.".to_owned(),
+                        }
+                    }
+                    None => "Cannot play this sound, I'm not in a voice channel or it was deleted."
+                        .to_owned(),
+                }
+            }
+            None => format!("No sound is bound to `/p{slot}`, see `/bindslot`."),
+        };
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Play the join or leave chime of the guild, unless one played recently.
+    pub(super) async fn play_chime(&self, ctx: &Context, guild: GuildId, join: bool) {
+        let settings = self.settings.get(guild).await;
+        let Some(sound) = (if join {
+            settings.join_chime
+        } else {
+            settings.leave_chime
+        }) else {
+            return;
+        };
+        {
+            let mut chimes = self.chimes.lock().await;
+            if chimes
+                .get(&guild)
+                .is_some_and(|played| played.elapsed() < CHIME_COOLDOWN)
+            {
+                debug!("skipping chime in guild {guild}, one played recently");
+                return;
+            }
+            chimes.insert(guild, Instant::now());
+        }
+
+        let manager = songbird::get(ctx)
+            .await
+            .expect("Failed to get songbird manager");
+        let guild_recorder = self.recorder.lock().await.get_guild_recorder(guild).await;
+        if play_sound(manager, &self.soundboard, &guild_recorder, guild, sound)
+            .await
+            .is_none()
+        {
+            warn!("cannot play chime {sound} in guild {guild}");
+        }
+    }
+}
+
+/// Play a sound in the voice channel the bot is in, and have the recorder
+/// capture it.
+pub(crate) async fn play_sound(
+    manager: Arc<Songbird>,
+    soundboard: &Soundboard,
+    guild_recorder: &RecorderSender,
+    guild: GuildId,
+    sound: Ulid,
+) -> Option<TrackHandle> {
+    let wav = soundboard.get_wav(sound).await?;
+    play_wav(manager, guild_recorder, guild, wav)
+        .await
+        .map(|(track, _)| track)
+}
+
+/// Play a WAV file in the voice channel the bot is in and have the recorder
+/// capture it, returns its track and when it started playing.
+pub(super) async fn play_wav(
+    manager: Arc<Songbird>,
+    guild_recorder: &RecorderSender,
+    guild: GuildId,
+    wav: Vec<u8>,
+) -> Option<(TrackHandle, Instant)> {
+    let call = manager.get(guild)?;
+    let mut call_guard = call.lock().await;
+    call_guard.current_channel()?;
+    // Nothing is recorded in soundboard-only calls, sounds included.
+    let bot = call_guard
+        .current_connection()
+        .filter(|_| matches!(call_guard.config().decode_mode, DecodeMode::Decode))
+        .map(|info| UserId::from(info.user_id.0));
+
+    let start = Instant::now();
+    let track = call_guard.play_input(Input::from(wav.clone()));
+    if let Some(bot) = bot {
+        let guild_recorder = guild_recorder.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Some(pcm) = wav::decode_mono(wav) {
+                let _ =
+                    guild_recorder.send_voice(RecorderAction::RegisterPlayback(bot, start, pcm));
+            }
+        });
+    }
+    Some((track, start))
+}
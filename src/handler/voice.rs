@@ -0,0 +1,719 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use itertools::Itertools;
+use log::{error, info, warn};
+use serenity::{
+    all::{
+        ChannelId, CommandInteraction, CreateAllowedMentions, CreateInteractionResponse,
+        CreateInteractionResponseMessage, GuildId, Mention, RoleId, UserId, VoiceState,
+    },
+    async_trait,
+    builder::CreateInteractionResponseFollowup,
+    client::Context,
+};
+use songbird::EventHandler as VoiceEventHandler;
+use songbird::{
+    driver::DecodeMode, error::JoinResult, events::context_data::DisconnectReason,
+    model::CloseCode, Call, CoreEvent, Event, EventContext, Songbird,
+};
+use tokio::{
+    sync::{oneshot, Mutex},
+    time::sleep,
+};
+
+use super::{mark_deferred, Handler};
+use crate::{
+    diagnostics,
+    interaction::InteractionError,
+    quality::{self, Quality},
+    recorder::{Recorder, RecorderAction, RecorderSender, RecorderStats, StoredVoice},
+    stats::Stats,
+};
+
+/// Rejoin attempts after a voice connection drop, each waiting longer.
+const RECONNECT_ATTEMPTS: u32 = 5;
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Clone)]
+struct VoiceHandler {
+    guild: GuildId,
+    guild_recorder: RecorderSender,
+    /// Voice is decoded as mono in low-power mode, rather than stereo.
+    mono: bool,
+    stats: Arc<Stats>,
+}
+
+impl VoiceHandler {
+    async fn new(recorder: &mut Recorder, guild: GuildId) -> Self {
+        Self {
+            guild,
+            guild_recorder: recorder.get_guild_recorder(guild).await,
+            mono: recorder.low_power(),
+            stats: recorder.stats(),
+        }
+    }
+}
+
+#[async_trait]
+impl VoiceEventHandler for VoiceHandler {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let sent = match ctx {
+            EventContext::SpeakingStateUpdate(event) => match event.user_id {
+                Some(user) => {
+                    self.guild_recorder
+                        .send(RecorderAction::MapUser(UserId::new(user.0), event.ssrc))
+                        .await
+                }
+                None => Ok(()),
+            },
+            EventContext::ClientDisconnect(event) => {
+                self.guild_recorder
+                    .send(RecorderAction::UnmapUser(UserId::new(event.user_id.0)))
+                    .await
+            }
+            EventContext::DriverConnect(_) | EventContext::DriverReconnect(_) => {
+                self.guild_recorder.send(RecorderAction::Reconnected).await
+            }
+            EventContext::VoiceTick(packet) => {
+                let _timer = self.stats.timer("receive");
+                packet
+                    .speaking
+                    .iter()
+                    .filter_map(|(ssrc, data)| {
+                        data.decoded_voice
+                            .as_ref()
+                            .map(|decoded| (*ssrc, data, decoded))
+                    })
+                    .try_for_each(|(ssrc, data, audio)| {
+                        self.guild_recorder
+                            .send_voice(RecorderAction::RegisterVoiceData(
+                                ssrc,
+                                if self.mono {
+                                    audio.clone()
+                                } else {
+                                    audio
+                                        .chunks_exact(2)
+                                        .map(|cs| ((cs[0] as i32 + cs[1] as i32) / 2) as i16)
+                                        .collect()
+                                },
+                                data.packet
+                                    .as_ref()
+                                    .map(|packet| packet.rtp().get_sequence().0 .0),
+                            ))
+                    })
+            }
+            _ => Ok(()),
+        };
+        // The recorder only stops if its task panicked, voice cannot be
+        // recorded anymore until the bot rejoins.
+        if let Err(err) = sent {
+            error!(
+                "recorder of guild {} stopped, no longer receiving voice: {err}",
+                self.guild
+            );
+            return Some(Event::Cancel);
+        }
+        None
+    }
+}
+
+/// Rejoins the voice channel once Songbird gave up reconnecting after the
+/// voice connection dropped.
+#[derive(Clone)]
+struct Reconnect {
+    songbird: Arc<Songbird>,
+    voice: VoiceHandler,
+    /// Whether voice is received, not in soundboard-only guilds.
+    receive: bool,
+    /// Set while rejoining, the failed attempts raising more disconnections.
+    rejoining: Arc<AtomicBool>,
+}
+
+impl Reconnect {
+    /// Listen to the voice of a call, and to its disconnections.
+    fn register(&self, call: &mut Call) {
+        call.remove_all_global_events();
+        call.add_global_event(Event::Core(CoreEvent::DriverDisconnect), self.clone());
+        let decode_mode = if self.receive {
+            DecodeMode::Decode
+        } else {
+            DecodeMode::Pass
+        };
+        let config = call.config().clone().decode_mode(decode_mode);
+        call.set_config(config);
+        if !self.receive {
+            return;
+        }
+        call.add_global_event(
+            Event::Core(CoreEvent::SpeakingStateUpdate),
+            self.voice.clone(),
+        );
+        call.add_global_event(Event::Core(CoreEvent::ClientDisconnect), self.voice.clone());
+        call.add_global_event(Event::Core(CoreEvent::VoiceTick), self.voice.clone());
+        call.add_global_event(Event::Core(CoreEvent::DriverConnect), self.voice.clone());
+        call.add_global_event(Event::Core(CoreEvent::DriverReconnect), self.voice.clone());
+    }
+
+    async fn rejoin(self, guild: GuildId, channel: ChannelId) {
+        for attempt in 1..=RECONNECT_ATTEMPTS {
+            sleep(RECONNECT_DELAY * attempt).await;
+            let Some(call) = self.songbird.get(guild) else {
+                break;
+            };
+            let mut call_lock = call.lock().await;
+            // Left or moved meanwhile.
+            if call_lock.current_channel().map(|c| ChannelId::from(c.0)) != Some(channel) {
+                break;
+            }
+            self.register(&mut call_lock);
+            let joined = match call_lock.join(channel).await {
+                Ok(handle) => {
+                    drop(call_lock);
+                    handle.await.is_ok()
+                }
+                Err(_) => false,
+            };
+            if joined {
+                info!("rejoined channel {channel} of guild {guild}");
+                break;
+            }
+            warn!("cannot rejoin channel {channel} of guild {guild}, attempt {attempt}");
+        }
+        self.rejoining.store(false, Ordering::Relaxed);
+    }
+}
+
+#[async_trait]
+impl VoiceEventHandler for Reconnect {
+    async fn act(&self, ctx: &EventContext<'_>) -> Option<Event> {
+        let EventContext::DriverDisconnect(disconnect) = ctx else {
+            return None;
+        };
+        // No reason means the bot left or was moved on purpose, and being
+        // kicked or the channel deleted closes with `Disconnected`. Attempts
+        // are discarded when Discord moves the call to another voice server,
+        // Songbird connecting to the new one by itself.
+        let (Some(reason), Some(channel)) = (disconnect.reason, disconnect.channel_id) else {
+            return None;
+        };
+        if matches!(
+            reason,
+            DisconnectReason::Requested
+                | DisconnectReason::AttemptDiscarded
+                | DisconnectReason::WsClosed(Some(CloseCode::Disconnected))
+        ) || self.rejoining.swap(true, Ordering::Relaxed)
+        {
+            return None;
+        }
+        let guild = GuildId::from(disconnect.guild_id.0);
+        let channel = ChannelId::from(channel.0);
+        warn!("voice connection of guild {guild} dropped ({reason:?}), rejoining");
+        tokio::spawn(self.clone().rejoin(guild, channel));
+        None
+    }
+}
+
+/// Join a voice channel with the recorder listening to it unless voice isn't
+/// to be received, rejoining it if the voice connection drops. Handlers are
+/// attached before joining, for the first packets not to be missed.
+pub(super) async fn join_recorded(
+    songbird: Arc<Songbird>,
+    recorder: &Mutex<Recorder>,
+    guild: GuildId,
+    channel: ChannelId,
+    receive: bool,
+) -> JoinResult<()> {
+    let call = songbird.get_or_insert(guild);
+    let mut call_lock = call.lock().await;
+
+    let reconnect = Reconnect {
+        voice: VoiceHandler::new(&mut *recorder.lock().await, guild).await,
+        songbird,
+        receive,
+        rejoining: Arc::new(AtomicBool::new(false)),
+    };
+    reconnect.register(&mut call_lock);
+
+    let handle = call_lock.join(channel).await?;
+    drop(call_lock);
+    handle.await
+}
+
+impl Handler {
+    /// Report on the voice connection, to tell whether bad recordings come from
+    /// the network to Discord or from the bot.
+    pub(super) async fn voice_diagnostics(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let manager = songbird::get(&ctx)
+            .await
+            .ok_or(InteractionError::VoiceUnavailable)?;
+        let endpoint = match manager.get(guild) {
+            Some(call) => call
+                .lock()
+                .await
+                .current_connection()
+                .map(|info| info.endpoint.clone()),
+            None => None,
+        };
+        let Some(endpoint) = endpoint else {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("I'm not in a voice channel. Use `/join` first."),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        };
+        command.defer(&ctx).await?;
+        mark_deferred();
+
+        let (tx, rx) = oneshot::channel();
+        self.recorder
+            .lock()
+            .await
+            .get_guild_recorder(guild)
+            .await
+            .send(RecorderAction::GetStats(tx))
+            .await?;
+        let round_trip = diagnostics::round_trip(&endpoint).await;
+        let RecorderStats { users, queued, .. } = rx.await?;
+        // Reception of the users present in the voice channel.
+        let mut users = users
+            .into_iter()
+            .filter(|user| user.packets > 0 && user.present)
+            .map(|user| (user.id, user.packets, user.lost_packets))
+            .collect_vec();
+
+        let mut content = format!(
+            "- Voice server: `{endpoint}` (region `{}`)\n- Round trip: {}",
+            diagnostics::voice_region(&endpoint),
+            match round_trip {
+                Some(rtt) => format!("{}ms", rtt.as_millis()),
+                None => "unreachable".to_owned(),
+            }
+        );
+        let (packets, lost) = users
+            .iter()
+            .fold((0, 0), |(packets, lost), (_, p, l)| (packets + p, lost + l));
+        let loss = lost as f64 / packets.max(1) as f64;
+        content.push_str(&format!(
+            "\n- Packet loss: {:.1}% ({lost}/{packets} packets)",
+            loss * 100.
+        ));
+        users.sort_by_key(|(_, packets, lost)| std::cmp::Reverse(lost * 1_000 / packets));
+        for (user, packets, lost) in users.iter().take(5) {
+            content.push_str(&format!(
+                "\n  - {}: {:.1}%",
+                Mention::from(*user),
+                *lost as f64 / *packets as f64 * 100.
+            ));
+        }
+        content.push_str(&format!("\n- Recorder queue: {queued} pending"));
+
+        let mut verdicts = Vec::new();
+        if round_trip.is_none_or(|rtt| rtt > diagnostics::HIGH_ROUND_TRIP)
+            || loss > diagnostics::HIGH_LOSS
+        {
+            verdicts.push(
+                "the connection between Discord and the bot is degraded, recordings may have gaps",
+            );
+        }
+        if queued > diagnostics::HIGH_QUEUE {
+            verdicts.push("the bot is falling behind the voice it receives");
+        }
+        for verdict in &verdicts {
+            content.push_str(&format!("\n⚠️ Looks like {verdict}."));
+        }
+        if packets == 0 {
+            content.push_str("\nNo voice received yet, packet loss is unknown.");
+        } else if verdicts.is_empty() {
+            content.push_str("\nEverything looks fine.");
+        }
+
+        command
+            .create_followup(
+                &ctx,
+                CreateInteractionResponseFollowup::new()
+                    .content(content)
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Check the buffered voice of every speaker and recommend setup fixes.
+    pub(super) async fn quality_report(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        command.defer(&ctx).await?;
+        mark_deferred();
+
+        let recorder = self.recorder.lock().await.get_guild_recorder(guild).await;
+        let (tx, rx) = oneshot::channel();
+        recorder.send(RecorderAction::GetTracks(tx)).await?;
+        // The bot plays sounds, it has no setup to check.
+        let bot = UserId::new(self.bot_id.load(Ordering::Relaxed));
+        let mut tracks = rx.await?;
+        tracks.retain(|(user, _, _)| *user != bot);
+        let tracks = StoredVoice::tracks(tracks).await;
+        let (tx, rx) = oneshot::channel();
+        recorder.send(RecorderAction::GetStats(tx)).await?;
+        let stats = rx.await?;
+        let vad = self.settings.get(guild).await.vad();
+
+        let timer = self.stats.timer("quality_report");
+        let qualities = tokio::task::spawn_blocking(move || {
+            let _timer = timer;
+            tracks
+                .into_iter()
+                .map(|(user, track)| {
+                    let (packets, lost) = stats
+                        .users
+                        .iter()
+                        .find(|stats| stats.id == user && stats.present)
+                        .map_or((0, 0), |stats| (stats.packets, stats.lost_packets));
+                    (user, Quality::analyze(&track.data, vad, packets, lost))
+                })
+                .collect_vec()
+        })
+        .await?;
+
+        let content = if qualities.is_empty() {
+            "No voice data found.".to_owned()
+        } else {
+            let mut content = "**Audio quality**".to_owned();
+            for (user, quality) in qualities {
+                let Some(quality) = quality else {
+                    content.push_str(&format!(
+                        "\n{}: less than {}s of voice, not enough to judge",
+                        Mention::from(user),
+                        quality::MIN_VOICE.as_secs()
+                    ));
+                    continue;
+                };
+                let recommendations = quality.recommendations();
+                content.push_str(&format!(
+                    "\n{} {}: voice {:.0} dBFS, noise floor {:.0} dBFS, clipping {:.2}%, packet loss {:.1}%",
+                    if recommendations.is_empty() { "✅" } else { "⚠️" },
+                    Mention::from(user),
+                    quality.level,
+                    quality.noise_floor,
+                    quality.clipping * 100.,
+                    quality.loss * 100.
+                ));
+                for recommendation in recommendations {
+                    content.push_str(&format!("\n  - {recommendation}"));
+                }
+            }
+            content
+        };
+        command
+            .create_followup(
+                &ctx,
+                CreateInteractionResponseFollowup::new()
+                    .content(content)
+                    .allowed_mentions(CreateAllowedMentions::new()),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Record who joins and leaves the voice channel the bot is in.
+    pub(super) async fn track_participants(
+        &self,
+        ctx: &Context,
+        old: Option<&VoiceState>,
+        new: &VoiceState,
+    ) {
+        let Some(guild) = new.guild_id else {
+            return;
+        };
+        let bot = UserId::new(self.bot_id.load(Ordering::Relaxed));
+        let old_channel = old.and_then(|state| state.channel_id);
+
+        if new.user_id == bot {
+            // New session when the bot moves to another channel.
+            if let Some(channel) = new.channel_id.filter(|c| Some(*c) != old_channel) {
+                let present = ctx
+                    .cache
+                    .guild(guild)
+                    .map(|guild| {
+                        guild
+                            .voice_states
+                            .values()
+                            .filter(|state| {
+                                state.channel_id == Some(channel) && state.user_id != bot
+                            })
+                            .map(|state| state.user_id)
+                            .collect_vec()
+                    })
+                    .unwrap_or_default();
+                self.timeline.start(guild, present).await;
+            }
+            return;
+        }
+
+        let bot_channel = ctx.cache.guild(guild).and_then(|guild| {
+            guild
+                .voice_states
+                .get(&bot)
+                .and_then(|state| state.channel_id)
+        });
+        let Some(bot_channel) = bot_channel else {
+            return;
+        };
+        match (
+            old_channel == Some(bot_channel),
+            new.channel_id == Some(bot_channel),
+        ) {
+            (false, true) => {
+                self.timeline.record(guild, new.user_id, true).await;
+                self.play_chime(ctx, guild, true).await;
+            }
+            (true, false) => {
+                self.timeline.record(guild, new.user_id, false).await;
+                self.play_chime(ctx, guild, false).await;
+            }
+            _ => (),
+        }
+    }
+
+    /// Let the recorder know who is in the voice channel the bot is in.
+    pub(super) async fn update_presence(&self, ctx: &Context, guild: GuildId) {
+        let bot = UserId::new(self.bot_id.load(Ordering::Relaxed));
+        let channel = ctx
+            .cache
+            .guild(guild)
+            .and_then(|guild| guild.voice_states.get(&bot).and_then(|s| s.channel_id));
+        self.set_presence(ctx, guild, channel).await;
+    }
+
+    /// Send the members of a voice channel and their roles to the recorder,
+    /// nobody if `channel` is `None`. Done before joining too, for the first
+    /// words of role-whitelisted members to be recorded.
+    pub(super) async fn set_presence(
+        &self,
+        ctx: &Context,
+        guild: GuildId,
+        channel: Option<ChannelId>,
+    ) {
+        let bot = UserId::new(self.bot_id.load(Ordering::Relaxed));
+        let members = match (ctx.cache.guild(guild), channel) {
+            (Some(guild), Some(channel)) => guild
+                .voice_states
+                .values()
+                .filter(|state| state.channel_id == Some(channel) && state.user_id != bot)
+                .map(|state| state.user_id)
+                .collect(),
+            _ => HashSet::new(),
+        };
+
+        // Roles are looked up as members come and go, and when their roles
+        // change.
+        let settings = self.settings.get(guild).await;
+        let mut roles = HashMap::new();
+        if settings.recorded_role.is_some() || !settings.whitelisted_roles.is_empty() {
+            for user in &members {
+                match guild.member(ctx, *user).await {
+                    Ok(member) => {
+                        roles.insert(*user, member.roles);
+                    }
+                    Err(err) => warn!("cannot fetch member {user} of guild {guild}: {err}"),
+                }
+            }
+        }
+        let holding = |matches: &dyn Fn(&RoleId) -> bool| {
+            roles
+                .iter()
+                .filter(|(_, roles)| roles.iter().any(matches))
+                .map(|(user, _)| *user)
+                .collect::<HashSet<_>>()
+        };
+        let role_holders = settings
+            .recorded_role
+            .map(|recorded| holding(&|role| *role == recorded));
+        // A role grant isn't consent, holders are asked the first time they are
+        // seen and only recorded once they accepted.
+        let mut to_ask = Vec::new();
+        let (role_whitelisted, guild_recorder) = {
+            let mut recorder = self.recorder.lock().await;
+            let mut consented = HashSet::new();
+            for user in holding(&|role| settings.whitelisted_roles.contains(role)) {
+                if recorder.has_valid_consent(guild, user, settings.whitelist_expiry) {
+                    consented.insert(user);
+                } else if recorder.request_role_consent(guild, user) {
+                    to_ask.push(user);
+                }
+            }
+            (consented, recorder.get_guild_recorder(guild).await)
+        };
+        if !to_ask.is_empty() {
+            let guild_name = guild.name(ctx).unwrap_or_else(|| "a server".to_owned());
+            for user in to_ask {
+                let intro = format!(
+                    "You hold a role recorded in **{guild_name}**, do you accept joining its \
+                     recorder whitelist?"
+                );
+                if !self.send_consent_request(ctx, guild, user, intro).await {
+                    info!("cannot ask role member {user} of guild {guild} for consent");
+                }
+            }
+        }
+
+        for action in [
+            RecorderAction::SetRoleHolders(role_holders),
+            RecorderAction::SetRoleWhitelisted(role_whitelisted),
+            RecorderAction::UpdatePresence(members),
+        ] {
+            if let Err(err) = guild_recorder.send(action).await {
+                error!("cannot update the presence of guild {guild}: {err}");
+                return;
+            }
+        }
+    }
+
+    pub(super) async fn disconnect_if_alone(
+        &self,
+        ctx: &Context,
+        guild_id: GuildId,
+        channel_id: ChannelId,
+    ) {
+        {
+            let Some(guild) = ctx.cache.guild(guild_id) else {
+                return;
+            };
+            let bot = UserId::new(self.bot_id.load(Ordering::Relaxed));
+            let in_channel = |state: &&VoiceState| state.channel_id == Some(channel_id);
+            if guild.voice_states.get(&bot).filter(in_channel).is_none()
+                || guild
+                    .voice_states
+                    .values()
+                    .any(|state| in_channel(&state) && state.user_id != bot)
+            {
+                return;
+            }
+        }
+
+        leave_voice(ctx, guild_id).await;
+    }
+
+    /// Join an auto-join channel as soon as a whitelisted user enters it, if
+    /// the bot isn't in another voice channel of the guild, and leave it once
+    /// no whitelisted user is left in it.
+    pub(super) async fn auto_join(&self, ctx: &Context, guild: GuildId, state: &VoiceState) {
+        let settings = self.settings.get(guild).await;
+        if settings.disabled || settings.auto_join_channels.is_empty() {
+            return;
+        }
+        let whitelist = self.recorder.lock().await.get_whitelist(guild);
+        let manager = songbird::get(ctx)
+            .await
+            .expect("Failed to get songbird manager");
+        let current = match manager.get(guild) {
+            Some(call) => call
+                .lock()
+                .await
+                .current_channel()
+                .map(|channel| ChannelId::from(channel.0)),
+            None => None,
+        };
+
+        match current {
+            None => {
+                let Some(channel) = state.channel_id.filter(|channel| {
+                    settings.auto_join_channels.contains(channel)
+                        && !settings.blocked_channels.contains(channel)
+                }) else {
+                    return;
+                };
+                if !whitelist.contains(&state.user_id) {
+                    return;
+                }
+                info!("auto-joining channel {channel} of guild {guild}");
+                self.auto_joined.lock().await.insert(guild, channel);
+                if let Err(err) = self.connect(ctx, guild, channel, None, channel).await {
+                    warn!("cannot auto-join channel {channel} of guild {guild}: {err}");
+                }
+            }
+            Some(channel) if self.auto_joined.lock().await.get(&guild) == Some(&channel) => {
+                let present = ctx.cache.guild(guild).map(|guild| {
+                    guild.voice_states.values().any(|state| {
+                        state.channel_id == Some(channel) && whitelist.contains(&state.user_id)
+                    })
+                });
+                if present == Some(false) {
+                    info!("leaving auto-joined channel {channel} of guild {guild}");
+                    self.auto_joined.lock().await.remove(&guild);
+                    leave_voice(ctx, guild).await;
+                }
+            }
+            Some(_) => (),
+        }
+    }
+
+    /// Leave the voice channel when the bot is moved into a blocked one.
+    pub(super) async fn leave_if_blocked(&self, ctx: &Context, guild: GuildId, state: &VoiceState) {
+        if state.user_id.get() != self.bot_id.load(Ordering::Relaxed) {
+            return;
+        }
+        let Some(channel) = state.channel_id else {
+            return;
+        };
+        if self
+            .settings
+            .get(guild)
+            .await
+            .blocked_channels
+            .contains(&channel)
+        {
+            info!("leaving blocked channel {channel} of guild {guild}");
+            self.auto_joined.lock().await.remove(&guild);
+            leave_voice(ctx, guild).await;
+        }
+    }
+}
+
+/// Leave the voice channel of a guild, if in one.
+/// Voice channel the bot is in, if any.
+pub(crate) async fn current_voice_channel(
+    songbird: &Songbird,
+    guild: GuildId,
+) -> Option<ChannelId> {
+    let call = songbird.get(guild)?;
+    let channel = call.lock().await.current_channel()?;
+    Some(ChannelId::from(channel.0))
+}
+
+pub(super) async fn leave_voice(ctx: &Context, guild: GuildId) {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Failed to get songbird manager");
+    if let Some(call) = manager.get(guild) {
+        let mut call_lock = call.lock().await;
+        if let Err(err) = call_lock.leave().await {
+            warn!("cannot leave voice channel of guild {guild}: {err}");
+        }
+        call_lock.remove_all_global_events();
+    }
+}
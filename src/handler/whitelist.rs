@@ -0,0 +1,468 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use itertools::Itertools;
+use log::{info, warn};
+use serenity::{
+    all::{
+        ButtonStyle, CommandInteraction, ComponentInteraction, CreateAllowedMentions, CreateButton,
+        CreateInteractionResponse, CreateInteractionResponseMessage, CreateMessage, GuildId,
+        Mention, UserId,
+    },
+    builder::CreateActionRow,
+    client::Context,
+    http::GuildPagination,
+};
+use tokio::{
+    sync::{oneshot, Mutex},
+    time::sleep,
+};
+
+use super::{Handler, MAX_MESSAGE_LENGTH};
+use crate::{
+    command,
+    interaction::InteractionError,
+    recorder::{Recorder, RecorderAction},
+    settings::Settings,
+};
+
+/// How often expired whitelist consents are looked for.
+const CONSENT_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+/// How long before their consent expires users are reminded to renew it.
+const CONSENT_REMINDER: Duration = Duration::from_secs(3 * 24 * 60 * 60);
+
+impl Handler {
+    pub(super) async fn get_whitelist(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+
+        let (list, guild_recorder) = {
+            let mut recorder = self.recorder.lock().await;
+            let list = recorder
+                .get_whitelist(guild)
+                .intersection(
+                    &ctx.cache
+                        .guild(guild)
+                        .ok_or(InteractionError::GuildUnavailable)?
+                        .members
+                        .keys()
+                        .copied()
+                        .collect(),
+                )
+                .copied()
+                .collect::<HashSet<_>>();
+            (list, recorder.get_guild_recorder(guild).await)
+        };
+        let (tx, rx) = oneshot::channel();
+        guild_recorder.send(RecorderAction::GetStats(tx)).await?;
+        let buffered = rx
+            .await?
+            .users
+            .into_iter()
+            .filter_map(|user| Some((user.id, (user.buffered, user.oldest?))))
+            .collect::<HashMap<_, _>>();
+
+        // Members with the most audio first.
+        let mut list = list
+            .into_iter()
+            .map(|user| (user, buffered.get(&user).copied()))
+            .collect_vec();
+        list.sort_by_key(|(user, buffered)| {
+            (
+                std::cmp::Reverse(buffered.map(|(duration, _)| duration)),
+                *user,
+            )
+        });
+        let mut content = String::new();
+        for (user, buffered) in list {
+            let line = match buffered {
+                Some((duration, oldest)) => format!(
+                    "- {}: {} buffered, oldest {} ago\n",
+                    Mention::from(user),
+                    humantime::format_duration(Duration::from_secs(duration.as_secs())),
+                    humantime::format_duration(Duration::from_secs(oldest.elapsed().as_secs()))
+                ),
+                None => format!("- {}: nothing buffered\n", Mention::from(user)),
+            };
+            // Leave room for the retention line.
+            if content.len() + line.len() > MAX_MESSAGE_LENGTH - 100 {
+                break;
+            }
+            content.push_str(&line);
+        }
+        if content.is_empty() {
+            content = "*Nobody.*".to_owned();
+        } else if let Some(retention) = self.settings.get(guild).await.retention {
+            content.push_str(&format!(
+                "Audio older than {} is deleted.",
+                humantime::format_duration(retention)
+            ));
+        }
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn join_whitelist(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        self.recorder
+            .lock()
+            .await
+            .add_whitelist(guild, command.user.id)
+            .await;
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("You are now in the whitelist."),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn leave_whitelist(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        self.recorder
+            .lock()
+            .await
+            .remove_whitelist(guild, command.user.id)
+            .await;
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content("You have been removed from the whitelist."),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn add_to_whitelist(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(user) = command::find_user_option(&command, "user") else {
+            return Ok(());
+        };
+        if !self.recorder.lock().await.request_consent(guild, user.id) {
+            command
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(format!("{} is already in the whitelist.", user))
+                            .allowed_mentions(CreateAllowedMentions::new()),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        // Users are only recorded once they accepted.
+        let guild_name = guild.name(&ctx).unwrap_or_else(|| "a server".to_owned());
+        let content = if self
+            .send_consent_request(
+                &ctx,
+                guild,
+                user.id,
+                format!(
+                    "{} wants to add you to the recorder whitelist of **{}**.",
+                    command.user, guild_name
+                ),
+            )
+            .await
+        {
+            format!("Asked {} to consent to being recorded.", user)
+        } else {
+            format!(
+                "Cannot send a direct message to {}, they need to join the whitelist themselves.",
+                user
+            )
+        };
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Ask a user waiting to consent by direct message, cancelling the
+    /// request if it cannot be sent. Returns whether it was sent.
+    pub(super) async fn send_consent_request(
+        &self,
+        ctx: &Context,
+        guild: GuildId,
+        user: UserId,
+        intro: String,
+    ) -> bool {
+        let sent = user
+            .direct_message(
+                ctx,
+                CreateMessage::new()
+                    .content(format!(
+                        "{intro} Your voice will be recorded in this server's voice channels \
+                         once you accept."
+                    ))
+                    .components(vec![CreateActionRow::Buttons(vec![
+                        CreateButton::new(format!("consent-accept-{guild}"))
+                            .label("Accept")
+                            .style(ButtonStyle::Success),
+                        CreateButton::new(format!("consent-decline-{guild}"))
+                            .label("Decline")
+                            .style(ButtonStyle::Danger),
+                    ])]),
+            )
+            .await
+            .is_ok();
+        if !sent {
+            self.recorder
+                .lock()
+                .await
+                .answer_consent(guild, user, false)
+                .await;
+        }
+        sent
+    }
+
+    pub(super) async fn answer_consent(
+        &self,
+        ctx: Context,
+        component: ComponentInteraction,
+        answer: &str,
+    ) -> Result<(), InteractionError> {
+        let Some((answer, guild)) = answer
+            .split_once('-')
+            .and_then(|(answer, guild)| Some((answer, GuildId::new(guild.parse().ok()?))))
+        else {
+            return Ok(());
+        };
+
+        // Reminders of expiring consents.
+        if answer == "renew" || answer == "leave" {
+            let mut recorder = self.recorder.lock().await;
+            let content = if !recorder.get_whitelist(guild).contains(&component.user.id) {
+                "You aren't in this server's whitelist anymore."
+            } else if answer == "renew" {
+                recorder.add_whitelist(guild, component.user.id).await;
+                "Thanks, you stay in the whitelist."
+            } else {
+                recorder.remove_whitelist(guild, component.user.id).await;
+                "You left the whitelist and won't be recorded anymore."
+            };
+            drop(recorder);
+            component
+                .create_response(
+                    &ctx,
+                    CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new()
+                            .content(content)
+                            .components(vec![]),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+
+        let accepted = answer == "accept";
+        let answered = self
+            .recorder
+            .lock()
+            .await
+            .answer_consent(guild, component.user.id, accepted)
+            .await;
+        let content = match (answered, accepted) {
+            (false, _) => "This request expired.",
+            (true, true) => "You are now in the whitelist, use `/recorder leave` to leave it.",
+            (true, false) => "You won't be recorded.",
+        };
+
+        component
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(content)
+                        .components(vec![]),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub(super) async fn remove_from_whitelist(
+        &self,
+        ctx: Context,
+        command: CommandInteraction,
+    ) -> Result<(), InteractionError> {
+        let Some(guild) = command.guild_id else {
+            return Ok(());
+        };
+        let Some(user) = command::find_user_option(&command, "user") else {
+            return Ok(());
+        };
+        let removed = self
+            .recorder
+            .lock()
+            .await
+            .remove_whitelist(guild, user.id)
+            .await;
+
+        command
+            .create_response(
+                &ctx,
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .content(if removed {
+                            format!("{} has been removed from the whitelist.", user)
+                        } else {
+                            format!("{} isn't in the whitelist.", user)
+                        })
+                        .allowed_mentions(CreateAllowedMentions::new()),
+                ),
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+/// Grant users of the legacy global whitelist access in every guild of the bot,
+/// listed through the API as a shard only knows its own guilds.
+pub(super) async fn migrate_legacy_whitelist(ctx: &Context, recorder: &Mutex<Recorder>) {
+    if !recorder.lock().await.has_legacy_whitelist() {
+        return;
+    }
+    const PAGE_SIZE: u64 = 200;
+    let mut guilds = Vec::new();
+    loop {
+        let page = match ctx
+            .http
+            .get_guilds(
+                guilds.last().copied().map(GuildPagination::After),
+                Some(PAGE_SIZE),
+            )
+            .await
+        {
+            Ok(page) => page,
+            Err(err) => {
+                warn!("cannot list guilds, legacy whitelist not migrated: {err}");
+                return;
+            }
+        };
+        let last_page = (page.len() as u64) < PAGE_SIZE;
+        guilds.extend(page.into_iter().map(|guild| guild.id));
+        if last_page {
+            break;
+        }
+    }
+    recorder.lock().await.migrate_legacy_whitelist(guilds).await;
+}
+
+/// Remove whitelisted users whose consent expired, reminding them beforehand.
+pub(super) fn consent_expiry_loop(
+    ctx: Context,
+    recorder: Arc<Mutex<Recorder>>,
+    settings: Arc<Settings>,
+) {
+    tokio::spawn(async move {
+        loop {
+            let guilds = recorder.lock().await.guilds_with_whitelist();
+            for guild in guilds {
+                // Served by the shards of another process.
+                if ctx.cache.guild(guild).is_none() {
+                    continue;
+                }
+                let Some(expiry) = settings.get(guild).await.whitelist_expiry else {
+                    continue;
+                };
+                let (expired, expiring) = recorder.lock().await.review_consents(
+                    guild,
+                    expiry,
+                    CONSENT_REMINDER.min(expiry / 2),
+                );
+                let guild_name = guild.name(&ctx).unwrap_or_else(|| "a server".to_owned());
+
+                for user in expired {
+                    recorder.lock().await.remove_whitelist(guild, user).await;
+                    info!("consent of user {user} expired in guild {guild}");
+                    let _ = user
+                        .direct_message(
+                            &ctx,
+                            CreateMessage::new().content(format!(
+                                "Your consent to be recorded in **{guild_name}** expired, you \
+                                 won't be recorded anymore. Use `/recorder join` there to be \
+                                 recorded again."
+                            )),
+                        )
+                        .await;
+                }
+                for user in expiring {
+                    let _ = user
+                        .direct_message(
+                            &ctx,
+                            CreateMessage::new()
+                                .content(format!(
+                                    "Your consent to be recorded in **{guild_name}** expires \
+                                     soon. Do you want to stay in the recorder whitelist?"
+                                ))
+                                .components(vec![CreateActionRow::Buttons(vec![
+                                    CreateButton::new(format!("consent-renew-{guild}"))
+                                        .label("Keep recording me")
+                                        .style(ButtonStyle::Success),
+                                    CreateButton::new(format!("consent-leave-{guild}"))
+                                        .label("Leave")
+                                        .style(ButtonStyle::Danger),
+                                ])]),
+                        )
+                        .await;
+                }
+            }
+            sleep(CONSENT_CHECK_INTERVAL).await;
+        }
+    });
+}
@@ -11,6 +11,8 @@ use ulid::Ulid;
 
 const LOGS_DURATION: Duration = Duration::from_secs(5 * 60);
 const MIN_LOGS_FETCH: Duration = Duration::from_secs(30);
+/// Number of plays kept for profiles.
+const RECENT_PLAYS: usize = 5;
 
 #[derive(Default)]
 pub struct History {
@@ -43,6 +45,47 @@ impl History {
         ))
     }
 
+    /// Most played sounds of a user since startup with their play count, and
+    /// their latest plays with how long ago they happened.
+    pub async fn get_user_plays(
+        &self,
+        guild: GuildId,
+        user: UserId,
+        favorites: usize,
+    ) -> (Vec<(Ulid, u32)>, Vec<(Ulid, Duration)>) {
+        let guild_counters = self.guild_counters.lock().await;
+        let Some(plays) = guild_counters
+            .get(&guild)
+            .and_then(|history| history.users.get(&user))
+        else {
+            return Default::default();
+        };
+        (
+            plays
+                .counts
+                .iter()
+                .sorted_by(|(s1, c1), (s2, c2)| c2.cmp(c1).then(s1.cmp(s2)))
+                .take(favorites)
+                .map(|(sound, count)| (*sound, *count))
+                .collect(),
+            plays
+                .recent
+                .iter()
+                .rev()
+                .map(|(ts, sound)| (*sound, ts.elapsed()))
+                .collect(),
+        )
+    }
+
+    /// Drop the plays of a user in every guild.
+    pub async fn forget(&self, user: UserId) {
+        for history in self.guild_counters.lock().await.values_mut() {
+            history.logs.retain(|(u, _, _)| *u != user);
+            history.users.remove(&user);
+        }
+    }
+
+    #[cfg(feature = "http-api")]
     pub async fn get_latest_played(&self, guild: GuildId, offset: usize) -> Option<Ulid> {
         self.guild_counters
             .lock()
@@ -55,6 +98,13 @@ impl History {
 #[derive(Default)]
 struct GuildHistory {
     logs: VecDeque<(UserId, Instant, Ulid)>,
+    users: HashMap<UserId, UserPlays>,
+}
+
+#[derive(Default)]
+struct UserPlays {
+    counts: HashMap<Ulid, u32>,
+    recent: VecDeque<(Instant, Ulid)>,
 }
 
 impl GuildHistory {
@@ -70,6 +120,13 @@ impl GuildHistory {
         }
         // Append log.
         self.logs.push_back((user, Instant::now(), sound));
+
+        let plays = self.users.entry(user).or_default();
+        *plays.counts.entry(sound).or_default() += 1;
+        if plays.recent.len() == RECENT_PLAYS {
+            plays.recent.pop_front();
+        }
+        plays.recent.push_back((Instant::now(), sound));
     }
 
     fn logs_counters(&self, duration: Duration) -> Vec<(UserId, u32)> {
@@ -85,6 +142,7 @@ impl GuildHistory {
             .collect()
     }
 
+    #[cfg(feature = "http-api")]
     fn last_played_sound(&self, offset: usize) -> Option<Ulid> {
         self.logs
             .iter()
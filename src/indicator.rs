@@ -0,0 +1,210 @@
+use std::collections::HashMap;
+
+use log::warn;
+use serenity::{
+    all::ShardId,
+    client::Context,
+    gateway::ActivityData,
+    model::id::{ChannelId, GuildId},
+};
+use tokio::sync::Mutex;
+
+/// Discord refuses longer nicknames.
+const MAX_NICKNAME_LEN: usize = 32;
+
+/// Guilds the bot is recording in, shown in the activity of their shard and
+/// optionally in the bot's nickname. Driven by the voice states of the bot,
+/// so every way of joining or leaving a channel updates it.
+#[derive(Default)]
+pub struct Indicator {
+    recordings: Mutex<HashMap<GuildId, Recording>>,
+}
+
+struct Recording {
+    shard: ShardId,
+    channel: ChannelId,
+    channel_name: String,
+    /// Nickname before it was prefixed, `None` if it was left unchanged.
+    previous_nickname: Option<Option<String>>,
+}
+
+impl Indicator {
+    /// Update the indicator after the bot joined, moved to or left
+    /// (`channel` being `None`) a voice channel of a guild.
+    pub async fn update(
+        &self,
+        ctx: &Context,
+        guild: GuildId,
+        channel: Option<ChannelId>,
+        nickname_prefix: Option<&str>,
+    ) {
+        // Fetched before locking, like the nickname changes below, so a slow
+        // request doesn't hold up the other guilds.
+        let channel_name = match channel {
+            Some(channel) => Some(
+                channel
+                    .name(ctx)
+                    .await
+                    .unwrap_or_else(|_| "voice".to_owned()),
+            ),
+            None => None,
+        };
+
+        let mut recordings = self.recordings.lock().await;
+        let change = match channel.zip(channel_name) {
+            Some((channel, _)) if recordings.get(&guild).is_some_and(|r| r.channel == channel) => {
+                return;
+            }
+            Some((channel, channel_name)) => {
+                let previous = recordings.remove(&guild);
+                let prefix = match previous {
+                    Some(_) => None,
+                    None => nickname_prefix.map(NicknameChange::Prefix),
+                };
+                recordings.insert(
+                    guild,
+                    Recording {
+                        shard: ctx.shard_id,
+                        channel,
+                        channel_name,
+                        previous_nickname: previous
+                            .and_then(|recording| recording.previous_nickname),
+                    },
+                );
+                prefix
+            }
+            None => {
+                let Some(recording) = recordings.remove(&guild) else {
+                    return;
+                };
+                recording.previous_nickname.map(NicknameChange::Restore)
+            }
+        };
+
+        let channels = recordings
+            .values()
+            .filter(|recording| recording.shard == ctx.shard_id)
+            .map(|recording| recording.channel_name.as_str())
+            .collect::<Vec<_>>();
+        ctx.set_activity(activity(&channels).map(ActivityData::custom));
+        drop(recordings);
+
+        match change {
+            Some(NicknameChange::Prefix(prefix)) => {
+                let Some(previous) = prefix_nickname(ctx, guild, prefix).await else {
+                    return;
+                };
+                let mut recordings = self.recordings.lock().await;
+                match recordings.get_mut(&guild) {
+                    Some(recording) => recording.previous_nickname = Some(previous),
+                    // Left the channel while the nickname was being changed.
+                    None => {
+                        drop(recordings);
+                        restore_nickname(ctx, guild, previous).await;
+                    }
+                }
+            }
+            Some(NicknameChange::Restore(previous)) => {
+                restore_nickname(ctx, guild, previous).await;
+            }
+            None => {}
+        }
+    }
+}
+
+enum NicknameChange<'a> {
+    Prefix(&'a str),
+    Restore(Option<String>),
+}
+
+/// Prefix the nickname of the bot in a guild, returns the previous one if it
+/// changed.
+async fn prefix_nickname(ctx: &Context, guild: GuildId, prefix: &str) -> Option<Option<String>> {
+    let bot = ctx.cache.current_user().id;
+    let member = match guild.member(ctx, bot).await {
+        Ok(member) => member,
+        Err(err) => {
+            warn!("cannot fetch own member in guild {guild}: {err}");
+            return None;
+        }
+    };
+    let name = member
+        .user
+        .global_name
+        .as_deref()
+        .unwrap_or(&member.user.name);
+    let previous = unprefixed(member.nick.as_deref(), prefix, name);
+    let nickname = format!("{prefix} {}", previous.as_deref().unwrap_or(name))
+        .chars()
+        .take(MAX_NICKNAME_LEN)
+        .collect::<String>();
+    match guild.edit_nickname(ctx, Some(&nickname)).await {
+        Ok(()) => Some(previous),
+        Err(err) => {
+            warn!("cannot prefix nickname in guild {guild}: {err}");
+            None
+        }
+    }
+}
+
+async fn restore_nickname(ctx: &Context, guild: GuildId, previous: Option<String>) {
+    if let Err(err) = guild.edit_nickname(ctx, previous.as_deref()).await {
+        warn!("cannot restore nickname in guild {guild}: {err}");
+    }
+}
+
+/// Nickname of the bot without the prefix left when it stopped before
+/// restoring it, `None` if the bot has no nickname besides its `name`.
+fn unprefixed(nickname: Option<&str>, prefix: &str, name: &str) -> Option<String> {
+    let nickname = nickname?;
+    match nickname
+        .strip_prefix(prefix)
+        .and_then(|rest| rest.strip_prefix(' '))
+    {
+        // Possibly truncated to fit the prefix.
+        Some(rest) if name.starts_with(rest) => None,
+        Some(rest) => Some(rest.to_owned()),
+        None => Some(nickname.to_owned()),
+    }
+}
+
+/// Activity of a shard recording in some channels, none if it isn't.
+fn activity(channels: &[&str]) -> Option<String> {
+    match channels {
+        [] => None,
+        [channel] => Some(format!("🔴 Recording in #{channel}")),
+        channels => Some(format!("🔴 Recording in {} channels", channels.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{activity, unprefixed};
+
+    #[test]
+    fn activities() {
+        assert_eq!(activity(&[]), None);
+        assert_eq!(
+            activity(&["general"]).as_deref(),
+            Some("🔴 Recording in #general")
+        );
+        assert_eq!(
+            activity(&["general", "gaming"]).as_deref(),
+            Some("🔴 Recording in 2 channels")
+        );
+    }
+
+    #[test]
+    fn strips_leftover_prefix() {
+        assert_eq!(unprefixed(None, "🔴", "Disrecord"), None);
+        assert_eq!(unprefixed(Some("🔴 Disrecord"), "🔴", "Disrecord"), None);
+        assert_eq!(
+            unprefixed(Some("🔴 Recorder"), "🔴", "Disrecord").as_deref(),
+            Some("Recorder")
+        );
+        assert_eq!(
+            unprefixed(Some("Recorder"), "🔴", "Disrecord").as_deref(),
+            Some("Recorder")
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use serenity::prelude::SerenityError;
+use songbird::error::JoinError;
+use thiserror::Error as ThisError;
+use tokio::{
+    sync::{mpsc::error::SendError, oneshot::error::RecvError},
+    task::JoinError as TaskError,
+};
+
+use crate::{error_code::Coded, recorder::RecorderAction, soundboard::SoundboardError};
+
+/// Failure while handling an interaction, reported to the user instead of
+/// killing the handler task.
+#[derive(ThisError, Debug)]
+pub enum InteractionError {
+    #[error("Discord request failed.")]
+    Discord(#[from] SerenityError),
+    #[error("This server isn't available yet.")]
+    GuildUnavailable,
+    #[error("Voice isn't available.")]
+    VoiceUnavailable,
+    #[error("Voice connexion failed.")]
+    Voice(#[from] JoinError),
+    #[error("The recorder stopped responding.")]
+    Recorder,
+    #[error("Background task failed.")]
+    Task(#[from] TaskError),
+    #[error(transparent)]
+    Soundboard(#[from] SoundboardError),
+}
+
+impl From<RecvError> for InteractionError {
+    fn from(_: RecvError) -> Self {
+        Self::Recorder
+    }
+}
+
+impl From<SendError<RecorderAction>> for InteractionError {
+    fn from(_: SendError<RecorderAction>) -> Self {
+        Self::Recorder
+    }
+}
+
+impl Coded for InteractionError {
+    fn code(&self) -> u16 {
+        match self {
+            Self::Discord(_) => 601,
+            Self::VoiceUnavailable => 602,
+            Self::Voice(_) => 603,
+            Self::Recorder => 604,
+            Self::Task(_) => 605,
+            Self::GuildUnavailable => 606,
+            Self::Soundboard(err) => err.code(),
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::Soundboard(err) => err.hint(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+/// Frames held while waiting for a missing packet, 60ms of audio.
+const REORDER_DEPTH: usize = 3;
+/// Sequence jumps larger than this, in frames, are taken as a new stream
+/// (e.g. after a reconnection) rather than as lost packets.
+const MAX_GAP: u16 = 50;
+/// Samples of a 20ms mono frame.
+const FRAME_LEN: usize = 960;
+
+/// Puts the voice frames of a speaker back in RTP sequence order, concealing
+/// the ones lost for good.
+#[derive(Debug, Default)]
+pub struct ReorderBuffer {
+    /// Sequence number of the next frame to release.
+    next: Option<u16>,
+    /// Frames received ahead of `next`, by distance from it.
+    pending: BTreeMap<u16, Vec<i16>>,
+    /// Last released frame, faded out to conceal a lost one.
+    last: Vec<i16>,
+    /// Whether the last released frame was concealed already.
+    concealing: bool,
+}
+
+impl ReorderBuffer {
+    /// Add a frame and return the frames ready in order. Frames without
+    /// sequence number were concealed by the decoder and fill the next slot.
+    pub fn push(&mut self, sequence: Option<u16>, frame: Vec<i16>) -> Vec<Vec<i16>> {
+        let Some(next) = self.next else {
+            self.next = sequence;
+            return vec![self.release(frame)];
+        };
+        let sequence = sequence.unwrap_or(next);
+        let distance = sequence.wrapping_sub(next);
+        if distance > u16::MAX / 2 {
+            // Late frame, already concealed.
+            return Vec::new();
+        }
+        if distance > MAX_GAP {
+            let mut ready = self.flush();
+            self.next = Some(sequence);
+            ready.push(self.release(frame));
+            return ready;
+        }
+        self.pending.insert(distance, frame);
+
+        let mut ready = Vec::new();
+        loop {
+            if let Some(frame) = self.pending.remove(&0) {
+                ready.push(self.release(frame));
+            } else if self.pending.len() > REORDER_DEPTH {
+                // Give up waiting for the missing frame.
+                ready.push(self.conceal());
+            } else {
+                break;
+            }
+        }
+        ready
+    }
+
+    /// Release every pending frame, concealing the missing ones.
+    pub fn flush(&mut self) -> Vec<Vec<i16>> {
+        let mut ready = Vec::new();
+        while !self.pending.is_empty() {
+            match self.pending.remove(&0) {
+                Some(frame) => ready.push(self.release(frame)),
+                None => ready.push(self.conceal()),
+            }
+        }
+        ready
+    }
+
+    fn release(&mut self, frame: Vec<i16>) -> Vec<i16> {
+        self.advance();
+        self.concealing = false;
+        self.last.clone_from(&frame);
+        frame
+    }
+
+    /// Replace the next frame by the last one faded out the first time, and
+    /// by silence after that.
+    fn conceal(&mut self) -> Vec<i16> {
+        self.advance();
+        let frame = if self.last.is_empty() {
+            vec![0; FRAME_LEN]
+        } else if self.concealing {
+            vec![0; self.last.len()]
+        } else {
+            let len = self.last.len() as f32;
+            self.last
+                .iter()
+                .enumerate()
+                .map(|(i, sample)| (*sample as f32 * (1. - i as f32 / len)) as i16)
+                .collect()
+        };
+        self.concealing = true;
+        frame
+    }
+
+    fn advance(&mut self) {
+        if let Some(next) = &mut self.next {
+            *next = next.wrapping_add(1);
+        }
+        self.pending = std::mem::take(&mut self.pending)
+            .into_iter()
+            .filter(|(distance, _)| *distance > 0)
+            .map(|(distance, frame)| (distance - 1, frame))
+            .collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ReorderBuffer;
+
+    #[test]
+    fn reorder_and_conceal() {
+        let mut buffer = ReorderBuffer::default();
+        assert_eq!(buffer.push(Some(u16::MAX), vec![1]), vec![vec![1]]);
+        // Out of order around the wrap.
+        assert!(buffer.push(Some(1), vec![3]).is_empty());
+        assert_eq!(buffer.push(Some(0), vec![2]), vec![vec![2], vec![3]]);
+        // Late duplicate.
+        assert!(buffer.push(Some(0), vec![2]).is_empty());
+
+        // Frame 2 lost, concealed once later frames pile up.
+        for sequence in 3..6 {
+            assert!(buffer
+                .push(Some(sequence), vec![sequence as i16])
+                .is_empty());
+        }
+        assert_eq!(
+            buffer.push(Some(6), vec![6]),
+            vec![vec![3], vec![3], vec![4], vec![5], vec![6]]
+        );
+
+        // Decoder concealed frames take the next slot.
+        assert_eq!(buffer.push(None, vec![7]), vec![vec![7]]);
+        // New stream.
+        assert_eq!(buffer.push(Some(1000), vec![9]), vec![vec![9]]);
+    }
+}
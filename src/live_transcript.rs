@@ -0,0 +1,77 @@
+use std::{path::PathBuf, sync::Arc};
+
+use log::{info, warn};
+use serenity::{
+    all::{ChannelId, CreateAllowedMentions, CreateMessage, GuildId, Mention},
+    client::Context,
+};
+use tokio::{sync::mpsc, time::sleep};
+
+use crate::{
+    handler::MAX_MESSAGE_LENGTH,
+    prefs::Preferences,
+    recorder::{RecorderAction, RecorderSender, FREQUENCY},
+    transcription::{recognize, END_OF_UTTERANCE},
+};
+
+/// Live transcripts skip short noises, and split long speeches to keep up.
+pub const MIN_UTTERANCE: usize = FREQUENCY;
+pub const MAX_UTTERANCE: usize = 30 * FREQUENCY;
+
+/// Transcribe utterances of a guild into a text channel, until stopped or
+/// replaced by another transcript.
+pub fn live_loop(
+    ctx: Context,
+    guild: GuildId,
+    channel: ChannelId,
+    guild_recorder: RecorderSender,
+    recognizer_path: PathBuf,
+    preferences: Arc<Preferences>,
+) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        guild_recorder
+            .send(RecorderAction::TranscribeLive(Some(tx)))
+            .await
+            .expect("Failed to register live transcript listener");
+        info!("live transcript of guild {guild} started in channel {channel}");
+        loop {
+            tokio::select! {
+                utterance = rx.recv() => {
+                    let Some((user, pcm)) = utterance else {
+                        break;
+                    };
+                    let language = preferences.get(user).await.language;
+                    let Some(text) = recognize(&recognizer_path, &pcm, language.as_deref())
+                        .await
+                        .map(|transcript| transcript.trim().to_owned())
+                        .filter(|transcript| !transcript.is_empty())
+                    else {
+                        continue;
+                    };
+                    let content = format!("**{}**: {text}", Mention::from(user))
+                        .chars()
+                        .take(MAX_MESSAGE_LENGTH)
+                        .collect::<String>();
+                    if let Err(err) = channel
+                        .send_message(
+                            &ctx,
+                            CreateMessage::new()
+                                .content(content)
+                                .allowed_mentions(CreateAllowedMentions::new()),
+                        )
+                        .await
+                    {
+                        warn!("cannot post live transcript: {err}");
+                    }
+                }
+                _ = sleep(END_OF_UTTERANCE / 4) => {
+                    if guild_recorder.send(RecorderAction::FlushTranscript).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        info!("live transcript of guild {guild} stopped");
+    });
+}
@@ -0,0 +1,342 @@
+use std::{
+    f32::consts::TAU,
+    io::Cursor,
+    path::Path,
+    process::ExitCode,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use itertools::Itertools;
+use serenity::{
+    all::ButtonStyle,
+    model::id::{GuildId, UserId},
+};
+use symphonia::core::{codecs::DecoderOptions, io::MediaSourceStream, probe::Hint};
+use tokio::{
+    fs,
+    sync::{oneshot, Mutex},
+    task::JoinSet,
+    time::{interval, MissedTickBehavior},
+};
+
+use crate::{
+    crypto::Encryption,
+    metrics::Metrics,
+    mix,
+    options::Options,
+    recorder::{Recorder, RecorderAction, Ssrc, StoredVoice, FREQUENCY},
+    settings::Settings,
+    soundboard::Soundboard,
+    stats::Stats,
+    wav,
+};
+
+/// Songbird delivers voice every 20ms.
+const TICK: Duration = Duration::from_millis(20);
+const TICKS_PER_SECOND: u32 = 50;
+const FRAME_LEN: usize = FREQUENCY / TICKS_PER_SECOND as usize;
+/// A packet is swapped with the next one every this many ticks, and one is
+/// lost every `LOST_EVERY` ticks, to go through reordering and concealment.
+const REORDERED_EVERY: u16 = 50;
+const LOST_EVERY: u16 = 101;
+
+/// Scale of a simulation.
+pub struct Load {
+    pub guilds: usize,
+    pub speakers: usize,
+    /// Sounds played per second in each guild.
+    pub plays_per_second: u32,
+    pub duration: Duration,
+}
+
+/// Feed synthetic voice and sound plays through the recorder and soundboard,
+/// with the configured buffer and sound options, then print throughput and
+/// memory figures.
+pub async fn run(options: &Options, load: Load) -> ExitCode {
+    let dir = std::env::temp_dir().join(format!("disrecord-loadtest-{}", std::process::id()));
+    if let Err(err) = fs::create_dir_all(dir.join("sounds")).await {
+        println!("cannot create {}: {err}", dir.display());
+        return ExitCode::FAILURE;
+    }
+    let code = simulate(options, &load, &dir).await;
+    let _ = fs::remove_dir_all(&dir).await;
+    code
+}
+
+async fn simulate(options: &Options, load: &Load, dir: &Path) -> ExitCode {
+    let memory_before = memory_usage();
+    let stats = Arc::new(Stats::default());
+    let encryption = Arc::new(Encryption::new(None));
+    // Nothing is encrypted in the fresh directory.
+    let settings = Settings::new(dir.join("guild-settings"), Arc::clone(&encryption))
+        .await
+        .expect("Cannot load test settings");
+    let recorder = Arc::new(Mutex::new(
+        Recorder::new(
+            options.voice_buffer_duration,
+            options.voice_buffer_expiration,
+            dir.join("record-whitelist"),
+            Arc::new(settings),
+            Arc::clone(&encryption),
+            options.low_power,
+            Arc::clone(&stats),
+            Arc::new(Metrics::default()),
+        )
+        .await
+        .expect("Cannot load test whitelist"),
+    ));
+    Recorder::cleanup_loop(
+        Arc::clone(&recorder),
+        options.max_voice_memory.map(|bytes| bytes as usize),
+    );
+    let soundboard = Arc::new(
+        Soundboard::new(
+            dir.join("soundboard"),
+            dir.join("sounds"),
+            options.sound_max_duration,
+            options.sound_cache_duration,
+            options.ffmpeg_path.clone(),
+            0,
+            encryption,
+        )
+        .await
+        .expect("Cannot load test soundboard"),
+    );
+
+    // Every guild gets its whitelisted speakers and a one second sound.
+    let sound = wav::package(&tone(440., FREQUENCY));
+    let mut guilds = Vec::with_capacity(load.guilds);
+    for g in 0..load.guilds {
+        let guild = GuildId::new(g as u64 + 1);
+        let users = (0..load.speakers)
+            .map(|s| UserId::new((g * load.speakers + s) as u64 + 1))
+            .collect_vec();
+        let mut recorder = recorder.lock().await;
+        for user in &users {
+            recorder.add_whitelist(guild, *user).await;
+        }
+        let guild_recorder = recorder.get_guild_recorder(guild).await;
+        for (s, user) in users.iter().enumerate() {
+            guild_recorder
+                .send(RecorderAction::MapUser(*user, ssrc(load, g, s)))
+                .await
+                .expect("Recorder stopped");
+        }
+        guild_recorder
+            .send(RecorderAction::UpdatePresence(users.into_iter().collect()))
+            .await
+            .expect("Recorder stopped");
+
+        let sound = match soundboard
+            .add_data(
+                sound.clone(),
+                "loadtest.wav",
+                guild,
+                "loadtest".to_owned(),
+                None,
+                ButtonStyle::Primary,
+                "loadtest".to_owned(),
+                None,
+                None,
+            )
+            .await
+        {
+            Ok(sound) => sound,
+            Err(err) => {
+                println!("cannot add the test sound: {err}");
+                return ExitCode::FAILURE;
+            }
+        };
+        guilds.push((guild_recorder, sound));
+    }
+
+    let frames = (0..load.speakers)
+        .map(|s| tone(220. + 55. * s as f32, FRAME_LEN))
+        .collect_vec();
+    let play_every = TICKS_PER_SECOND.checked_div(load.plays_per_second);
+    let ticks = (load.duration.as_millis() / TICK.as_millis()) as u32;
+    let mut ticker = interval(TICK);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Burst);
+    let mut plays = JoinSet::new();
+    let mut sent = 0;
+    let mut late_ticks = 0;
+
+    let started = Instant::now();
+    for tick in 0..ticks {
+        let scheduled = ticker.tick().await;
+        if scheduled.elapsed() > TICK {
+            late_ticks += 1;
+        }
+
+        let sequence = tick as u16;
+        for (g, (guild_recorder, sound)) in guilds.iter().enumerate() {
+            let _timer = stats.timer("receive");
+            for (s, frame) in frames.iter().enumerate() {
+                if sequence % LOST_EVERY == LOST_EVERY / 2 {
+                    continue;
+                }
+                let sequence = match sequence % REORDERED_EVERY {
+                    0 => sequence.wrapping_add(1),
+                    1 => sequence.wrapping_sub(1),
+                    _ => sequence,
+                };
+                guild_recorder
+                    .send_voice(RecorderAction::RegisterVoiceData(
+                        ssrc(load, g, s),
+                        frame.clone(),
+                        Some(sequence),
+                    ))
+                    .expect("Recorder stopped");
+                sent += 1;
+            }
+
+            if play_every.is_some_and(|every| tick % every.max(1) == 0) {
+                let soundboard = Arc::clone(&soundboard);
+                let stats = Arc::clone(&stats);
+                let sound = *sound;
+                plays.spawn(async move {
+                    let start = Instant::now();
+                    let wav = soundboard.get_wav(sound).await?;
+                    tokio::task::spawn_blocking(move || {
+                        let _timer = stats.timer("sound decode");
+                        decode(wav)
+                    })
+                    .await
+                    .ok()??;
+                    Some(start.elapsed())
+                });
+            }
+        }
+    }
+    let feeding = started.elapsed();
+
+    // Recorders handle actions in order, answering means the backlog is done.
+    for (guild_recorder, _) in &guilds {
+        let (tx, rx) = oneshot::channel();
+        guild_recorder
+            .send(RecorderAction::GetTalkTimes(tx))
+            .await
+            .expect("Recorder stopped");
+        let _ = rx.await;
+    }
+    let backlog = started.elapsed() - feeding;
+
+    let mut latencies = Vec::new();
+    let mut failed_plays = 0;
+    while let Some(play) = plays.join_next().await {
+        match play {
+            Ok(Some(latency)) => latencies.push(latency),
+            _ => failed_plays += 1,
+        }
+    }
+
+    let mixdown = match guilds.first() {
+        Some((guild_recorder, _)) => {
+            let (tx, rx) = oneshot::channel();
+            guild_recorder
+                .send(RecorderAction::GetTracks(tx))
+                .await
+                .expect("Recorder stopped");
+            let tracks = StoredVoice::tracks(rx.await.expect("Tracks fetching error"))
+                .await
+                .into_iter()
+                .map(|(_, track)| track)
+                .collect_vec();
+            let timer = stats.timer("mixdown");
+            let start = Instant::now();
+            tokio::task::spawn_blocking(move || {
+                let _timer = timer;
+                mix::mixdown(&tracks, true)
+            })
+            .await
+            .expect("Mixdown failure");
+            Some(start.elapsed())
+        }
+        None => None,
+    };
+
+    println!(
+        "{} guilds with {} speakers each for {}",
+        load.guilds,
+        load.speakers,
+        humantime::format_duration(load.duration)
+    );
+    println!(
+        "voice: {sent} frames in {feeding:.2?} ({:.0} frames/s), {late_ticks}/{ticks} late ticks, backlog handled in {backlog:.2?}",
+        sent as f64 / feeding.as_secs_f64()
+    );
+    if !latencies.is_empty() || failed_plays > 0 {
+        println!(
+            "sounds: {} plays, {failed_plays} failed, {:.2?} average latency, {:.2?} max",
+            latencies.len(),
+            latencies.iter().sum::<Duration>() / latencies.len().max(1) as u32,
+            latencies.iter().max().copied().unwrap_or_default()
+        );
+    }
+    if let Some(mixdown) = mixdown {
+        println!("mixdown of a guild: {mixdown:.2?}");
+    }
+    match (memory_before, memory_usage()) {
+        (Some((before, _)), Some((resident, peak))) => println!(
+            "memory: {} MiB resident ({:+} MiB during the test), {} MiB peak",
+            resident / 1024,
+            (resident as i64 - before as i64) / 1024,
+            peak / 1024
+        ),
+        _ => println!("memory: unavailable on this platform"),
+    }
+    print!("{}", stats.report());
+    ExitCode::SUCCESS
+}
+
+fn ssrc(load: &Load, guild: usize, speaker: usize) -> Ssrc {
+    (guild * load.speakers + speaker) as Ssrc + 1
+}
+
+fn tone(frequency: f32, len: usize) -> Vec<i16> {
+    (0..len)
+        .map(|i| ((i as f32 * frequency * TAU / FREQUENCY as f32).sin() * 8_000.) as i16)
+        .collect()
+}
+
+/// Decode a WAV sound as songbird does when playing it, returns its number of
+/// frames.
+fn decode(wav: Vec<u8>) -> Option<usize> {
+    let mut hint = Hint::new();
+    hint.with_extension("wav");
+    let mut format = symphonia::default::get_probe()
+        .format(
+            &hint,
+            MediaSourceStream::new(Box::new(Cursor::new(wav)), Default::default()),
+            &Default::default(),
+            &Default::default(),
+        )
+        .ok()?
+        .format;
+    let params = format.default_track()?.codec_params.clone();
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&params, &DecoderOptions::default())
+        .ok()?;
+    let mut frames = 0;
+    while let Ok(packet) = format.next_packet() {
+        frames += decoder.decode(&packet).ok()?.frames();
+    }
+    Some(frames)
+}
+
+/// Resident and peak memory of the process in KiB, from procfs.
+fn memory_usage() -> Option<(u64, u64)> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let field = |name: &str| -> Option<u64> {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix(name))?
+            .trim()
+            .strip_suffix("kB")?
+            .trim()
+            .parse()
+            .ok()
+    };
+    Some((field("VmRSS:")?, field("VmHWM:")?))
+}
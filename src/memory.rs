@@ -0,0 +1,71 @@
+use std::time::Instant;
+
+use serenity::model::id::{GuildId, UserId};
+
+/// Memory used by the voice buffer of a user.
+#[derive(Debug, Clone, Copy)]
+pub struct Usage {
+    pub guild: GuildId,
+    pub user: UserId,
+    pub bytes: usize,
+    /// When voice was last buffered for the user.
+    pub last_active: Instant,
+}
+
+/// Bytes of their oldest audio to drop from each user for the voice buffers
+/// to fit in `budget`, least recently active users first.
+pub fn plan_eviction(mut usages: Vec<Usage>, budget: usize) -> Vec<(GuildId, UserId, usize)> {
+    let mut excess = usages
+        .iter()
+        .map(|usage| usage.bytes)
+        .sum::<usize>()
+        .saturating_sub(budget);
+    usages.sort_by_key(|usage| usage.last_active);
+
+    let mut evictions = Vec::new();
+    for usage in usages {
+        if excess == 0 {
+            break;
+        }
+        let evicted = usage.bytes.min(excess);
+        if evicted > 0 {
+            excess -= evicted;
+            evictions.push((usage.guild, usage.user, evicted));
+        }
+    }
+    evictions
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use serenity::model::id::{GuildId, UserId};
+
+    use super::{plan_eviction, Usage};
+
+    #[test]
+    fn evicts_least_recently_active_first() {
+        let now = Instant::now();
+        let usage = |guild: u64, user: u64, bytes: usize, idle: u64| Usage {
+            guild: GuildId::new(guild),
+            user: UserId::new(user),
+            bytes,
+            last_active: now - Duration::from_secs(idle),
+        };
+        let usages = vec![
+            usage(1, 1, 400, 10),
+            usage(1, 2, 300, 60),
+            usage(2, 3, 500, 30),
+        ];
+
+        assert!(plan_eviction(usages.clone(), 1_200).is_empty());
+        assert_eq!(
+            plan_eviction(usages, 600),
+            vec![
+                (GuildId::new(1), UserId::new(2), 300),
+                (GuildId::new(2), UserId::new(3), 300),
+            ]
+        );
+    }
+}
@@ -0,0 +1,157 @@
+// Metrics are only rendered by the HTTP API, but still collected without it.
+#![cfg_attr(not(feature = "http-api"), allow(dead_code))]
+
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex};
+
+/// Minimal registry of gauges and counters rendered using the Prometheus text
+/// format.
+#[derive(Default)]
+pub struct Metrics {
+    families: Mutex<BTreeMap<&'static str, Family>>,
+    histograms: Mutex<BTreeMap<&'static str, HistogramFamily>>,
+}
+
+struct Family {
+    kind: &'static str,
+    help: &'static str,
+    values: BTreeMap<String, f64>,
+}
+
+struct HistogramFamily {
+    help: &'static str,
+    buckets: &'static [f64],
+    values: BTreeMap<String, Histogram>,
+}
+
+#[derive(Default)]
+struct Histogram {
+    /// Cumulative count of each bucket.
+    counts: Vec<u64>,
+    count: u64,
+    sum: f64,
+}
+
+/// Format label pairs, eg. `{path="/data"}`, escaping their values.
+pub fn labels(pairs: &[(&str, &str)]) -> String {
+    let pairs = pairs.iter().map(|(name, value)| {
+        let value = value
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n");
+        format!("{name}=\"{value}\"")
+    });
+    format!("{{{}}}", pairs.collect::<Vec<_>>().join(","))
+}
+
+impl Metrics {
+    /// `labels` must be empty or formatted by [`labels`].
+    pub fn set_gauge(&self, name: &'static str, help: &'static str, labels: &str, value: f64) {
+        self.update(name, "gauge", help, labels, |v| *v = value);
+    }
+
+    pub fn increment(&self, name: &'static str, help: &'static str, labels: &str, by: f64) {
+        self.update(name, "counter", help, labels, |v| *v += by);
+    }
+
+    /// `buckets` are the upper bounds of the histogram buckets, in increasing
+    /// order, and must not change between observations of the same metric.
+    pub fn observe(
+        &self,
+        name: &'static str,
+        help: &'static str,
+        labels: &str,
+        buckets: &'static [f64],
+        value: f64,
+    ) {
+        let mut histograms = self.histograms.lock().expect("Metrics lock poisoned");
+        let histogram = histograms
+            .entry(name)
+            .or_insert_with(|| HistogramFamily {
+                help,
+                buckets,
+                values: BTreeMap::new(),
+            })
+            .values
+            .entry(labels.to_owned())
+            .or_insert_with(|| Histogram {
+                counts: vec![0; buckets.len()],
+                ..Default::default()
+            });
+        for (count, _) in histogram
+            .counts
+            .iter_mut()
+            .zip(buckets)
+            .filter(|(_, &bound)| value <= bound)
+        {
+            *count += 1;
+        }
+        histogram.count += 1;
+        histogram.sum += value;
+    }
+
+    fn update<F: FnOnce(&mut f64)>(
+        &self,
+        name: &'static str,
+        kind: &'static str,
+        help: &'static str,
+        labels: &str,
+        f: F,
+    ) {
+        let mut families = self.families.lock().expect("Metrics lock poisoned");
+        let family = families.entry(name).or_insert_with(|| Family {
+            kind,
+            help,
+            values: BTreeMap::new(),
+        });
+        f(family.values.entry(labels.to_owned()).or_default());
+    }
+
+    pub fn render(&self) -> String {
+        let families = self.families.lock().expect("Metrics lock poisoned");
+        let mut out = String::new();
+        for (name, family) in families.iter() {
+            let _ = writeln!(out, "# HELP {name} {}", family.help);
+            let _ = writeln!(out, "# TYPE {name} {}", family.kind);
+            for (labels, value) in &family.values {
+                let _ = writeln!(out, "{name}{labels} {value}");
+            }
+        }
+
+        let histograms = self.histograms.lock().expect("Metrics lock poisoned");
+        for (name, family) in histograms.iter() {
+            let _ = writeln!(out, "# HELP {name} {}", family.help);
+            let _ = writeln!(out, "# TYPE {name} histogram");
+            for (labels, histogram) in &family.values {
+                // Bucket bounds are added to the other labels.
+                let prefix = match labels.strip_suffix('}') {
+                    Some(labels) => format!("{labels},"),
+                    None => "{".to_owned(),
+                };
+                for (bound, count) in family.buckets.iter().zip(&histogram.counts) {
+                    let _ = writeln!(out, "{name}_bucket{prefix}le=\"{bound}\"}} {count}");
+                }
+                let _ = writeln!(
+                    out,
+                    "{name}_bucket{prefix}le=\"+Inf\"}} {}",
+                    histogram.count
+                );
+                let _ = writeln!(out, "{name}_sum{labels} {}", histogram.sum);
+                let _ = writeln!(out, "{name}_count{labels} {}", histogram.count);
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(
+            labels(&[("kind", "command"), ("path", "C:\\a \"b\"\nc")]),
+            r#"{kind="command",path="C:\\a \"b\"\nc"}"#
+        );
+    }
+}
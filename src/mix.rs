@@ -0,0 +1,469 @@
+use std::{
+    f64::consts::{FRAC_PI_4, TAU},
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use itertools::Itertools;
+
+use crate::{recorder::FREQUENCY, vad};
+
+/// Speakers gain bounds when normalizing, avoids boosting noise of almost
+/// silent tracks.
+const MIN_GAIN: f64 = 0.25;
+const MAX_GAIN: f64 = 4.;
+/// Spread of speakers across the stereo field of mixdowns, from 0 (mono) to 1
+/// (hard left to hard right).
+pub const DEFAULT_STEREO_WIDTH: f64 = 0.6;
+/// Pitch and length of the tone played to align local recordings, a whole
+/// number of periods so it ends on a zero crossing.
+const SYNC_TONE_FREQUENCY: f64 = 1000.;
+const SYNC_TONE_DURATION: Duration = Duration::from_millis(250);
+
+/// Contiguous run of samples received from a speaker.
+#[derive(Clone, Copy, Debug)]
+pub struct Segment {
+    pub start: Instant,
+    pub len: usize,
+}
+
+impl Segment {
+    pub fn end(&self) -> Instant {
+        self.start + samples_duration(self.len)
+    }
+}
+
+/// Voice data of a speaker, along with when each part of it was received.
+#[derive(Debug)]
+pub struct Track {
+    pub data: Vec<i16>,
+    pub segments: Vec<Segment>,
+}
+
+impl Track {
+    /// Part of the track received between `since` and `until`.
+    pub fn between(&self, since: Instant, until: Instant) -> Track {
+        let mut data = Vec::new();
+        let mut segments = Vec::new();
+        let mut consumed = 0;
+        for segment in &self.segments {
+            let Some(samples) = self.data.get(consumed..consumed + segment.len) else {
+                break;
+            };
+            consumed += segment.len;
+            if segment.end() <= since || segment.start >= until {
+                continue;
+            }
+            let skipped =
+                samples_len(since.saturating_duration_since(segment.start)).min(segment.len);
+            let kept = samples_len(until.saturating_duration_since(segment.start)).min(segment.len);
+            segments.push(Segment {
+                start: segment.start + samples_duration(skipped),
+                len: kept - skipped,
+            });
+            data.extend_from_slice(&samples[skipped..kept]);
+        }
+        Track { data, segments }
+    }
+}
+
+/// Beep starting at full level on its first period, its onset being easy to
+/// spot in waveforms to line up recordings.
+pub fn sync_tone() -> Vec<i16> {
+    (0..samples_len(SYNC_TONE_DURATION))
+        .map(|i| {
+            let phase = TAU * SYNC_TONE_FREQUENCY * i as f64 / FREQUENCY as f64;
+            (phase.sin() * i16::MAX as f64 / 2.) as i16
+        })
+        .collect()
+}
+
+/// Moment of the session bookmarked by a user.
+#[derive(Clone, Debug)]
+pub struct Marker {
+    pub at: Instant,
+    pub label: String,
+}
+
+pub fn samples_duration(len: usize) -> Duration {
+    Duration::from_secs_f64(len as f64 / FREQUENCY as f64)
+}
+
+pub fn samples_len(duration: Duration) -> usize {
+    (duration.as_secs_f64() * FREQUENCY as f64) as usize
+}
+
+/// Sum tracks on a common timeline starting at the earliest segment. When
+/// `normalize` is set, every speaker is first brought to the average loudness
+/// of all speakers.
+pub fn mixdown(tracks: &[Track], normalize: bool) -> Vec<i16> {
+    mix(tracks, normalize, &vec![vec![1.]; tracks.len()])
+}
+
+/// Mixdown as interleaved stereo samples, each track panned to its position
+/// from -1 (left) to 1 (right). The constant power pan law keeps speakers
+/// equally loud wherever they are.
+pub fn mixdown_stereo(tracks: &[Track], normalize: bool, pans: &[f64]) -> Vec<i16> {
+    let channel_gains = pans
+        .iter()
+        .map(|pan| {
+            let angle = (pan.clamp(-1., 1.) + 1.) * FRAC_PI_4;
+            vec![angle.cos(), angle.sin()]
+        })
+        .collect_vec();
+    mix(tracks, normalize, &channel_gains)
+}
+
+/// Pan of each of `count` speakers, evenly spread from `-width` to `width`.
+pub fn pan_positions(count: usize, width: f64) -> Vec<f64> {
+    if count <= 1 {
+        return vec![0.; count];
+    }
+    (0..count)
+        .map(|i| width * (2. * i as f64 / (count - 1) as f64 - 1.))
+        .collect_vec()
+}
+
+/// Sum tracks into as many interleaved channels as each track has gains.
+fn mix(tracks: &[Track], normalize: bool, channel_gains: &[Vec<f64>]) -> Vec<i16> {
+    let Some(origin) = origin(tracks) else {
+        return Vec::new();
+    };
+    let channels = channel_gains.first().map_or(1, Vec::len);
+
+    let gains = if normalize {
+        gains(tracks)
+    } else {
+        vec![1.; tracks.len()]
+    };
+
+    let mut mix = Vec::<f64>::new();
+    for ((track, gain), channel_gains) in tracks.iter().zip(gains).zip(channel_gains) {
+        let mut consumed = 0;
+        for segment in &track.segments {
+            let Some(samples) = track.data.get(consumed..consumed + segment.len) else {
+                break;
+            };
+            consumed += segment.len;
+
+            let start = samples_len(segment.start.duration_since(origin)) * channels;
+            let end = start + samples.len() * channels;
+            if mix.len() < end {
+                mix.resize(end, 0.);
+            }
+            for (frame, &sample) in mix[start..].chunks_exact_mut(channels).zip(samples) {
+                for (mixed, channel_gain) in frame.iter_mut().zip(channel_gains) {
+                    *mixed += sample as f64 * gain * channel_gain;
+                }
+            }
+        }
+    }
+
+    mix.into_iter()
+        .map(|sample| sample.clamp(i16::MIN as f64, i16::MAX as f64) as i16)
+        .collect_vec()
+}
+
+/// Each track on the timeline of all tracks, from its first segment with the
+/// silences between its segments filled, along with the offset of its first
+/// segment from the earliest one.
+pub fn align(tracks: &[Track]) -> Vec<(Duration, Vec<i16>)> {
+    let Some(origin) = origin(tracks) else {
+        return Vec::new();
+    };
+    tracks
+        .iter()
+        .map(|track| {
+            let Some(first) = track.segments.iter().map(|segment| segment.start).min() else {
+                return (Duration::ZERO, Vec::new());
+            };
+            let mut aligned = Vec::with_capacity(track.data.len());
+            let mut consumed = 0;
+            for segment in &track.segments {
+                let Some(samples) = track.data.get(consumed..consumed + segment.len) else {
+                    break;
+                };
+                consumed += segment.len;
+
+                let start = samples_len(segment.start.duration_since(first));
+                let end = start + samples.len();
+                if aligned.len() < end {
+                    aligned.resize(end, 0);
+                }
+                aligned[start..end].copy_from_slice(samples);
+            }
+            (first.duration_since(origin), aligned)
+        })
+        .collect_vec()
+}
+
+/// Audacity list of files (LOF) importing each file as a track starting at
+/// its offset, file names being relative to the list.
+pub fn audacity_lof(files: &[(String, Duration)]) -> String {
+    let mut lof = String::new();
+    for (name, offset) in files {
+        let _ = writeln!(lof, "file \"{name}\" offset {:.6}", offset.as_secs_f64());
+    }
+    lof
+}
+
+/// Audacity label track marking each segment of a track made of the
+/// concatenated `segments`, one tab separated `start end label` line per
+/// segment, in seconds.
+pub fn audacity_labels(segments: &[Segment], label: &str) -> String {
+    let mut labels = String::new();
+    let mut offset = 0;
+    for segment in segments {
+        let _ = writeln!(
+            labels,
+            "{:.6}\t{:.6}\t{label}",
+            samples_duration(offset).as_secs_f64(),
+            samples_duration(offset + segment.len).as_secs_f64(),
+        );
+        offset += segment.len;
+    }
+    labels
+}
+
+/// Audacity point labels of the markers, on a track made of the concatenated
+/// `segments`. Markers between two segments are moved to the start of the
+/// next one.
+pub fn marker_labels(segments: &[Segment], markers: &[Marker]) -> String {
+    let mut labels = String::new();
+    for marker in markers {
+        let mut offset = 0;
+        for segment in segments {
+            if marker.at < segment.end() {
+                offset += samples_len(marker.at.saturating_duration_since(segment.start));
+                break;
+            }
+            offset += segment.len;
+        }
+        let at = samples_duration(offset).as_secs_f64();
+        let _ = writeln!(labels, "{at:.6}\t{at:.6}\t{}", marker.label);
+    }
+    labels
+}
+
+/// Audacity point labels of the markers, on the mixdown of `tracks`.
+pub fn mixdown_marker_labels(tracks: &[Track], markers: &[Marker]) -> String {
+    let Some(origin) = origin(tracks) else {
+        return String::new();
+    };
+    let mut labels = String::new();
+    for marker in markers {
+        let at = marker.at.saturating_duration_since(origin).as_secs_f64();
+        let _ = writeln!(labels, "{at:.6}\t{at:.6}\t{}", marker.label);
+    }
+    labels
+}
+
+/// Start of the earliest segment of the tracks.
+fn origin(tracks: &[Track]) -> Option<Instant> {
+    tracks
+        .iter()
+        .flat_map(|track| track.segments.iter().map(|segment| segment.start))
+        .min()
+}
+
+fn gains(tracks: &[Track]) -> Vec<f64> {
+    let levels = tracks
+        .iter()
+        .map(|track| vad::rms(&track.data))
+        .collect_vec();
+    let voiced = levels.iter().filter(|&&rms| rms > 0.).collect_vec();
+    if voiced.is_empty() {
+        return vec![1.; tracks.len()];
+    }
+    let target = voiced.iter().copied().sum::<f64>() / voiced.len() as f64;
+
+    levels
+        .into_iter()
+        .map(|rms| {
+            if rms > 0. {
+                (target / rms).clamp(MIN_GAIN, MAX_GAIN)
+            } else {
+                1.
+            }
+        })
+        .collect_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{
+        align, audacity_labels, audacity_lof, marker_labels, mixdown, mixdown_stereo,
+        pan_positions, sync_tone, Marker, Segment, Track,
+    };
+    use crate::recorder::FREQUENCY;
+
+    fn track(start: Instant, len: usize, amplitude: i16) -> Track {
+        Track {
+            data: vec![amplitude; len],
+            segments: vec![Segment { start, len }],
+        }
+    }
+
+    #[test]
+    fn mixdown_aligns_tracks() {
+        let origin = Instant::now();
+        let tracks = [
+            track(origin, FREQUENCY, 1_000),
+            track(origin + Duration::from_millis(500), FREQUENCY, 2_000),
+        ];
+        let mix = mixdown(&tracks, false);
+        assert_eq!(mix.len(), FREQUENCY * 3 / 2);
+        assert_eq!(mix[0], 1_000);
+        assert_eq!(mix[FREQUENCY * 3 / 4], 3_000);
+        assert_eq!(mix[FREQUENCY * 5 / 4], 2_000);
+    }
+
+    #[test]
+    fn mixdown_normalizes_speakers() {
+        let origin = Instant::now();
+        let tracks = [
+            track(origin, FREQUENCY, 1_000),
+            track(origin + Duration::from_secs(1), FREQUENCY, 3_000),
+        ];
+        let mix = mixdown(&tracks, true);
+        assert_eq!(mix[0], 2_000);
+        assert_eq!(mix[FREQUENCY + 1], 2_000);
+    }
+
+    #[test]
+    fn track_between() {
+        let origin = Instant::now();
+        let track = Track {
+            data: vec![1; 2 * FREQUENCY],
+            segments: vec![
+                Segment {
+                    start: origin,
+                    len: FREQUENCY,
+                },
+                Segment {
+                    start: origin + Duration::from_secs(2),
+                    len: FREQUENCY,
+                },
+            ],
+        };
+        let end = origin + Duration::from_secs(4);
+        let recent = track.between(origin + Duration::from_millis(2_500), end);
+        assert_eq!(recent.data.len(), FREQUENCY / 2);
+        assert_eq!(recent.segments.len(), 1);
+        assert_eq!(recent.segments[0].len, FREQUENCY / 2);
+        let middle = track.between(
+            origin + Duration::from_millis(500),
+            origin + Duration::from_millis(2_500),
+        );
+        assert_eq!(middle.data.len(), FREQUENCY);
+        assert_eq!(middle.segments.len(), 2);
+        assert!(track.between(end, end).data.is_empty());
+        assert_eq!(track.between(origin, end).data.len(), 2 * FREQUENCY);
+    }
+
+    #[test]
+    fn mixdown_pans_speakers() {
+        let origin = Instant::now();
+        let tracks = [
+            track(origin, FREQUENCY, 1_000),
+            track(origin + Duration::from_secs(1), FREQUENCY, 2_000),
+        ];
+        assert_eq!(pan_positions(3, 0.5), [-0.5, 0., 0.5]);
+        let mix = mixdown_stereo(&tracks, false, &pan_positions(2, 1.));
+        assert_eq!(mix.len(), FREQUENCY * 4);
+        assert_eq!(mix[..2], [1_000, 0]);
+        assert_eq!(mix[FREQUENCY * 2..FREQUENCY * 2 + 2], [0, 2_000]);
+    }
+
+    #[test]
+    fn align_fills_silences() {
+        let origin = Instant::now();
+        let tracks = [
+            track(origin, FREQUENCY / 2, 1_000),
+            Track {
+                data: vec![2_000; FREQUENCY],
+                segments: vec![
+                    Segment {
+                        start: origin + Duration::from_secs(1),
+                        len: FREQUENCY / 2,
+                    },
+                    Segment {
+                        start: origin + Duration::from_secs(2),
+                        len: FREQUENCY / 2,
+                    },
+                ],
+            },
+        ];
+        let aligned = align(&tracks);
+        assert_eq!(aligned[0], (Duration::ZERO, vec![1_000; FREQUENCY / 2]));
+        assert_eq!(aligned[1].0, Duration::from_secs(1));
+        assert_eq!(aligned[1].1.len(), FREQUENCY * 3 / 2);
+        assert_eq!(aligned[1].1[FREQUENCY * 3 / 4], 0);
+        assert_eq!(aligned[1].1[FREQUENCY * 5 / 4], 2_000);
+        assert_eq!(
+            audacity_lof(&[("alice.flac".to_owned(), aligned[1].0)]),
+            "file \"alice.flac\" offset 1.000000\n"
+        );
+    }
+
+    #[test]
+    fn labels_follow_segments() {
+        let origin = Instant::now();
+        let segments = [
+            Segment {
+                start: origin,
+                len: FREQUENCY / 2,
+            },
+            Segment {
+                start: origin + Duration::from_secs(3),
+                len: FREQUENCY,
+            },
+        ];
+        assert_eq!(
+            audacity_labels(&segments, "alice"),
+            "0.000000\t0.500000\talice\n0.500000\t1.500000\talice\n"
+        );
+    }
+
+    #[test]
+    fn markers_skip_silence() {
+        let origin = Instant::now();
+        let segments = [
+            Segment {
+                start: origin,
+                len: FREQUENCY,
+            },
+            Segment {
+                start: origin + Duration::from_secs(3),
+                len: FREQUENCY,
+            },
+        ];
+        let marker = |secs: f64, label: &str| Marker {
+            at: origin + Duration::from_secs_f64(secs),
+            label: label.to_owned(),
+        };
+        assert_eq!(
+            marker_labels(
+                &segments,
+                &[
+                    marker(0.5, "laugh"),
+                    marker(2., "gap"),
+                    marker(3.25, "quote")
+                ]
+            ),
+            "0.500000\t0.500000\tlaugh\n1.000000\t1.000000\tgap\n1.250000\t1.250000\tquote\n"
+        );
+    }
+
+    #[test]
+    fn sync_tone_starts_sharply() {
+        let tone = sync_tone();
+        assert_eq!(tone.len(), FREQUENCY / 4);
+        // A quarter period in, 1 kHz being 48 samples long.
+        assert_eq!(tone[12], i16::MAX / 2);
+        // Ends on the last sample of a period.
+        assert_eq!(tone.last(), tone.get(47));
+    }
+}
@@ -1,16 +1,28 @@
-use std::{net::IpAddr, path::PathBuf, time::Duration};
+#[cfg(feature = "http-api")]
+use std::net::{IpAddr, SocketAddr};
+use std::{ops::RangeInclusive, path::PathBuf, time::Duration};
 
-use clap::{ArgAction, Parser};
+#[cfg(feature = "storage")]
+use clap::Args;
+use clap::{ArgAction, Parser, Subcommand};
 use log::LevelFilter;
 use parse_duration::parse::Error as DurationError;
+use parse_size::Error as SizeError;
+#[cfg(any(feature = "http-api", feature = "storage"))]
+use reqwest::Url;
+use serenity::model::id::GuildId;
+
+use crate::crypto::EncryptionKey;
 
 #[derive(Parser, Debug)]
-#[command(version, about)]
+#[command(version, about, subcommand_negates_reqs = true)]
 pub struct Options {
+    #[command(subcommand)]
+    pub command: Option<HostCommand>,
     #[arg(short = 'v', long = "verbose", action = ArgAction::Count)]
     log_level: u8,
-    #[arg(short = 't', long)]
-    pub discord_token: String,
+    #[arg(short = 't', long, required = true)]
+    pub discord_token: Option<String>,
     #[arg(short = 'w', long, default_value("record-whitelist"))]
     pub record_whitelist_path: PathBuf,
     #[arg(
@@ -20,6 +32,13 @@ pub struct Options {
         default_value("3m")
     )]
     pub voice_buffer_duration: Duration,
+    #[arg(
+        short = 'M',
+        long,
+        value_parser(Options::parse_duration),
+        default_value("1h")
+    )]
+    pub voice_buffer_max_duration: Duration,
     #[arg(
         short = 'e',
         long,
@@ -47,14 +66,181 @@ pub struct Options {
     pub sound_cache_duration: Duration,
     #[arg(short = 'f', long, default_value("ffmpeg"))]
     pub ffmpeg_path: PathBuf,
+    /// Image shown by MP4 exports, the server icon if unset
+    #[arg(long)]
+    pub export_cover_path: Option<PathBuf>,
     #[arg(short = 'r', long)]
     pub disable_delete: bool,
     #[arg(short = 'g', long)]
     pub allow_grey: bool,
+    #[cfg(feature = "http-api")]
     #[arg(short = 'a', long, default_value("127.0.0.1"))]
     pub soundboard_http_address: IpAddr,
+    #[cfg(feature = "http-api")]
     #[arg(short = 'p', long, default_value("8080"))]
     pub soundboard_http_port: u16,
+    #[arg(
+        short = 'm',
+        long,
+        value_parser(Options::parse_size),
+        default_value("256MiB")
+    )]
+    pub min_free_space: u64,
+    /// Memory the voice buffers of every server may use together, the oldest
+    /// audio of the least recently active users being dropped beyond it,
+    /// unlimited if unset
+    #[arg(long, value_parser(Options::parse_size))]
+    pub max_voice_memory: Option<u64>,
+    #[arg(short = 'G', long, default_value("guild-settings"))]
+    pub guild_settings_path: PathBuf,
+    #[arg(long, default_value("schedules"))]
+    pub schedules_path: PathBuf,
+    #[arg(long, default_value("download-audit"))]
+    pub download_audit_path: PathBuf,
+    #[arg(long, default_value("4"))]
+    pub max_concurrent_exports: usize,
+    #[arg(long, default_value("1"))]
+    pub max_concurrent_guild_exports: usize,
+    #[cfg(feature = "storage")]
+    #[command(flatten)]
+    pub storage: StorageOptions,
+    #[cfg(feature = "http-api")]
+    #[arg(long, default_value("api-keys"))]
+    pub api_keys_path: PathBuf,
+    /// Refuse guild requests to the HTTP API without an API key, for when it
+    /// is reachable by others than the host
+    #[cfg(feature = "http-api")]
+    #[arg(long)]
+    pub require_api_key: bool,
+    /// Serve `/metrics` on this address rather than with the HTTP API, which
+    /// stops serving it when `--require-api-key` is set
+    #[cfg(feature = "http-api")]
+    #[arg(long)]
+    pub metrics_address: Option<SocketAddr>,
+    #[cfg(feature = "http-api")]
+    #[arg(long)]
+    pub share_public_url: Option<Url>,
+    #[cfg(feature = "http-api")]
+    #[arg(long, value_parser(Options::parse_duration), default_value("1h"))]
+    pub share_expiration: Duration,
+    /// Memory the files served behind sharing links may use together, new
+    /// links being refused beyond it
+    #[cfg(feature = "http-api")]
+    #[arg(long, value_parser(Options::parse_size), default_value("1GiB"))]
+    pub share_max_size: u64,
+    /// Interactions answered or deferred slower than this are reported, Discord
+    /// expects a response within 3 seconds
+    #[arg(long, value_parser(Options::parse_duration), default_value("2s"))]
+    pub slow_interaction_threshold: Duration,
+    /// Channel where operator alerts such as slow interactions and low disk space are posted
+    #[arg(long)]
+    pub log_channel_id: Option<u64>,
+    /// Hexadecimal 256-bit key encrypting the state and sounds written to disk
+    #[arg(
+        long,
+        env = "DISRECORD_ENCRYPTION_KEY",
+        value_parser(EncryptionKey::parse)
+    )]
+    pub encryption_key: Option<EncryptionKey>,
+    /// Decode voice as mono and skip optional DSP (noise gate, denoise, voice
+    /// commands), for low-power hosts
+    #[arg(long)]
+    pub low_power: bool,
+    /// Program reading a WAV file on stdin and printing its transcript
+    #[cfg(any(feature = "transcription", feature = "voice-commands"))]
+    #[arg(long)]
+    pub speech_recognizer_path: Option<PathBuf>,
+    #[cfg(feature = "transcription")]
+    #[arg(long, default_value("user-preferences"))]
+    pub user_preferences_path: PathBuf,
+    /// Number of gateway shards, `auto` for the count recommended by Discord
+    #[arg(long, value_parser(Shards::parse), default_value("1"))]
+    pub shards: Shards,
+    /// Shards run by this process, such as `0-3`, to spread the bot over
+    /// several processes, defaults to all of them
+    #[arg(long, value_parser(Options::parse_shard_range))]
+    pub shard_range: Option<RangeInclusive<u32>>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum Shards {
+    Auto,
+    Count(u32),
+}
+
+impl Shards {
+    fn parse(input: &str) -> Result<Self, String> {
+        if input == "auto" {
+            return Ok(Self::Auto);
+        }
+        match input.parse() {
+            Ok(0) => Err("expected at least one shard".to_owned()),
+            Ok(count) => Ok(Self::Count(count)),
+            Err(err) => Err(err.to_string()),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub enum HostCommand {
+    /// Validate the configuration and print a readiness report
+    Check,
+    /// Feed synthetic voice and sound plays through the recorder and the
+    /// soundboard, then report throughput and memory usage
+    Loadtest {
+        /// Simulated guilds, each with its own recorder
+        #[arg(long, default_value("10"))]
+        guilds: usize,
+        /// Whitelisted users speaking at once in each guild
+        #[arg(long, default_value("5"))]
+        speakers: usize,
+        /// Sounds played per second in each guild
+        #[arg(long, default_value("1"))]
+        plays_per_second: u32,
+        #[arg(long, value_parser(Options::parse_duration), default_value("30s"))]
+        duration: Duration,
+    },
+    /// Securely delete the whitelist, schedules and download audit of servers
+    /// while the bot is stopped, then print what was deleted
+    Purge {
+        /// Purge every server
+        #[arg(long, conflicts_with = "guild", required_unless_present = "guild")]
+        all: bool,
+        /// Purge a single server
+        #[arg(long)]
+        guild: Option<GuildId>,
+        /// Delete the uploaded sounds too
+        #[arg(long)]
+        sounds: bool,
+    },
+}
+
+#[cfg(feature = "storage")]
+#[derive(Args, Clone, Debug)]
+#[command(next_help_heading = "Storage")]
+pub struct StorageOptions {
+    #[arg(long)]
+    pub storage_upload_url: Option<Url>,
+    #[arg(long)]
+    pub storage_public_url: Option<Url>,
+    #[arg(long)]
+    pub storage_username: Option<String>,
+    #[arg(long)]
+    pub storage_password: Option<String>,
+    #[arg(long)]
+    pub storage_bearer_token: Option<String>,
+    /// Access key ID signing requests with AWS Signature Version 4, for S3
+    /// buckets or GCS buckets using HMAC keys
+    #[arg(long, requires = "storage_secret_access_key")]
+    pub storage_access_key_id: Option<String>,
+    #[arg(long, env = "DISRECORD_STORAGE_SECRET_ACCESS_KEY")]
+    pub storage_secret_access_key: Option<String>,
+    /// Region of the bucket, `auto` for GCS
+    #[arg(long, default_value("us-east-1"))]
+    pub storage_region: String,
+    /// Log of the files uploaded for each server, for `purge` to delete them
+    #[arg(long, default_value("storage-uploads"))]
+    pub storage_index_path: PathBuf,
 }
 
 impl Options {
@@ -62,6 +248,37 @@ impl Options {
         parse_duration::parse(input)
     }
 
+    fn parse_size(input: &str) -> Result<u64, SizeError> {
+        parse_size::parse_size(input)
+    }
+
+    fn parse_shard_range(input: &str) -> Result<RangeInclusive<u32>, String> {
+        let (start, end) = input.split_once('-').unwrap_or((input, input));
+        let start = start.trim().parse::<u32>().map_err(|err| err.to_string())?;
+        let end = end.trim().parse::<u32>().map_err(|err| err.to_string())?;
+        if start > end {
+            return Err("the first shard comes after the last one".to_owned());
+        }
+        Ok(start..=end)
+    }
+
+    /// Shards to run and the total count, `None` to let Discord decide.
+    pub fn sharding(&self) -> Result<Option<(RangeInclusive<u32>, u32)>, String> {
+        match (self.shards, &self.shard_range) {
+            (Shards::Auto, None) => Ok(None),
+            (Shards::Auto, Some(_)) => {
+                Err("--shard-range requires an explicit --shards count".to_owned())
+            }
+            (Shards::Count(count), None) => Ok(Some((0..=count - 1, count))),
+            (Shards::Count(count), Some(range)) if *range.end() < count => {
+                Ok(Some((range.clone(), count)))
+            }
+            (Shards::Count(count), Some(_)) => {
+                Err(format!("--shard-range must be within the {count} shards"))
+            }
+        }
+    }
+
     pub fn log_level(&self) -> LevelFilter {
         match self.log_level {
             0 => LevelFilter::Error,
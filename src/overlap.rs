@@ -0,0 +1,149 @@
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    time::{Duration, Instant},
+};
+
+use serenity::model::id::UserId;
+
+use crate::mix::Segment;
+
+/// Silences shorter than this don't end a turn, voice packets stopping
+/// between words.
+const PAUSE: Duration = Duration::from_millis(500);
+/// Shorter overlaps are backchannels or network jitter, not talk-over.
+const MIN_OVERLAP: Duration = Duration::from_millis(300);
+
+/// How often and how long a speaker talked over another one.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Overlap {
+    /// Speaker who started talking while the other one was.
+    pub interrupter: UserId,
+    pub interrupted: UserId,
+    pub count: usize,
+    pub duration: Duration,
+}
+
+/// Turns of a speaker since `origin`, merging segments separated by short
+/// pauses.
+fn turns(segments: &[Segment], origin: Instant) -> Vec<(Duration, Duration)> {
+    let mut turns: Vec<(Duration, Duration)> = Vec::new();
+    for segment in segments {
+        let start = segment.start.saturating_duration_since(origin);
+        let end = segment.end().saturating_duration_since(origin);
+        match turns.last_mut() {
+            Some((_, last_end)) if start <= *last_end + PAUSE => *last_end = end.max(*last_end),
+            _ => turns.push((start, end)),
+        }
+    }
+    turns
+}
+
+/// Talk-over between every pair of speakers, most frequent first. A speaker
+/// starting a turn while another one is talking interrupts them for as long
+/// as both talk.
+pub fn analyze(speakers: &[(UserId, Vec<Segment>)]) -> Vec<Overlap> {
+    let Some(origin) = speakers
+        .iter()
+        .flat_map(|(_, segments)| segments.iter().map(|segment| segment.start))
+        .min()
+    else {
+        return Vec::new();
+    };
+    let turns = speakers
+        .iter()
+        .map(|(user, segments)| (*user, turns(segments, origin)))
+        .collect::<Vec<_>>();
+
+    let mut overlaps = HashMap::<(UserId, UserId), Overlap>::new();
+    for (interrupter, interrupter_turns) in &turns {
+        for (interrupted, interrupted_turns) in &turns {
+            if interrupter == interrupted {
+                continue;
+            }
+            let mut other = interrupted_turns.iter().peekable();
+            for &(start, end) in interrupter_turns {
+                while other
+                    .next_if(|(_, other_end)| *other_end <= start)
+                    .is_some()
+                {}
+                let Some(&&(other_start, other_end)) = other.peek() else {
+                    break;
+                };
+                let overlap = end.min(other_end).saturating_sub(start);
+                if other_start < start && overlap >= MIN_OVERLAP {
+                    let entry = overlaps
+                        .entry((*interrupter, *interrupted))
+                        .or_insert(Overlap {
+                            interrupter: *interrupter,
+                            interrupted: *interrupted,
+                            count: 0,
+                            duration: Duration::ZERO,
+                        });
+                    entry.count += 1;
+                    entry.duration += overlap;
+                }
+            }
+        }
+    }
+    let mut overlaps = overlaps.into_values().collect::<Vec<_>>();
+    overlaps.sort_by(|a, b| {
+        b.count
+            .cmp(&a.count)
+            .then(b.duration.cmp(&a.duration))
+            .then(a.interrupter.cmp(&b.interrupter))
+            .then(a.interrupted.cmp(&b.interrupted))
+    });
+    overlaps
+}
+
+/// CSV of the overlaps, durations in milliseconds.
+pub fn to_csv(overlaps: &[Overlap]) -> String {
+    let mut csv = "interrupter,interrupted,count,duration_ms\n".to_owned();
+    for overlap in overlaps {
+        let _ = writeln!(
+            csv,
+            "{},{},{},{}",
+            overlap.interrupter,
+            overlap.interrupted,
+            overlap.count,
+            overlap.duration.as_millis()
+        );
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use serenity::model::id::UserId;
+
+    use super::analyze;
+    use crate::{mix::Segment, recorder::FREQUENCY};
+
+    #[test]
+    fn counts_talk_over() {
+        let origin = Instant::now();
+        let segment = |start: f64, len: f64| Segment {
+            start: origin + Duration::from_secs_f64(start),
+            len: (len * FREQUENCY as f64) as usize,
+        };
+        let host = UserId::new(1);
+        let guest = UserId::new(2);
+        let overlaps = analyze(&[
+            // One turn despite the short pause.
+            (host, vec![segment(0., 4.), segment(4.2, 5.8)]),
+            // Talks over the host twice, the backchannel is ignored.
+            (
+                guest,
+                vec![segment(1., 0.2), segment(3., 1.), segment(8., 4.)],
+            ),
+        ]);
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!(overlaps[0].interrupter, guest);
+        assert_eq!(overlaps[0].interrupted, host);
+        assert_eq!(overlaps[0].count, 2);
+        assert!((overlaps[0].duration.as_secs_f64() - 3.).abs() < 0.01);
+    }
+}
@@ -0,0 +1,243 @@
+use std::net::{IpAddr, SocketAddr};
+
+use log::info;
+use reqwest::{redirect::Policy, Client, Response, Url};
+use serde::Deserialize;
+use thiserror::Error as ThisError;
+
+use crate::error_code::Coded;
+
+/// Largest sound file downloaded from a pack.
+const MAX_SOUND_SIZE: u64 = 8 * (1 << 20);
+/// Largest pack index downloaded.
+const MAX_INDEX_SIZE: u64 = 1 << 20;
+
+/// Community index of sound packs, a JSON manifest listing packs and the URLs
+/// of their sounds.
+#[derive(Deserialize, Debug)]
+pub struct PackIndex {
+    pub packs: Vec<Pack>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Pack {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    pub sounds: Vec<PackSound>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PackSound {
+    pub name: String,
+    pub url: Url,
+    #[serde(default)]
+    pub emoji: Option<String>,
+}
+
+impl PackSound {
+    /// Last segment of the URL, used to guess the sound format.
+    pub fn filename(&self) -> &str {
+        self.url
+            .path_segments()
+            .and_then(|mut segments| segments.next_back())
+            .unwrap_or_default()
+    }
+}
+
+/// Whether `ip` is reachable from the internet, as opposed to the machine of
+/// the bot or its local network.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // Carrier-grade NAT.
+                || a == 100 && b & 0xc0 == 64)
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                let segment = ip.segments()[0];
+                !(ip.is_loopback()
+                    || ip.is_unspecified()
+                    || ip.is_multicast()
+                    // Unique local.
+                    || segment & 0xfe00 == 0xfc00
+                    // Link-local.
+                    || segment & 0xffc0 == 0xfe80)
+            }
+        },
+    }
+}
+
+/// Client fetching `url`, refused unless it is an HTTP(S) URL resolving to
+/// public addresses only, so that members cannot make the bot reach internal
+/// services. The client is pinned to the checked address and does not follow
+/// redirects, which could otherwise lead back to a private address.
+async fn public_client(url: &Url) -> Result<Client, PackError> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(PackError::ForbiddenUrl);
+    }
+    let port = url.port_or_known_default().ok_or(PackError::InvalidUrl)?;
+    let builder = Client::builder().redirect(Policy::none());
+    let host = url.host_str().ok_or(PackError::InvalidUrl)?;
+    let builder = match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) if is_public(ip) => builder,
+        Ok(_) => return Err(PackError::ForbiddenUrl),
+        Err(_) => {
+            let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|_| PackError::InvalidUrl)?
+                .collect();
+            let Some(&addr) = addrs.first() else {
+                return Err(PackError::InvalidUrl);
+            };
+            if !addrs.iter().all(|addr| is_public(addr.ip())) {
+                return Err(PackError::ForbiddenUrl);
+            }
+            builder.resolve(host, addr)
+        }
+    };
+    builder.build().map_err(|_| PackError::InvalidUrl)
+}
+
+/// Read the body of `response`, refused with `too_large` as soon as it is
+/// known to exceed `max_size` rather than buffered whole, or `failed` if the
+/// connection drops.
+async fn read_limited(
+    mut response: Response,
+    max_size: u64,
+    too_large: PackError,
+    failed: PackError,
+) -> Result<Vec<u8>, PackError> {
+    if response.content_length().is_some_and(|len| len > max_size) {
+        return Err(too_large);
+    }
+    let mut data = Vec::new();
+    loop {
+        match response.chunk().await {
+            Ok(Some(chunk)) if (data.len() + chunk.len()) as u64 > max_size => {
+                return Err(too_large)
+            }
+            Ok(Some(chunk)) => data.extend_from_slice(&chunk),
+            Ok(None) => return Ok(data),
+            Err(_) => return Err(failed),
+        }
+    }
+}
+
+pub async fn fetch_index(url: &str) -> Result<PackIndex, PackError> {
+    let url = Url::parse(url).map_err(|_| PackError::InvalidUrl)?;
+    info!("fetching sound pack index {url}");
+    let response = public_client(&url)
+        .await?
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|_| PackError::IndexFetch)?;
+    let index = read_limited(
+        response,
+        MAX_INDEX_SIZE,
+        PackError::IndexTooLarge,
+        PackError::IndexFetch,
+    )
+    .await?;
+    serde_json::from_slice(&index).map_err(|_| PackError::InvalidIndex)
+}
+
+pub async fn fetch_sound(sound: &PackSound) -> Result<Vec<u8>, PackError> {
+    info!("fetching sound pack sound {}", sound.url);
+    let response = public_client(&sound.url)
+        .await?
+        .get(sound.url.clone())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|_| PackError::SoundFetch)?;
+    read_limited(
+        response,
+        MAX_SOUND_SIZE,
+        PackError::SoundTooLarge,
+        PackError::SoundFetch,
+    )
+    .await
+}
+
+#[derive(ThisError, Debug)]
+pub enum PackError {
+    #[error("Invalid pack index URL.")]
+    InvalidUrl,
+    #[error("This URL points to a private address.")]
+    ForbiddenUrl,
+    #[error("Failed to fetch pack index.")]
+    IndexFetch,
+    #[error("Pack index is not valid.")]
+    InvalidIndex,
+    #[error("Cannot find that pack in the index.")]
+    PackNotFound,
+    #[error("Failed to fetch sound.")]
+    SoundFetch,
+    #[error("Sound file is too large.")]
+    SoundTooLarge,
+    #[error("Pack index is too large.")]
+    IndexTooLarge,
+}
+
+impl Coded for PackError {
+    fn code(&self) -> u16 {
+        match self {
+            Self::InvalidUrl => 201,
+            Self::IndexFetch => 202,
+            Self::InvalidIndex => 203,
+            Self::PackNotFound => 204,
+            Self::SoundFetch => 205,
+            Self::SoundTooLarge => 206,
+            Self::ForbiddenUrl => 207,
+            Self::IndexTooLarge => 208,
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::PackNotFound => Some("Use `/packs browse` to list the available packs."),
+            Self::ForbiddenUrl => Some("Packs can only be fetched from public HTTP(S) URLs."),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_internal_addresses() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in ["1.1.1.1", "93.184.216.34", "2606:4700::1111"] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use serenity::model::id::UserId;
+use thiserror::Error as ThisError;
+use tokio::{fs, sync::Mutex};
+
+use crate::{
+    crypto::{Encryption, LoadError},
+    error_code::Coded,
+};
+
+/// Per user preferences, shared by every guild and persisted as JSON.
+pub struct Preferences {
+    path: PathBuf,
+    encryption: Arc<Encryption>,
+    users: Mutex<HashMap<UserId, UserPreferences>>,
+}
+
+impl Preferences {
+    pub async fn new(path: PathBuf, encryption: Arc<Encryption>) -> Result<Self, LoadError> {
+        let users: HashMap<UserId, UserPreferences> = encryption.read_json(&path).await?;
+        info!("loaded preferences of {} users", users.len());
+
+        Ok(Self {
+            path,
+            encryption,
+            users: Mutex::new(users),
+        })
+    }
+
+    pub async fn get(&self, user: UserId) -> UserPreferences {
+        self.users
+            .lock()
+            .await
+            .get(&user)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Modify the preferences of a user and persist all preferences to disk.
+    pub async fn update<R, F: FnOnce(&mut UserPreferences) -> R>(
+        &self,
+        user: UserId,
+        f: F,
+    ) -> Result<R, PreferencesError> {
+        let mut users = self.users.lock().await;
+        let res = f(users.entry(user).or_default());
+        self.save(&users).await?;
+        Ok(res)
+    }
+
+    /// Returns whether the user had preferences.
+    pub async fn remove(&self, user: UserId) -> Result<bool, PreferencesError> {
+        let mut users = self.users.lock().await;
+        if users.remove(&user).is_none() {
+            return Ok(false);
+        }
+        self.save(&users).await?;
+        Ok(true)
+    }
+
+    async fn save(&self, users: &HashMap<UserId, UserPreferences>) -> Result<(), PreferencesError> {
+        let data = serde_json::to_vec(users).map_err(|_| PreferencesError::SaveFailed)?;
+        fs::write(&self.path, self.encryption.encrypt(data))
+            .await
+            .map_err(|_| PreferencesError::SaveFailed)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct UserPreferences {
+    /// Language code passed to the speech recognizer, detected when unset.
+    pub language: Option<String>,
+}
+
+/// Validate a language code such as `en` or `pt-BR`.
+pub fn parse_language(code: &str) -> Option<String> {
+    let mut parts = code.trim().splitn(2, '-');
+    let language = parts.next()?;
+    let region = parts.next();
+    let valid = (2..=3).contains(&language.len())
+        && language.chars().all(|c| c.is_ascii_alphabetic())
+        && region.is_none_or(|region| {
+            (2..=4).contains(&region.len()) && region.chars().all(|c| c.is_ascii_alphanumeric())
+        });
+    valid.then(|| match region {
+        Some(region) => format!(
+            "{}-{}",
+            language.to_ascii_lowercase(),
+            region.to_ascii_uppercase()
+        ),
+        None => language.to_ascii_lowercase(),
+    })
+}
+
+#[derive(ThisError, Debug)]
+pub enum PreferencesError {
+    #[error("Failed to save preferences.")]
+    SaveFailed,
+}
+
+impl Coded for PreferencesError {
+    fn code(&self) -> u16 {
+        match self {
+            Self::SaveFailed => 402,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_language;
+
+    #[test]
+    fn language_codes() {
+        assert_eq!(parse_language("EN").as_deref(), Some("en"));
+        assert_eq!(parse_language("pt-br").as_deref(), Some("pt-BR"));
+        assert_eq!(parse_language("yue").as_deref(), Some("yue"));
+        assert_eq!(parse_language("english"), None);
+        assert_eq!(parse_language("en-"), None);
+        assert_eq!(parse_language("e1"), None);
+    }
+}
@@ -0,0 +1,149 @@
+use std::{process::ExitCode, sync::Arc};
+
+use serenity::model::id::GuildId;
+
+#[cfg(feature = "http-api")]
+use crate::api_keys::ApiKeys;
+#[cfg(feature = "storage")]
+use crate::storage::Storage;
+use crate::{
+    audit::DownloadAudit, crypto::Encryption, metrics::Metrics, options::Options,
+    recorder::Recorder, schedule::Schedules, settings::Settings, soundboard::Soundboard,
+    stats::Stats,
+};
+
+/// Securely delete the recording data of a guild, or of every guild if
+/// `None`, then print what was deleted. Meant to be run while the bot is
+/// stopped, as it would otherwise write its own state back.
+pub async fn run(options: &Options, guild: Option<GuildId>, sounds: bool) -> ExitCode {
+    let mut failed = false;
+    match guild {
+        Some(guild) => println!("purging guild {guild}"),
+        None => println!("purging every guild"),
+    }
+
+    println!("voice buffers, sessions and shared links: only kept in memory, dropped when the bot stopped");
+
+    let encryption = Arc::new(Encryption::new(options.encryption_key.clone()));
+    // Nothing is deleted from state that cannot be read.
+    let settings =
+        match Settings::new(options.guild_settings_path.clone(), Arc::clone(&encryption)).await {
+            Ok(settings) => Arc::new(settings),
+            Err(err) => {
+                println!("{err}");
+                return ExitCode::FAILURE;
+            }
+        };
+    match Recorder::new(
+        options.voice_buffer_duration,
+        options.voice_buffer_expiration,
+        options.record_whitelist_path.clone(),
+        settings,
+        Arc::clone(&encryption),
+        options.low_power,
+        Arc::new(Stats::default()),
+        Arc::new(Metrics::default()),
+    )
+    .await
+    {
+        Ok(mut recorder) => {
+            let users = recorder.purge(guild).await;
+            println!("whitelist: {users} users and their consents deleted");
+        }
+        Err(err) => {
+            failed = true;
+            println!("whitelist: {err}");
+        }
+    }
+
+    match Schedules::new(options.schedules_path.clone(), Arc::clone(&encryption)).await {
+        Ok(schedules) => match schedules.purge(guild).await {
+            Ok(count) => println!("schedules: {count} deleted"),
+            Err(err) => {
+                failed = true;
+                println!("schedules: {err}");
+            }
+        },
+        Err(err) => {
+            failed = true;
+            println!("schedules: {err}");
+        }
+    }
+
+    match DownloadAudit::new(options.download_audit_path.clone(), Arc::clone(&encryption)).await {
+        Ok(audit) => match audit.purge(guild).await {
+            Ok(count) => println!("download audit: {count} records deleted"),
+            Err(err) => {
+                failed = true;
+                println!("download audit: cannot rewrite the log: {err}");
+            }
+        },
+        Err(err) => {
+            failed = true;
+            println!("download audit: {err}");
+        }
+    }
+
+    #[cfg(feature = "http-api")]
+    match ApiKeys::new(options.api_keys_path.clone(), Arc::clone(&encryption)).await {
+        Ok(api_keys) => match api_keys.purge(guild).await {
+            Ok(count) => println!("API keys: {count} deleted"),
+            Err(err) => {
+                failed = true;
+                println!("API keys: {err}");
+            }
+        },
+        Err(err) => {
+            failed = true;
+            println!("API keys: {err}");
+        }
+    }
+
+    #[cfg(feature = "storage")]
+    match Storage::new(options.storage.clone()) {
+        Some(storage) => match storage.purge(guild).await {
+            Ok(count) => println!("external storage: {count} uploaded files deleted"),
+            Err(err) => {
+                failed = true;
+                println!("external storage: {err}");
+            }
+        },
+        None => println!("external storage: not configured"),
+    }
+
+    if sounds {
+        let soundboard = Soundboard::new(
+            options.soundboard_metadata_path.clone(),
+            options.sounds_dir_path.clone(),
+            options.sound_max_duration,
+            options.sound_cache_duration,
+            options.ffmpeg_path.clone(),
+            0,
+            encryption,
+        )
+        .await;
+        match soundboard {
+            Ok(soundboard) => match soundboard.purge(guild).await {
+                Ok(count) => println!("sounds: {count} deleted"),
+                Err(err) => {
+                    failed = true;
+                    println!("sounds: {err}");
+                }
+            },
+            Err(err) => {
+                failed = true;
+                println!("sounds: {err}");
+            }
+        }
+    } else {
+        println!("sounds: kept, use --sounds to delete them too");
+    }
+
+    if failed {
+        println!("purge incomplete");
+        ExitCode::FAILURE
+    } else {
+        println!("purge complete");
+        ExitCode::SUCCESS
+    }
+}
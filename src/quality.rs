@@ -0,0 +1,122 @@
+use std::time::Duration;
+
+use crate::{
+    diagnostics, mix,
+    vad::{self, Vad, FRAME_SIZE},
+};
+
+/// Samples this loud are most likely clipped by the speaker's setup.
+const CLIPPED_SAMPLE: u16 = 32_000;
+/// Ratio of clipped samples above which distortion is audible.
+const HIGH_CLIPPING: f64 = 0.001;
+/// Voice quieter than this is hard to hear next to other speakers, even after
+/// normalization boosts its noise along.
+const LOW_LEVEL: f32 = -35.;
+/// Level of the quietest frames above which background noise is audible.
+const HIGH_NOISE_FLOOR: f32 = -45.;
+/// Percentile of frame levels taken as the noise floor.
+const NOISE_FLOOR_PERCENTILE: f64 = 0.1;
+/// Less voice than this isn't enough to judge a setup.
+pub const MIN_VOICE: Duration = Duration::from_secs(5);
+
+/// Audio quality of the buffered voice of a speaker.
+#[derive(Debug)]
+pub struct Quality {
+    /// Ratio of clipped samples.
+    pub clipping: f64,
+    /// Level of voice frames, in dBFS.
+    pub level: f32,
+    /// Level of the quietest frames, in dBFS.
+    pub noise_floor: f32,
+    /// Ratio of voice packets lost on the way.
+    pub loss: f64,
+}
+
+impl Quality {
+    /// Analyze voice samples, along with the packets received and lost for
+    /// them. `None` if there isn't enough voice.
+    pub fn analyze(pcm: &[i16], vad: Vad, packets: u64, lost: u64) -> Option<Self> {
+        let voice = vad
+            .voice_ranges(pcm)
+            .into_iter()
+            .flat_map(|range| pcm[range].iter().copied())
+            .collect::<Vec<_>>();
+        if mix::samples_duration(voice.len()) < MIN_VOICE {
+            return None;
+        }
+
+        let clipped = pcm
+            .iter()
+            .filter(|sample| sample.unsigned_abs() >= CLIPPED_SAMPLE)
+            .count();
+        let mut levels = pcm
+            .chunks_exact(FRAME_SIZE)
+            .map(vad::level)
+            .collect::<Vec<_>>();
+        let index = (levels.len() as f64 * NOISE_FLOOR_PERCENTILE) as usize;
+        let (_, noise_floor, _) = levels.select_nth_unstable_by(index, f32::total_cmp);
+        Some(Self {
+            clipping: clipped as f64 / pcm.len() as f64,
+            level: vad::level(&voice),
+            noise_floor: *noise_floor,
+            loss: lost as f64 / packets.max(1) as f64,
+        })
+    }
+
+    /// What the speaker should fix in their setup, nothing if it sounds fine.
+    pub fn recommendations(&self) -> Vec<&'static str> {
+        let mut recommendations = Vec::new();
+        if self.clipping > HIGH_CLIPPING {
+            recommendations
+                .push("Clipping: lower the input volume or move away from the microphone.");
+        }
+        if self.level < LOW_LEVEL {
+            recommendations
+                .push("Too quiet: raise the input volume or move closer to the microphone.");
+        }
+        if self.noise_floor > HIGH_NOISE_FLOOR {
+            recommendations.push(
+                "Noisy background: enable noise suppression or use a directional microphone.",
+            );
+        }
+        if self.loss > diagnostics::HIGH_LOSS {
+            recommendations
+                .push("Dropouts: use a wired connection or close apps using the network.");
+        }
+        recommendations
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use super::Quality;
+    use crate::{recorder::FREQUENCY, vad::Vad};
+
+    #[test]
+    fn flags_bad_setups() {
+        let tone = |amplitude: f32, noise: i16| {
+            (0..10 * FREQUENCY)
+                .map(|i| {
+                    let voice = if i % FREQUENCY < FREQUENCY * 3 / 4 {
+                        (i as f32 * 200. * TAU / FREQUENCY as f32).sin() * amplitude
+                    } else {
+                        0.
+                    };
+                    (voice as i16).saturating_add(if i % 2 == 0 { noise } else { -noise })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let good = Quality::analyze(&tone(8_000., 10), Vad::default(), 500, 1).unwrap();
+        assert!(good.recommendations().is_empty(), "{good:?}");
+
+        let clipped = Quality::analyze(&tone(40_000., 10), Vad::default(), 500, 0).unwrap();
+        assert_eq!(clipped.recommendations().len(), 1);
+        let noisy = Quality::analyze(&tone(8_000., 400), Vad::default(), 500, 50).unwrap();
+        assert_eq!(noisy.recommendations().len(), 2, "{noisy:?}");
+
+        assert!(Quality::analyze(&[0; FREQUENCY], Vad::default(), 50, 0).is_none());
+    }
+}
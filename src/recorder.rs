@@ -1,24 +1,66 @@
 use std::{
     collections::{HashMap, HashSet, VecDeque},
-    mem,
     path::PathBuf,
-    sync::Arc,
-    time::{Duration, Instant},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
 use itertools::Itertools;
-use log::{debug, info, log, log_enabled, Level};
+use log::{debug, error, info, log, log_enabled, warn, Level};
 use serenity::model::id::{GuildId, UserId};
 use tokio::{
-    fs::{File, OpenOptions},
-    io::AsyncWriteExt,
-    sync::{mpsc, mpsc::UnboundedSender, oneshot::Sender as OneshotSender, Mutex},
+    sync::{
+        mpsc::{
+            self,
+            error::{SendError, TrySendError},
+        },
+        oneshot,
+        oneshot::Sender as OneshotSender,
+        Mutex,
+    },
     time::sleep,
 };
 
+#[cfg(any(feature = "transcription", feature = "voice-commands"))]
+use tokio::sync::mpsc::UnboundedSender;
+
+#[cfg(feature = "transcription")]
+use crate::live_transcript;
+#[cfg(any(feature = "transcription", feature = "voice-commands"))]
+use crate::transcription::Utterances;
+#[cfg(feature = "voice-commands")]
+use crate::voice_command;
+use crate::{
+    crypto::{Encryption, LoadError},
+    disk,
+    jitter::ReorderBuffer,
+    memory::{self, Usage},
+    metrics::Metrics,
+    mix::{self, Marker, Segment, Track},
+    resample::{self, Decimator},
+    settings::{GuildSettings, Settings},
+    stats::Stats,
+};
+
 pub type Ssrc = u32;
+/// Voice frame along with its RTP sequence number, if it wasn't concealed, and
+/// when it was received.
+type Frame = (Vec<i16>, Option<u16>, Instant);
 
 pub const FREQUENCY: usize = 48_000;
+/// Longest gap between two packets of a speaker still considered contiguous,
+/// songbird ticks every 20ms.
+const MAX_SEGMENT_GAP: Duration = Duration::from_millis(40);
+/// Frames kept per SSRC until it is mapped to a user, 5 seconds of audio.
+const MAX_UNMAPPED_FRAMES: usize = 250;
+/// Actions queued per guild recorder, 10 seconds of voice of 10 speakers.
+const ACTION_QUEUE: usize = 5_000;
+/// Room kept in the queue for actions other than voice data, which are never
+/// dropped and rarely wait.
+const CONTROL_HEADROOM: usize = 100;
 
 // Log every voice data packet on debug and only one every five minutes on info
 // level.
@@ -38,128 +80,566 @@ macro_rules! log_voice_data {
 pub struct Recorder {
     buffer_size: Duration,
     clean_timeout: Duration,
-    whitelist: HashSet<UserId>,
+    whitelist: HashMap<GuildId, HashSet<UserId>>,
+    /// Users added by someone else, waiting for them to accept being recorded.
+    pending_consents: HashMap<GuildId, HashSet<UserId>>,
     whitelist_path: PathBuf,
-    guilds: HashMap<GuildId, UnboundedSender<RecorderAction>>,
+    /// Users of the whitelist from before it was scoped per guild, waiting to
+    /// be migrated to every guild of the bot.
+    legacy_whitelist: HashSet<UserId>,
+    legacy_whitelist_path: PathBuf,
+    /// When whitelisted users last gave their consent, stored next to the
+    /// whitelist as its format has no room for it.
+    consented_at: HashMap<GuildId, HashMap<UserId, SystemTime>>,
+    consents_path: PathBuf,
+    /// Users reminded that their consent expires soon.
+    reminded: HashSet<(GuildId, UserId)>,
+    /// Holders of whitelisted roles already asked for their consent.
+    role_prompted: HashSet<(GuildId, UserId)>,
+    settings: Arc<Settings>,
+    encryption: Arc<Encryption>,
+    /// Skip optional DSP to save CPU.
+    low_power: bool,
+    stats: Arc<Stats>,
+    metrics: Arc<Metrics>,
+    guilds: HashMap<GuildId, RecorderSender>,
 }
 
 impl Recorder {
+    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         buffer_size: Duration,
         clean_timeout: Duration,
         whitelist_path: PathBuf,
-    ) -> Self {
+        settings: Arc<Settings>,
+        encryption: Arc<Encryption>,
+        low_power: bool,
+        stats: Arc<Stats>,
+        metrics: Arc<Metrics>,
+    ) -> Result<Self, LoadError> {
         info!("creating storage");
         assert!(buffer_size > Duration::from_secs(1));
 
-        let whitelist = tokio::fs::read(&whitelist_path)
-            .await
-            .ok()
-            .map(|file| {
-                file.chunks(mem::size_of::<u64>())
-                    .map(|l| {
-                        UserId::new(u64::from_be_bytes(
-                            l.try_into().expect("Invalid whitelist user id"),
-                        ))
-                    })
-                    .collect::<HashSet<_>>()
-            })
-            .unwrap_or_default();
-        info!("initial whitelist has {} users", whitelist.len());
+        let legacy_whitelist_path = whitelist_path.with_extension("legacy");
+        let whitelist = match encryption.read(&whitelist_path).await? {
+            Some(file) => match serde_json::from_slice(&file) {
+                Ok(whitelist) => whitelist,
+                // The legacy format is a list of big-endian user IDs, a file
+                // that could be JSON is corrupt rather than legacy.
+                Err(err)
+                    if file.len() % std::mem::size_of::<u64>() != 0
+                        || matches!(file.trim_ascii_start().first(), Some(b'{' | b'[')) =>
+                {
+                    return Err(LoadError::Parse(whitelist_path, err.to_string()));
+                }
+                Err(_) => {
+                    // Kept aside until migrated, the whitelist file is
+                    // overwritten with the new format.
+                    warn!(
+                        "whitelist in legacy global format, moving it to {}",
+                        legacy_whitelist_path.display()
+                    );
+                    tokio::fs::rename(&whitelist_path, &legacy_whitelist_path)
+                        .await
+                        .map_err(|err| {
+                            LoadError::Migrate(whitelist_path.clone(), err.to_string())
+                        })?;
+                    HashMap::new()
+                }
+            },
+            None => HashMap::new(),
+        };
+        let legacy_whitelist: HashSet<_> = match tokio::fs::read(&legacy_whitelist_path).await {
+            Ok(file) => file
+                .chunks_exact(std::mem::size_of::<u64>())
+                .map(|id| u64::from_be_bytes(id.try_into().expect("Chunk of u64 size")))
+                .filter(|&id| id != 0)
+                .map(UserId::new)
+                .collect(),
+            Err(_) => HashSet::new(),
+        };
+        if !legacy_whitelist.is_empty() {
+            info!("legacy whitelist has {} users", legacy_whitelist.len());
+        }
+        info!(
+            "initial whitelist has {} users in {} guilds",
+            whitelist.values().map(HashSet::len).sum::<usize>(),
+            whitelist.len()
+        );
 
-        Self {
+        let consents_path = whitelist_path.with_extension("consents");
+        let mut consented_at: HashMap<GuildId, HashMap<UserId, SystemTime>> =
+            encryption.read_json(&consents_path).await?;
+        // Consents given before they were dated count from now.
+        let now = SystemTime::now();
+        for (guild, users) in &whitelist {
+            let consents = consented_at.entry(*guild).or_default();
+            for user in users {
+                consents.entry(*user).or_insert(now);
+            }
+        }
+
+        Ok(Self {
             buffer_size,
             clean_timeout,
             whitelist,
+            pending_consents: HashMap::new(),
             whitelist_path,
+            legacy_whitelist,
+            legacy_whitelist_path,
+            consented_at,
+            consents_path,
+            reminded: HashSet::new(),
+            role_prompted: HashSet::new(),
+            settings,
+            encryption,
+            low_power,
+            stats,
+            metrics,
             guilds: HashMap::new(),
-        }
+        })
     }
 
-    pub fn get_whitelist(&self) -> HashSet<UserId> {
-        info!("fetching whitelist ({} users)", self.whitelist.len());
-        self.whitelist.clone()
+    pub fn get_whitelist(&self, guild: GuildId) -> HashSet<UserId> {
+        let whitelist = self.whitelist.get(&guild).cloned().unwrap_or_default();
+        info!(
+            "fetching whitelist of guild {guild} ({} users)",
+            whitelist.len()
+        );
+        whitelist
     }
 
-    pub async fn add_whitelist(&mut self, user: UserId) {
-        info!("adding user {user} to whitelist");
-        if self.whitelist.insert(user) {
-            let mut file = OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(&self.whitelist_path)
-                .await
-                .expect("Cannot create whitelist file");
-            file.write_u64(user.get())
-                .await
-                .expect("Cannot append user id to whitelist");
-
-            for guild in self.guilds.values() {
-                guild
+    /// Returns whether the user wasn't already in the whitelist, renewing their
+    /// consent either way.
+    pub async fn add_whitelist(&mut self, guild: GuildId, user: UserId) -> bool {
+        info!("adding user {user} to whitelist of guild {guild}");
+        self.consented_at
+            .entry(guild)
+            .or_default()
+            .insert(user, SystemTime::now());
+        self.reminded.remove(&(guild, user));
+        let added = self.whitelist.entry(guild).or_default().insert(user);
+        self.save_whitelist().await;
+        if added {
+            if let Some(guild_recorder) = self.guilds.get(&guild) {
+                if let Err(err) = guild_recorder
                     .send(RecorderAction::AddToWhitelist(user))
-                    .expect("Failed to propagate whitelist removal");
+                    .await
+                {
+                    error!("cannot propagate whitelist addition to guild {guild}: {err}");
+                }
             }
 
-            info!("user {user} added to whitelist");
+            info!("user {user} added to whitelist of guild {guild}");
+            true
         } else {
-            info!("user {user} already in whitelist");
+            info!("consent of user {user} renewed in guild {guild}");
+            false
         }
     }
 
-    pub async fn remove_whitelist(&mut self, user: UserId) {
-        info!("removing user {user} from whitelist");
-        if self.whitelist.remove(&user) {
-            File::create(&self.whitelist_path)
-                .await
-                .expect("Cannot open whitelist file")
-                .write_all(
-                    &self
-                        .whitelist
-                        .iter()
-                        .flat_map(|user| user.get().to_be_bytes())
-                        .collect::<Vec<_>>(),
-                )
-                .await
-                .expect("Cannot write whitelist file");
+    /// Returns whether the user was in the whitelist.
+    pub async fn remove_whitelist(&mut self, guild: GuildId, user: UserId) -> bool {
+        info!("removing user {user} from whitelist of guild {guild}");
+        if self
+            .whitelist
+            .get_mut(&guild)
+            .is_some_and(|whitelist| whitelist.remove(&user))
+        {
+            if let Some(consents) = self.consented_at.get_mut(&guild) {
+                consents.remove(&user);
+            }
+            self.reminded.remove(&(guild, user));
+            self.save_whitelist().await;
 
-            for guild in self.guilds.values() {
-                guild
+            if let Some(guild_recorder) = self.guilds.get(&guild) {
+                if let Err(err) = guild_recorder
                     .send(RecorderAction::RemoveFromWhitelist(user))
-                    .expect("Failed to propagate whitelist removal");
+                    .await
+                {
+                    error!("cannot propagate whitelist removal to guild {guild}: {err}");
+                }
             }
 
-            info!("user {user} removed from whitelist");
+            info!("user {user} removed from whitelist of guild {guild}");
+            true
         } else {
-            info!("user {user} not in whitelist");
+            info!("user {user} not in whitelist of guild {guild}");
+            false
         }
     }
 
-    pub async fn get_guild_recorder(&mut self, guild: GuildId) -> UnboundedSender<RecorderAction> {
+    pub fn has_legacy_whitelist(&self) -> bool {
+        !self.legacy_whitelist.is_empty()
+    }
+
+    /// Add the users of the legacy global whitelist to the whitelist of every
+    /// guild of the bot, as they consented to being recorded in all of them.
+    pub async fn migrate_legacy_whitelist(&mut self, guilds: Vec<GuildId>) {
+        if self.legacy_whitelist.is_empty() {
+            return;
+        }
+        info!(
+            "migrating {} users of legacy whitelist to {} guilds",
+            self.legacy_whitelist.len(),
+            guilds.len()
+        );
+        let now = SystemTime::now();
+        for guild in guilds {
+            for &user in &self.legacy_whitelist {
+                if !self.whitelist.entry(guild).or_default().insert(user) {
+                    continue;
+                }
+                self.consented_at
+                    .entry(guild)
+                    .or_default()
+                    .entry(user)
+                    .or_insert(now);
+                if let Some(guild_recorder) = self.guilds.get(&guild) {
+                    if let Err(err) = guild_recorder
+                        .send(RecorderAction::AddToWhitelist(user))
+                        .await
+                    {
+                        error!("cannot propagate whitelist addition to guild {guild}: {err}");
+                    }
+                }
+            }
+        }
+        self.legacy_whitelist.clear();
+        self.save_whitelist().await;
+        if let Err(err) = tokio::fs::remove_file(&self.legacy_whitelist_path).await {
+            warn!("cannot remove migrated legacy whitelist: {err}");
+        }
+    }
+
+    /// Guilds with a whitelist.
+    pub fn guilds_with_whitelist(&self) -> Vec<GuildId> {
+        self.whitelist.keys().copied().collect()
+    }
+
+    /// Users of a guild whose consent is older than `expiry`, and users whose
+    /// consent expires within `notice` who weren't reminded yet.
+    pub fn review_consents(
+        &mut self,
+        guild: GuildId,
+        expiry: Duration,
+        notice: Duration,
+    ) -> (Vec<UserId>, Vec<UserId>) {
+        let mut expired = Vec::new();
+        let mut expiring = Vec::new();
+        for (user, consented_at) in self.consented_at.get(&guild).into_iter().flatten() {
+            let age = consented_at.elapsed().unwrap_or_default();
+            if age >= expiry {
+                expired.push(*user);
+            } else if age + notice >= expiry && self.reminded.insert((guild, *user)) {
+                expiring.push(*user);
+            }
+        }
+        (expired, expiring)
+    }
+
+    /// Guilds whose whitelist contains the user.
+    pub fn whitelisted_guilds(&self, user: UserId) -> Vec<GuildId> {
+        self.whitelist
+            .iter()
+            .filter(|(_, whitelist)| whitelist.contains(&user))
+            .map(|(guild, _)| *guild)
+            .collect()
+    }
+
+    /// Remove a user from every whitelist and pending consent, returns the
+    /// number of whitelists the user was removed from.
+    pub async fn forget_user(&mut self, user: UserId) -> usize {
+        for pending in self.pending_consents.values_mut() {
+            pending.remove(&user);
+        }
+        let mut removed = 0;
+        for guild in self.whitelisted_guilds(user) {
+            if self.remove_whitelist(guild, user).await {
+                removed += 1;
+            }
+        }
+        removed
+    }
+
+    /// Forget the whitelist and consents of a guild, or of every guild if
+    /// `None`, overwriting the previous files. Returns the number of users
+    /// removed from a whitelist.
+    pub async fn purge(&mut self, guild: Option<GuildId>) -> usize {
+        let purged = |id: &GuildId| guild.is_none_or(|guild| guild == *id);
+        let removed = self
+            .whitelist
+            .iter()
+            .filter(|(id, _)| purged(id))
+            .map(|(_, whitelist)| whitelist.len())
+            .sum();
+        self.whitelist.retain(|id, _| !purged(id));
+        self.consented_at.retain(|id, _| !purged(id));
+        self.pending_consents.retain(|id, _| !purged(id));
+        self.reminded.retain(|(id, _)| !purged(id));
+        self.role_prompted.retain(|(id, _)| !purged(id));
+        for path in [&self.whitelist_path, &self.consents_path] {
+            if let Err(err) = disk::shred(path).await {
+                warn!("cannot overwrite {}: {err}", path.display());
+            }
+        }
+        self.save_whitelist().await;
+        removed
+    }
+
+    /// Mark a user as waiting to consent to be recorded. Returns false if the
+    /// user is already in the whitelist.
+    pub fn request_consent(&mut self, guild: GuildId, user: UserId) -> bool {
+        if self
+            .whitelist
+            .get(&guild)
+            .is_some_and(|whitelist| whitelist.contains(&user))
+        {
+            return false;
+        }
+        info!("waiting for consent of user {user} in guild {guild}");
+        self.pending_consents.entry(guild).or_default().insert(user);
+        true
+    }
+
+    /// Mark a holder of a whitelisted role as waiting to consent, the first
+    /// time they are seen only. Returns whether they should be asked.
+    pub fn request_role_consent(&mut self, guild: GuildId, user: UserId) -> bool {
+        self.role_prompted.insert((guild, user)) && self.request_consent(guild, user)
+    }
+
+    /// Whether the user consented to be recorded in the guild less than
+    /// `expiry` ago, if consents expire.
+    pub fn has_valid_consent(
+        &self,
+        guild: GuildId,
+        user: UserId,
+        expiry: Option<Duration>,
+    ) -> bool {
+        self.consented_at
+            .get(&guild)
+            .and_then(|consents| consents.get(&user))
+            .is_some_and(|consented_at| {
+                expiry.is_none_or(|expiry| consented_at.elapsed().unwrap_or_default() < expiry)
+            })
+    }
+
+    /// Resolve a pending consent, adding the user to the whitelist if accepted.
+    /// Returns false if there was no pending consent.
+    pub async fn answer_consent(&mut self, guild: GuildId, user: UserId, accepted: bool) -> bool {
+        if !self
+            .pending_consents
+            .get_mut(&guild)
+            .is_some_and(|pending| pending.remove(&user))
+        {
+            return false;
+        }
+        info!("user {user} answered consent in guild {guild}: {accepted}");
+        if accepted {
+            self.add_whitelist(guild, user).await;
+        }
+        true
+    }
+
+    /// Failures are logged, the state being kept in memory and saved again on
+    /// the next change.
+    async fn save_whitelist(&mut self) {
+        self.whitelist.retain(|_, whitelist| !whitelist.is_empty());
+        self.consented_at.retain(|_, consents| !consents.is_empty());
+        let files = [
+            (&self.whitelist_path, serde_json::to_vec(&self.whitelist)),
+            (&self.consents_path, serde_json::to_vec(&self.consented_at)),
+        ];
+        for (path, data) in files {
+            let written = match data {
+                Ok(data) => tokio::fs::write(path, self.encryption.encrypt(data))
+                    .await
+                    .map_err(|err| err.to_string()),
+                Err(err) => Err(err.to_string()),
+            };
+            if let Err(err) = written {
+                error!("cannot save {}: {err}", path.display());
+            }
+        }
+    }
+
+    /// Persist the whitelist before exiting.
+    pub async fn flush(&mut self) {
+        self.save_whitelist().await;
+        info!("recorder state saved");
+    }
+
+    pub fn low_power(&self) -> bool {
+        self.low_power
+    }
+
+    pub fn stats(&self) -> Arc<Stats> {
+        Arc::clone(&self.stats)
+    }
+
+    pub async fn get_guild_recorder(&mut self, guild: GuildId) -> RecorderSender {
         match self.guilds.get(&guild) {
             Some(channel) => channel.clone(),
             None => {
                 let channel = GuildRecorder {
-                    whitelist: self.whitelist.clone(),
+                    settings: self.settings.get(guild).await,
+                    paused: false,
+                    absent_since: None,
+                    joined_at: HashMap::new(),
+                    opted_out: HashSet::new(),
+                    talk_time: HashMap::new(),
+                    #[cfg(feature = "voice-commands")]
+                    utterances: Utterances::new(
+                        voice_command::MIN_UTTERANCE,
+                        voice_command::MAX_UTTERANCE,
+                        false,
+                    ),
+                    #[cfg(feature = "voice-commands")]
+                    voice_commands: None,
+                    #[cfg(feature = "transcription")]
+                    transcript_utterances: Utterances::new(
+                        live_transcript::MIN_UTTERANCE,
+                        live_transcript::MAX_UTTERANCE,
+                        true,
+                    ),
+                    #[cfg(feature = "transcription")]
+                    live_transcript: None,
+                    whitelist: self.whitelist.get(&guild).cloned().unwrap_or_default(),
+                    role_holders: None,
+                    role_whitelisted: HashSet::new(),
                     buffer_size: self.buffer_size,
                     voice_data: HashMap::new(),
+                    markers: VecDeque::new(),
+                    ssrcs: HashMap::new(),
+                    unmapped: HashMap::new(),
                     voice_data_received: 0,
                     clean_timeout: self.clean_timeout,
+                    low_power: self.low_power,
+                    stats: Arc::clone(&self.stats),
                 }
                 .run_loop();
+                let channel = RecorderSender {
+                    tx: channel,
+                    guild,
+                    congested: Arc::new(AtomicBool::new(false)),
+                    metrics: Arc::clone(&self.metrics),
+                };
                 self.guilds.insert(guild, channel.clone());
                 channel
             }
         }
     }
 
-    pub fn cleanup_loop(recorder: Arc<Mutex<Self>>) {
+    /// Drop the buffered voice data of a user in every guild.
+    pub async fn clear_data(&self, user: UserId) {
+        info!("clearing voice data of user {user}");
+        for (guild, guild_recorder) in &self.guilds {
+            if let Err(err) = guild_recorder.send(RecorderAction::ClearData(user)).await {
+                error!("cannot clear voice data of user {user} in guild {guild}: {err}");
+            }
+        }
+    }
+
+    /// Buffered voice data of a user in every guild.
+    pub async fn get_user_data(&self, user: UserId) -> Vec<(GuildId, Vec<i16>)> {
+        let mut data = Vec::new();
+        for (guild, guild_recorder) in &self.guilds {
+            let (tx, rx) = oneshot::channel();
+            let voice_data = match guild_recorder
+                .send(RecorderAction::GetVoiceData(user, tx))
+                .await
+            {
+                Ok(()) => rx.await.map_err(|err| err.to_string()),
+                Err(err) => Err(err.to_string()),
+            };
+            match voice_data {
+                Ok(Some(voice_data)) => data.push((*guild, voice_data.samples().await)),
+                Ok(None) => (),
+                Err(err) => {
+                    error!("cannot fetch voice data of user {user} in guild {guild}: {err}")
+                }
+            }
+        }
+        data
+    }
+
+    /// Propagate settings changes to the guild recorder if it is running.
+    pub async fn apply_settings(&self, guild: GuildId, settings: GuildSettings) {
+        if let Some(guild_recorder) = self.guilds.get(&guild) {
+            if let Err(err) = guild_recorder
+                .send(RecorderAction::ApplySettings(Box::new(settings)))
+                .await
+            {
+                error!("cannot propagate settings to guild {guild}: {err}");
+            }
+        }
+    }
+
+    /// Expire old voice data periodically, then evict the oldest audio of the
+    /// least recently active users while voice buffers use more than
+    /// `max_memory` bytes.
+    pub fn cleanup_loop(recorder: Arc<Mutex<Self>>, max_memory: Option<usize>) {
         tokio::spawn(async move {
+            let metrics = Arc::clone(&recorder.lock().await.metrics);
             loop {
                 sleep(Duration::from_secs(30)).await;
-                for guild_recorder in recorder.lock().await.guilds.values() {
-                    guild_recorder
-                        .send(RecorderAction::CleanOld)
-                        .expect("Failed to send cleanup message");
+                let guilds = recorder.lock().await.guilds.clone();
+                for (guild, guild_recorder) in &guilds {
+                    if let Err(err) = guild_recorder.send(RecorderAction::CleanOld).await {
+                        error!("cannot clean old voice data of guild {guild}: {err}");
+                    }
+                }
+
+                let mut usages = Vec::new();
+                for (guild, guild_recorder) in &guilds {
+                    let (tx, rx) = oneshot::channel();
+                    if guild_recorder
+                        .send(RecorderAction::GetStats(tx))
+                        .await
+                        .is_err()
+                    {
+                        continue;
+                    }
+                    let Ok(stats) = rx.await else {
+                        continue;
+                    };
+                    usages.extend(stats.users.into_iter().filter(|user| user.memory > 0).map(
+                        |user| Usage {
+                            guild: *guild,
+                            user: user.id,
+                            bytes: user.memory,
+                            last_active: user.last_insert,
+                        },
+                    ));
+                }
+                let total = usages.iter().map(|usage| usage.bytes).sum::<usize>();
+                metrics.set_gauge(
+                    "disrecord_voice_memory_bytes",
+                    "Memory used by the voice buffers of every guild.",
+                    "",
+                    total as f64,
+                );
+
+                let Some(max_memory) = max_memory.filter(|max_memory| total > *max_memory) else {
+                    continue;
+                };
+                warn!("voice buffers use {total} bytes, over the {max_memory} bytes budget");
+                for (guild, user, bytes) in memory::plan_eviction(usages, max_memory) {
+                    warn!("evicting {bytes} bytes of the oldest voice data of user {user} in guild {guild}");
+                    metrics.increment(
+                        "disrecord_evicted_voice_bytes_total",
+                        "Voice data dropped to stay within the memory budget.",
+                        "",
+                        bytes as f64,
+                    );
+                    if let Some(guild_recorder) = guilds.get(&guild) {
+                        if let Err(err) = guild_recorder
+                            .send(RecorderAction::Evict(user, bytes))
+                            .await
+                        {
+                            error!("cannot evict voice data of guild {guild}: {err}");
+                        }
+                    }
                 }
             }
         });
@@ -167,148 +647,508 @@ impl Recorder {
 }
 
 pub struct GuildRecorder {
+    settings: GuildSettings,
+    paused: bool,
+    /// Since when no whitelisted user is in the voice channel.
+    absent_since: Option<Instant>,
+    /// When users present in the voice channel joined it, or when the bot
+    /// joined if they were already there.
+    joined_at: HashMap<UserId, Instant>,
+    /// Users who opted out of this session, until they leave the channel.
+    opted_out: HashSet<UserId>,
+    /// Samples buffered for users present in the voice channel since they
+    /// joined it.
+    talk_time: HashMap<UserId, usize>,
+    #[cfg(feature = "voice-commands")]
+    utterances: Utterances,
+    #[cfg(feature = "voice-commands")]
+    voice_commands: Option<UnboundedSender<(UserId, Vec<i16>)>>,
+    #[cfg(feature = "transcription")]
+    transcript_utterances: Utterances,
+    /// Receives utterances to transcribe into a text channel.
+    #[cfg(feature = "transcription")]
+    live_transcript: Option<UnboundedSender<(UserId, Vec<i16>)>>,
     whitelist: HashSet<UserId>,
+    /// Present users holding the role recordings are restricted to, anyone
+    /// whitelisted is recorded if unset.
+    role_holders: Option<HashSet<UserId>>,
+    /// Present users holding a whitelisted role, recorded as if whitelisted.
+    role_whitelisted: HashSet<UserId>,
     buffer_size: Duration,
-    voice_data: HashMap<Ssrc, UserVoiceData>,
+    voice_data: HashMap<UserId, UserVoiceData>,
+    /// Moments bookmarked with `/mark`, dropped along with the voice data.
+    markers: VecDeque<Marker>,
+    /// Users currently sending audio with each SSRC, Discord reallocating them
+    /// when users reconnect.
+    ssrcs: HashMap<Ssrc, UserId>,
+    /// Voice received from SSRCs not mapped to a user yet.
+    unmapped: HashMap<Ssrc, VecDeque<Frame>>,
     voice_data_received: usize,
     clean_timeout: Duration,
+    low_power: bool,
+    stats: Arc<Stats>,
 }
 
 impl GuildRecorder {
-    fn run_loop(mut self) -> UnboundedSender<RecorderAction> {
-        let (tx, mut rx) = mpsc::unbounded_channel::<RecorderAction>();
+    fn buffer_size(&self) -> Duration {
+        self.settings.buffer_duration.unwrap_or(self.buffer_size)
+    }
+
+    fn storage_factor(&self) -> usize {
+        resample::factor(self.settings.storage_rate())
+    }
+
+    fn is_whitelisted(&self, user: &UserId) -> bool {
+        self.whitelist.contains(user) || self.role_whitelisted.contains(user)
+    }
+
+    /// Buffer voice received from an SSRC, for the user it is mapped to.
+    fn register_voice_data(
+        &mut self,
+        ssrc: Ssrc,
+        data: Vec<i16>,
+        sequence: Option<u16>,
+        received: Instant,
+    ) {
+        let _timer = self.stats.timer("recorder");
+        let user = self.ssrcs.get(&ssrc).copied();
+        if user.is_some_and(|user| self.opted_out.contains(&user)) {
+            return;
+        }
+        // Listen even when paused to allow resuming.
+        #[cfg(feature = "voice-commands")]
+        if self.voice_commands.is_some() && self.settings.voice_commands && !self.low_power {
+            if let Some(user) = user.filter(|user| self.is_whitelisted(user)) {
+                let _timer = self.stats.timer("voice-commands");
+                self.utterances.push(user, &data, &self.settings.vad());
+            }
+        }
+
+        if self.paused {
+            return;
+        }
+
+        #[cfg(feature = "transcription")]
+        if self.live_transcript.is_some() {
+            if let Some(user) = user.filter(|user| self.is_whitelisted(user)) {
+                let _timer = self.stats.timer("live-transcript");
+                self.transcript_utterances
+                    .push(user, &data, &self.settings.vad());
+            }
+        }
+
+        log_voice_data!(
+            self,
+            "registering {} bytes voice data for ssrc {ssrc}",
+            data.len() * 2
+        );
+
+        let buffer_size = self.buffer_size();
+        match user.and_then(|user| self.voice_data.get_mut(&user)) {
+            Some(user_data) => {
+                log_voice_data!(
+                    self,
+                    "adding voice data to user {} for ssrc {ssrc}",
+                    user_data.id
+                );
+                if (self.whitelist.contains(&user_data.id)
+                    || self.role_whitelisted.contains(&user_data.id))
+                    && self
+                        .role_holders
+                        .as_ref()
+                        .is_none_or(|holders| holders.contains(&user_data.id))
+                {
+                    user_data.packets += 1;
+                    if sequence.is_none() {
+                        user_data.lost_packets += 1;
+                    }
+                    if let Some(gate) = self
+                        .settings
+                        .noise_gate
+                        .as_ref()
+                        .filter(|_| !self.low_power)
+                    {
+                        let _timer = self.stats.timer("noise-gate");
+                        if !gate.accept(&mut user_data.gate_remaining, &data) {
+                            return;
+                        }
+                    }
+                    *self.talk_time.entry(user_data.id).or_default() += data.len();
+                    for frame in user_data.reorder.push(sequence, data) {
+                        user_data.push_data(frame, buffer_size, received);
+                    }
+                    log_voice_data!(
+                        self,
+                        "added voice data to user {} for ssrc {ssrc}",
+                        user_data.id
+                    );
+                }
+            }
+            None => {
+                log_voice_data!(self, "no user mapping found for ssrc {ssrc}",);
+            }
+        }
+    }
+
+    /// Remove the SSRC mappings of a user, except the one given.
+    fn unmap_user(&mut self, user: UserId, keep: Option<Ssrc>) {
+        self.ssrcs
+            .retain(|ssrc, mapped| *mapped != user || Some(*ssrc) == keep);
+    }
+
+    fn run_loop(mut self) -> mpsc::Sender<RecorderAction> {
+        let (tx, mut rx) = mpsc::channel::<RecorderAction>(ACTION_QUEUE);
         tokio::spawn(async move {
-            loop {
-                let event = rx.recv().await.expect("Event channel closed.");
+            // Replies are ignored as requesters may have given up waiting.
+            while let Some(event) = rx.recv().await {
                 match event {
+                    RecorderAction::ApplySettings(settings) => {
+                        self.settings = *settings;
+                        let factor = self.storage_factor();
+
+                        // Shrink buffers right away if needed.
+                        let max_len = self.buffer_size().as_secs() as usize * FREQUENCY;
+                        for user_data in self.voice_data.values_mut() {
+                            user_data.set_factor(factor);
+                            let len = user_data.len();
+                            if len > max_len {
+                                user_data.drop_front(len - max_len);
+                                if let Some(data) = &mut user_data.data {
+                                    data.shrink_to(max_len / factor);
+                                }
+                            }
+                        }
+                    }
+                    RecorderAction::SetPaused(paused, tx) => {
+                        info!("setting recording paused to {paused}");
+                        let changed = self.paused != paused;
+                        self.paused = paused;
+                        let _ = tx.send(changed);
+                    }
+                    RecorderAction::SetRoleHolders(role_holders) => {
+                        self.role_holders = role_holders;
+                    }
+                    RecorderAction::SetRoleWhitelisted(role_whitelisted) => {
+                        self.role_whitelisted = role_whitelisted;
+                    }
+                    RecorderAction::UpdatePresence(members) => {
+                        self.joined_at.retain(|user, _| members.contains(user));
+                        self.opted_out.retain(|user| members.contains(user));
+                        self.talk_time.retain(|user, _| members.contains(user));
+                        for user in &members {
+                            self.joined_at.entry(*user).or_insert_with(Instant::now);
+                        }
+
+                        let present = members.iter().any(|user| self.is_whitelisted(user));
+                        match (present, self.absent_since) {
+                            (false, None) => {
+                                info!("no whitelisted user present");
+                                self.absent_since = Some(Instant::now());
+                            }
+                            (true, Some(absent_since)) => {
+                                // Make retention clocks ignore the absence.
+                                let absence = absent_since.elapsed();
+                                info!(
+                                    "whitelisted users back after {}",
+                                    humantime::format_duration(absence)
+                                );
+                                if self.settings.pause_retention_when_absent {
+                                    for user_data in self.voice_data.values_mut() {
+                                        user_data.shift(absence);
+                                    }
+                                }
+                                self.absent_since = None;
+                            }
+                            _ => (),
+                        }
+                    }
                     RecorderAction::AddToWhitelist(user) => {
                         self.whitelist.insert(user);
                     }
                     RecorderAction::RemoveFromWhitelist(user) => {
                         self.whitelist.remove(&user);
-                        if let Some(user_data) = self
-                            .voice_data
-                            .values_mut()
-                            .find(|user_data| user_data.id == user)
-                        {
-                            user_data.data = None;
+                        if let Some(user_data) = self.voice_data.get_mut(&user) {
+                            user_data.clear();
+                        }
+                    }
+                    RecorderAction::ClearData(user) => {
+                        if let Some(user_data) = self.voice_data.get_mut(&user) {
+                            user_data.clear();
+                        }
+                    }
+                    RecorderAction::Evict(user, bytes) => {
+                        if let Some(user_data) = self.voice_data.get_mut(&user) {
+                            user_data.evict(bytes);
+                        }
+                    }
+                    RecorderAction::ClearAllData => {
+                        info!("clearing voice data of every user");
+                        for user_data in self.voice_data.values_mut() {
+                            user_data.clear();
+                        }
+                        self.markers.clear();
+                    }
+                    RecorderAction::OptOut(user) => {
+                        info!("user {user} opted out of the session");
+                        self.opted_out.insert(user);
+                        self.talk_time.remove(&user);
+                        if let Some(user_data) = self.voice_data.get_mut(&user) {
+                            match self.joined_at.get(&user) {
+                                Some(joined_at) => user_data.drop_since(*joined_at),
+                                None => user_data.clear(),
+                            }
                         }
                     }
                     RecorderAction::MapUser(id, ssrc) => {
                         info!("mapping ssrc {ssrc} to user {id}");
-                        let user_data = if let Some(previous) = self
-                            .voice_data
-                            .iter()
-                            .find_map(|(ssrc, user)| (user.id == id).then_some(*ssrc))
-                        {
-                            info!("replacing mapping for ssrc {ssrc} and user {id}");
-                            let mut previous = self.voice_data.remove(&previous).unwrap();
-                            previous.last_insert = Instant::now();
-                            previous
-                        } else {
-                            info!("creating new mapping for ssrc {ssrc} and user {id}");
-                            UserVoiceData::new(id)
-                        };
-                        self.voice_data.insert(ssrc, user_data);
-                        info!("mapped ssrc {ssrc} to user {id}");
-                    }
-                    RecorderAction::RegisterVoiceData(ssrc, data) => {
-                        log_voice_data!(
-                            self,
-                            "registering {} bytes voice data for ssrc {ssrc}",
-                            data.len() * 2
-                        );
-
-                        match self.voice_data.get_mut(&ssrc) {
-                            Some(user_data) => {
-                                log_voice_data!(
-                                    self,
-                                    "adding voice data to user {} for ssrc {ssrc}",
-                                    user_data.id
-                                );
-                                if self.whitelist.contains(&user_data.id) {
-                                    user_data.push_data(data, self.buffer_size);
-                                    log_voice_data!(
-                                        self,
-                                        "added voice data to user {} for ssrc {ssrc}",
-                                        user_data.id
-                                    );
-                                }
+                        if let Some(previous) = self.ssrcs.insert(ssrc, id) {
+                            if previous != id {
+                                info!("ssrc {ssrc} reallocated from user {previous} to user {id}");
+                                self.unmap_user(previous, None);
+                            }
+                        }
+                        self.unmap_user(id, Some(ssrc));
+                        let buffer_size = self.buffer_size();
+                        let whitelisted = self.is_whitelisted(&id);
+                        match self.voice_data.get_mut(&id) {
+                            Some(user_data) if user_data.ssrc != ssrc => {
+                                info!("user {id} moved from ssrc {} to {ssrc}", user_data.ssrc);
+                                user_data.end_stream(buffer_size);
+                                user_data.ssrc = ssrc;
+                                user_data.last_insert = Instant::now();
                             }
+                            Some(_) => (),
                             None => {
-                                log_voice_data!(self, "no user mapping found for ssrc {ssrc}",);
+                                info!("creating new mapping for ssrc {ssrc} and user {id}");
+                                let factor = self.storage_factor();
+                                self.voice_data
+                                    .insert(id, UserVoiceData::new(id, ssrc, factor));
+                            }
+                        }
+                        // Speaking events come right before the first packet.
+                        if whitelisted {
+                            if let Some(user_data) = self.voice_data.get_mut(&id) {
+                                user_data.warm_up(buffer_size);
+                            }
+                        }
+                        info!("mapped ssrc {ssrc} to user {id}");
+                        if let Some(pending) = self.unmapped.remove(&ssrc) {
+                            info!(
+                                "registering {} frames received before ssrc {ssrc} was mapped",
+                                pending.len()
+                            );
+                            for (data, sequence, received) in pending {
+                                self.register_voice_data(ssrc, data, sequence, received);
+                            }
+                        }
+                    }
+                    RecorderAction::Reconnected => {
+                        // Mappings and buffers are kept, but sequence numbers
+                        // start over on the new connection.
+                        info!("voice connection renewed, ending streams");
+                        let buffer_size = self.buffer_size();
+                        for user_data in self.voice_data.values_mut() {
+                            user_data.end_stream(buffer_size);
+                        }
+                        self.unmapped.clear();
+                    }
+                    RecorderAction::UnmapUser(id) => {
+                        info!("user {id} disconnected from voice");
+                        self.unmap_user(id, None);
+                        let buffer_size = self.buffer_size();
+                        if let Some(user_data) = self.voice_data.get_mut(&id) {
+                            user_data.end_stream(buffer_size);
+                        }
+                    }
+                    RecorderAction::RegisterVoiceData(ssrc, data, sequence) => {
+                        if !self.ssrcs.contains_key(&ssrc) {
+                            // Voice may arrive before the speaking event mapping
+                            // its SSRC, such as after a voice server migration.
+                            let pending = self.unmapped.entry(ssrc).or_default();
+                            if pending.len() >= MAX_UNMAPPED_FRAMES {
+                                pending.pop_front();
                             }
+                            pending.push_back((data, sequence, Instant::now()));
+                            continue;
                         }
+                        self.register_voice_data(ssrc, data, sequence, Instant::now());
+                    }
+                    RecorderAction::RegisterPlayback(bot, start, pcm) => {
+                        if self.paused {
+                            continue;
+                        }
+                        let buffer_size = self.buffer_size();
+                        let factor = self.storage_factor();
+                        self.voice_data
+                            .entry(bot)
+                            .or_insert_with(|| UserVoiceData::new(bot, 0, factor))
+                            .overlay(start, pcm, buffer_size);
+                    }
+                    RecorderAction::ImportTrack(user, start, pcm, tx) => {
+                        let buffer_size = self.buffer_size();
+                        let factor = self.storage_factor();
+                        let ssrc = self.voice_data.get(&user).map_or(0, |data| data.ssrc);
+                        let mut user_data = UserVoiceData::new(user, ssrc, factor);
+                        let imported = user_data.import(start, pcm, buffer_size);
+                        if imported {
+                            self.voice_data.insert(user, user_data);
+                            info!("imported a track for user {user}");
+                        }
+                        let _ = tx.send(imported);
+                    }
+                    RecorderAction::GetCaptureInfo(user, tx) => {
+                        let info = self.voice_data.get(&user).and_then(|user_data| {
+                            (user_data.len() > 0).then(|| CaptureInfo {
+                                ssrc: user_data.ssrc,
+                                start: user_data
+                                    .segments
+                                    .front()
+                                    .map(|segment| to_system_time(segment.start)),
+                                end: user_data
+                                    .segments
+                                    .back()
+                                    .map(|segment| to_system_time(segment.end())),
+                                packets: user_data.packets,
+                                lost_packets: user_data.lost_packets,
+                            })
+                        });
+                        let _ = tx.send(info);
+                    }
+                    RecorderAction::AddMarker(marker) => {
+                        info!("adding marker {:?}", marker.label);
+                        self.markers.push_back(marker);
+                    }
+                    RecorderAction::GetMarkers(tx) => {
+                        let _ = tx.send(Vec::from(self.markers.clone()));
+                    }
+                    RecorderAction::GetStats(tx) => {
+                        let users = self
+                            .voice_data
+                            .values()
+                            .map(|user_data| UserStats {
+                                id: user_data.id,
+                                buffered: mix::samples_duration(user_data.len()),
+                                oldest: user_data.segments.front().map(|segment| segment.start),
+                                memory: user_data.memory(),
+                                last_insert: user_data.last_insert,
+                                packets: user_data.packets,
+                                lost_packets: user_data.lost_packets,
+                                present: self.joined_at.contains_key(&user_data.id),
+                            })
+                            .collect();
+                        let _ = tx.send(RecorderStats {
+                            users,
+                            ssrcs: self.ssrcs.len(),
+                            queued: rx.len(),
+                            paused: self.paused,
+                        });
+                    }
+                    RecorderAction::GetSegments(user, tx) => {
+                        let segments = self
+                            .voice_data
+                            .get(&user)
+                            .map(|user_data| Vec::from(user_data.segments.clone()))
+                            .unwrap_or_default();
+                        let _ = tx.send(segments);
+                    }
+                    RecorderAction::GetAllSegments(tx) => {
+                        let segments = self
+                            .voice_data
+                            .iter()
+                            .map(|(user, user_data)| (*user, Vec::from(user_data.segments.clone())))
+                            .collect_vec();
+                        let _ = tx.send(segments);
                     }
                     RecorderAction::GetVoiceData(user, tx) => {
                         info!("fetching data for user {user}");
-                        let data =
-                            match self.voice_data.values().find_map(|user_data| {
-                                (user_data.id == user).then_some(&user_data.data)
-                            }) {
-                                Some(Some(data)) if !data.is_empty() => Some(data.clone()),
-                                _ => None,
-                            };
+                        let data = self.voice_data.get(&user).and_then(UserVoiceData::stored);
                         info!(
                             "fetched {} bytes of data for user {user}",
-                            data.as_ref().map(|d| d.len()).unwrap_or(0) * 2
+                            data.as_ref().map(|d| d.data.len()).unwrap_or(0) * 2
                         );
-                        tx.send(data).expect("Voice data send failed.");
+                        let _ = tx.send(data);
                     }
-                    RecorderAction::GetVoiceDataChunks(user, len, min_duration, tx) => {
-                        info!("fetching data for user {user}");
-                        let data = match self
+                    RecorderAction::GetTalkTimes(tx) => {
+                        let talk_times = self
+                            .talk_time
+                            .iter()
+                            .map(|(user, len)| (*user, mix::samples_duration(*len)))
+                            .sorted_by(|(_, d1), (_, d2)| d2.cmp(d1))
+                            .collect_vec();
+                        let _ = tx.send(talk_times);
+                    }
+                    RecorderAction::GetTracks(tx) => {
+                        info!("fetching tracks of every user");
+                        let tracks = self
                             .voice_data
-                            .values()
-                            .find_map(|user_data| (user_data.id == user).then_some(&user_data.data))
-                        {
-                            Some(Some(data)) if !data.is_empty() => {
-                                let mut data = data.clone();
-                                data.make_contiguous().reverse();
-                                let data = Vec::from(data);
-
-                                let mut chunks = data
-                                    .chunks(FREQUENCY / 50)
-                                    .group_by(|c| c.iter().any(|&f| f != 0))
-                                    .into_iter()
-                                    .filter(|&(is_voice, _)| is_voice)
-                                    .filter_map(|(_, frames)| {
-                                        let mut chunk =
-                                            frames.into_iter().flatten().copied().collect_vec();
-                                        if chunk.len()
-                                            < min_duration.as_millis() as usize * FREQUENCY / 1000
-                                        {
-                                            return None;
-                                        }
-                                        chunk.reverse();
-                                        Some(chunk)
-                                    })
-                                    .take(len)
-                                    .collect_vec();
-                                if chunks.is_empty() {
-                                    None
-                                } else {
-                                    chunks.reverse();
-                                    Some(chunks)
+                            .iter()
+                            .filter_map(|(user, user_data)| {
+                                user_data.stored().map(|data| {
+                                    (*user, data, Vec::from(user_data.segments.clone()))
+                                })
+                            })
+                            .collect_vec();
+                        info!("fetched {} tracks", tracks.len());
+                        let _ = tx.send(tracks);
+                    }
+                    #[cfg(feature = "voice-commands")]
+                    RecorderAction::ListenVoiceCommands(tx) => {
+                        info!("voice commands listener registered");
+                        self.voice_commands = Some(tx);
+                    }
+                    #[cfg(feature = "voice-commands")]
+                    RecorderAction::FlushUtterances => {
+                        if let Some(tx) = &self.voice_commands {
+                            for utterance in self.utterances.take_finished() {
+                                if tx.send(utterance).is_err() {
+                                    self.voice_commands = None;
+                                    break;
                                 }
                             }
-                            _ => None,
-                        };
-                        info!(
-                            "fetched {} voice chunks for user {user}",
-                            data.as_ref().map(|d| d.len()).unwrap_or(0)
-                        );
-                        tx.send(data).expect("Voice data chunks send failed.");
+                        }
+                    }
+                    #[cfg(feature = "transcription")]
+                    RecorderAction::TranscribeLive(tx) => {
+                        info!("live transcript listener set: {}", tx.is_some());
+                        self.live_transcript = tx;
+                        self.transcript_utterances.clear();
+                    }
+                    #[cfg(feature = "transcription")]
+                    RecorderAction::FlushTranscript => {
+                        if let Some(tx) = &self.live_transcript {
+                            for utterance in self.transcript_utterances.take_finished() {
+                                if tx.send(utterance).is_err() {
+                                    self.live_transcript = None;
+                                    break;
+                                }
+                            }
+                        }
                     }
                     RecorderAction::CleanOld => {
+                        if self.settings.pause_retention_when_absent && self.absent_since.is_some()
+                        {
+                            debug!("skipping cleaning while no whitelisted user is present");
+                            continue;
+                        }
+
                         debug!("cleaning users voice data that hasn't speak for a while");
+                        let retention = self.settings.retention.unwrap_or(self.clean_timeout);
                         let mut cleaned = 0;
+                        let buffer_size = self.buffer_size();
+                        self.markers
+                            .retain(|marker| marker.at.elapsed() <= buffer_size);
                         for user_data in self.voice_data.values_mut() {
-                            if user_data.last_insert.elapsed() > self.clean_timeout
+                            // Release frames still waiting for a lost packet.
+                            for frame in user_data.reorder.flush() {
+                                user_data.push_data(frame, buffer_size, Instant::now());
+                            }
+                            if user_data.last_insert.elapsed() > retention
                                 && user_data.data.is_some()
                             {
-                                user_data.data = None;
+                                user_data.clear();
                                 cleaned += 1;
+                            } else {
+                                user_data.expire(retention);
                             }
                         }
                         log!(
@@ -327,49 +1167,696 @@ impl GuildRecorder {
     }
 }
 
+/// Sends actions to a guild recorder through a bounded queue in which voice
+/// data is dropped while it is nearly full, and concealed as lost packets,
+/// rather than piling up while the recorder stalls. Other actions wait for
+/// room in the queue, in the order they are sent.
+#[derive(Clone)]
+pub struct RecorderSender {
+    tx: mpsc::Sender<RecorderAction>,
+    guild: GuildId,
+    /// Whether voice data is being dropped.
+    congested: Arc<AtomicBool>,
+    metrics: Arc<Metrics>,
+}
+
+impl RecorderSender {
+    /// Queue voice data, `RegisterVoiceData` or `RegisterPlayback`, without
+    /// waiting. It is dropped if the queue is nearly full.
+    pub fn send_voice(&self, action: RecorderAction) -> Result<(), SendError<RecorderAction>> {
+        if self.tx.capacity() > CONTROL_HEADROOM {
+            match self.tx.try_send(action) {
+                Ok(()) => {
+                    if self.congested.swap(false, Ordering::Relaxed) {
+                        info!("recorder of guild {} caught up", self.guild);
+                    }
+                    return Ok(());
+                }
+                Err(TrySendError::Closed(action)) => return Err(SendError(action)),
+                Err(TrySendError::Full(_)) => (),
+            }
+        }
+
+        if !self.congested.swap(true, Ordering::Relaxed) {
+            warn!(
+                "recorder of guild {} falling behind, dropping voice data",
+                self.guild
+            );
+        }
+        self.metrics.increment(
+            "disrecord_dropped_voice_frames_total",
+            "Voice frames dropped while a recorder queue was full.",
+            "",
+            1.,
+        );
+        Ok(())
+    }
+
+    /// Queue any other action, waiting for room in the queue.
+    pub async fn send(&self, action: RecorderAction) -> Result<(), SendError<RecorderAction>> {
+        self.tx.send(action).await
+    }
+}
+
+/// State of the recorder of a guild.
+#[derive(Debug)]
+pub struct RecorderStats {
+    /// Every user voice data was kept for.
+    pub users: Vec<UserStats>,
+    /// SSRCs currently mapped to a user.
+    pub ssrcs: usize,
+    /// Actions waiting to be handled, mostly voice data to buffer.
+    pub queued: usize,
+    /// Whether recording is paused with `/pause`.
+    pub paused: bool,
+}
+
+/// Voice data kept for a user.
+#[derive(Debug)]
+pub struct UserStats {
+    pub id: UserId,
+    /// Duration of the audio buffered.
+    pub buffered: Duration,
+    /// When the oldest buffered sample was received, if any.
+    pub oldest: Option<Instant>,
+    /// Memory used by the voice buffer, in bytes.
+    pub memory: usize,
+    /// When voice was last buffered.
+    pub last_insert: Instant,
+    /// Packets received and lost by the decoder.
+    pub packets: u64,
+    pub lost_packets: u64,
+    /// Whether the user is in the voice channel.
+    pub present: bool,
+}
+
+/// Audio of a user at the storage rate of its guild, `factor` times fewer
+/// samples than captured.
+#[derive(Debug)]
+pub struct StoredVoice {
+    pub data: Vec<i16>,
+    factor: usize,
+}
+
+impl StoredVoice {
+    pub fn rate(&self) -> usize {
+        FREQUENCY / self.factor
+    }
+
+    /// Number of samples at the capture rate.
+    pub fn captured_len(&self) -> usize {
+        self.data.len() * self.factor
+    }
+
+    /// Audio back at the capture rate, resampled on a blocking thread.
+    pub async fn samples(self) -> Vec<i16> {
+        if self.factor == 1 {
+            return self.data;
+        }
+        tokio::task::spawn_blocking(move || resample::interpolate(&self.data, self.factor))
+            .await
+            .expect("Resampling panicked")
+    }
+
+    /// Tracks of users at the capture rate, from the answer to `GetTracks`.
+    pub async fn tracks(stored: Vec<(UserId, Self, Vec<Segment>)>) -> Vec<(UserId, Track)> {
+        let mut tracks = Vec::with_capacity(stored.len());
+        for (user, data, segments) in stored {
+            let data = data.samples().await;
+            tracks.push((user, Track { data, segments }));
+        }
+        tracks
+    }
+}
+
+/// Details about how the voice data of a user was captured.
+#[derive(Debug)]
+pub struct CaptureInfo {
+    pub ssrc: Ssrc,
+    pub start: Option<SystemTime>,
+    pub end: Option<SystemTime>,
+    pub packets: u64,
+    pub lost_packets: u64,
+}
+
+fn to_system_time(instant: Instant) -> SystemTime {
+    SystemTime::now() - instant.elapsed()
+}
+
 struct UserVoiceData {
     id: UserId,
+    /// Latest SSRC the user sent audio with.
+    ssrc: Ssrc,
+    /// Samples at the storage rate, 48kHz divided by `factor`. Segments and
+    /// every length given to or returned by methods are in 48kHz samples.
     data: Option<VecDeque<i16>>,
+    factor: usize,
+    decimator: Decimator,
+    segments: VecDeque<Segment>,
     last_insert: Instant,
+    gate_remaining: usize,
+    packets: u64,
+    lost_packets: u64,
+    reorder: ReorderBuffer,
 }
 
 impl UserVoiceData {
-    fn new(id: UserId) -> Self {
+    fn new(id: UserId, ssrc: Ssrc, factor: usize) -> Self {
         Self {
             id,
+            ssrc,
             data: None,
+            factor,
+            decimator: Decimator::new(factor),
+            segments: VecDeque::new(),
             last_insert: Instant::now(),
+            gate_remaining: 0,
+            packets: 0,
+            lost_packets: 0,
+            reorder: ReorderBuffer::default(),
         }
     }
 
-    fn push_data(&mut self, new_data: Vec<i16>, buffer_size: Duration) {
-        self.last_insert = Instant::now();
-        let data = self.data.get_or_insert_with(|| {
-            VecDeque::with_capacity(buffer_size.as_secs() as usize * FREQUENCY)
-        });
+    fn clear(&mut self) {
+        self.data = None;
+        self.segments.clear();
+        self.packets = 0;
+        self.lost_packets = 0;
+        self.reorder = ReorderBuffer::default();
+        self.decimator = Decimator::new(self.factor);
+    }
+
+    /// Buffered samples, at 48kHz.
+    fn len(&self) -> usize {
+        self.data.as_ref().map_or(0, VecDeque::len) * self.factor
+    }
+
+    /// Size of the buffered samples, in bytes. Buffers are allocated for the
+    /// whole buffer duration ahead of time, counting their capacity would
+    /// charge users for audio they don't have.
+    fn memory(&self) -> usize {
+        self.data
+            .as_ref()
+            .map_or(0, |data| data.len() * size_of::<i16>())
+    }
+
+    /// Drop about `bytes` of the oldest samples.
+    fn evict(&mut self, bytes: usize) {
+        let Some(data) = &self.data else {
+            return;
+        };
+        let count = bytes.div_ceil(size_of::<i16>());
+        if count >= data.len() {
+            self.clear();
+            return;
+        }
+        self.drop_front(count * self.factor);
+        if let Some(data) = &mut self.data {
+            data.shrink_to_fit();
+        }
+    }
+
+    /// Buffered audio at the storage rate, resampled back to 48kHz by the
+    /// receiver rather than blocking the recorder.
+    fn stored(&self) -> Option<StoredVoice> {
+        let data = Vec::from(self.data.clone().filter(|data| !data.is_empty())?);
+        Some(StoredVoice {
+            data,
+            factor: self.factor,
+        })
+    }
+
+    /// Store audio at another rate, resampling the buffered audio.
+    fn set_factor(&mut self, factor: usize) {
+        if factor == self.factor {
+            return;
+        }
+        // Rare enough to resample in place, unlike exports.
+        let samples = self
+            .stored()
+            .map(|stored| resample::interpolate(&stored.data, stored.factor));
+        self.factor = factor;
+        self.decimator = Decimator::new(factor);
+        let Some(samples) = samples else {
+            return;
+        };
+        let mut data = VecDeque::from(Decimator::new(factor).process(&samples));
+
+        // Keep segments whole numbers of stored samples.
+        for segment in &mut self.segments {
+            segment.len -= segment.len % factor;
+        }
+        self.segments.retain(|segment| segment.len > 0);
+        data.resize(
+            self.segments
+                .iter()
+                .map(|segment| segment.len)
+                .sum::<usize>()
+                / factor,
+            0,
+        );
+        self.data = Some(data);
+    }
+
+    /// Allocate the buffer ahead of the first packet, for it not to be delayed.
+    fn warm_up(&mut self, buffer_size: Duration) {
+        let max_len = buffer_size.as_secs() as usize * FREQUENCY / self.factor;
+        self.data
+            .get_or_insert_with(|| VecDeque::with_capacity(max_len));
+    }
+
+    /// Release the frames of the current RTP stream and start a new one.
+    fn end_stream(&mut self, buffer_size: Duration) {
+        for frame in self.reorder.flush() {
+            self.push_data(frame, buffer_size, Instant::now());
+        }
+        self.reorder = ReorderBuffer::default();
+    }
+
+    fn push_data(&mut self, new_data: Vec<i16>, buffer_size: Duration, received: Instant) {
+        let stored = if self.factor == 1 {
+            new_data
+        } else {
+            self.decimator.process(&new_data)
+        };
+        self.push_stored(stored, buffer_size, received);
+    }
+
+    /// Append samples already at the storage rate, received at `now`.
+    fn push_stored(&mut self, new_data: Vec<i16>, buffer_size: Duration, now: Instant) {
+        self.last_insert = self.last_insert.max(now);
+        let max_len = buffer_size.as_secs() as usize * FREQUENCY / self.factor;
+        let data = self
+            .data
+            .get_or_insert_with(|| VecDeque::with_capacity(max_len));
 
         // Make space without increasing capacity (if needed).
-        if data.capacity() < data.len() + new_data.len() {
-            for _ in 0..new_data.len() - (data.capacity() - data.len()) {
-                data.pop_front();
+        if max_len < data.len() + new_data.len() {
+            let dropped = data.len() + new_data.len() - max_len;
+            self.drop_front(dropped * self.factor);
+        }
+
+        let len = new_data.len() * self.factor;
+        match self.segments.back_mut() {
+            Some(last) if now.saturating_duration_since(last.end()) < MAX_SEGMENT_GAP => {
+                last.len += len;
+            }
+            _ => self.segments.push_back(Segment { start: now, len }),
+        }
+
+        self.data
+            .as_mut()
+            .expect("Voice data not initialized")
+            .extend(new_data);
+    }
+
+    /// Mix audio played since `start` into the buffer, over the end of the
+    /// audio still playing if any.
+    fn overlay(&mut self, start: Instant, pcm: Vec<i16>, buffer_size: Duration) {
+        let mut pcm = if self.factor == 1 {
+            pcm
+        } else {
+            Decimator::new(self.factor).process(&pcm)
+        };
+        if let (Some(data), Some(last)) = (&mut self.data, self.segments.back()) {
+            let overlap = (mix::samples_len(last.end().saturating_duration_since(start))
+                / self.factor)
+                .min(data.len())
+                .min(pcm.len());
+            let tail = data.len() - overlap;
+            for (mixed, sample) in data.range_mut(tail..).zip(pcm.drain(..overlap)) {
+                *mixed = mixed.saturating_add(sample);
+            }
+        }
+        if !pcm.is_empty() {
+            self.push_stored(pcm, buffer_size, Instant::now());
+        }
+    }
+
+    /// Replace the voice data by audio recorded elsewhere since `start`, cut to
+    /// what fits in the buffer and ends before now. Returns whether anything
+    /// was left, the voice data being kept otherwise.
+    fn import(&mut self, start: Instant, pcm: Vec<i16>, buffer_size: Duration) -> bool {
+        let now = Instant::now();
+        let mut pcm = if self.factor == 1 {
+            pcm
+        } else {
+            Decimator::new(self.factor).process(&pcm)
+        };
+        pcm.truncate(mix::samples_len(now.saturating_duration_since(start)) / self.factor);
+        let max_len = buffer_size.as_secs() as usize * FREQUENCY / self.factor;
+        let dropped = pcm.len().saturating_sub(max_len);
+        pcm.drain(..dropped);
+        if pcm.is_empty() {
+            return false;
+        }
+
+        self.last_insert = now;
+        self.segments.clear();
+        self.segments.push_back(Segment {
+            start: start + mix::samples_duration(dropped * self.factor),
+            len: pcm.len() * self.factor,
+        });
+        self.data = Some(VecDeque::from(pcm));
+        true
+    }
+
+    /// Drop the oldest samples, keeping segments in sync.
+    fn drop_front(&mut self, count: usize) {
+        let mut count = count.div_ceil(self.factor) * self.factor;
+        if let Some(data) = &mut self.data {
+            data.drain(..(count / self.factor).min(data.len()));
+        }
+        while count > 0 {
+            let Some(first) = self.segments.front_mut() else {
+                break;
+            };
+            if first.len <= count {
+                count -= first.len;
+                self.segments.pop_front();
+            } else {
+                first.start += mix::samples_duration(count);
+                first.len -= count;
+                count = 0;
+            }
+        }
+    }
+
+    /// Drop the samples received since `since`, keeping segments in sync.
+    fn drop_since(&mut self, since: Instant) {
+        let mut count = 0;
+        while let Some(last) = self.segments.back_mut() {
+            if last.start >= since {
+                count += last.len;
+                self.segments.pop_back();
+            } else {
+                let kept = ((since.duration_since(last.start).as_secs_f64() * FREQUENCY as f64)
+                    as usize)
+                    .min(last.len)
+                    / self.factor
+                    * self.factor;
+                count += last.len - kept;
+                last.len = kept;
+                break;
             }
         }
-        data.extend(new_data);
+        if let Some(data) = &mut self.data {
+            data.truncate(data.len().saturating_sub(count / self.factor));
+        }
+    }
+
+    /// Move all timestamps forward, as if data was received later.
+    fn shift(&mut self, duration: Duration) {
+        self.last_insert += duration;
+        for segment in &mut self.segments {
+            segment.start += duration;
+        }
+    }
+
+    /// Drop samples received more than `retention` ago.
+    fn expire(&mut self, retention: Duration) {
+        let Some(limit) = Instant::now().checked_sub(retention) else {
+            return;
+        };
+        let expired = self
+            .segments
+            .iter()
+            .take_while(|segment| segment.start < limit)
+            .map(|segment| {
+                if segment.end() <= limit {
+                    segment.len
+                } else {
+                    (limit.duration_since(segment.start).as_secs_f64() * FREQUENCY as f64) as usize
+                }
+            })
+            .sum();
+        if expired > 0 {
+            self.drop_front(expired);
+        }
     }
 }
 
 #[derive(Debug)]
 pub enum RecorderAction {
+    ApplySettings(Box<GuildSettings>),
+    /// Stop or restart buffering voice data, replies whether the state changed.
+    SetPaused(bool, OneshotSender<bool>),
+    /// Users currently in the voice channel listened to.
+    UpdatePresence(HashSet<UserId>),
+    /// Users of the voice channel holding the role recordings are restricted
+    /// to, if any.
+    SetRoleHolders(Option<HashSet<UserId>>),
+    /// The voice connection was established again, possibly to another voice
+    /// server.
+    Reconnected,
+    /// Users of the voice channel holding a whitelisted role.
+    SetRoleWhitelisted(HashSet<UserId>),
     AddToWhitelist(UserId),
     RemoveFromWhitelist(UserId),
+    ClearData(UserId),
+    /// Drop about this many bytes of the oldest voice data of a user.
+    Evict(UserId, usize),
+    /// Drop the buffered voice data of every user.
+    ClearAllData,
+    /// Stop buffering a user until they leave the voice channel, dropping what
+    /// was captured since they joined it.
+    OptOut(UserId),
     MapUser(UserId, Ssrc),
-    RegisterVoiceData(Ssrc, Vec<i16>),
-    GetVoiceData(UserId, OneshotSender<Option<VecDeque<i16>>>),
-    GetVoiceDataChunks(
-        UserId,
-        usize,
-        Duration,
-        OneshotSender<Option<Vec<Vec<i16>>>>,
-    ),
+    /// Forget the SSRC of a user who disconnected from voice, keeping their
+    /// voice data.
+    UnmapUser(UserId),
+    /// Voice data of a tick, and the RTP sequence number of its packet, `None`
+    /// if it was lost and concealed by the decoder.
+    RegisterVoiceData(Ssrc, Vec<i16>, Option<u16>),
+    /// Sound played by the bot since the instant given, recorded as its own
+    /// voice data so exports match what participants heard.
+    RegisterPlayback(UserId, Instant, Vec<i16>),
+    /// Replace the voice data of a user by audio recorded elsewhere, starting
+    /// at the instant given. Answers whether any of it ended before now.
+    ImportTrack(UserId, Instant, Vec<i16>, OneshotSender<bool>),
+    GetCaptureInfo(UserId, OneshotSender<Option<CaptureInfo>>),
+    GetVoiceData(UserId, OneshotSender<Option<StoredVoice>>),
+    /// Voice data and speaking segments of every user, resampled into tracks
+    /// with `StoredVoice::tracks`.
+    GetTracks(OneshotSender<Vec<(UserId, StoredVoice, Vec<Segment>)>>),
+    /// Talk time of users since they joined the voice channel, longest first.
+    GetTalkTimes(OneshotSender<Vec<(UserId, Duration)>>),
+    /// Speaking segments of a user, in the order of their voice data.
+    GetSegments(UserId, OneshotSender<Vec<Segment>>),
+    /// Speaking segments of every user.
+    GetAllSegments(OneshotSender<Vec<(UserId, Vec<Segment>)>>),
+    /// Bookmark a moment of the session.
+    AddMarker(Marker),
+    /// Bookmarked moments still covered by the buffer, oldest first.
+    GetMarkers(OneshotSender<Vec<Marker>>),
+    /// Buffers, reception and memory usage of every user.
+    GetStats(OneshotSender<RecorderStats>),
+    #[cfg(feature = "voice-commands")]
+    ListenVoiceCommands(UnboundedSender<(UserId, Vec<i16>)>),
+    #[cfg(feature = "voice-commands")]
+    FlushUtterances,
+    /// Start or stop sending utterances to transcribe into a text channel.
+    #[cfg(feature = "transcription")]
+    TranscribeLive(Option<UnboundedSender<(UserId, Vec<i16>)>>),
+    #[cfg(feature = "transcription")]
+    FlushTranscript,
     CleanOld,
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{atomic::AtomicBool, Arc},
+        time::{Duration, Instant, SystemTime},
+    };
+
+    use serenity::model::id::{GuildId, UserId};
+    use tokio::sync::{mpsc, oneshot};
+
+    use super::{
+        Recorder, RecorderAction, RecorderSender, UserVoiceData, ACTION_QUEUE, CONTROL_HEADROOM,
+    };
+    use crate::{crypto::Encryption, metrics::Metrics, settings::Settings, stats::Stats};
+
+    #[test]
+    fn evicts_buffered_samples_only() {
+        let buffer_size = Duration::from_secs(3600);
+        let mut user_data = UserVoiceData::new(UserId::new(1), 0, 1);
+        // Allocated ahead of the first packet, without being charged for it.
+        user_data.warm_up(buffer_size);
+        assert!(user_data.data.as_ref().unwrap().capacity() >= 3600 * 48_000);
+        assert_eq!(user_data.memory(), 0);
+        user_data.push_stored(vec![1; 480_000], buffer_size, Instant::now());
+        assert_eq!(user_data.memory(), 960_000);
+
+        user_data.evict(160_000);
+        assert_eq!(user_data.len(), 400_000);
+        assert_eq!(user_data.memory(), 800_000);
+    }
+
+    #[tokio::test]
+    async fn drops_voice_data_but_keeps_control_actions_in_order() {
+        let (tx, mut rx) = mpsc::channel(ACTION_QUEUE);
+        let sender = RecorderSender {
+            tx,
+            guild: GuildId::new(1),
+            congested: Arc::new(AtomicBool::new(false)),
+            metrics: Arc::new(Metrics::default()),
+        };
+        for sequence in 0..ACTION_QUEUE as u16 {
+            let voice = RecorderAction::RegisterVoiceData(1, vec![0; 960], Some(sequence));
+            sender.send_voice(voice).unwrap();
+        }
+        // Voice data over the headroom is dropped, control actions still fit.
+        assert_eq!(rx.len(), ACTION_QUEUE - CONTROL_HEADROOM);
+        for user in 1..=CONTROL_HEADROOM as u64 {
+            sender
+                .send(RecorderAction::UnmapUser(UserId::new(user)))
+                .await
+                .unwrap();
+        }
+        assert_eq!(rx.len(), ACTION_QUEUE);
+
+        for _ in 0..ACTION_QUEUE - CONTROL_HEADROOM {
+            assert!(matches!(
+                rx.try_recv(),
+                Ok(RecorderAction::RegisterVoiceData(..))
+            ));
+        }
+        for user in 1..=CONTROL_HEADROOM as u64 {
+            assert!(matches!(
+                rx.try_recv(),
+                Ok(RecorderAction::UnmapUser(unmapped)) if unmapped.get() == user
+            ));
+        }
+    }
+
+    #[test]
+    fn imports_what_fits_before_now() {
+        let buffer_size = Duration::from_secs(2);
+        let mut user_data = UserVoiceData::new(UserId::new(1), 0, 2);
+        user_data.push_stored(vec![1; 1000], buffer_size, Instant::now());
+
+        // Starting later than now, nothing is imported.
+        let later = Instant::now() + Duration::from_secs(10);
+        assert!(!user_data.import(later, vec![2; 48_000], buffer_size));
+        assert_eq!(user_data.len(), 2000);
+
+        // Cut to what ended before now, then to the buffer size.
+        let start = Instant::now() - Duration::from_secs(3);
+        assert!(user_data.import(start, vec![2; 5 * 48_000], buffer_size));
+        assert_eq!(user_data.len(), 2 * 48_000);
+        assert_eq!(user_data.segments.len(), 1);
+        let dropped = user_data.segments[0].start - start;
+        assert!(dropped >= Duration::from_secs(1) && dropped < Duration::from_millis(1100));
+        assert_eq!(user_data.segments[0].len, user_data.len());
+        assert!(user_data.data.unwrap().iter().all(|&sample| sample == 2));
+    }
+
+    #[tokio::test]
+    async fn captures_first_packet() {
+        let dir = std::env::temp_dir().join(format!("disrecord-recorder-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let mut recorder = Recorder::new(
+            Duration::from_secs(60),
+            Duration::from_secs(300),
+            dir.join("record-whitelist"),
+            Arc::new(
+                Settings::new(dir.join("guild-settings"), Arc::new(Encryption::new(None)))
+                    .await
+                    .unwrap(),
+            ),
+            Arc::new(Encryption::new(None)),
+            false,
+            Arc::new(Stats::default()),
+            Arc::new(Metrics::default()),
+        )
+        .await
+        .unwrap();
+        let guild = GuildId::new(1);
+        let (early, late) = (UserId::new(2), UserId::new(3));
+        recorder.add_whitelist(guild, early).await;
+        recorder.add_whitelist(guild, late).await;
+        let guild_recorder = recorder.get_guild_recorder(guild).await;
+        let send = |action| guild_recorder.send(action);
+        let send_voice = |action| guild_recorder.send_voice(action).unwrap();
+
+        // Voice arriving before the speaking event of its SSRC, kept at the
+        // time it was received. Stats are only replied once it is handled.
+        send_voice(RecorderAction::RegisterVoiceData(10, vec![1; 960], Some(0)));
+        let (tx, rx) = oneshot::channel();
+        send(RecorderAction::GetStats(tx)).await.unwrap();
+        rx.await.unwrap();
+        let handled_at = SystemTime::now();
+        send(RecorderAction::MapUser(early, 10)).await.unwrap();
+        send_voice(RecorderAction::RegisterVoiceData(10, vec![2; 960], Some(1)));
+        // Buffer allocated by the speaking event, ahead of the first packet.
+        send(RecorderAction::MapUser(late, 11)).await.unwrap();
+        send_voice(RecorderAction::RegisterVoiceData(11, vec![3; 960], Some(0)));
+
+        for (user, first, len) in [(early, 1, 1920), (late, 3, 960)] {
+            let (tx, rx) = oneshot::channel();
+            send(RecorderAction::GetVoiceData(user, tx)).await.unwrap();
+            let data = rx.await.unwrap().unwrap().data;
+            assert_eq!((data[0], data.len()), (first, len));
+        }
+        let (tx, rx) = oneshot::channel();
+        send(RecorderAction::GetCaptureInfo(early, tx))
+            .await
+            .unwrap();
+        let start = rx.await.unwrap().unwrap().start.unwrap();
+        assert!(start <= handled_at);
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+
+    #[tokio::test]
+    async fn migrates_legacy_whitelist() {
+        let dir = std::env::temp_dir().join(format!("disrecord-legacy-{}", std::process::id()));
+        tokio::fs::create_dir_all(&dir).await.unwrap();
+        let path = dir.join("record-whitelist");
+        let legacy = [2u64, 3]
+            .iter()
+            .flat_map(|id| id.to_be_bytes())
+            .collect::<Vec<_>>();
+        tokio::fs::write(&path, legacy).await.unwrap();
+        let load = || async {
+            Recorder::new(
+                Duration::from_secs(60),
+                Duration::from_secs(300),
+                path.clone(),
+                Arc::new(
+                    Settings::new(dir.join("guild-settings"), Arc::new(Encryption::new(None)))
+                        .await
+                        .unwrap(),
+                ),
+                Arc::new(Encryption::new(None)),
+                false,
+                Arc::new(Stats::default()),
+                Arc::new(Metrics::default()),
+            )
+            .await
+        };
+
+        // Kept aside until the guilds of the bot are known.
+        let mut recorder = load().await.unwrap();
+        recorder
+            .add_whitelist(GuildId::new(1), UserId::new(4))
+            .await;
+        let mut recorder = load().await.unwrap();
+        assert!(recorder.has_legacy_whitelist());
+
+        recorder
+            .migrate_legacy_whitelist(vec![GuildId::new(1), GuildId::new(5)])
+            .await;
+        let recorder = load().await.unwrap();
+        assert!(!recorder.has_legacy_whitelist());
+        let users = |ids: &[u64]| ids.iter().copied().map(UserId::new).collect();
+        assert_eq!(recorder.get_whitelist(GuildId::new(1)), users(&[2, 3, 4]));
+        assert_eq!(recorder.get_whitelist(GuildId::new(5)), users(&[2, 3]));
+
+        // Corrupt JSON isn't taken for legacy user IDs.
+        tokio::fs::write(&path, b"{\"1\": [2").await.unwrap();
+        assert!(load().await.is_err());
+        let _ = tokio::fs::remove_dir_all(&dir).await;
+    }
+}
@@ -0,0 +1,225 @@
+use std::f64::consts::{PI, TAU};
+
+use itertools::Itertools;
+
+use crate::recorder::FREQUENCY;
+
+/// Rates voice data can be stored at, Discord's 48kHz divided by an integer
+/// factor.
+pub const STORAGE_RATES: [usize; 4] = [48_000, 24_000, 16_000, 12_000];
+
+/// Half length of the low-pass filter, in taps per unit of the factor.
+const TAPS_PER_FACTOR: usize = 16;
+/// Cutoff of the low-pass filter relative to the Nyquist frequency of the
+/// lower rate, leaving room for the transition band.
+const CUTOFF: f64 = 0.9;
+
+/// Factor between the capture rate and a storage rate.
+pub fn factor(rate: usize) -> usize {
+    (FREQUENCY / rate.clamp(1, FREQUENCY)).max(1)
+}
+
+/// Downsamples a stream of frames by an integer factor, keeping the samples
+/// the filter needs between frames. Output is delayed by half the filter.
+#[derive(Debug)]
+pub struct Decimator {
+    factor: usize,
+    taps: Vec<f32>,
+    history: Vec<f32>,
+    /// Index in `history` of the next input sample giving an output one.
+    next: usize,
+}
+
+impl Decimator {
+    pub fn new(factor: usize) -> Self {
+        let taps = lowpass(factor);
+        Self {
+            factor,
+            history: vec![0.; taps.len() - 1],
+            next: taps.len() - 1,
+            taps,
+        }
+    }
+
+    pub fn process(&mut self, input: &[i16]) -> Vec<i16> {
+        if self.factor == 1 {
+            return input.to_vec();
+        }
+        let len = self.taps.len();
+        self.history
+            .extend(input.iter().map(|&sample| sample as f32));
+        let mut output = Vec::with_capacity(input.len() / self.factor + 1);
+        while self.next < self.history.len() {
+            let window = &self.history[self.next + 1 - len..=self.next];
+            let sample: f32 = self
+                .taps
+                .iter()
+                .zip(window.iter().rev())
+                .map(|(tap, x)| tap * x)
+                .sum();
+            output.push(to_sample(sample));
+            self.next += self.factor;
+        }
+        let consumed = self.history.len() - (len - 1);
+        self.history.drain(..consumed);
+        self.next -= consumed;
+        output
+    }
+}
+
+/// Downsample interleaved frames of `channels` channels from the capture rate
+/// to `rate`, one of the storage rates.
+pub fn downsample(pcm: &[i16], channels: u16, rate: usize) -> Vec<i16> {
+    let factor = factor(rate);
+    if factor == 1 {
+        return pcm.to_vec();
+    }
+    let channels = channels.max(1) as usize;
+    let decimated = (0..channels)
+        .map(|channel| {
+            let samples = pcm
+                .iter()
+                .skip(channel)
+                .step_by(channels)
+                .copied()
+                .collect_vec();
+            Decimator::new(factor).process(&samples)
+        })
+        .collect_vec();
+    let len = decimated.iter().map(Vec::len).min().unwrap_or(0);
+    (0..len)
+        .flat_map(|i| decimated.iter().map(move |samples| samples[i]))
+        .collect()
+}
+
+/// Upsample by an integer factor, inserting zeros between samples and
+/// filtering out the images.
+pub fn interpolate(input: &[i16], factor: usize) -> Vec<i16> {
+    if factor == 1 {
+        return input.to_vec();
+    }
+    let taps = lowpass(factor);
+    let half = taps.len() / 2;
+    (0..input.len() * factor)
+        .map(|n| {
+            // Only the taps falling on input samples contribute.
+            let mut sample = 0.;
+            for k in ((n + half) % factor..taps.len().min(n + half + 1)).step_by(factor) {
+                if let Some(&x) = input.get((n + half - k) / factor) {
+                    sample += taps[k] * x as f32;
+                }
+            }
+            to_sample(sample * factor as f32)
+        })
+        .collect_vec()
+}
+
+/// Resample between arbitrary rates, such as sounds uploaded at 44.1kHz, with
+/// the windowed sinc filter evaluated at fractional positions.
+pub fn convert(pcm: &[i16], from: usize, to: usize) -> Vec<i16> {
+    if from == to || from == 0 || to == 0 || pcm.is_empty() {
+        return pcm.to_vec();
+    }
+    let step = from as f64 / to as f64;
+    // Relative to the input rate, below the Nyquist frequency of the lower one.
+    let cutoff = CUTOFF / 2. * step.recip().min(1.);
+    let half = TAPS_PER_FACTOR as f64 * step.max(1.);
+    let len = (pcm.len() as u64 * to as u64 / from as u64) as usize;
+    (0..len)
+        .map(|i| {
+            let center = i as f64 * step;
+            let first = (center - half).ceil().max(0.) as usize;
+            let last = ((center + half).floor() as usize).min(pcm.len() - 1);
+            let (mut sample, mut gain) = (0., 0.);
+            for (k, &x) in pcm.iter().enumerate().take(last + 1).skip(first) {
+                let n = k as f64 - center;
+                let sinc = if n == 0. {
+                    2. * cutoff
+                } else {
+                    (TAU * cutoff * n).sin() / (PI * n)
+                };
+                let window = 0.42 + 0.5 * (PI * n / half).cos() + 0.08 * (TAU * n / half).cos();
+                sample += sinc * window * x as f64;
+                gain += sinc * window;
+            }
+            // Unit gain even where the window is cut by the edges.
+            to_sample((sample / gain) as f32)
+        })
+        .collect_vec()
+}
+
+/// Blackman windowed sinc low-pass filter for a factor, with unit gain.
+fn lowpass(factor: usize) -> Vec<f32> {
+    let half = TAPS_PER_FACTOR * factor;
+    let cutoff = CUTOFF / (2 * factor) as f64;
+    let taps = (0..=2 * half)
+        .map(|i| {
+            let n = i as f64 - half as f64;
+            let sinc = if n == 0. {
+                2. * cutoff
+            } else {
+                (TAU * cutoff * n).sin() / (PI * n)
+            };
+            let window = 0.42 - 0.5 * (PI * i as f64 / half as f64).cos()
+                + 0.08 * (TAU * i as f64 / half as f64).cos();
+            sinc * window
+        })
+        .collect_vec();
+    let gain: f64 = taps.iter().sum();
+    taps.into_iter().map(|tap| (tap / gain) as f32).collect()
+}
+
+fn to_sample(sample: f32) -> i16 {
+    sample.round().clamp(i16::MIN as f32, i16::MAX as f32) as i16
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::TAU;
+
+    use super::{convert, factor, interpolate, Decimator, TAPS_PER_FACTOR};
+    use crate::recorder::FREQUENCY;
+
+    #[test]
+    fn round_trip_keeps_voice() {
+        assert_eq!(factor(24_000), 2);
+        assert_eq!(factor(16_000), 3);
+        let input = (0..FREQUENCY / 10)
+            .map(|i| ((i as f32 * 1_000. * TAU / FREQUENCY as f32).sin() * 8_000.) as i16)
+            .collect::<Vec<_>>();
+        for factor in [2, 3, 4] {
+            let mut decimator = Decimator::new(factor);
+            let stored = input
+                .chunks(960)
+                .flat_map(|frame| decimator.process(frame))
+                .collect::<Vec<_>>();
+            assert_eq!(stored.len(), input.len() / factor);
+
+            let output = interpolate(&stored, factor);
+            assert_eq!(output.len(), input.len());
+            let delay = TAPS_PER_FACTOR * factor;
+            let error = (delay * 4..input.len() - delay * 4)
+                .map(|i| (output[i] as i32 - input[i - delay] as i32).abs())
+                .max()
+                .unwrap();
+            assert!(error < 160, "error {error} with factor {factor}");
+        }
+    }
+
+    #[test]
+    fn converts_any_rate() {
+        let sine = |rate: usize, len: usize| {
+            (0..len)
+                .map(|i| ((i as f32 * 1_000. * TAU / rate as f32).sin() * 8_000.) as i16)
+                .collect::<Vec<_>>()
+        };
+        let output = convert(&sine(44_100, 4_410), 44_100, FREQUENCY);
+        assert_eq!(output.len(), 4_800);
+        let expected = sine(FREQUENCY, 4_800);
+        let error = (200..4_600)
+            .map(|i| (output[i] as i32 - expected[i] as i32).abs())
+            .max()
+            .unwrap();
+        assert!(error < 160, "error {error}");
+    }
+}
@@ -0,0 +1,623 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serenity::{
+    async_trait,
+    client::Context,
+    model::id::{ChannelId, GuildId},
+};
+use songbird::{Event, EventContext, EventHandler as VoiceEventHandler, Songbird, TrackEvent};
+use thiserror::Error as ThisError;
+use tokio::{fs, sync::Mutex, time::sleep};
+use ulid::Ulid;
+
+use crate::{
+    crypto::{Encryption, LoadError},
+    disk,
+    error_code::Coded,
+    handler::{play_sound, Handler},
+};
+
+/// How far ahead the next run of an expression is searched.
+const MAX_LOOKAHEAD_DAYS: u64 = 5 * 366;
+
+/// Cron expression with the five standard fields (minute, hour, day of month,
+/// month, day of week), evaluated in UTC.
+///
+/// Fields accept `*`, numbers, ranges, lists and steps, months and days of
+/// week also accept their three letter English names.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cron {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day of month and day of week fields are restricted, runs
+    /// match either of them when both are.
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl Cron {
+    pub fn parse(expression: &str) -> Option<Self> {
+        let fields: Vec<_> = expression.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return None;
+        };
+        const MONTHS: &[&str] = &[
+            "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+        ];
+        const WEEKDAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+        let mut weekdays_mask = parse_field(weekdays, 0, 7, WEEKDAYS, 0)?;
+        // Both 0 and 7 are Sunday.
+        if weekdays_mask & 1 << 7 != 0 {
+            weekdays_mask = (weekdays_mask & !(1 << 7)) | 1;
+        }
+        Some(Self {
+            expression: fields.join(" "),
+            minutes: parse_field(minutes, 0, 59, &[], 0)?,
+            hours: parse_field(hours, 0, 23, &[], 0)?,
+            days: parse_field(days, 1, 31, &[], 1)?,
+            months: parse_field(months, 1, 12, MONTHS, 1)?,
+            weekdays: weekdays_mask,
+            days_restricted: !days.starts_with('*'),
+            weekdays_restricted: !weekdays.starts_with('*'),
+        })
+    }
+
+    fn matches_day(&self, days_since_epoch: u64) -> bool {
+        let (_, month, day) = civil_from_days(days_since_epoch);
+        // 1970-01-01 was a Thursday.
+        let weekday = (days_since_epoch + 4) % 7;
+        if self.months & 1 << month == 0 {
+            return false;
+        }
+        let day_match = self.days & 1 << day != 0;
+        let weekday_match = self.weekdays & 1 << weekday != 0;
+        if self.days_restricted && self.weekdays_restricted {
+            day_match || weekday_match
+        } else {
+            day_match && weekday_match
+        }
+    }
+
+    /// Whether the expression matches the minute starting at the given number
+    /// of seconds since the epoch.
+    pub fn matches(&self, timestamp: u64) -> bool {
+        let minute = timestamp / 60 % 60;
+        let hour = timestamp / 3600 % 24;
+        self.minutes & 1 << minute != 0
+            && self.hours & 1 << hour != 0
+            && self.matches_day(timestamp / 86400)
+    }
+
+    /// Start of the first matching minute after the given time.
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let timestamp = time.duration_since(UNIX_EPOCH).ok()?.as_secs() / 60 * 60 + 60;
+        let first_day = timestamp / 86400;
+        for day in first_day..first_day + MAX_LOOKAHEAD_DAYS {
+            if !self.matches_day(day) {
+                continue;
+            }
+            let start = if day == first_day {
+                timestamp
+            } else {
+                day * 86400
+            };
+            if let Some(minute) = (start..(day + 1) * 86400)
+                .step_by(60)
+                .find(|minute| self.matches(*minute))
+            {
+                return Some(UNIX_EPOCH + Duration::from_secs(minute));
+            }
+        }
+        None
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expression)
+    }
+}
+
+impl TryFrom<String> for Cron {
+    type Error = String;
+
+    fn try_from(expression: String) -> Result<Self, Self::Error> {
+        Self::parse(&expression).ok_or_else(|| format!("invalid cron expression: {expression}"))
+    }
+}
+
+impl From<Cron> for String {
+    fn from(cron: Cron) -> Self {
+        cron.expression
+    }
+}
+
+/// Bit mask of the values of a cron field, `names` being the names of the
+/// values starting at `names_offset`.
+fn parse_field(field: &str, min: u64, max: u64, names: &[&str], names_offset: u64) -> Option<u64> {
+    let value = |value: &str| {
+        let value = match names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(value))
+        {
+            Some(position) => position as u64 + names_offset,
+            None => value.parse().ok()?,
+        };
+        (min..=max).contains(&value).then_some(value)
+    };
+
+    let mut mask = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u64>().ok().filter(|s| *s > 0)?),
+            None => (item, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((start, end)) => (value(start)?, value(end)?),
+                // A step without range starts at the value, as in `5/15`.
+                None if step > 1 => (value(range)?, max),
+                None => (value(range)?, value(range)?),
+            },
+        };
+        if start > end {
+            return None;
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Some(mask)
+}
+
+/// Year, month and day of a number of days since the epoch.
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    // From http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month, day)
+}
+
+/// Action run at the times of a schedule.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Job {
+    /// Join a voice channel if needed and play a sound in it.
+    PlaySound { sound: Ulid, channel: ChannelId },
+    /// Join a voice channel, record it for a while and post the mixdown.
+    Record {
+        channel: ChannelId,
+        duration: Duration,
+        post_channel: ChannelId,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: Ulid,
+    pub cron: Cron,
+    pub job: Job,
+}
+
+/// Per guild schedules, persisted as JSON.
+pub struct Schedules {
+    path: PathBuf,
+    encryption: Arc<Encryption>,
+    guilds: Mutex<HashMap<GuildId, Vec<Schedule>>>,
+}
+
+impl Schedules {
+    pub async fn new(path: PathBuf, encryption: Arc<Encryption>) -> Result<Self, LoadError> {
+        let guilds: HashMap<GuildId, Vec<Schedule>> = encryption.read_json(&path).await?;
+        info!(
+            "loaded {} schedules",
+            guilds.values().map(Vec::len).sum::<usize>()
+        );
+
+        Ok(Self {
+            path,
+            encryption,
+            guilds: Mutex::new(guilds),
+        })
+    }
+
+    pub async fn list(&self, guild: GuildId) -> Vec<Schedule> {
+        self.guilds
+            .lock()
+            .await
+            .get(&guild)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub async fn add(
+        &self,
+        guild: GuildId,
+        cron: Cron,
+        job: Job,
+    ) -> Result<Schedule, ScheduleError> {
+        if cron.next_after(SystemTime::now()).is_none() {
+            return Err(ScheduleError::NeverRuns);
+        }
+        let mut guilds = self.guilds.lock().await;
+        let schedule = Schedule {
+            id: Ulid::new(),
+            cron,
+            job,
+        };
+        guilds.entry(guild).or_default().push(schedule.clone());
+        self.save(&guilds).await?;
+        Ok(schedule)
+    }
+
+    pub async fn remove(&self, guild: GuildId, id: Ulid) -> Result<(), ScheduleError> {
+        let mut guilds = self.guilds.lock().await;
+        let schedules = guilds.entry(guild).or_default();
+        let len = schedules.len();
+        schedules.retain(|schedule| schedule.id != id);
+        if schedules.len() == len {
+            return Err(ScheduleError::NotFound);
+        }
+        self.save(&guilds).await
+    }
+
+    /// Delete the schedules of a guild, or of every guild if `None`,
+    /// overwriting the previous file. Returns the number of deleted schedules.
+    pub async fn purge(&self, guild: Option<GuildId>) -> Result<usize, ScheduleError> {
+        let mut guilds = self.guilds.lock().await;
+        let mut purged = 0;
+        guilds.retain(|id, schedules| {
+            let in_scope = guild.is_none_or(|guild| guild == *id);
+            if in_scope {
+                purged += schedules.len();
+            }
+            !in_scope
+        });
+        disk::shred(&self.path)
+            .await
+            .map_err(|_| ScheduleError::SaveFailed)?;
+        self.save(&guilds).await?;
+        Ok(purged)
+    }
+
+    /// Delete the schedules that won't run anymore after `time`. Returns the
+    /// number of deleted schedules.
+    async fn prune(&self, time: SystemTime) -> Result<usize, ScheduleError> {
+        let mut guilds = self.guilds.lock().await;
+        let mut pruned = 0;
+        for schedules in guilds.values_mut() {
+            let len = schedules.len();
+            schedules.retain(|schedule| schedule.cron.next_after(time).is_some());
+            pruned += len - schedules.len();
+        }
+        guilds.retain(|_, schedules| !schedules.is_empty());
+        if pruned > 0 {
+            info!("pruned {pruned} schedules that won't run anymore");
+            self.save(&guilds).await?;
+        }
+        Ok(pruned)
+    }
+
+    /// Jobs to run at the minute starting at the given timestamp.
+    async fn due(&self, timestamp: u64) -> Vec<(GuildId, Job)> {
+        self.guilds
+            .lock()
+            .await
+            .iter()
+            .flat_map(|(guild, schedules)| {
+                schedules
+                    .iter()
+                    .filter(|schedule| schedule.cron.matches(timestamp))
+                    .map(|schedule| (*guild, schedule.job.clone()))
+            })
+            .collect()
+    }
+
+    async fn save(&self, guilds: &HashMap<GuildId, Vec<Schedule>>) -> Result<(), ScheduleError> {
+        let data = serde_json::to_vec(guilds).map_err(|_| ScheduleError::SaveFailed)?;
+        fs::write(&self.path, self.encryption.encrypt(data))
+            .await
+            .map_err(|_| ScheduleError::SaveFailed)
+    }
+
+    /// Run the jobs of every guild at the start of the minutes they are
+    /// scheduled at.
+    pub fn run_loop(self: Arc<Self>, ctx: Context, handler: Handler) {
+        tokio::spawn(async move {
+            let mut last_run = None;
+            loop {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .expect("System time before the epoch");
+                let minute = now.as_secs() / 60 * 60;
+                if last_run != Some(minute) {
+                    last_run = Some(minute);
+                    if let Err(err) = self.prune(UNIX_EPOCH + now).await {
+                        warn!("cannot prune schedules: {err}");
+                    }
+                    for (guild, job) in self.due(minute).await {
+                        // Served by the shards of another process.
+                        if ctx.cache.guild(guild).is_none() {
+                            continue;
+                        }
+                        let ctx = ctx.clone();
+                        let handler = handler.clone();
+                        tokio::spawn(async move { run_job(&ctx, &handler, guild, job).await });
+                    }
+                }
+                sleep(Duration::from_secs(minute + 60) - now).await;
+            }
+        });
+    }
+}
+
+async fn run_job(ctx: &Context, handler: &Handler, guild: GuildId, job: Job) {
+    if handler.settings.get(guild).await.disabled {
+        info!("skipping scheduled job in guild {guild}, the bot is disabled there");
+        return;
+    }
+    match job {
+        Job::PlaySound { sound, channel } => {
+            let manager = songbird::get(ctx)
+                .await
+                .expect("Failed to get songbird manager");
+            let call = manager.get_or_insert(guild);
+            let current_channel = call.lock().await.current_channel();
+            let joined = match current_channel {
+                Some(current) if current.0.get() == channel.get() => false,
+                Some(_) => {
+                    // Don't leave a channel that may be recorded.
+                    info!("skipping scheduled sound in guild {guild}, already in another channel");
+                    return;
+                }
+                None => {
+                    let join = call.lock().await.join(channel).await;
+                    if let Err(err) = match join {
+                        Ok(handle) => handle.await,
+                        Err(err) => Err(err),
+                    } {
+                        warn!("cannot join channel {channel} for a scheduled sound: {err}");
+                        let _ = manager.remove(guild).await;
+                        return;
+                    }
+                    true
+                }
+            };
+            info!("playing scheduled sound {sound} in guild {guild}");
+            let guild_recorder = handler
+                .recorder
+                .lock()
+                .await
+                .get_guild_recorder(guild)
+                .await;
+            match play_sound(
+                Arc::clone(&manager),
+                &handler.soundboard,
+                &guild_recorder,
+                guild,
+                sound,
+            )
+            .await
+            {
+                Some(track) if joined => {
+                    if let Err(err) = track.add_event(
+                        Event::Track(TrackEvent::End),
+                        LeaveAfterSound { manager, guild },
+                    ) {
+                        warn!("cannot leave after the scheduled sound: {err}");
+                    }
+                }
+                Some(_) => (),
+                None => {
+                    warn!("cannot play scheduled sound {sound} in guild {guild}");
+                    if joined {
+                        if let Err(err) = manager.remove(guild).await {
+                            warn!("cannot leave voice after the scheduled sound: {err}");
+                        }
+                    }
+                }
+            }
+        }
+        Job::Record {
+            channel,
+            duration,
+            post_channel,
+        } => {
+            info!("starting scheduled recording of channel {channel} in guild {guild}");
+            handler
+                .record_session(ctx, guild, channel, duration, post_channel)
+                .await;
+        }
+    }
+}
+
+/// Leaves a voice channel joined only to play a scheduled sound.
+struct LeaveAfterSound {
+    manager: Arc<Songbird>,
+    guild: GuildId,
+}
+
+#[async_trait]
+impl VoiceEventHandler for LeaveAfterSound {
+    async fn act(&self, _ctx: &EventContext<'_>) -> Option<Event> {
+        if let Err(err) = self.manager.remove(self.guild).await {
+            warn!("cannot leave voice after the scheduled sound: {err}");
+        }
+        Some(Event::Cancel)
+    }
+}
+
+#[derive(ThisError, Debug)]
+pub enum ScheduleError {
+    #[error("Failed to save schedules.")]
+    SaveFailed,
+    #[error("Schedule not found.")]
+    NotFound,
+    #[error("This schedule would never run.")]
+    NeverRuns,
+}
+
+impl Coded for ScheduleError {
+    fn code(&self) -> u16 {
+        match self {
+            Self::SaveFailed => 403,
+            Self::NotFound => 404,
+            Self::NeverRuns => 405,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Arc,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    };
+
+    use serenity::model::id::{ChannelId, GuildId};
+    use ulid::Ulid;
+
+    use super::{Cron, Job, Schedule, Schedules};
+    use crate::crypto::{Encryption, EncryptionKey};
+
+    #[test]
+    fn cron_expressions() {
+        // Weekdays at 9:30, 2024-01-01 was a Monday.
+        let standup = Cron::parse("30 9 * * mon-fri").unwrap();
+        let monday = 1704067200;
+        assert!(standup.matches(monday + 9 * 3600 + 30 * 60));
+        assert!(!standup.matches(monday + 9 * 3600 + 31 * 60));
+        assert!(!standup.matches(monday + 5 * 86400 + 9 * 3600 + 30 * 60));
+        assert_eq!(
+            standup.next_after(UNIX_EPOCH + Duration::from_secs(monday + 10 * 3600)),
+            Some(UNIX_EPOCH + Duration::from_secs(monday + 86400 + 9 * 3600 + 30 * 60))
+        );
+
+        // Birthdays on February 29th.
+        let leap = Cron::parse("0 0 29 feb *").unwrap();
+        assert_eq!(
+            leap.next_after(UNIX_EPOCH + Duration::from_secs(monday)),
+            Some(UNIX_EPOCH + Duration::from_secs(monday + 59 * 86400))
+        );
+
+        assert!(Cron::parse("*/15 * * * 7").is_some());
+        assert!(Cron::parse("60 * * * *").is_none());
+        assert!(Cron::parse("* * * *").is_none());
+        assert!(Cron::parse("5-1 * * * *").is_none());
+    }
+
+    #[tokio::test]
+    async fn prunes_schedules_that_never_run() {
+        let path =
+            std::env::temp_dir().join(format!("disrecord-schedules-prune-{}", std::process::id()));
+        let encryption = Arc::new(Encryption::default());
+        let schedules = Schedules::new(path.clone(), Arc::clone(&encryption))
+            .await
+            .unwrap();
+        let job = Job::PlaySound {
+            sound: Ulid::new(),
+            channel: ChannelId::new(1),
+        };
+        let guild = GuildId::new(1);
+        let never = Cron::parse("0 0 30 feb *").unwrap();
+        assert!(schedules
+            .add(guild, never.clone(), job.clone())
+            .await
+            .is_err());
+        schedules
+            .add(guild, Cron::parse("0 * * * *").unwrap(), job.clone())
+            .await
+            .unwrap();
+
+        // Loaded from an older file.
+        schedules
+            .guilds
+            .lock()
+            .await
+            .get_mut(&guild)
+            .unwrap()
+            .push(Schedule {
+                id: Ulid::new(),
+                cron: never,
+                job,
+            });
+        assert_eq!(schedules.prune(SystemTime::now()).await.unwrap(), 1);
+        assert_eq!(
+            Schedules::new(path.clone(), Arc::clone(&encryption))
+                .await
+                .unwrap()
+                .list(guild)
+                .await
+                .len(),
+            1
+        );
+        let _ = tokio::fs::remove_file(path).await;
+    }
+
+    #[tokio::test]
+    async fn purges_one_guild_or_all() {
+        let path = std::env::temp_dir().join(format!("disrecord-schedules-{}", std::process::id()));
+        let key = EncryptionKey::parse(&"2a".repeat(32)).unwrap();
+        let encryption = Arc::new(Encryption::new(Some(key)));
+        let schedules = Schedules::new(path.clone(), Arc::clone(&encryption))
+            .await
+            .unwrap();
+        let job = Job::PlaySound {
+            sound: Ulid::new(),
+            channel: ChannelId::new(1),
+        };
+        for guild in [1, 1, 2] {
+            schedules
+                .add(
+                    GuildId::new(guild),
+                    Cron::parse("0 * * * *").unwrap(),
+                    job.clone(),
+                )
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(schedules.purge(Some(GuildId::new(1))).await.unwrap(), 2);
+        let reloaded = Schedules::new(path.clone(), Arc::clone(&encryption))
+            .await
+            .unwrap();
+        assert!(reloaded.list(GuildId::new(1)).await.is_empty());
+        assert_eq!(reloaded.list(GuildId::new(2)).await.len(), 1);
+        // Encrypted, so unreadable without the key rather than overwritten.
+        assert!(
+            Schedules::new(path.clone(), Arc::new(Encryption::default()))
+                .await
+                .is_err()
+        );
+        assert_eq!(reloaded.purge(None).await.unwrap(), 1);
+        assert!(Schedules::new(path.clone(), encryption)
+            .await
+            .unwrap()
+            .list(GuildId::new(2))
+            .await
+            .is_empty());
+        let _ = tokio::fs::remove_file(path).await;
+    }
+}
@@ -0,0 +1,170 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use log::info;
+use serde::{Deserialize, Serialize};
+use serenity::model::id::{ChannelId, GuildId, RoleId};
+use thiserror::Error as ThisError;
+use tokio::{fs, sync::Mutex};
+use ulid::Ulid;
+
+use crate::{
+    crypto::{Encryption, LoadError},
+    dsp::NoiseGate,
+    error_code::Coded,
+    mix,
+    recorder::FREQUENCY,
+    vad::Vad,
+};
+
+pub const KILL_SWITCH_EMOJI: &str = "🛑";
+
+/// Per guild settings, persisted as JSON to keep compatibility when adding new
+/// fields.
+pub struct Settings {
+    path: PathBuf,
+    encryption: Arc<Encryption>,
+    guilds: Mutex<HashMap<GuildId, GuildSettings>>,
+}
+
+impl Settings {
+    pub async fn new(path: PathBuf, encryption: Arc<Encryption>) -> Result<Self, LoadError> {
+        let guilds: HashMap<GuildId, GuildSettings> = encryption.read_json(&path).await?;
+        info!("loaded settings of {} guilds", guilds.len());
+
+        Ok(Self {
+            path,
+            encryption,
+            guilds: Mutex::new(guilds),
+        })
+    }
+
+    pub async fn get(&self, guild: GuildId) -> GuildSettings {
+        self.guilds
+            .lock()
+            .await
+            .get(&guild)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Modify the settings of a guild and persist all settings to disk.
+    pub async fn update<R, F: FnOnce(&mut GuildSettings) -> R>(
+        &self,
+        guild: GuildId,
+        f: F,
+    ) -> Result<R, SettingsError> {
+        let mut guilds = self.guilds.lock().await;
+        let res = f(guilds.entry(guild).or_default());
+        let data = serde_json::to_vec(&*guilds).map_err(|_| SettingsError::SaveFailed)?;
+        fs::write(&self.path, self.encryption.encrypt(data))
+            .await
+            .map_err(|_| SettingsError::SaveFailed)?;
+        Ok(res)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+#[serde(default)]
+pub struct GuildSettings {
+    /// Kill switch stopping every recording and soundboard activity.
+    pub disabled: bool,
+    /// Voice isn't received at all, only the soundboard is used.
+    pub soundboard_only: bool,
+    pub vad_threshold: Option<f32>,
+    pub trim_silence: bool,
+    pub noise_gate: Option<NoiseGate>,
+    pub denoise: bool,
+    /// Length of the rolling buffer, the host default if unset.
+    pub buffer_duration: Option<Duration>,
+    /// How long recorded audio is kept, the host default if unset.
+    pub retention: Option<Duration>,
+    /// Stop expiring recorded audio while no whitelisted user is present.
+    pub pause_retention_when_absent: bool,
+    /// Post a message when the bot starts recording a voice channel.
+    pub announce_recording: bool,
+    /// Where recording announcements go, the voice channel's text chat if
+    /// unset.
+    pub announcement_channel: Option<ChannelId>,
+    /// Reaction on the recording announcement stopping the recording of the
+    /// reacting user, `KILL_SWITCH_EMOJI` if unset.
+    pub kill_switch_emoji: Option<String>,
+    /// Notice included with every export, `{date}` being replaced by the
+    /// export date.
+    pub legal_notice: Option<String>,
+    /// Prefix added to the bot's nickname while it records, such as 🔴.
+    pub recording_nickname: Option<String>,
+    /// Sounds played when someone joins or leaves the voice channel the bot
+    /// is in.
+    pub join_chime: Option<Ulid>,
+    pub leave_chime: Option<Ulid>,
+    /// Sounds played by the `/p1` to `/p9` quick-play commands, by slot.
+    pub sound_slots: HashMap<u8, Ulid>,
+    /// Whitelisted users must consent again after this long, never if unset.
+    pub whitelist_expiry: Option<Duration>,
+    /// Only whitelisted users holding this role are recorded, if set.
+    pub recorded_role: Option<RoleId>,
+    /// Members holding any of these roles are recorded as if whitelisted.
+    pub whitelisted_roles: Vec<RoleId>,
+    /// Voice channels joined as soon as a whitelisted user enters them.
+    pub auto_join_channels: Vec<ChannelId>,
+    /// Voice channels the bot refuses to join, such as the AFK one.
+    pub blocked_channels: Vec<ChannelId>,
+    /// Joining another channel while listening moves the bot there, ending
+    /// the current session, instead of being refused.
+    pub join_switches_channel: bool,
+    /// Spread of speakers across the stereo field of mixdowns, 0 for mono
+    /// mixdowns, the default width if unset.
+    pub stereo_width: Option<f64>,
+    /// Post the recording every this long while connected, never if unset.
+    pub session_split: Option<Duration>,
+    /// Rate voice is buffered at, lower rates use less memory, 48kHz if unset.
+    pub storage_rate: Option<usize>,
+    #[cfg(feature = "voice-commands")]
+    pub voice_commands: bool,
+}
+
+impl GuildSettings {
+    pub fn vad(&self) -> Vad {
+        self.vad_threshold.map(Vad::new).unwrap_or_default()
+    }
+
+    pub fn stereo_width(&self) -> f64 {
+        self.stereo_width.unwrap_or(mix::DEFAULT_STEREO_WIDTH)
+    }
+
+    pub fn storage_rate(&self) -> usize {
+        self.storage_rate.unwrap_or(FREQUENCY)
+    }
+
+    pub fn kill_switch_emoji(&self) -> &str {
+        self.kill_switch_emoji
+            .as_deref()
+            .unwrap_or(KILL_SWITCH_EMOJI)
+    }
+
+    /// Legal notice of an export made now.
+    pub fn render_legal_notice(&self) -> Option<String> {
+        let template = self.legal_notice.as_ref()?;
+        let now = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+        Some(template.replace("{date}", &now[..10]))
+    }
+}
+
+#[derive(ThisError, Debug)]
+pub enum SettingsError {
+    #[error("Failed to save settings.")]
+    SaveFailed,
+}
+
+impl Coded for SettingsError {
+    fn code(&self) -> u16 {
+        match self {
+            Self::SaveFailed => 401,
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use axum::body::Bytes;
+use hmac::{Hmac, Mac};
+use log::info;
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use reqwest::Url;
+use serenity::model::id::{GuildId, UserId};
+use sha2::Sha256;
+use thiserror::Error as ThisError;
+use tokio::{sync::Mutex, time::sleep};
+
+use crate::{crypto, error_code::Coded};
+
+const ID_LENGTH: usize = 32;
+
+/// Clips and recordings too large to be attached served over HTTP behind
+/// unguessable tokens, until they expire or the voice they hold is deleted.
+/// Tokens are signed along with their expiration, so that they cannot be
+/// forged nor extended.
+pub struct Shares {
+    public_url: Url,
+    /// Key signing tokens, links not outliving the bot anyway.
+    secret: [u8; 32],
+    expiration: Duration,
+    /// Total size of the files kept in memory.
+    max_size: usize,
+    clips: Mutex<HashMap<String, SharedClip>>,
+}
+
+/// Shared file, cheap to clone as its data is reference counted.
+#[derive(Clone)]
+pub struct SharedClip {
+    pub filename: String,
+    pub data: Bytes,
+    guild: GuildId,
+    /// Users whose voice is in the file.
+    users: Vec<UserId>,
+    expires_at: Instant,
+}
+
+impl Shares {
+    pub fn new(public_url: Option<Url>, expiration: Duration, max_size: usize) -> Option<Self> {
+        let mut public_url = public_url?;
+        // Avoid replacing the last path segment when joining tokens.
+        if !public_url.path().ends_with('/') {
+            public_url.set_path(&format!("{}/", public_url.path()));
+        }
+
+        Some(Self {
+            public_url,
+            secret: thread_rng().gen(),
+            expiration,
+            max_size,
+            clips: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn expiration(&self) -> Duration {
+        self.expiration
+    }
+
+    /// Drop expired clips periodically, rather than only when new ones are
+    /// shared.
+    pub fn prune_loop(self: Arc<Self>) {
+        tokio::spawn(async move {
+            loop {
+                sleep(Duration::from_secs(60)).await;
+                let now = Instant::now();
+                self.clips
+                    .lock()
+                    .await
+                    .retain(|_, clip| clip.expires_at > now);
+            }
+        });
+    }
+
+    /// Store a clip holding the voice of `users` and return the link it is
+    /// served at.
+    pub async fn create(
+        &self,
+        guild: GuildId,
+        users: Vec<UserId>,
+        filename: String,
+        data: Vec<u8>,
+    ) -> Result<Url, ShareError> {
+        let mut clips = self.clips.lock().await;
+        let now = Instant::now();
+        clips.retain(|_, clip| clip.expires_at > now);
+        let used = clips.values().map(|clip| clip.data.len()).sum::<usize>();
+        if used + data.len() > self.max_size {
+            return Err(ShareError::Full);
+        }
+
+        let id = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(ID_LENGTH)
+            .map(char::from)
+            .collect::<String>();
+        let expires = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            + self.expiration;
+        let payload = format!("{id}.{}", expires.as_secs());
+        let token = format!("{payload}.{}", self.sign(&payload));
+        clips.insert(
+            id,
+            SharedClip {
+                filename,
+                data: data.into(),
+                guild,
+                users,
+                expires_at: now + self.expiration,
+            },
+        );
+        info!("shared clip created, {} clips shared", clips.len());
+
+        Ok(self
+            .public_url
+            .join(&format!("share/{token}"))
+            .expect("Invalid share link"))
+    }
+
+    /// Clip of a token, if its signature is valid and it hasn't expired.
+    pub async fn get(&self, token: &str) -> Option<SharedClip> {
+        let (payload, signature) = token.rsplit_once('.')?;
+        if !crypto::digests_match(&self.sign(payload), signature) {
+            return None;
+        }
+        let (id, expires) = payload.split_once('.')?;
+        let expires = UNIX_EPOCH + Duration::from_secs(expires.parse().ok()?);
+        if expires <= SystemTime::now() {
+            return None;
+        }
+
+        self.clips
+            .lock()
+            .await
+            .get(id)
+            .filter(|clip| clip.expires_at > Instant::now())
+            .cloned()
+    }
+
+    /// Hexadecimal HMAC-SHA256 of a token payload.
+    fn sign(&self, payload: &str) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any size");
+        mac.update(payload.as_bytes());
+        mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect()
+    }
+
+    /// Revoke the links to clips of a guild, or of every guild if `None`,
+    /// holding the voice of a user, or of anyone if `None`. Returns the number
+    /// of links revoked.
+    pub async fn revoke(&self, guild: Option<GuildId>, user: Option<UserId>) -> usize {
+        let mut clips = self.clips.lock().await;
+        let before = clips.len();
+        clips.retain(|_, clip| {
+            guild.is_some_and(|guild| guild != clip.guild)
+                || user.is_some_and(|user| !clip.users.contains(&user))
+        });
+        let revoked = before - clips.len();
+        if revoked > 0 {
+            info!("{revoked} shared clips revoked");
+        }
+        revoked
+    }
+}
+
+#[derive(ThisError, Debug)]
+pub enum ShareError {
+    #[error("Too many files are shared at the moment.")]
+    Full,
+}
+
+impl Coded for ShareError {
+    fn code(&self) -> u16 {
+        match self {
+            Self::Full => 801,
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::Full => Some("Try again once earlier links expire."),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn revokes_clips_of_user_or_guild() {
+        let url = Url::parse("https://example.com").ok();
+        let shares = Shares::new(url, Duration::from_secs(60), 10).unwrap();
+        let (first, second) = (GuildId::new(1), GuildId::new(2));
+        let share = |guild, users: &[u64]| {
+            let users = users.iter().copied().map(UserId::new).collect();
+            shares.create(guild, users, "clip.wav".to_owned(), vec![0; 2])
+        };
+        share(first, &[3]).await.unwrap();
+        share(first, &[3, 4]).await.unwrap();
+        share(first, &[4]).await.unwrap();
+        share(second, &[3]).await.unwrap();
+        share(second, &[4]).await.unwrap();
+        // Over the size limit.
+        assert!(share(second, &[5]).await.is_err());
+
+        assert_eq!(shares.revoke(Some(first), Some(UserId::new(3))).await, 2);
+        assert_eq!(shares.revoke(None, Some(UserId::new(3))).await, 1);
+        assert_eq!(shares.revoke(Some(second), None).await, 1);
+        assert_eq!(shares.revoke(None, None).await, 1);
+    }
+
+    #[tokio::test]
+    async fn refuses_forged_tokens() {
+        let url = Url::parse("https://example.com").ok();
+        let shares = Shares::new(url, Duration::from_secs(60), 10).unwrap();
+        let link = shares
+            .create(
+                GuildId::new(1),
+                Vec::new(),
+                "clip.wav".to_owned(),
+                vec![0; 2],
+            )
+            .await
+            .unwrap();
+        let token = link.path_segments().unwrap().next_back().unwrap();
+        assert!(shares.get(token).await.is_some());
+
+        let (id, rest) = token.split_once('.').unwrap();
+        let (expires, signature) = rest.split_once('.').unwrap();
+        let extended = format!(
+            "{id}.{}.{signature}",
+            expires.parse::<u64>().unwrap() + 3600
+        );
+        assert!(shares.get(&extended).await.is_none());
+        assert!(shares.get(&format!("{id}.{expires}.")).await.is_none());
+        assert!(shares.get(id).await.is_none());
+    }
+}
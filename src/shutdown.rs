@@ -0,0 +1,61 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+#[cfg(unix)]
+use tokio::signal::unix::{self, SignalKind};
+use tokio::{
+    signal,
+    sync::{RwLock, RwLockReadGuard},
+    time::timeout,
+};
+
+/// Resolves on the first SIGINT or SIGTERM.
+#[cfg(unix)]
+pub async fn signal() {
+    let mut terminate =
+        unix::signal(SignalKind::terminate()).expect("Cannot listen to termination signals");
+    tokio::select! {
+        _ = signal::ctrl_c() => (),
+        _ = terminate.recv() => (),
+    }
+}
+
+/// Resolves on the first Ctrl-C.
+#[cfg(not(unix))]
+pub async fn signal() {
+    signal::ctrl_c()
+        .await
+        .expect("Cannot listen to termination signals");
+}
+
+/// Interactions being handled, which the bot waits for before exiting.
+#[derive(Default)]
+pub struct InFlight {
+    closed: AtomicBool,
+    lock: RwLock<()>,
+}
+
+impl InFlight {
+    /// Guard held while handling an interaction, `None` once shutting down.
+    pub async fn enter(&self) -> Option<RwLockReadGuard<'_, ()>> {
+        if self.closed.load(Ordering::Relaxed) {
+            return None;
+        }
+        let guard = self.lock.read().await;
+        // Checked again as draining may have started and finished while
+        // waiting for the guard.
+        if self.closed.load(Ordering::Relaxed) {
+            return None;
+        }
+        Some(guard)
+    }
+
+    /// Refuse new interactions and wait for the current ones, returns whether
+    /// they all finished in time.
+    pub async fn drain(&self, max_wait: Duration) -> bool {
+        self.closed.store(true, Ordering::Relaxed);
+        timeout(max_wait, self.lock.write()).await.is_ok()
+    }
+}
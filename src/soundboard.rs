@@ -1,9 +1,8 @@
 use std::{
-    collections::{hash_map::DefaultHasher, HashMap},
+    collections::{hash_map::DefaultHasher, HashMap, HashSet},
     ffi::OsStr,
     hash::{Hash, Hasher},
     path::{Path, PathBuf},
-    process::Stdio,
     sync::Arc,
     time::{Duration, Instant},
 };
@@ -17,13 +16,25 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use serenity::{
     all::ButtonStyle,
-    model::{channel::Attachment, id::GuildId},
+    model::{
+        channel::Attachment,
+        id::{GuildId, UserId},
+    },
 };
 use thiserror::Error as ThisError;
-use tokio::{fs, fs::OpenOptions, io::AsyncWriteExt, process::Command, sync::Mutex, time::sleep};
+use tokio::{fs, fs::OpenOptions, io::AsyncWriteExt, sync::Mutex, time::sleep};
 use ulid::Ulid;
 
-use crate::{button, wav};
+use crate::{
+    button,
+    crypto::{Encryption, LoadError},
+    disk,
+    error_code::Coded,
+    ffmpeg, wav,
+};
+
+/// Separates the group from the name in sound references.
+const GROUP_SEPARATOR: char = '/';
 
 #[derive(Debug)]
 pub struct Soundboard {
@@ -32,7 +43,13 @@ pub struct Soundboard {
     max_duration: Duration,
     cache_duration: Duration,
     ffmpeg_path: PathBuf,
+    min_free_space: u64,
+    encryption: Arc<Encryption>,
     sounds: Mutex<HashMap<Ulid, Sound>>,
+    /// Stored next to the metadata file, as the metadata format has no room
+    /// for it.
+    uploaders_path: PathBuf,
+    uploaders: Mutex<HashMap<Ulid, UserId>>,
 }
 
 impl Soundboard {
@@ -42,10 +59,12 @@ impl Soundboard {
         max_duration: Duration,
         cache_duration: Duration,
         ffmpeg_path: PathBuf,
-    ) -> Self {
-        let sounds = fs::read(&metadata_path)
-            .await
-            .ok()
+        min_free_space: u64,
+        encryption: Arc<Encryption>,
+    ) -> Result<Self, LoadError> {
+        let mut sounds = encryption
+            .read(&metadata_path)
+            .await?
             .map(|file| {
                 let mut deserializer = bincode::Deserializer::from_slice(
                     &file,
@@ -71,15 +90,33 @@ impl Soundboard {
             })
             .unwrap_or_default();
         info!("loaded {} sounds", sounds.len());
+        let migrated = migrate_names(&mut sounds);
+
+        let uploaders_path = metadata_path.with_extension("uploaders");
+        let uploaders = encryption.read_json(&uploaders_path).await?;
 
-        Self {
+        let soundboard = Self {
             metadata_path,
             sounds_dir_path,
             max_duration,
             cache_duration,
             ffmpeg_path,
+            min_free_space,
+            encryption,
             sounds: Mutex::new(sounds),
+            uploaders_path,
+            uploaders: Mutex::new(uploaders),
+        };
+        if migrated > 0 {
+            info!("renamed {migrated} sounds to follow the naming policy");
+            soundboard
+                .overwrite_metadata_file(&*soundboard.sounds.lock().await)
+                .await
+                .map_err(|err| {
+                    LoadError::Migrate(soundboard.metadata_path.clone(), err.to_string())
+                })?;
         }
+        Ok(soundboard)
     }
 
     pub fn cache_loop(self: Arc<Self>) {
@@ -114,18 +151,35 @@ impl Soundboard {
             .collect()
     }
 
+    /// References of the sounds matching a search, qualified by their group
+    /// when their name is used in several groups.
     pub async fn names_matching(&self, guild: GuildId, search: &str, max: usize) -> Vec<String> {
         let regex = search_regex(search);
-        self.sounds
-            .lock()
-            .await
+        let qualified = search.contains(GROUP_SEPARATOR);
+        let sounds = self.sounds.lock().await;
+        let sounds = sounds
             .values()
+            .filter(|sound| sound.metadata.guild == guild.get())
+            .collect_vec();
+        let name_counts = sounds
+            .iter()
+            .counts_by(|sound| sound.metadata.name.to_lowercase());
+        sounds
+            .into_iter()
             .filter_map(|sound| {
-                (sound.metadata.guild == guild.get() && regex.is_match(&sound.metadata.name))
-                    .then(|| sound.metadata.name.clone())
+                let reference = sound.metadata.reference();
+                if !regex.is_match(&sound.metadata.name)
+                    && !(qualified && regex.is_match(&reference))
+                {
+                    return None;
+                }
+                Some(if name_counts[&sound.metadata.name.to_lowercase()] > 1 {
+                    reference
+                } else {
+                    sound.metadata.name.clone()
+                })
             })
             .sorted()
-            .dedup()
             .take(max)
             .collect()
     }
@@ -146,13 +200,59 @@ impl Soundboard {
             .collect()
     }
 
+    pub async fn name(&self, id: Ulid) -> Option<String> {
+        Some(self.sounds.lock().await.get(&id)?.metadata.name.clone())
+    }
+
+    pub async fn metadata(&self, id: Ulid) -> Option<SoundMetadata> {
+        Some(self.sounds.lock().await.get(&id)?.metadata.clone())
+    }
+
     pub async fn get_wav(&self, id: Ulid) -> Option<Vec<u8>> {
         self.sounds
             .lock()
             .await
             .get_mut(&id)?
-            .get_wav_data(&self.sounds_dir_path, true)
+            .get_wav_data(&self.sounds_dir_path, &self.encryption, true)
+            .await
+    }
+
+    /// Read every sound of a guild not cached yet into the cache.
+    pub async fn precache(&self, guild: GuildId) {
+        let paths = self
+            .sounds
+            .lock()
             .await
+            .values()
+            .filter(|sound| {
+                sound.metadata.guild == guild.get() && matches!(sound.data, CachedSound::Fs)
+            })
+            .map(|sound| {
+                (
+                    sound.metadata.id,
+                    sound.metadata.get_file_path(&self.sounds_dir_path),
+                )
+            })
+            .collect_vec();
+
+        // Read without holding the lock to keep the board responsive.
+        let mut cached = 0;
+        for (id, path) in paths {
+            let Some(data) = fs::read(&path)
+                .await
+                .ok()
+                .and_then(|data| self.encryption.decrypt(data))
+            else {
+                continue;
+            };
+            if let Some(sound) = self.sounds.lock().await.get_mut(&id) {
+                if matches!(sound.data, CachedSound::Fs) {
+                    sound.data = CachedSound::Cached(data, Instant::now());
+                    cached += 1;
+                }
+            }
+        }
+        info!("precached {cached} sounds of guild {guild}");
     }
 
     pub async fn get_wav_by_name(
@@ -161,94 +261,65 @@ impl Soundboard {
         name: &str,
         group: Option<&str>,
     ) -> Result<Vec<u8>, SoundboardError> {
-        let name_regex = match_regex(name);
-        let group_regex = group.map(match_regex);
-
         let mut sounds = self.sounds.lock().await;
-        let mut matching = sounds.values_mut().filter(|sound| {
-            sound.metadata.guild == guild.get()
-                && name_regex.is_match(&sound.metadata.name)
-                && group_regex
-                    .as_ref()
-                    .map(|rg| rg.is_match(&sound.metadata.group))
-                    .unwrap_or(true)
-        });
-        let sound = matching.next().ok_or(SoundboardError::SoundNotFound)?;
-        if matching.next().is_some() {
-            return Err(SoundboardError::SoundNameAmbiguous);
+        let id = find_id(&sounds, guild, name, group)?;
+        sounds
+            .get_mut(&id)
+            .ok_or(SoundboardError::SoundNotFound)?
+            .get_wav_data(&self.sounds_dir_path, &self.encryption, true)
+            .await
+            .ok_or(SoundboardError::SoundNotFound)
+    }
+
+    /// Download an attachment as a WAV sound, to be previewed before being
+    /// added with `add_data`.
+    pub async fn fetch(&self, attachment: &Attachment) -> Result<Vec<u8>, SoundboardError> {
+        // Verify duration.
+        if wav::duration_from_size(attachment.size as usize) > self.max_duration {
+            return Err(SoundboardError::TooLong);
         }
 
-        sound
-            .get_wav_data(&self.sounds_dir_path, true)
+        // Fetch sound data.
+        let data = attachment
+            .download()
             .await
-            .ok_or(SoundboardError::SoundNotFound)
+            .map_err(|_| SoundboardError::SoundFetch)?;
+        self.to_wav(data, &attachment.filename).await
     }
 
+    /// Add a sound from raw file data, `filename` being used to guess its format
+    /// if it needs to be transcoded.
     #[allow(clippy::too_many_arguments)]
-    pub async fn add(
+    pub async fn add_data(
         &self,
-        attachment: &Attachment,
+        data: Vec<u8>,
+        filename: &str,
         guild: GuildId,
         name: String,
         emoji: Option<String>,
         color: ButtonStyle,
         mut group: String,
         requested_index: Option<usize>,
+        uploader: Option<UserId>,
     ) -> Result<Ulid, SoundboardError> {
+        validate_name(&name)?;
+        validate_name(&group)?;
+
         // Verify duration.
-        if wav::duration_from_size(attachment.size as usize) > self.max_duration {
+        if wav::duration_from_size(data.len()) > self.max_duration {
             return Err(SoundboardError::TooLong);
         }
 
-        // Fetch sound data.
-        let data = attachment
-            .download()
-            .await
-            .map_err(|_| SoundboardError::SoundFetch)?;
+        let data = self.to_wav(data, filename).await?;
 
-        // If sound is already PCM s16le WAV, keep it as is, transcode it otherwise.
-        let data = if wav::is_valid_pcm_s16le(&data) {
-            data
-        } else {
-            let filename = PathBuf::from(&attachment.filename);
-            let extension = filename
-                .extension()
-                .and_then(OsStr::to_str)
-                .ok_or(SoundboardError::InvalidSound)?;
-
-            let mut cmd = Command::new(&self.ffmpeg_path);
-            cmd.args(["-f", extension]) // Input file format.
-                .args(["-i", "-"]) // Read from stdin.
-                .args(["-f", "wav"]) // Output to raw PCM.
-                .arg("-") // Output to stdout.
-                .stdin(Stdio::piped())
-                .stdout(Stdio::piped())
-                .stderr(Stdio::null());
-            let mut child = cmd
-                .spawn()
-                .map_err(|_| SoundboardError::TranscodingFailed)?;
-
-            let mut stdin = child
-                .stdin
-                .take()
-                .ok_or(SoundboardError::TranscodingFailed)?;
-            tokio::spawn(async move {
-                stdin
-                    .write_all(&data)
-                    .await
-                    .expect("Failed to write sound to ffmpeg");
-            });
-
-            let out = child
-                .wait_with_output()
-                .await
-                .map_err(|_| SoundboardError::TranscodingFailed)?;
-            if !out.status.success() || out.stdout.len() % 2 != 0 {
-                return Err(SoundboardError::TranscodingFailed);
-            }
-
-            out.stdout
-        };
+        // Refuse to fill the volume, metadata is usually stored next to sounds.
+        if !disk::has_space_for(
+            &self.sounds_dir_path,
+            self.min_free_space,
+            data.len() as u64,
+        ) {
+            return Err(SoundboardError::LowDiskSpace);
+        }
 
         let mut sounds = self.sounds.lock().await;
 
@@ -257,8 +328,7 @@ impl Soundboard {
         group = sounds
             .values()
             .find_map(|sound| {
-                group_regex
-                    .is_match(&sound.metadata.group)
+                (sound.metadata.guild == guild.get() && group_regex.is_match(&sound.metadata.group))
                     .then(|| sound.metadata.group.clone())
             })
             .unwrap_or(group);
@@ -314,12 +384,16 @@ impl Soundboard {
             color,
             group,
             index,
+            looping: false,
         };
 
         // Write sound to disk.
-        fs::write(metadata.get_file_path(&self.sounds_dir_path), &data)
-            .await
-            .map_err(|_| SoundboardError::SoundWrite)?;
+        fs::write(
+            metadata.get_file_path(&self.sounds_dir_path),
+            self.encryption.encrypt(data.clone()),
+        )
+        .await
+        .map_err(|_| SoundboardError::SoundWrite)?;
 
         // Write metadata to disk (partial or full overwrite).
         sounds.insert(
@@ -329,7 +403,8 @@ impl Soundboard {
                 data: CachedSound::Cached(data, Instant::now()),
             },
         );
-        if overwrite_required {
+        // Encrypted metadata cannot be appended to.
+        if overwrite_required || self.encryption.enabled() {
             self.overwrite_metadata_file(&sounds).await?;
         } else {
             let mut file = OpenOptions::new()
@@ -342,36 +417,49 @@ impl Soundboard {
                 .await
                 .map_err(|_| SoundboardError::SoundWrite)?;
         }
+        drop(sounds);
+
+        if let Some(uploader) = uploader {
+            let mut uploaders = self.uploaders.lock().await;
+            uploaders.insert(id, uploader);
+            self.overwrite_uploaders_file(&uploaders).await?;
+        }
 
         Ok(id)
     }
 
+    /// If sound is already PCM s16le WAV, keep it as is, transcode it
+    /// otherwise, `filename` being used to guess its format.
+    async fn to_wav(&self, data: Vec<u8>, filename: &str) -> Result<Vec<u8>, SoundboardError> {
+        if wav::is_valid_pcm_s16le(&data) {
+            return Ok(data);
+        }
+        let filename = PathBuf::from(filename);
+        let extension = filename
+            .extension()
+            .and_then(OsStr::to_str)
+            .ok_or(SoundboardError::InvalidSound)?;
+
+        let out = ffmpeg::pipe(&self.ffmpeg_path, data, &["-f", extension], &["-f", "wav"])
+            .await
+            .ok_or(SoundboardError::TranscodingFailed)?;
+        if out.len() % 2 != 0 {
+            return Err(SoundboardError::TranscodingFailed);
+        }
+        Ok(out)
+    }
+
     pub async fn delete(
         &self,
         guild: GuildId,
         name: &str,
         group: Option<&str>,
     ) -> Result<(), SoundboardError> {
-        let name_regex = match_regex(name);
-        let group_regex = group.map(match_regex);
-
         let mut sounds = self.sounds.lock().await;
-        let mut matching = sounds.iter().filter_map(|(id, sound)| {
-            (sound.metadata.guild == guild.get()
-                && name_regex.is_match(&sound.metadata.name)
-                && group_regex
-                    .as_ref()
-                    .map(|rg| rg.is_match(&sound.metadata.group))
-                    .unwrap_or(true))
-            .then_some(*id)
-        });
-        let id = matching.next().ok_or(SoundboardError::SoundNotFound)?;
-        if matching.next().is_some() {
-            return Err(SoundboardError::SoundNameAmbiguous);
-        }
-
+        let id = find_id(&sounds, guild, name, group)?;
         let sound = sounds.remove(&id).ok_or(SoundboardError::SoundNotFound)?;
         self.overwrite_metadata_file(&sounds).await?;
+        self.forget_uploader(id).await?;
         fs::remove_file(sound.metadata.get_file_path(&self.sounds_dir_path))
             .await
             .map_err(|_| SoundboardError::DeleteFailed)
@@ -382,6 +470,7 @@ impl Soundboard {
         let sound = sounds.remove(&id).ok_or(SoundboardError::SoundNotFound)?;
         assert_eq!(sound.metadata.guild, guild.get());
         self.overwrite_metadata_file(&sounds).await?;
+        self.forget_uploader(id).await?;
         fs::remove_file(sound.metadata.get_file_path(&self.sounds_dir_path))
             .await
             .map_err(|_| SoundboardError::DeleteFailed)
@@ -394,30 +483,18 @@ impl Soundboard {
         group: Option<&str>,
         new_name: String,
     ) -> Result<bool, SoundboardError> {
-        let name_regex = match_regex(name);
-
-        // Check if it's the same name.
-        if name_regex.is_match(&new_name) {
-            return Ok(false);
-        }
-
-        let group_regex = group.map(match_regex);
+        validate_name(&new_name)?;
         let mut sounds = self.sounds.lock().await;
 
         // Get sound id. We must resolve ambiguity first.
-        let mut matching = sounds.values().filter_map(|sound| {
-            (sound.metadata.guild == guild.get()
-                && name_regex.is_match(&sound.metadata.name)
-                && group_regex
-                    .as_ref()
-                    .map(|rg| rg.is_match(&sound.metadata.group))
-                    .unwrap_or(true))
-            .then(|| (sound.metadata.id, sound.metadata.group.clone()))
-        });
-        let (id, group) = matching.next().ok_or(SoundboardError::SoundNotFound)?;
-        if matching.next().is_some() {
-            return Err(SoundboardError::SoundNameAmbiguous);
+        let id = find_id(&sounds, guild, name, group)?;
+        let sound = &sounds[&id].metadata;
+
+        // Check if it's the same name.
+        if match_regex(&sound.name).is_match(&new_name) {
+            return Ok(false);
         }
+        let group = sound.group.clone();
 
         // Check if a sound with the requested name already exists.
         let new_name_regex = match_regex(&new_name);
@@ -445,11 +522,20 @@ impl Soundboard {
         group: Option<&str>,
         mut new_group: String,
     ) -> Result<bool, SoundboardError> {
-        let name_regex = match_regex(name);
+        validate_name(&new_group)?;
         let new_group_regex = match_regex(&new_group);
         let mut sounds = self.sounds.lock().await;
 
+        // Find requested sound to change.
+        let id = find_id(&sounds, guild, name, group)?;
+
+        // Check if old and new groups are the same.
+        if new_group_regex.is_match(&sounds[&id].metadata.group) {
+            return Ok(false);
+        }
+
         // Check if the name is already taken in the target group.
+        let name_regex = match_regex(&sounds[&id].metadata.name);
         if sounds.values().any(|sound| {
             sound.metadata.guild == guild.get()
                 && name_regex.is_match(&sound.metadata.name)
@@ -462,9 +548,9 @@ impl Soundboard {
         new_group = sounds
             .values()
             .find_map(|sound| {
-                new_group_regex
-                    .is_match(&sound.metadata.group)
-                    .then(|| sound.metadata.group.clone())
+                (sound.metadata.guild == guild.get()
+                    && new_group_regex.is_match(&sound.metadata.group))
+                .then(|| sound.metadata.group.clone())
             })
             .unwrap_or(new_group);
 
@@ -479,26 +565,7 @@ impl Soundboard {
             .map(|i| i + 1)
             .unwrap_or(0);
 
-        // Find requested sound to change.
-        let group_regex = group.map(match_regex);
-        let mut matching = sounds.values_mut().filter(|sound| {
-            sound.metadata.guild == guild.get()
-                && name_regex.is_match(&sound.metadata.name)
-                && group_regex
-                    .as_ref()
-                    .map(|rg| rg.is_match(&sound.metadata.group))
-                    .unwrap_or(true)
-        });
-        let sound = matching.next().ok_or(SoundboardError::SoundNotFound)?;
-        if matching.next().is_some() {
-            return Err(SoundboardError::SoundNameAmbiguous);
-        }
-
-        // Check if old and new groups are the same.
-        if new_group_regex.is_match(&sound.metadata.group) {
-            return Ok(false);
-        }
-
+        let sound = sounds.get_mut(&id).ok_or(SoundboardError::SoundNotFound)?;
         sound.metadata.group = new_group;
         sound.metadata.index = index;
         self.overwrite_metadata_file(&sounds).await?;
@@ -512,22 +579,9 @@ impl Soundboard {
         group: Option<&str>,
         f: F,
     ) -> Result<R, SoundboardError> {
-        let name_regex = match_regex(name);
-        let group_regex = group.map(match_regex);
-
         let mut sounds = self.sounds.lock().await;
-        let mut matching = sounds.values_mut().filter(|sound| {
-            sound.metadata.guild == guild.get()
-                && name_regex.is_match(&sound.metadata.name)
-                && group_regex
-                    .as_ref()
-                    .map(|rg| rg.is_match(&sound.metadata.group))
-                    .unwrap_or(true)
-        });
-        let sound = matching.next().ok_or(SoundboardError::SoundNotFound)?;
-        if matching.next().is_some() {
-            return Err(SoundboardError::SoundNameAmbiguous);
-        }
+        let id = find_id(&sounds, guild, name, group)?;
+        let sound = sounds.get_mut(&id).ok_or(SoundboardError::SoundNotFound)?;
 
         let (res, overwrite) = f(sound);
         if overwrite {
@@ -572,31 +626,31 @@ impl Soundboard {
         .await
     }
 
+    pub async fn change_looping(
+        &self,
+        guild: GuildId,
+        name: &str,
+        group: Option<&str>,
+        looping: bool,
+    ) -> Result<bool, SoundboardError> {
+        self.change_sound_field(guild, name, group, |s| {
+            if s.metadata.looping == looping {
+                (false, false)
+            } else {
+                s.metadata.looping = looping;
+                (true, true)
+            }
+        })
+        .await
+    }
+
     pub async fn get_id(
         &self,
         guild: GuildId,
         name: &str,
         group: Option<&str>,
     ) -> Result<Ulid, SoundboardError> {
-        let name_regex = match_regex(name);
-        let group_regex = group.map(match_regex);
-
-        let sounds = self.sounds.lock().await;
-        let mut matching = sounds.iter().filter_map(|(id, sound)| {
-            (sound.metadata.guild == guild.get()
-                && name_regex.is_match(&sound.metadata.name)
-                && group_regex
-                    .as_ref()
-                    .map(|rg| rg.is_match(&sound.metadata.group))
-                    .unwrap_or(true))
-            .then_some(*id)
-        });
-        let id = matching.next().ok_or(SoundboardError::SoundNotFound)?;
-        if matching.next().is_some() {
-            return Err(SoundboardError::SoundNameAmbiguous);
-        }
-
-        Ok(id)
+        find_id(&*self.sounds.lock().await, guild, name, group)
     }
 
     pub async fn random_id(&self, guild: GuildId) -> Option<Ulid> {
@@ -637,22 +691,156 @@ impl Soundboard {
         &self,
         sounds: &HashMap<Ulid, Sound>,
     ) -> Result<(), SoundboardError> {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create(true)
-            .truncate(true)
-            .open(&self.metadata_path)
+        let mut data = Vec::new();
+        for sound in sounds.values() {
+            data.extend(
+                bincode::serialize(&sound.metadata).map_err(|_| SoundboardError::SoundWrite)?,
+            );
+        }
+
+        fs::write(&self.metadata_path, self.encryption.encrypt(data))
             .await
             .map_err(|_| SoundboardError::SoundWrite)?;
+        Ok(())
+    }
 
-        for sound in sounds.values() {
-            file.write(
-                &bincode::serialize(&sound.metadata).map_err(|_| SoundboardError::SoundWrite)?,
-            )
+    async fn overwrite_uploaders_file(
+        &self,
+        uploaders: &HashMap<Ulid, UserId>,
+    ) -> Result<(), SoundboardError> {
+        let data = serde_json::to_vec(uploaders).map_err(|_| SoundboardError::SoundWrite)?;
+        fs::write(&self.uploaders_path, self.encryption.encrypt(data))
             .await
-            .map_err(|_| SoundboardError::SoundWrite)?;
+            .map_err(|_| SoundboardError::SoundWrite)
+    }
+
+    async fn forget_uploader(&self, id: Ulid) -> Result<(), SoundboardError> {
+        let mut uploaders = self.uploaders.lock().await;
+        if uploaders.remove(&id).is_some() {
+            self.overwrite_uploaders_file(&uploaders).await?;
+        }
+        Ok(())
+    }
+
+    /// JSON metadata and WAV data of the sounds uploaded by a user, in every
+    /// guild.
+    pub async fn uploaded_by(
+        &self,
+        user: UserId,
+    ) -> Result<Vec<(Value, Vec<u8>)>, SoundboardError> {
+        let ids = self
+            .uploaders
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, uploader)| **uploader == user)
+            .map(|(id, _)| *id)
+            .collect_vec();
+
+        let mut sounds = self.sounds.lock().await;
+        let mut uploaded = Vec::new();
+        for id in ids {
+            if let Some(sound) = sounds.get_mut(&id) {
+                let data = sound
+                    .get_wav_data(&self.sounds_dir_path, &self.encryption, false)
+                    .await
+                    .ok_or(SoundboardError::BackupFailed)?;
+                uploaded.push((
+                    json!({
+                        "id": sound.metadata.id.to_string(),
+                        "guild": sound.metadata.guild.to_string(),
+                        "group": sound.metadata.group,
+                        "name": sound.metadata.name,
+                        "emoji": sound.metadata.emoji,
+                        "color": button::as_str(sound.metadata.color),
+                    }),
+                    data,
+                ));
+            }
+        }
+        Ok(uploaded)
+    }
+
+    /// Delete every sound uploaded by a user, returns the number of deleted
+    /// sounds.
+    pub async fn delete_uploaded_by(&self, user: UserId) -> Result<usize, SoundboardError> {
+        // Same locking order as additions and deletions.
+        let mut sounds = self.sounds.lock().await;
+        let mut uploaders = self.uploaders.lock().await;
+        let ids = uploaders
+            .iter()
+            .filter(|(_, uploader)| **uploader == user)
+            .map(|(id, _)| *id)
+            .collect_vec();
+        if ids.is_empty() {
+            return Ok(0);
         }
 
+        let removed = ids
+            .iter()
+            .filter_map(|id| {
+                uploaders.remove(id);
+                sounds.remove(id)
+            })
+            .collect_vec();
+        self.overwrite_metadata_file(&sounds).await?;
+        self.overwrite_uploaders_file(&uploaders).await?;
+        for sound in &removed {
+            fs::remove_file(sound.metadata.get_file_path(&self.sounds_dir_path))
+                .await
+                .map_err(|_| SoundboardError::DeleteFailed)?;
+        }
+        Ok(removed.len())
+    }
+
+    /// Delete the sounds of a guild, or every sound if `None`, overwriting
+    /// their files and the previous metadata. Returns the number of deleted
+    /// sounds.
+    pub async fn purge(&self, guild: Option<GuildId>) -> Result<usize, SoundboardError> {
+        // Same locking order as additions and deletions.
+        let mut sounds = self.sounds.lock().await;
+        let mut uploaders = self.uploaders.lock().await;
+        let ids = sounds
+            .values()
+            .filter(|sound| guild.is_none_or(|guild| guild == sound.metadata.guild))
+            .map(|sound| sound.metadata.id)
+            .collect_vec();
+
+        let removed = ids
+            .iter()
+            .filter_map(|id| {
+                uploaders.remove(id);
+                sounds.remove(id)
+            })
+            .collect_vec();
+        // Metadata first, for a failure midway not to list deleted sounds.
+        for path in [&self.metadata_path, &self.uploaders_path] {
+            disk::shred(path)
+                .await
+                .map_err(|_| SoundboardError::DeleteFailed)?;
+        }
+        self.overwrite_metadata_file(&sounds).await?;
+        self.overwrite_uploaders_file(&uploaders).await?;
+        for sound in &removed {
+            let path = sound.metadata.get_file_path(&self.sounds_dir_path);
+            disk::shred(&path)
+                .await
+                .map_err(|_| SoundboardError::DeleteFailed)?;
+            fs::remove_file(path)
+                .await
+                .map_err(|_| SoundboardError::DeleteFailed)?;
+        }
+        Ok(removed.len())
+    }
+
+    /// Rewrite the metadata files once pending changes are done, before
+    /// exiting.
+    pub async fn flush(&self) -> Result<(), SoundboardError> {
+        let sounds = self.sounds.lock().await;
+        self.overwrite_metadata_file(&sounds).await?;
+        self.overwrite_uploaders_file(&*self.uploaders.lock().await)
+            .await?;
+        info!("soundboard metadata of {} sounds saved", sounds.len());
         Ok(())
     }
 
@@ -696,7 +884,7 @@ impl Soundboard {
             data.push((
                 format!("{}.wav", sound.metadata.id.to_string()),
                 sound
-                    .get_wav_data(&self.sounds_dir_path, false)
+                    .get_wav_data(&self.sounds_dir_path, &self.encryption, false)
                     .await
                     .ok_or(SoundboardError::BackupFailed)?,
             ));
@@ -718,9 +906,18 @@ pub struct SoundMetadata {
     pub color: ButtonStyle,
     group: String,
     index: usize,
+    /// Played continuously until its button is pressed again, for ambient
+    /// sounds.
+    #[serde(default)]
+    pub looping: bool,
 }
 
 impl SoundMetadata {
+    /// Name qualified by the group, unambiguous in the guild.
+    pub fn reference(&self) -> String {
+        format!("{}{GROUP_SEPARATOR}{}", self.group, self.name)
+    }
+
     fn get_file_path(&self, dir_path: &Path) -> PathBuf {
         let mut path = dir_path.join(self.id.to_string());
         path.set_extension("wav");
@@ -741,11 +938,16 @@ enum CachedSound {
 }
 
 impl Sound {
-    async fn get_wav_data(&mut self, dir_path: &Path, cache: bool) -> Option<Vec<u8>> {
+    async fn get_wav_data(
+        &mut self,
+        dir_path: &Path,
+        encryption: &Encryption,
+        cache: bool,
+    ) -> Option<Vec<u8>> {
         match &mut self.data {
             CachedSound::Fs => {
                 let path = self.metadata.get_file_path(dir_path);
-                let data = fs::read(&path).await.ok()?;
+                let data = encryption.decrypt(fs::read(&path).await.ok()?)?;
                 if cache {
                     self.data = CachedSound::Cached(data.clone(), Instant::now());
                 }
@@ -775,14 +977,125 @@ pub enum SoundboardError {
     TranscodingFailed,
     #[error("Failed to save file.")]
     SoundWrite,
+    #[error("Not enough disk space left to save this sound.")]
+    LowDiskSpace,
     #[error("Cannot find that sound.")]
     SoundNotFound,
-    #[error("Sound name is ambiguous. Try to add a group too.")]
-    SoundNameAmbiguous,
+    #[error("Sound name is ambiguous, use one of `{0}`.")]
+    SoundNameAmbiguous(String),
+    #[error("Sound and group names cannot contain `/`.")]
+    InvalidName,
     #[error("Failed to delete sound.")]
     DeleteFailed,
     #[error("Failed to create backup.")]
     BackupFailed,
+    #[error("This upload expired.")]
+    UploadExpired,
+}
+
+impl Coded for SoundboardError {
+    fn code(&self) -> u16 {
+        match self {
+            Self::NameTaken => 101,
+            Self::TooLong => 102,
+            Self::SoundFetch => 103,
+            Self::InvalidSound => 104,
+            Self::TranscodingFailed => 105,
+            Self::SoundWrite => 106,
+            Self::LowDiskSpace => 107,
+            Self::SoundNotFound => 108,
+            Self::SoundNameAmbiguous(_) => 109,
+            Self::InvalidName => 110,
+            Self::DeleteFailed => 111,
+            Self::BackupFailed => 112,
+            Self::UploadExpired => 113,
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            Self::NameTaken => Some("Pick another name or group."),
+            Self::TooLong => Some("Trim the sound before uploading it."),
+            Self::InvalidSound => Some("Upload a common audio file such as MP3, Ogg or WAV."),
+            Self::LowDiskSpace => Some("Ask the bot host to free some space."),
+            Self::UploadExpired => Some("Upload the sound again."),
+            _ => None,
+        }
+    }
+}
+
+/// Sound of a guild referenced by `name`, in `group` if given. Without group,
+/// `name` may be a `group/name` reference.
+fn find_id(
+    sounds: &HashMap<Ulid, Sound>,
+    guild: GuildId,
+    name: &str,
+    group: Option<&str>,
+) -> Result<Ulid, SoundboardError> {
+    let (group, name) = match (group, name.split_once(GROUP_SEPARATOR)) {
+        (None, Some((group, name))) => (Some(group), name),
+        _ => (group, name),
+    };
+    let name_regex = match_regex(name);
+    let group_regex = group.map(match_regex);
+
+    let matching = sounds
+        .values()
+        .filter(|sound| {
+            sound.metadata.guild == guild.get()
+                && name_regex.is_match(&sound.metadata.name)
+                && group_regex
+                    .as_ref()
+                    .map(|rg| rg.is_match(&sound.metadata.group))
+                    .unwrap_or(true)
+        })
+        .collect_vec();
+    match matching[..] {
+        [] => Err(SoundboardError::SoundNotFound),
+        [sound] => Ok(sound.metadata.id),
+        _ => Err(SoundboardError::SoundNameAmbiguous(
+            matching
+                .iter()
+                .map(|sound| sound.metadata.reference())
+                .sorted()
+                .join("`, `"),
+        )),
+    }
+}
+
+/// Names are unique within a group, ignoring case, and may be used in several
+/// groups. Neither contains the separator of `group/name` references.
+fn validate_name(name: &str) -> Result<(), SoundboardError> {
+    if name.contains(GROUP_SEPARATOR) {
+        return Err(SoundboardError::InvalidName);
+    }
+    Ok(())
+}
+
+/// Rename the sounds saved before the naming policy was enforced: separators
+/// are replaced and names taken in a group are numbered. Returns the number of
+/// sounds renamed.
+fn migrate_names(sounds: &mut HashMap<Ulid, Sound>) -> usize {
+    let mut renamed = 0;
+    let mut taken = HashSet::new();
+    // Oldest sounds keep their name.
+    for id in sounds.keys().copied().sorted().collect_vec() {
+        let metadata = &mut sounds.get_mut(&id).expect("Sound disappeared").metadata;
+        let group = metadata.group.replace(GROUP_SEPARATOR, "-");
+        let base = metadata.name.replace(GROUP_SEPARATOR, "-");
+        let mut name = base.clone();
+        let mut n = 1;
+        while !taken.insert((metadata.guild, group.to_lowercase(), name.to_lowercase())) {
+            n += 1;
+            name = format!("{base} ({n})");
+        }
+        if group != metadata.group || name != metadata.name {
+            metadata.group = group;
+            metadata.name = name;
+            renamed += 1;
+        }
+    }
+    renamed
 }
 
 fn match_regex(searching: &str) -> Regex {
@@ -792,3 +1105,103 @@ fn match_regex(searching: &str) -> Regex {
 fn search_regex(searching: &str) -> Regex {
     Regex::new(&format!("(?i){}", regex::escape(searching))).expect("Failed to build search regex")
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, sync::Arc, time::Duration};
+
+    use serenity::{all::ButtonStyle, model::id::GuildId};
+    use ulid::Ulid;
+
+    use super::{
+        find_id, migrate_names, CachedSound, Sound, SoundMetadata, Soundboard, SoundboardError,
+    };
+    use crate::crypto::Encryption;
+
+    fn sound(created: u64, group: &str, name: &str) -> (Ulid, Sound) {
+        let id = Ulid::from_parts(created, 0);
+        let metadata = SoundMetadata {
+            guild: 1,
+            id,
+            name: name.to_owned(),
+            emoji: None,
+            color: ButtonStyle::Primary,
+            group: group.to_owned(),
+            index: 0,
+            looping: false,
+        };
+        let data = CachedSound::Fs;
+        (id, Sound { metadata, data })
+    }
+
+    #[test]
+    fn group_qualified_references() {
+        let guild = GuildId::new(1);
+        let mut sounds = HashMap::from([
+            sound(1, "memes", "bruh"),
+            sound(2, "music", "bruh"),
+            sound(3, "music/old", "Intro"),
+            sound(4, "music", "intro"),
+            sound(5, "memes", "Bruh"),
+        ]);
+        assert_eq!(migrate_names(&mut sounds), 2);
+        assert!(find_id(&sounds, guild, "memes/bruh (2)", None).is_ok());
+        assert!(matches!(
+            find_id(&sounds, guild, "bruh", None),
+            Err(SoundboardError::SoundNameAmbiguous(candidates)) if candidates == "memes/bruh`, `music/bruh"
+        ));
+        let id = find_id(&sounds, guild, "Memes/bruh", None).unwrap();
+        assert_eq!(find_id(&sounds, guild, "bruh", Some("memes")).unwrap(), id);
+        assert_eq!(sounds[&id].metadata.group, "memes");
+        assert_eq!(
+            sounds[&find_id(&sounds, guild, "music-old/intro", None).unwrap()]
+                .metadata
+                .reference(),
+            "music-old/Intro"
+        );
+    }
+
+    #[tokio::test]
+    async fn changes_looping() {
+        let path =
+            std::env::temp_dir().join(format!("disrecord-soundboard-{}", std::process::id()));
+        let new = || {
+            Soundboard::new(
+                path.clone(),
+                std::env::temp_dir(),
+                Duration::from_secs(60),
+                Duration::from_secs(60),
+                "ffmpeg".into(),
+                0,
+                Arc::new(Encryption::new(None)),
+            )
+        };
+        let soundboard = new().await.unwrap();
+        soundboard
+            .sounds
+            .lock()
+            .await
+            .extend([sound(1, "memes", "bruh")]);
+        let guild = GuildId::new(1);
+
+        assert!(soundboard
+            .change_looping(guild, "bruh", None, true)
+            .await
+            .unwrap());
+        assert!(!soundboard
+            .change_looping(guild, "bruh", None, true)
+            .await
+            .unwrap());
+        assert!(soundboard
+            .change_looping(guild, "missing", None, true)
+            .await
+            .is_err());
+
+        // Saved with the metadata.
+        let reloaded = new().await.unwrap();
+        let id = Ulid::from_parts(1, 0);
+        assert!(reloaded.metadata(id).await.unwrap().looping);
+        let _ = tokio::fs::remove_file(&path).await;
+        let _ = tokio::fs::remove_file(path.with_extension("uploaders")).await;
+    }
+}
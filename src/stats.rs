@@ -0,0 +1,121 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Clock ticks per second of the CPU times in `/proc`, `USER_HZ` is 100 on
+/// every architecture Linux supports.
+const CLOCK_TICKS: u64 = 100;
+
+/// Time spent in each processing stage since startup, to find out what
+/// saturates the CPU of low-power hosts.
+pub struct Stats {
+    started: Instant,
+    stages: Mutex<BTreeMap<&'static str, Stage>>,
+}
+
+#[derive(Default)]
+struct Stage {
+    total: Duration,
+    count: u64,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            stages: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl Stats {
+    /// Time a stage until the returned timer is dropped.
+    pub fn timer(self: &Arc<Self>, stage: &'static str) -> StageTimer {
+        StageTimer {
+            stats: Arc::clone(self),
+            stage,
+            start: Instant::now(),
+        }
+    }
+
+    pub fn record(&self, stage: &'static str, elapsed: Duration) {
+        let mut stages = self.stages.lock().expect("Stats lock poisoned");
+        let stage = stages.entry(stage).or_default();
+        stage.total += elapsed;
+        stage.count += 1;
+    }
+
+    /// CPU usage of the process since startup, as a share of a single core,
+    /// then one line per stage with the share of the uptime spent in it.
+    /// Stages are timed on the wall clock, so include the time they wait.
+    pub fn report(&self) -> String {
+        let uptime = self.started.elapsed();
+        let mut report = match process_cpu_time() {
+            Some(cpu_time) => format!(
+                "Process: {:.2}% CPU\n",
+                cpu_time.as_secs_f64() * 100. / uptime.as_secs_f64()
+            ),
+            None => "Process: CPU usage unavailable on this platform\n".to_owned(),
+        };
+        let stages = self.stages.lock().expect("Stats lock poisoned");
+        for (name, stage) in stages.iter() {
+            let _ = writeln!(
+                report,
+                "`{name}`: {:.2}% of the uptime, {} total over {} runs",
+                stage.total.as_secs_f64() * 100. / uptime.as_secs_f64(),
+                humantime::format_duration(Duration::from_millis(stage.total.as_millis() as u64)),
+                stage.count
+            );
+        }
+        report
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.elapsed()
+    }
+}
+
+/// User and system CPU time of every thread of the process.
+fn process_cpu_time() -> Option<Duration> {
+    parse_cpu_time(&std::fs::read_to_string("/proc/self/stat").ok()?)
+}
+
+fn parse_cpu_time(stat: &str) -> Option<Duration> {
+    // The command name may contain spaces and parentheses, the fields after
+    // it start with the third one, `state`.
+    let (_, fields) = stat.rsplit_once(')')?;
+    let mut fields = fields.split_whitespace().skip(11);
+    let utime = fields.next()?.parse::<u64>().ok()?;
+    let stime = fields.next()?.parse::<u64>().ok()?;
+    Some(Duration::from_millis((utime + stime) * 1000 / CLOCK_TICKS))
+}
+
+pub struct StageTimer {
+    stats: Arc<Stats>,
+    stage: &'static str,
+    start: Instant,
+}
+
+impl Drop for StageTimer {
+    fn drop(&mut self) {
+        self.stats.record(self.stage, self.start.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::parse_cpu_time;
+
+    #[test]
+    fn parses_process_cpu_time() {
+        let stat =
+            "1234 (dis (record) x) S 1 1234 1234 0 -1 4194560 5046 0 0 0 250 125 0 0 20 0 9 0";
+        assert_eq!(parse_cpu_time(stat), Some(Duration::from_millis(3750)));
+        assert_eq!(parse_cpu_time("1234 (disrecord) S 1"), None);
+    }
+}
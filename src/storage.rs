@@ -0,0 +1,345 @@
+use std::{path::PathBuf, time::SystemTime};
+
+use hmac::{Hmac, Mac};
+use log::{info, warn};
+use reqwest::{Client, Method, RequestBuilder, StatusCode, Url};
+use serde::{Deserialize, Serialize};
+use serenity::model::id::GuildId;
+use sha2::{Digest, Sha256};
+use thiserror::Error as ThisError;
+use tokio::{fs, fs::OpenOptions, io::AsyncWriteExt};
+use ulid::Ulid;
+
+use crate::{disk, error_code::Coded, options::StorageOptions};
+
+/// External storage used to offload files too large to be sent as Discord
+/// attachments. Files are uploaded using an HTTP `PUT`, authenticated with
+/// basic or bearer authentication for WebDAV servers, or signed with AWS
+/// Signature Version 4 for S3 buckets and GCS buckets using HMAC keys. Uploads
+/// are logged for them to be deleted with `DELETE` requests when purging a
+/// guild.
+pub struct Storage {
+    client: Client,
+    upload_url: Url,
+    public_url: Url,
+    username: Option<String>,
+    password: Option<String>,
+    bearer_token: Option<String>,
+    signing: Option<SigningKey>,
+    /// Log of the uploaded files, as JSON lines.
+    index_path: PathBuf,
+}
+
+/// File uploaded for a guild.
+#[derive(Serialize, Deserialize)]
+struct Upload {
+    guild: GuildId,
+    path: String,
+}
+
+impl Storage {
+    pub fn new(options: StorageOptions) -> Option<Self> {
+        let upload_url = with_trailing_slash(options.storage_upload_url?);
+        Some(Self {
+            client: Client::new(),
+            public_url: options
+                .storage_public_url
+                .map(with_trailing_slash)
+                .unwrap_or_else(|| upload_url.clone()),
+            upload_url,
+            username: options.storage_username,
+            password: options.storage_password,
+            bearer_token: options.storage_bearer_token,
+            signing: options
+                .storage_access_key_id
+                .zip(options.storage_secret_access_key)
+                .map(|(access_key_id, secret_access_key)| SigningKey {
+                    access_key_id,
+                    secret_access_key,
+                    region: options.storage_region,
+                }),
+            index_path: options.storage_index_path,
+        })
+    }
+
+    /// Upload a file of a guild and return the public link to it. A random
+    /// prefix is added to the filename to avoid collisions and guessable
+    /// links.
+    pub async fn upload(
+        &self,
+        guild: GuildId,
+        filename: &str,
+        data: Vec<u8>,
+    ) -> Result<Url, StorageError> {
+        let path = format!("{}-{}", Ulid::new(), filename);
+        let upload_url = self
+            .upload_url
+            .join(&path)
+            .map_err(|_| StorageError::InvalidUrl)?;
+
+        info!("uploading {} bytes to {upload_url}", data.len());
+        self.authenticate(Method::PUT, upload_url, data)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|_| StorageError::UploadFailed)?;
+        let url = self
+            .public_url
+            .join(&path)
+            .map_err(|_| StorageError::InvalidUrl)?;
+
+        let mut line =
+            serde_json::to_vec(&Upload { guild, path }).expect("Cannot serialize upload");
+        line.push(b'\n');
+        let written = match OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.index_path)
+            .await
+        {
+            Ok(mut file) => file.write_all(&line).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = written {
+            warn!("cannot log upload to external storage: {err}");
+        }
+        Ok(url)
+    }
+
+    /// Delete the files uploaded for a guild, or for every guild if `None`,
+    /// then rewrite the upload log without them. Files that cannot be deleted
+    /// stay in the log for a later purge. Returns the number of deleted
+    /// files.
+    pub async fn purge(&self, guild: Option<GuildId>) -> Result<usize, StorageError> {
+        let uploads = match fs::read_to_string(&self.index_path).await {
+            Ok(file) => file
+                .lines()
+                .filter_map(|line| serde_json::from_str::<Upload>(line).ok())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        let (purged, mut kept): (Vec<_>, Vec<_>) = uploads
+            .into_iter()
+            .partition(|upload| guild.is_none_or(|guild| guild == upload.guild));
+
+        let (mut deleted, mut failed) = (0, false);
+        for upload in purged {
+            if self.delete(&upload.path).await {
+                deleted += 1;
+            } else {
+                failed = true;
+                kept.push(upload);
+            }
+        }
+        let mut file = Vec::new();
+        for upload in &kept {
+            file.extend(serde_json::to_vec(upload).expect("Cannot serialize upload"));
+            file.push(b'\n');
+        }
+        disk::shred(&self.index_path)
+            .await
+            .map_err(|_| StorageError::DeleteFailed)?;
+        fs::write(&self.index_path, file)
+            .await
+            .map_err(|_| StorageError::DeleteFailed)?;
+        if failed {
+            return Err(StorageError::DeleteFailed);
+        }
+        Ok(deleted)
+    }
+
+    /// Delete an uploaded file, returns whether it is gone.
+    async fn delete(&self, path: &str) -> bool {
+        let Ok(url) = self.upload_url.join(path) else {
+            return false;
+        };
+        match self
+            .authenticate(Method::DELETE, url, Vec::new())
+            .send()
+            .await
+        {
+            Ok(response) if response.status() == StatusCode::NOT_FOUND => true,
+            Ok(response) => response.error_for_status().is_ok(),
+            Err(_) => false,
+        }
+    }
+
+    fn authenticate(&self, method: Method, url: Url, body: Vec<u8>) -> RequestBuilder {
+        let mut request = self.client.request(method.clone(), url.clone());
+        if let Some(signing) = &self.signing {
+            for (name, value) in signing.sign(&method, &url, &body, SystemTime::now()) {
+                request = request.header(name, value);
+            }
+        }
+        request = request.body(body);
+        if let Some(username) = &self.username {
+            request = request.basic_auth(username, self.password.as_ref());
+        }
+        if let Some(token) = &self.bearer_token {
+            request = request.bearer_auth(token);
+        }
+        request
+    }
+}
+
+/// Credentials signing requests with AWS Signature Version 4.
+struct SigningKey {
+    access_key_id: String,
+    secret_access_key: String,
+    region: String,
+}
+
+impl SigningKey {
+    /// Headers authenticating a request sent at `now` with `body`.
+    fn sign(
+        &self,
+        method: &Method,
+        url: &Url,
+        body: &[u8],
+        now: SystemTime,
+    ) -> [(&'static str, String); 3] {
+        // `2013-05-24T00:00:00Z` becomes `20130524T000000Z`.
+        let timestamp = humantime::format_rfc3339_seconds(now)
+            .to_string()
+            .replace(['-', ':'], "");
+        let date = &timestamp[..8];
+        let host = match url.port() {
+            Some(port) => format!("{}:{port}", url.host_str().unwrap_or_default()),
+            None => url.host_str().unwrap_or_default().to_owned(),
+        };
+        let payload_hash = hex(&Sha256::digest(body));
+
+        let canonical_request = format!(
+            "{method}\n{}\n{}\nhost:{host}\nx-amz-content-sha256:{payload_hash}\nx-amz-date:{timestamp}\n\n{SIGNED_HEADERS}\n{payload_hash}",
+            canonical_path(url.path()),
+            url.query().unwrap_or_default(),
+        );
+        let scope = format!("{date}/{}/s3/aws4_request", self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{timestamp}\n{scope}\n{}",
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let signature = hex(&hmac(
+            &self.derive_key(date, "s3"),
+            string_to_sign.as_bytes(),
+        ));
+
+        [
+            (
+                "authorization",
+                format!(
+                    "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={SIGNED_HEADERS}, Signature={signature}",
+                    self.access_key_id
+                ),
+            ),
+            ("x-amz-content-sha256", payload_hash),
+            ("x-amz-date", timestamp),
+        ]
+    }
+
+    /// Key signing the requests of a day for a service.
+    fn derive_key(&self, date: &str, service: &str) -> Vec<u8> {
+        [date, self.region.as_str(), service, "aws4_request"]
+            .into_iter()
+            .fold(
+                format!("AWS4{}", self.secret_access_key).into_bytes(),
+                |key, data| hmac(&key, data.as_bytes()),
+            )
+    }
+}
+
+/// Headers covered by request signatures.
+const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any size");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Percent-encoded path of a URL with the reserved characters it may still
+/// contain encoded too, as expected in signed requests.
+fn canonical_path(path: &str) -> String {
+    path.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b'%' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+fn with_trailing_slash(mut url: Url) -> Url {
+    if !url.path().ends_with('/') {
+        url.set_path(&format!("{}/", url.path()));
+    }
+    url
+}
+
+#[derive(ThisError, Debug)]
+pub enum StorageError {
+    #[error("Invalid storage URL.")]
+    InvalidUrl,
+    #[error("Failed to upload file to external storage.")]
+    UploadFailed,
+    #[error("Failed to delete files from external storage.")]
+    DeleteFailed,
+}
+
+impl Coded for StorageError {
+    fn code(&self) -> u16 {
+        match self {
+            Self::InvalidUrl => 301,
+            Self::UploadFailed => 302,
+            Self::DeleteFailed => 303,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn derives_signing_key() {
+        // Example of the AWS documentation.
+        let key = SigningKey {
+            access_key_id: "AKIDEXAMPLE".to_owned(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_owned(),
+            region: "us-east-1".to_owned(),
+        };
+        assert_eq!(
+            hex(&key.derive_key("20120215", "iam")),
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d"
+        );
+    }
+
+    #[test]
+    fn signs_requests() {
+        let key = SigningKey {
+            access_key_id: "AKIDEXAMPLE".to_owned(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_owned(),
+            region: "us-east-1".to_owned(),
+        };
+        let url = Url::parse("https://bucket.s3.amazonaws.com/a%20b(1).wav").unwrap();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(1369353600);
+        let [(_, authorization), (_, payload_hash), (_, timestamp)] =
+            key.sign(&Method::PUT, &url, b"", now);
+        assert_eq!(timestamp, "20130524T000000Z");
+        assert_eq!(
+            payload_hash,
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+        assert!(authorization.starts_with(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20130524/us-east-1/s3/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature="
+        ));
+        assert_eq!(canonical_path(url.path()), "/a%20b%281%29.wav");
+    }
+}
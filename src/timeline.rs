@@ -0,0 +1,78 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt::Write,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serenity::model::id::{GuildId, UserId};
+use tokio::sync::Mutex;
+
+/// Oldest events are dropped past this count.
+const MAX_EVENTS_PER_GUILD: usize = 10_000;
+
+/// Join and leave times of participants of the voice channel the bot listens
+/// to, per guild.
+#[derive(Default)]
+pub struct Timeline {
+    guilds: Mutex<HashMap<GuildId, VecDeque<ParticipantEvent>>>,
+}
+
+struct ParticipantEvent {
+    time: SystemTime,
+    user: UserId,
+    joined: bool,
+}
+
+impl Timeline {
+    /// Start a new session with the members already present in the channel.
+    pub async fn start(&self, guild: GuildId, present: impl IntoIterator<Item = UserId>) {
+        let now = SystemTime::now();
+        self.guilds.lock().await.insert(
+            guild,
+            present
+                .into_iter()
+                .map(|user| ParticipantEvent {
+                    time: now,
+                    user,
+                    joined: true,
+                })
+                .collect(),
+        );
+    }
+
+    pub async fn record(&self, guild: GuildId, user: UserId, joined: bool) {
+        let mut guilds = self.guilds.lock().await;
+        let events = guilds.entry(guild).or_default();
+        if events.len() >= MAX_EVENTS_PER_GUILD {
+            events.pop_front();
+        }
+        events.push_back(ParticipantEvent {
+            time: SystemTime::now(),
+            user,
+            joined,
+        });
+    }
+
+    /// CSV of the session events, timestamps in milliseconds since the Unix
+    /// epoch.
+    pub async fn to_csv(&self, guild: GuildId) -> Option<String> {
+        let guilds = self.guilds.lock().await;
+        let events = guilds.get(&guild).filter(|events| !events.is_empty())?;
+
+        let mut csv = "timestamp,user,event\n".to_owned();
+        for event in events {
+            let _ = writeln!(
+                csv,
+                "{},{},{}",
+                event
+                    .time
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis(),
+                event.user,
+                if event.joined { "join" } else { "leave" }
+            );
+        }
+        Some(csv)
+    }
+}
@@ -0,0 +1,131 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    process::Stdio,
+    time::{Duration, Instant},
+};
+
+use itertools::Itertools;
+use serenity::model::id::UserId;
+use tokio::{io::AsyncWriteExt, process::Command};
+
+use crate::{vad::Vad, wav};
+
+/// Silence ending an utterance.
+pub const END_OF_UTTERANCE: Duration = Duration::from_millis(700);
+
+/// Voiced audio of each user, split into utterances on silences.
+pub struct Utterances {
+    min_len: usize,
+    max_len: usize,
+    /// Whether utterances reaching `max_len` are cut and kept, rather than
+    /// dropped.
+    split_long: bool,
+    users: HashMap<UserId, (Vec<i16>, Instant)>,
+}
+
+impl Utterances {
+    pub fn new(min_len: usize, max_len: usize, split_long: bool) -> Self {
+        Self {
+            min_len,
+            max_len,
+            split_long,
+            users: HashMap::new(),
+        }
+    }
+
+    pub fn push(&mut self, user: UserId, frame: &[i16], vad: &Vad) {
+        if !vad.is_voice(frame) {
+            return;
+        }
+        let (utterance, last_voice) = self
+            .users
+            .entry(user)
+            .or_insert_with(|| (Vec::new(), Instant::now()));
+        *last_voice = Instant::now();
+        if utterance.len() < self.max_len {
+            utterance.extend_from_slice(frame);
+        }
+    }
+
+    #[cfg(feature = "transcription")]
+    pub fn clear(&mut self) {
+        self.users.clear();
+    }
+
+    /// Remove and return utterances followed by enough silence, or long enough
+    /// to be split.
+    pub fn take_finished(&mut self) -> Vec<(UserId, Vec<i16>)> {
+        let finished = self
+            .users
+            .iter()
+            .filter(|(_, (utterance, last_voice))| {
+                last_voice.elapsed() >= END_OF_UTTERANCE
+                    || (self.split_long && utterance.len() >= self.max_len)
+            })
+            .map(|(user, _)| *user)
+            .collect_vec();
+        finished
+            .into_iter()
+            .filter_map(|user| {
+                let (utterance, _) = self.users.remove(&user)?;
+                (utterance.len() >= self.min_len
+                    && (self.split_long || utterance.len() < self.max_len))
+                    .then_some((user, utterance))
+            })
+            .collect_vec()
+    }
+}
+
+/// Transcribe speech using an external program reading a WAV file on stdin and
+/// printing the transcript on stdout. The language code of the speaker, if
+/// known, is passed as its only argument.
+pub async fn recognize(
+    recognizer_path: &Path,
+    pcm: &[i16],
+    language: Option<&str>,
+) -> Option<String> {
+    let mut child = Command::new(recognizer_path)
+        .args(language)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()?;
+
+    let mut stdin = child.stdin.take()?;
+    let data = wav::package(pcm);
+    tokio::spawn(async move {
+        let _ = stdin.write_all(&data).await;
+    });
+
+    let out = child.wait_with_output().await.ok()?;
+    out.status
+        .success()
+        .then(|| String::from_utf8_lossy(&out.stdout).into_owned())
+}
+
+/// File extension of a transcript, recognizers may print SRT subtitles rather
+/// than plain text.
+#[cfg(feature = "transcription")]
+pub fn extension(transcript: &str) -> &'static str {
+    if transcript.lines().any(|line| line.contains(" --> ")) {
+        "srt"
+    } else {
+        "txt"
+    }
+}
+
+#[cfg(all(test, feature = "transcription"))]
+mod tests {
+    use super::extension;
+
+    #[test]
+    fn transcript_extension() {
+        assert_eq!(extension("hello there\n"), "txt");
+        assert_eq!(
+            extension("1\n00:00:00,000 --> 00:00:01,500\nhello there\n"),
+            "srt"
+        );
+    }
+}
@@ -0,0 +1,204 @@
+use std::{
+    collections::HashMap,
+    ops::Range,
+    time::{Duration, Instant},
+};
+
+use serenity::model::{
+    id::{GuildId, MessageId, UserId},
+    mention::Mention,
+};
+use thiserror::Error as ThisError;
+use tokio::sync::Mutex;
+
+use crate::{error_code::Coded, mix, recorder::FREQUENCY};
+
+/// Audio kept before a clip so its start can be moved earlier.
+pub const MARGIN: Duration = Duration::from_secs(10);
+/// Edges can't be moved closer than this.
+const MIN_LEN: usize = FREQUENCY / 2;
+/// Clips not saved within this delay are forgotten.
+const TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+#[derive(ThisError, Debug)]
+pub enum TrimError {
+    #[error("This clip expired, use `/clip` again.")]
+    Expired,
+    #[error("Only the one who made this clip can trim it.")]
+    NotOwner,
+}
+
+impl Coded for TrimError {
+    fn code(&self) -> u16 {
+        match self {
+            Self::Expired => 501,
+            Self::NotOwner => 502,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    Start,
+    End,
+}
+
+/// Parse the edge and seconds of a nudge button, such as `start-5` or
+/// `end+1`.
+pub fn parse_nudge(action: &str) -> Option<(Edge, i64)> {
+    let (edge, seconds) = match action.strip_prefix("start") {
+        Some(seconds) => (Edge::Start, seconds),
+        None => (Edge::End, action.strip_prefix("end")?),
+    };
+    Some((edge, seconds.parse().ok()?))
+}
+
+/// Recorded audio being trimmed into a clip.
+#[derive(Debug)]
+pub struct Clip {
+    pub guild: GuildId,
+    /// User who made the clip, the only one allowed to trim it.
+    pub owner: UserId,
+    /// User whose voice is clipped.
+    pub user: UserId,
+    /// Soundboard name the clip is saved as, if any.
+    pub sound_name: Option<String>,
+    data: Vec<i16>,
+    range: Range<usize>,
+    initial: Range<usize>,
+    created: Instant,
+}
+
+impl Clip {
+    /// Clip of the last `len` samples of `data`.
+    pub fn new(
+        guild: GuildId,
+        owner: UserId,
+        user: UserId,
+        sound_name: Option<String>,
+        data: Vec<i16>,
+        len: usize,
+    ) -> Self {
+        let range = data.len().saturating_sub(len)..data.len();
+        Self {
+            guild,
+            owner,
+            user,
+            sound_name,
+            data,
+            initial: range.clone(),
+            range,
+            created: Instant::now(),
+        }
+    }
+
+    pub fn samples(&self) -> &[i16] {
+        &self.data[self.range.clone()]
+    }
+
+    /// Move an edge by a number of seconds, staying within the recorded audio.
+    pub fn nudge(&mut self, edge: Edge, seconds: i64) {
+        let delta = seconds.unsigned_abs() as usize * FREQUENCY;
+        let Range { start, end } = self.range;
+        match edge {
+            Edge::Start if seconds < 0 => self.range.start = start.saturating_sub(delta),
+            Edge::Start => {
+                self.range.start = (start + delta).min(end.saturating_sub(MIN_LEN)).max(start)
+            }
+            Edge::End if seconds < 0 => {
+                self.range.end = end.saturating_sub(delta).max(start + MIN_LEN).min(end)
+            }
+            Edge::End => self.range.end = (end + delta).min(self.data.len()),
+        }
+    }
+
+    /// Length of the clip and how far its edges moved.
+    pub fn describe(&self) -> String {
+        let offset = |from: usize, to: usize| (to as f64 - from as f64) / FREQUENCY as f64;
+        format!(
+            "Clip of {}: {:.1}s, start {:+.1}s, end {:+.1}s",
+            Mention::from(self.user),
+            mix::samples_duration(self.range.len()).as_secs_f64(),
+            offset(self.initial.start, self.range.start),
+            offset(self.initial.end, self.range.end)
+        )
+    }
+}
+
+/// Clips being trimmed, by the message holding their buttons.
+#[derive(Default)]
+pub struct Trims {
+    clips: Mutex<HashMap<MessageId, Clip>>,
+}
+
+impl Trims {
+    pub async fn insert(&self, message: MessageId, clip: Clip) {
+        let mut clips = self.clips.lock().await;
+        clips.retain(|_, clip| clip.created.elapsed() < TIMEOUT);
+        clips.insert(message, clip);
+    }
+
+    /// Nudge an edge of a clip, returns its new audio and description.
+    pub async fn nudge(
+        &self,
+        message: MessageId,
+        user: UserId,
+        edge: Edge,
+        seconds: i64,
+    ) -> Result<(Vec<i16>, String), TrimError> {
+        let mut clips = self.clips.lock().await;
+        let clip = clips.get_mut(&message).ok_or(TrimError::Expired)?;
+        if clip.owner != user {
+            return Err(TrimError::NotOwner);
+        }
+        clip.nudge(edge, seconds);
+        Ok((clip.samples().to_vec(), clip.describe()))
+    }
+
+    /// Stop trimming a clip, returns it to be saved or discarded.
+    pub async fn take(&self, message: MessageId, user: UserId) -> Result<Clip, TrimError> {
+        let mut clips = self.clips.lock().await;
+        match clips.get(&message) {
+            None => Err(TrimError::Expired),
+            Some(clip) if clip.owner != user => Err(TrimError::NotOwner),
+            Some(_) => Ok(clips.remove(&message).expect("Clip just found")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serenity::model::id::{GuildId, UserId};
+
+    use super::{parse_nudge, Clip, Edge, MIN_LEN};
+    use crate::recorder::FREQUENCY;
+
+    #[test]
+    fn nudges_stay_within_audio() {
+        assert_eq!(parse_nudge("start-5"), Some((Edge::Start, -5)));
+        assert_eq!(parse_nudge("end+1"), Some((Edge::End, 1)));
+        assert_eq!(parse_nudge("save"), None);
+
+        let mut clip = Clip::new(
+            GuildId::new(1),
+            UserId::new(1),
+            UserId::new(1),
+            None,
+            vec![0; 12 * FREQUENCY],
+            5 * FREQUENCY,
+        );
+        clip.nudge(Edge::End, 1);
+        assert_eq!(clip.samples().len(), 5 * FREQUENCY);
+        clip.nudge(Edge::Start, -5);
+        clip.nudge(Edge::Start, -5);
+        assert_eq!(clip.samples().len(), 12 * FREQUENCY);
+        clip.nudge(Edge::End, -5);
+        clip.nudge(Edge::Start, 5);
+        clip.nudge(Edge::Start, 5);
+        assert_eq!(clip.samples().len(), MIN_LEN);
+        assert_eq!(
+            clip.describe(),
+            "Clip of <@1>: 0.5s, start -0.5s, end -5.0s"
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serenity::{
+    all::ButtonStyle,
+    model::id::{GuildId, MessageId, UserId},
+};
+use tokio::{sync::Mutex, time::sleep};
+
+use crate::soundboard::SoundboardError;
+
+/// Uploads not confirmed within this delay are forgotten.
+const TIMEOUT: Duration = Duration::from_secs(15 * 60);
+
+/// Sound uploaded with `/soundboard upload`, added to the soundboard once its
+/// preview is confirmed.
+pub struct PendingUpload {
+    pub guild: GuildId,
+    pub uploader: UserId,
+    pub name: String,
+    pub group: String,
+    pub emoji: Option<String>,
+    pub color: ButtonStyle,
+    pub index: Option<usize>,
+    pub looping: bool,
+    /// Sound converted to WAV.
+    pub data: Vec<u8>,
+}
+
+/// Uploads being previewed, by the message holding their preview.
+#[derive(Default)]
+pub struct Uploads {
+    uploads: Mutex<HashMap<MessageId, (PendingUpload, Instant)>>,
+}
+
+impl Uploads {
+    /// Drop expired uploads periodically, their sound data being held in
+    /// memory until then.
+    pub fn prune_loop(self: Arc<Self>) {
+        tokio::spawn(async move {
+            loop {
+                sleep(Duration::from_secs(60)).await;
+                prune(&mut *self.uploads.lock().await);
+            }
+        });
+    }
+
+    pub async fn insert(&self, message: MessageId, upload: PendingUpload) {
+        let mut uploads = self.uploads.lock().await;
+        prune(&mut uploads);
+        uploads.insert(message, (upload, Instant::now()));
+    }
+
+    /// Stop previewing an upload, returns it to be added or discarded.
+    pub async fn take(&self, message: MessageId) -> Result<PendingUpload, SoundboardError> {
+        let mut uploads = self.uploads.lock().await;
+        prune(&mut uploads);
+        uploads
+            .remove(&message)
+            .map(|(upload, _)| upload)
+            .ok_or(SoundboardError::UploadExpired)
+    }
+}
+
+fn prune(uploads: &mut HashMap<MessageId, (PendingUpload, Instant)>) {
+    uploads.retain(|_, (_, created)| created.elapsed() < TIMEOUT);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use serenity::{
+        all::ButtonStyle,
+        model::id::{GuildId, MessageId, UserId},
+    };
+
+    use super::{PendingUpload, Uploads, TIMEOUT};
+
+    fn upload() -> PendingUpload {
+        PendingUpload {
+            guild: GuildId::new(1),
+            uploader: UserId::new(2),
+            name: "bruh".to_owned(),
+            group: String::new(),
+            emoji: None,
+            color: ButtonStyle::Primary,
+            index: None,
+            looping: false,
+            data: vec![0; 44],
+        }
+    }
+
+    #[tokio::test]
+    async fn expires_uploads() {
+        let uploads = Uploads::default();
+        let (expired, pending) = (MessageId::new(1), MessageId::new(2));
+        uploads
+            .uploads
+            .lock()
+            .await
+            .insert(expired, (upload(), Instant::now() - TIMEOUT));
+        uploads.insert(pending, upload()).await;
+
+        // Pruned as soon as another upload is previewed.
+        assert!(!uploads.uploads.lock().await.contains_key(&expired));
+        assert!(uploads.take(expired).await.is_err());
+        assert!(uploads.take(pending).await.is_ok());
+        assert!(uploads.take(pending).await.is_err());
+    }
+}
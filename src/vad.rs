@@ -0,0 +1,228 @@
+use std::{iter, ops::Range, time::Duration};
+
+use crate::recorder::FREQUENCY;
+
+/// Number of samples analyzed at once, 20ms like Discord voice packets.
+pub const FRAME_SIZE: usize = FREQUENCY / 50;
+pub const DEFAULT_THRESHOLD: f32 = -50.;
+pub const MIN_THRESHOLD: f32 = -90.;
+/// Longest silence kept when trimming recordings.
+pub const TRIMMED_SILENCE: usize = FREQUENCY / 2;
+/// How far before the size limit a quiet point to split recordings at is
+/// searched.
+const SPLIT_SEARCH: usize = 10 * FREQUENCY;
+/// Number of quiet frames tolerated inside a voice range, avoids cutting words
+/// on short pauses.
+const HANGOVER_FRAMES: usize = 10;
+
+/// Energy based voice activity detector.
+#[derive(Clone, Copy, Debug)]
+pub struct Vad {
+    threshold: f32,
+}
+
+impl Vad {
+    /// `threshold` is the minimum level (in dBFS) of a frame to be considered
+    /// as voice.
+    pub fn new(threshold: f32) -> Self {
+        Self {
+            threshold: threshold.clamp(MIN_THRESHOLD, 0.),
+        }
+    }
+
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    pub fn is_voice(&self, frame: &[i16]) -> bool {
+        level(frame) >= self.threshold
+    }
+
+    /// The latest `count` stretches of voice lasting at least `min_duration`,
+    /// oldest first.
+    pub fn latest_voice(&self, pcm: &[i16], count: usize, min_duration: Duration) -> Vec<Vec<i16>> {
+        let min_len = min_duration.as_millis() as usize * FREQUENCY / 1000;
+        let mut chunks = self
+            .voice_ranges(pcm)
+            .into_iter()
+            .rev()
+            .filter(|range| range.len() >= min_len)
+            .take(count)
+            .map(|range| pcm[range].to_vec())
+            .collect::<Vec<_>>();
+        chunks.reverse();
+        chunks
+    }
+
+    /// Sample ranges containing voice, aligned on frames. Ranges separated by
+    /// short pauses are merged together.
+    pub fn voice_ranges(&self, pcm: &[i16]) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut current: Option<Range<usize>> = None;
+        let mut quiet = 0;
+        for (i, frame) in pcm.chunks(FRAME_SIZE).enumerate() {
+            let start = i * FRAME_SIZE;
+            if self.is_voice(frame) {
+                quiet = 0;
+                current.get_or_insert(start..start).end = start + frame.len();
+            } else if current.is_some() {
+                quiet += 1;
+                if quiet > HANGOVER_FRAMES {
+                    ranges.extend(current.take());
+                }
+            }
+        }
+        ranges.extend(current);
+        ranges
+    }
+
+    /// Shorten every silent stretch longer than `max_silence` samples, including
+    /// leading and trailing ones.
+    pub fn trim_silence(&self, pcm: &[i16], max_silence: usize) -> Vec<i16> {
+        let mut trimmed = Vec::with_capacity(pcm.len());
+        let mut previous_end = 0;
+        for range in self
+            .voice_ranges(pcm)
+            .into_iter()
+            .chain(iter::once(pcm.len()..pcm.len()))
+        {
+            trimmed
+                .extend_from_slice(&pcm[previous_end..range.start.min(previous_end + max_silence)]);
+            trimmed.extend_from_slice(&pcm[range.clone()]);
+            previous_end = range.end;
+        }
+        trimmed
+    }
+}
+
+impl Default for Vad {
+    fn default() -> Self {
+        Self::new(DEFAULT_THRESHOLD)
+    }
+}
+
+/// Split samples in chunks of at most `max_len` samples, each ending in the
+/// middle of the quietest frame before the limit to avoid cutting words.
+pub fn split_at_silence(pcm: &[i16], max_len: usize) -> Vec<&[i16]> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    while pcm.len() - start > max_len {
+        let limit = start + max_len;
+        let search_start = limit.saturating_sub(SPLIT_SEARCH).max(start);
+        // Latest frame wins ties, keeping chunks as long as possible.
+        let split = (search_start..limit.saturating_sub(FRAME_SIZE))
+            .step_by(FRAME_SIZE)
+            .map(|frame_start| {
+                let frame = &pcm[frame_start..frame_start + FRAME_SIZE];
+                (frame_start + FRAME_SIZE / 2, rms(frame))
+            })
+            .reduce(|best, candidate| {
+                if candidate.1 <= best.1 {
+                    candidate
+                } else {
+                    best
+                }
+            })
+            .map(|(split, _)| split)
+            .filter(|split| *split > start)
+            .unwrap_or(limit);
+        chunks.push(&pcm[start..split]);
+        start = split;
+    }
+    chunks.push(&pcm[start..]);
+    chunks
+}
+
+/// RMS level of samples in dBFS, `MIN_THRESHOLD` for digital silence.
+pub fn level(samples: &[i16]) -> f32 {
+    if samples.is_empty() {
+        return MIN_THRESHOLD;
+    }
+    ((20. * (rms(samples) / i16::MAX as f64).log10()) as f32).max(MIN_THRESHOLD)
+}
+
+/// Root mean square of samples, 0 when empty.
+pub fn rms(samples: &[i16]) -> f64 {
+    if samples.is_empty() {
+        return 0.;
+    }
+    (samples.iter().map(|&s| (s as f64).powi(2)).sum::<f64>() / samples.len() as f64).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::iter;
+
+    use itertools::Itertools;
+
+    use super::{Vad, FRAME_SIZE, MIN_THRESHOLD, SPLIT_SEARCH};
+
+    fn tone(frames: usize, amplitude: i16) -> impl Iterator<Item = i16> {
+        (0..frames * FRAME_SIZE).map(move |i| if i % 2 == 0 { amplitude } else { -amplitude })
+    }
+
+    #[test]
+    fn split_at_silence() {
+        // Voice, a short pause within the search range, then voice again.
+        let pcm = tone(200, 5000)
+            .chain(iter::repeat_n(0, 5 * FRAME_SIZE))
+            .chain(tone(200, 5000))
+            .collect_vec();
+        let max_len = 250 * FRAME_SIZE;
+        assert!(max_len - 200 * FRAME_SIZE < SPLIT_SEARCH);
+
+        let chunks = super::split_at_silence(&pcm, max_len);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks.iter().all(|chunk| chunk.len() <= max_len));
+        assert_eq!(
+            chunks.iter().map(|chunk| chunk.len()).sum::<usize>(),
+            pcm.len()
+        );
+        assert!(chunks[0].ends_with(&[0; FRAME_SIZE / 2]));
+
+        // Short enough data is kept whole.
+        assert_eq!(super::split_at_silence(&pcm, pcm.len()).len(), 1);
+    }
+
+    #[test]
+    fn level() {
+        assert_eq!(super::level(&[0; FRAME_SIZE]), MIN_THRESHOLD);
+        assert!(super::level(&[i16::MAX; FRAME_SIZE]).abs() < 0.01);
+        assert!((super::level(&[i16::MAX / 10; FRAME_SIZE]) + 20.).abs() < 0.01);
+    }
+
+    #[test]
+    fn voice_ranges() {
+        let pcm = iter::empty()
+            .chain(tone(5, 0))
+            .chain(tone(3, 8_000))
+            .chain(tone(2, 0))
+            .chain(tone(3, 8_000))
+            .chain(tone(30, 10))
+            .chain(tone(4, 8_000))
+            .collect_vec();
+        assert_eq!(
+            Vad::default().voice_ranges(&pcm),
+            vec![
+                5 * FRAME_SIZE..13 * FRAME_SIZE,
+                43 * FRAME_SIZE..47 * FRAME_SIZE
+            ]
+        );
+    }
+
+    #[test]
+    fn trim_silence() {
+        let pcm = iter::empty()
+            .chain(tone(30, 0))
+            .chain(tone(3, 8_000))
+            .chain(tone(30, 0))
+            .chain(tone(3, 8_000))
+            .collect_vec();
+        let trimmed = Vad::default().trim_silence(&pcm, 2 * FRAME_SIZE);
+        assert_eq!(trimmed.len(), 10 * FRAME_SIZE);
+        assert_eq!(
+            &trimmed[2 * FRAME_SIZE..5 * FRAME_SIZE],
+            &pcm[30 * FRAME_SIZE..33 * FRAME_SIZE]
+        );
+    }
+}
@@ -0,0 +1,206 @@
+use std::{
+    path::PathBuf,
+    sync::Arc,
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+
+use itertools::Itertools;
+use log::{info, warn};
+use serenity::{
+    all::{ChannelId, CreateAllowedMentions, CreateMessage, GuildId, Mention},
+    client::Context,
+    model::id::UserId,
+};
+use tokio::{
+    sync::{mpsc, oneshot},
+    time::sleep,
+};
+
+use crate::{
+    command,
+    mix::Marker,
+    recorder::{RecorderAction, RecorderSender, FREQUENCY},
+    soundboard::Soundboard,
+    transcription::{self, END_OF_UTTERANCE},
+};
+
+pub const WAKE_WORD: &str = "hey recorder";
+/// Commands are short, longer utterances are regular speech.
+pub const MAX_UTTERANCE: usize = 5 * FREQUENCY;
+pub const MIN_UTTERANCE: usize = FREQUENCY / 2;
+
+#[derive(PartialEq, Debug)]
+pub enum VoiceCommand {
+    Bookmark,
+    Pause,
+    Resume,
+    Stop,
+    Play(String),
+}
+
+/// Parse a transcript of the form "hey recorder <command>", ignoring case and
+/// punctuation.
+pub fn parse(transcript: &str) -> Option<VoiceCommand> {
+    let words = transcript
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect_vec();
+    let wake_len = WAKE_WORD.split(' ').count();
+    let start = words
+        .windows(wake_len)
+        .position(|window| window.join(" ") == WAKE_WORD)?
+        + wake_len;
+
+    match words.get(start..)? {
+        [command, ..] if command == "bookmark" => Some(VoiceCommand::Bookmark),
+        [command, ..] if command == "pause" => Some(VoiceCommand::Pause),
+        [command, ..] if command == "resume" => Some(VoiceCommand::Resume),
+        [command, ..] if command == "stop" => Some(VoiceCommand::Stop),
+        [command, sound @ ..] if command == "play" && !sound.is_empty() => {
+            Some(VoiceCommand::Play(sound.join(" ")))
+        }
+        _ => None,
+    }
+}
+
+/// Transcribe utterances of a guild and run the voice commands found, until
+/// another listener replaces this one.
+pub fn listen_loop(
+    ctx: Context,
+    guild: GuildId,
+    channel: ChannelId,
+    guild_recorder: RecorderSender,
+    soundboard: Arc<Soundboard>,
+    recognizer_path: PathBuf,
+) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        guild_recorder
+            .send(RecorderAction::ListenVoiceCommands(tx))
+            .await
+            .expect("Failed to register voice commands listener");
+        loop {
+            tokio::select! {
+                utterance = rx.recv() => {
+                    let Some((user, pcm)) = utterance else {
+                        break;
+                    };
+                    let Some(command) = transcription::recognize(&recognizer_path, &pcm, None)
+                        .await
+                        .as_deref()
+                        .and_then(parse)
+                    else {
+                        continue;
+                    };
+                    info!("running voice command {command:?} of user {user} in guild {guild}");
+                    run(&ctx, guild, channel, user, command, &guild_recorder, &soundboard).await;
+                }
+                _ = sleep(END_OF_UTTERANCE / 4) => {
+                    if guild_recorder.send(RecorderAction::FlushUtterances).await.is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
+async fn run(
+    ctx: &Context,
+    guild: GuildId,
+    channel: ChannelId,
+    user: UserId,
+    command: VoiceCommand,
+    guild_recorder: &RecorderSender,
+    soundboard: &Soundboard,
+) {
+    let manager = songbird::get(ctx)
+        .await
+        .expect("Failed to get songbird manager");
+    let content = match command {
+        VoiceCommand::Bookmark => {
+            let username = match user.to_user(ctx).await {
+                Ok(user) => command::resolve_username(ctx, &user, guild).await,
+                Err(_) => user.to_string(),
+            };
+            guild_recorder
+                .send(RecorderAction::AddMarker(Marker {
+                    at: Instant::now(),
+                    label: format!("Bookmark ({username})"),
+                }))
+                .await
+                .expect("Marker request failure");
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs();
+            format!("🔖 Bookmark by {} at <t:{now}:T>.", Mention::from(user))
+        }
+        VoiceCommand::Pause | VoiceCommand::Resume => {
+            let paused = command == VoiceCommand::Pause;
+            let (tx, rx) = oneshot::channel();
+            guild_recorder
+                .send(RecorderAction::SetPaused(paused, tx))
+                .await
+                .expect("Pause request failure");
+            match rx.await {
+                Ok(true) if paused => "Recording paused.".to_owned(),
+                Ok(true) => "Recording resumed.".to_owned(),
+                _ => return,
+            }
+        }
+        VoiceCommand::Stop => {
+            if let Some(call) = manager.get(guild) {
+                let mut call_lock = call.lock().await;
+                if let Err(err) = call_lock.leave().await {
+                    warn!("voice disconnection failure: {err}");
+                }
+                call_lock.remove_all_global_events();
+            }
+            "Left the voice channel.".to_owned()
+        }
+        VoiceCommand::Play(name) => {
+            match soundboard.get_id(guild, &name, None).await {
+                Ok(sound) => {
+                    crate::handler::play_sound(manager, soundboard, guild_recorder, guild, sound)
+                        .await;
+                }
+                Err(err) => warn!("voice command sound {name} not played: {err}"),
+            }
+            return;
+        }
+    };
+
+    if let Err(err) = channel
+        .send_message(
+            ctx,
+            CreateMessage::new()
+                .content(content)
+                .allowed_mentions(CreateAllowedMentions::new()),
+        )
+        .await
+    {
+        warn!("cannot send voice command message: {err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, VoiceCommand};
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(
+            parse("Hey recorder, bookmark."),
+            Some(VoiceCommand::Bookmark)
+        );
+        assert_eq!(parse("okay hey Recorder pause"), Some(VoiceCommand::Pause));
+        assert_eq!(
+            parse("hey recorder play air horn!"),
+            Some(VoiceCommand::Play("air horn".to_owned()))
+        );
+        assert_eq!(parse("hey recorder play"), None);
+        assert_eq!(parse("bookmark"), None);
+    }
+}
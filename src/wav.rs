@@ -1,6 +1,14 @@
-use std::time::Duration;
+use std::{io::Cursor, time::Duration};
+
+use symphonia::core::{
+    audio::SampleBuffer, codecs::DecoderOptions, io::MediaSourceStream, probe::Hint,
+};
+
+use crate::{recorder::FREQUENCY, resample};
 
 pub const HEADER_SIZE: usize = 44;
+/// `cbSize` field of the `fmt ` chunk and `fact` chunk of float WAV files.
+const FLOAT_HEADER_EXTENSION: usize = 14;
 
 const HEADER_TEMPLATES: [&[u8]; 2] = [
     &[82, 73, 70, 70],
@@ -10,29 +18,152 @@ const HEADER_TEMPLATES: [&[u8]; 2] = [
     ],
 ];
 
+/// Encoding of the samples of a WAV file. Voice is captured as 16-bit samples,
+/// so wider formats only pad them for tools expecting those, without adding
+/// any precision.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SampleFormat {
+    #[default]
+    S16,
+    S24,
+    F32,
+}
+
+impl SampleFormat {
+    pub const ALL: [Self; 3] = [Self::S16, Self::S24, Self::F32];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::S16 => "16",
+            Self::S24 => "24",
+            Self::F32 => "32f",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|format| format.name() == name)
+    }
+
+    pub fn bytes(self) -> usize {
+        match self {
+            Self::S16 => 2,
+            Self::S24 => 3,
+            Self::F32 => 4,
+        }
+    }
+
+    /// Size of the header of WAV files holding samples of this format, float
+    /// samples requiring a longer `fmt ` chunk and a `fact` chunk.
+    pub fn header_size(self) -> usize {
+        match self {
+            Self::S16 | Self::S24 => HEADER_SIZE,
+            Self::F32 => HEADER_SIZE + FLOAT_HEADER_EXTENSION,
+        }
+    }
+}
+
 /// Package i16 LE PCM data into a WAV container.
 pub fn package(pcm: &[i16]) -> Vec<u8> {
+    package_channels(pcm, 1)
+}
+
+/// Package i16 LE PCM data of `channels` interleaved channels into a WAV
+/// container.
+pub fn package_channels(pcm: &[i16], channels: u16) -> Vec<u8> {
     let mut data = Vec::with_capacity(HEADER_SIZE + pcm.len() * 2);
-    write_header(&mut data, pcm.len() * 2);
+    write_header(&mut data, pcm.len() * 2, channels);
     data.extend(pcm.iter().flat_map(|n| n.to_le_bytes()));
     data
 }
 
+/// Package i16 PCM data of `channels` interleaved channels sampled at `rate`
+/// into a WAV container, converting the samples to `format`.
+pub fn package_as(pcm: &[i16], channels: u16, rate: usize, format: SampleFormat) -> Vec<u8> {
+    let mut data = Vec::with_capacity(format.header_size() + pcm.len() * format.bytes());
+    write_format_header(
+        &mut data,
+        pcm.len() * format.bytes(),
+        channels,
+        rate,
+        format,
+    );
+    for &sample in pcm {
+        match format {
+            SampleFormat::S16 => data.extend(sample.to_le_bytes()),
+            SampleFormat::S24 => data.extend(&((sample as i32) << 8).to_le_bytes()[..3]),
+            SampleFormat::F32 => data.extend((sample as f32 / 32_768.).to_le_bytes()),
+        }
+    }
+    data
+}
+
+/// Append a comment to a WAV file as a `LIST` `INFO` chunk, after its PCM
+/// data.
+pub fn append_comment(wav: &mut Vec<u8>, comment: &str) {
+    // Chunks are word aligned and the comment null terminated.
+    let mut comment = comment.as_bytes().to_vec();
+    comment.push(0);
+    if comment.len() % 2 == 1 {
+        comment.push(0);
+    }
+    wav.extend_from_slice(b"LIST");
+    wav.extend_from_slice(&((comment.len() + 12) as u32).to_le_bytes());
+    wav.extend_from_slice(b"INFOICMT");
+    wav.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+    wav.extend(comment);
+    let riff_len = (wav.len() - 8) as u32;
+    wav[4..8].copy_from_slice(&riff_len.to_le_bytes());
+}
+
 /// Package i16 LE PCM data into a WAV container by prepending the buffer with a
 /// header.
 #[allow(dead_code)]
 pub fn package_mut_raw(data: &mut Vec<u8>) {
     data.reserve_exact(HEADER_SIZE);
-    write_header(data, data.len());
+    write_header(data, data.len(), 1);
     data.rotate_right(HEADER_SIZE);
 }
 
 /// `pcm_len` being the number of bytes of the PCM payload.
-fn write_header(buffer: &mut Vec<u8>, pcm_len: usize) {
+fn write_header(buffer: &mut Vec<u8>, pcm_len: usize, channels: u16) {
+    write_format_header(buffer, pcm_len, channels, FREQUENCY, SampleFormat::S16);
+}
+
+fn write_format_header(
+    buffer: &mut Vec<u8>,
+    pcm_len: usize,
+    channels: u16,
+    rate: usize,
+    format: SampleFormat,
+) {
+    let start = buffer.len();
+    let block_align = channels * format.bytes() as u16;
     buffer.extend_from_slice(HEADER_TEMPLATES[0]);
-    buffer.extend_from_slice(&((pcm_len + HEADER_SIZE - 8) as u32).to_le_bytes()); // Total length without data up to this point
-    buffer.extend_from_slice(HEADER_TEMPLATES[1]);
+    buffer.extend_from_slice(&((pcm_len + format.header_size() - 8) as u32).to_le_bytes()); // Total length without data up to this point
+    let (fmt, data) = HEADER_TEMPLATES[1].split_at(HEADER_TEMPLATES[1].len() - 4);
+    buffer.extend_from_slice(fmt);
+    if format == SampleFormat::F32 {
+        // Non-PCM formats have a `cbSize` field, empty, and a `fact` chunk
+        // holding the number of frames.
+        buffer.extend_from_slice(&0u16.to_le_bytes());
+        buffer.extend_from_slice(b"fact");
+        buffer.extend_from_slice(&4u32.to_le_bytes());
+        buffer.extend_from_slice(&((pcm_len / block_align as usize) as u32).to_le_bytes());
+        buffer[start + 16..start + 20].copy_from_slice(&18u32.to_le_bytes());
+        // WAVE_FORMAT_IEEE_FLOAT.
+        buffer[start + 20..start + 22].copy_from_slice(&3u16.to_le_bytes());
+    }
+    buffer.extend_from_slice(data);
     buffer.extend_from_slice(&((pcm_len as u32).to_le_bytes())); // PCM data length
+
+    // The template is mono s16le at the recording frequency, patch the
+    // channel count, rates, block align and bits per sample.
+    buffer[start + 22..start + 24].copy_from_slice(&channels.to_le_bytes());
+    buffer[start + 24..start + 28].copy_from_slice(&(rate as u32).to_le_bytes());
+    buffer[start + 28..start + 32]
+        .copy_from_slice(&(rate as u32 * block_align as u32).to_le_bytes());
+    buffer[start + 32..start + 34].copy_from_slice(&block_align.to_le_bytes());
+    buffer[start + 34..start + 36].copy_from_slice(&(format.bytes() as u16 * 8).to_le_bytes());
 }
 
 // TODO: use Bytes to remove usage of rotate_left while keeping AsRef<u8> impl.
@@ -60,7 +191,48 @@ pub fn duration_from_size(size: usize) -> Duration {
         return Duration::from_secs(0);
     }
     // Multiply by 1_000 and use milliseconds to gain in precision.
-    Duration::from_millis(((size - HEADER_SIZE) / 2 * 1_000 / crate::recorder::FREQUENCY) as u64)
+    Duration::from_millis(((size - HEADER_SIZE) / 2 * 1_000 / FREQUENCY) as u64)
+}
+
+/// Decode a WAV file to mono PCM at the recording frequency, mixing channels
+/// down and resampling if needed.
+pub fn decode_mono(wav: Vec<u8>) -> Option<Vec<i16>> {
+    if is_valid_pcm_s16le(&wav) {
+        return Some(
+            wav[HEADER_SIZE..]
+                .chunks_exact(2)
+                .map(|bytes| i16::from_le_bytes([bytes[0], bytes[1]]))
+                .collect(),
+        );
+    }
+
+    let mut hint = Hint::new();
+    hint.with_extension("wav");
+    let mut format = symphonia::default::get_probe()
+        .format(
+            &hint,
+            MediaSourceStream::new(Box::new(Cursor::new(wav)), Default::default()),
+            &Default::default(),
+            &Default::default(),
+        )
+        .ok()?
+        .format;
+    let params = format.default_track()?.codec_params.clone();
+    let rate = params.sample_rate? as usize;
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&params, &DecoderOptions::default())
+        .ok()?;
+    let mut mono = Vec::new();
+    while let Ok(packet) = format.next_packet() {
+        let decoded = decoder.decode(&packet).ok()?;
+        let channels = decoded.spec().channels.count().max(1);
+        let mut samples = SampleBuffer::<i16>::new(decoded.capacity() as u64, *decoded.spec());
+        samples.copy_interleaved_ref(decoded);
+        mono.extend(samples.samples().chunks_exact(channels).map(|frame| {
+            (frame.iter().map(|&sample| sample as i32).sum::<i32>() / channels as i32) as i16
+        }));
+    }
+    Some(resample::convert(&mono, rate, FREQUENCY))
 }
 
 #[cfg(test)]
@@ -128,6 +300,54 @@ mod tests {
         assert_eq!(pcm, wav);
     }
 
+    #[test]
+    fn package_as() {
+        let pcm = iter::repeat_with(random::<i16>).take(64).collect_vec();
+        assert_eq!(
+            super::package_as(
+                &pcm,
+                1,
+                crate::recorder::FREQUENCY,
+                super::SampleFormat::S16
+            ),
+            super::package(&pcm)
+        );
+
+        let wav = super::package_as(&[-32_768, 256], 2, 16_000, super::SampleFormat::S24);
+        assert_eq!(wav[24..28], 16_000u32.to_le_bytes());
+        assert_eq!(wav[28..32], (16_000u32 * 6).to_le_bytes());
+        assert_eq!(wav[34..36], 24u16.to_le_bytes());
+        assert_eq!(&wav[HEADER_SIZE..], [0, 0, 0x80, 0, 0, 1]);
+
+        let wav = super::package_as(&[-16_384], 1, 48_000, super::SampleFormat::F32);
+        assert_eq!(wav[4..8], (wav.len() as u32 - 8).to_le_bytes());
+        assert_eq!(wav[16..20], 18u32.to_le_bytes());
+        assert_eq!(wav[20..22], 3u16.to_le_bytes());
+        assert_eq!(&wav[38..50], b"fact\x04\0\0\0\x01\0\0\0");
+        assert_eq!(&wav[50..58], b"data\x04\0\0\0");
+        assert_eq!(wav[58..], (-0.5f32).to_le_bytes());
+
+        // Readable by decoders.
+        let wav = super::package_as(
+            &pcm,
+            1,
+            crate::recorder::FREQUENCY,
+            super::SampleFormat::F32,
+        );
+        assert_eq!(super::decode_mono(wav).unwrap(), pcm);
+    }
+
+    #[test]
+    fn append_comment() {
+        let mut wav = super::package(&[1, 2]);
+        super::append_comment(&mut wav, "consent");
+
+        assert_eq!(&wav[HEADER_SIZE + 4..HEADER_SIZE + 12], b"LIST\x14\0\0\0");
+        assert_eq!(&wav[HEADER_SIZE + 12..HEADER_SIZE + 20], b"INFOICMT");
+        assert_eq!(&wav[HEADER_SIZE + 24..], b"consent\0");
+        assert_eq!(wav[4..8], ((wav.len() - 8) as u32).to_le_bytes());
+    }
+
     #[test]
     fn validate() {
         assert!(super::is_valid_pcm_s16le(include_bytes!("hello.wav")));
@@ -140,4 +360,10 @@ mod tests {
             Duration::from_millis(1120)
         );
     }
+
+    #[test]
+    fn decode_mono() {
+        let pcm = iter::repeat_with(random::<i16>).take(64).collect_vec();
+        assert_eq!(super::decode_mono(super::package(&pcm)), Some(pcm));
+    }
 }